@@ -0,0 +1,1217 @@
+use crate::{
+    key_vec::{KeyVec, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+    ssa::{
+        Block, BlockData, BlockSentinel, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData,
+        InstSentinel, Location, Ssa,
+    },
+};
+
+pub fn generate(types: &Types, ssa: &Ssa) -> String {
+    let mut generator = Generator {
+        types,
+        ssa,
+        blocks: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| String::new()).collect()),
+        args_allocations: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        insts_allocations: KeyVec::from_vec((0..ssa.insts.len()).map(|_| None).collect()),
+        argument_areas: KeyVec::from_vec(vec![0; ssa.blocks.len()]),
+        heap_offsets: Vec::new(),
+    };
+
+    generator.generate();
+
+    generator.result()
+}
+
+// Every value lives in a stack slot, registers only hold values within the
+// generated code of a single instruction:
+// - `x0` to `x8` hold arguments and returned values,
+// - `x9` holds an address, as the base of `Allocation::Indirect`,
+// - `w10` to `w12` hold operands,
+// - `x17` holds addresses of slots out of reach of immediate offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Allocation {
+    // Relative to `sp`, which stays fixed within the body of a function.
+    Stack { offset: u64, size: u64 },
+    // Memory pointed by `x9`, only valid within the generated code of a
+    // single instruction.
+    Indirect { offset: u64, size: u64 },
+    Register { register: u8, size: u64 },
+    Immediate(u32),
+}
+
+impl Allocation {
+    // An address held in `x9` itself.
+    const X9: Allocation = Allocation::Register {
+        register: 9,
+        size: 8,
+    };
+}
+
+// Class of an argument or a returned value in AAPCS64, keb has no floating
+// point values so composites are never homogeneous floating point aggregates.
+enum Class {
+    None,
+    // Passed in one register for each doubleword.
+    Registers,
+    // Copied to memory by the caller and passed by address in a register, or
+    // written to the address given by the caller in `x8` when returned.
+    Memory,
+}
+
+struct Generator<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    blocks: KeyVec<BlockSentinel, String>,
+    args_allocations: KeyVec<BlockSentinel, Option<Allocation>>,
+    insts_allocations: KeyVec<InstSentinel, Option<Allocation>>,
+    // Size of the area where the callers of each function write the
+    // arguments of the memory class.
+    argument_areas: KeyVec<BlockSentinel, u64>,
+    // Offsets of the vecs held by the elements of vecs, each table is at the
+    // label `heap_offsets{i}` of `.rodata`.
+    heap_offsets: Vec<Vec<u64>>,
+}
+
+struct Frame<'a> {
+    name: &'a str,
+    return_type: Type,
+    // Where to write the returned value when it is of the memory class.
+    return_pointer: Option<Allocation>,
+    // Where the address of the argument area is saved, reused by tail calls.
+    argument_area: Option<Allocation>,
+    stack_size: u64,
+}
+
+impl Generator<'_> {
+    fn result(self) -> String {
+        let mut asm = String::new();
+
+        asm.push_str(".text\n");
+        asm.push_str(".global main\n\n");
+        asm.extend(self.blocks.entries().flat_map(|(_, asm)| [asm, "\n"]));
+
+        if !self.heap_offsets.is_empty() {
+            asm.push_str(".section .rodata\n.balign 4\n");
+            for (i, offsets) in self.heap_offsets.iter().enumerate() {
+                asm.push_str(&format!(
+                    "heap_offsets{i}:\n  .word {}\n",
+                    offsets
+                        .iter()
+                        .map(|offset| offset.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        // Read by the runtime to report the traps.
+        asm.push_str(&format!(
+            ".section .rodata\n.global builtin_source_path\nbuiltin_source_path:\n  .byte {}\n",
+            self.ssa
+                .path
+                .bytes()
+                .chain([0])
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+
+        asm
+    }
+
+    fn generate(&mut self) {
+        self.size_argument_areas();
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            match block_data {
+                BlockData::ExternFunction { name, .. } => {
+                    self.blocks[block] = format!(".set f{}_{name}, {name}\n", block.as_u32());
+                }
+                BlockData::Function { .. } => self.generate_function(block),
+                BlockData::Block { .. } => {}
+            }
+        }
+    }
+
+    // Functions of the program take the arguments of the memory class in an
+    // area given by the caller in `x0`, which they reuse for the arguments of
+    // their tail calls. The area is as large as the arguments of the
+    // functions reached through tail calls, other arguments follow its
+    // address.
+    fn size_argument_areas(&mut self) {
+        for (function, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::Function { arg, .. } = block_data
+                && let Class::Memory = self.classify(*arg)
+            {
+                self.argument_areas[function] = self.type_size(*arg);
+            }
+        }
+
+        let tail_calls = self.ssa.tail_calls();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(caller, callee) in &tail_calls {
+                if self.argument_areas[callee] > self.argument_areas[caller] {
+                    self.argument_areas[caller] = self.argument_areas[callee];
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn generate_function(&mut self, function: Block) {
+        let ssa = self.ssa;
+
+        let BlockData::Function {
+            name,
+            arg,
+            ret,
+            insts,
+        } = &ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let mut frame = Frame {
+            name,
+            return_type: *ret,
+            return_pointer: None,
+            argument_area: None,
+            stack_size: 0,
+        };
+
+        // Blocks are emitted in the order they were created, which is also
+        // the order in which their values are defined.
+        let mut blocks = Cfg::new(self.ssa, function).blocks()[1..].to_vec();
+        blocks.sort_by_key(|block| block.as_u32());
+
+        self.reserve_stack_allocations(function, &blocks, &mut frame);
+
+        let mut body = String::new();
+
+        if let Class::Memory = self.classify(*ret) {
+            let allocation = self.reserve_stack_allocation(8, &mut frame.stack_size);
+            frame.return_pointer = Some(allocation);
+            body.push_str(&self.store(8, &allocation));
+        }
+
+        if self.argument_areas[function] != 0 {
+            let allocation = self.reserve_stack_allocation(8, &mut frame.stack_size);
+            frame.argument_area = Some(allocation);
+            body.push_str(&self.store(0, &allocation));
+        }
+        let first_register = (self.argument_areas[function] != 0) as u8;
+
+        let allocation = self.expr_allocation(Expr::BlockArg(function));
+
+        match self.classify(*arg) {
+            Class::None => {}
+            Class::Registers => {
+                for (register, doubleword) in self
+                    .doublewords(allocation, self.type_size(*arg))
+                    .iter()
+                    .enumerate()
+                {
+                    body.push_str(&self.store(first_register + register as u8, doubleword));
+                }
+            }
+            // The argument is copied out of the area, so that tail calls can
+            // write theirs there.
+            Class::Memory => {
+                let size = self.type_size(*arg);
+                body.push_str("  mov x9, x0\n");
+                body.push_str(&self.move_(&Allocation::Indirect { offset: 0, size }, &allocation));
+            }
+        }
+
+        for inst in insts {
+            body.push_str(&self.generate_inst(*inst, &mut frame));
+        }
+
+        for block in blocks {
+            let BlockData::Block { insts, .. } = &ssa.blocks[block] else {
+                panic!()
+            };
+
+            body.push_str(&format!("block{}:\n", block.as_u32()));
+            for inst in insts {
+                body.push_str(&self.generate_inst(*inst, &mut frame));
+            }
+        }
+
+        let mut asm = String::new();
+
+        if name == "main" {
+            asm.push_str(&format!(".set main, f{}_{name}\n", function.as_u32()));
+        }
+
+        asm.push_str(&format!("f{}_{name}:\n", function.as_u32()));
+
+        // The frame record is pushed first, then the slots of the values are
+        // reserved below it. `sp` must stay a multiple of 16.
+        let frame_size = frame.stack_size.next_multiple_of(16);
+
+        asm.push_str("  stp x29, x30, [sp, #-16]!\n");
+        asm.push_str("  mov x29, sp\n");
+        if frame_size <= 4095 {
+            asm.push_str(&format!("  sub sp, sp, #{frame_size}\n"));
+        } else {
+            asm.push_str(&immediate(16, 8, frame_size as u32));
+            asm.push_str("  sub sp, sp, x16\n");
+        }
+        asm.push('\n');
+        asm.push_str(&body);
+
+        self.blocks[function] = asm;
+    }
+
+    // Reserve a stack slot for every value of the function.
+    fn reserve_stack_allocations(&mut self, function: Block, blocks: &[Block], frame: &mut Frame) {
+        let ssa = self.ssa;
+
+        let arg_type = self.expr_type(Expr::BlockArg(function));
+        self.args_allocations[function] =
+            Some(self.reserve_stack_allocation(self.type_size(arg_type), &mut frame.stack_size));
+
+        for &block in [function].iter().chain(blocks) {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            if block != function {
+                let arg_type = self.expr_type(Expr::BlockArg(block));
+                self.args_allocations[block] = Some(
+                    self.reserve_stack_allocation(self.type_size(arg_type), &mut frame.stack_size),
+                );
+            }
+
+            for inst in insts {
+                let inst_type = self.expr_type(Expr::Inst(*inst));
+                self.insts_allocations[*inst] = Some(
+                    self.reserve_stack_allocation(self.type_size(inst_type), &mut frame.stack_size),
+                );
+            }
+        }
+    }
+
+    fn generate_inst(&mut self, inst: Inst, frame: &mut Frame) -> String {
+        match &self.ssa.insts[inst] {
+            InstData::Field(expr, field) => {
+                let allocation = self.expr_allocation(*expr);
+
+                let expr_type = self.expr_type(*expr);
+
+                let field_offset = self.field_offset(expr_type, *field as usize);
+                let field_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                self.move_(
+                    &self.offset_allocation(allocation, field_offset, field_size),
+                    &self.expr_allocation(Expr::Inst(inst)),
+                )
+            }
+            InstData::Record(fields, type_) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                let mut inst_asm = String::new();
+
+                for (i, field) in fields.iter().enumerate() {
+                    let field_size = self.type_size(self.expr_type(*field));
+                    inst_asm.push_str(&self.move_(
+                        &self.expr_allocation(*field),
+                        &self.offset_allocation(
+                            allocation,
+                            self.field_offset(*type_, i),
+                            field_size,
+                        ),
+                    ));
+                }
+
+                inst_asm
+            }
+            InstData::Array(elements, _) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                let mut inst_asm = String::new();
+
+                let mut offset = 0;
+                for element in elements {
+                    let element_size = self.type_size(self.expr_type(*element));
+                    inst_asm.push_str(&self.move_(
+                        &self.expr_allocation(*element),
+                        &self.offset_allocation(allocation, offset, element_size),
+                    ));
+
+                    offset += element_size;
+                }
+
+                inst_asm
+            }
+            InstData::Length(base) if self.is_vec(*base) => {
+                // The length follows the pointer to the elements.
+                format!(
+                    "{}{}",
+                    self.load(9, &self.expr_allocation(*base)),
+                    self.move_(
+                        &Allocation::Indirect { offset: 8, size: 4 },
+                        &self.expr_allocation(Expr::Inst(inst)),
+                    ),
+                )
+            }
+            InstData::Length(base) => self.move_(
+                &self.length_allocation(*base),
+                &self.expr_allocation(Expr::Inst(inst)),
+            ),
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let element_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let mut inst_asm =
+                    self.element_address(inst, *base, *index, element_size, location);
+
+                inst_asm.push_str(&self.move_(
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                    &self.expr_allocation(Expr::Inst(inst)),
+                ));
+
+                inst_asm
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let mut inst_asm =
+                    self.element_address(inst, *base, *index, element_size, location);
+
+                inst_asm.push_str(&self.move_(
+                    &self.expr_allocation(*value),
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                ));
+
+                inst_asm
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            } => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Slice { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let inst_number = inst.as_u32();
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                // The length is `end - start`, the pointer is the base
+                // address offsetted by `start` elements.
+                format!(
+                    "{}{}  cmp w10, w11\n  b.hi i{inst_number}_out_of_bounds\n{}  cmp w11, w12\n  b.ls i{inst_number}_in_bounds\ni{inst_number}_out_of_bounds:\n{}i{inst_number}_in_bounds:\n  sub w11, w11, w10\n{}{}{}  umaddl x9, w10, w12, x9\n{}",
+                    self.load(10, &self.expr_allocation(*start)),
+                    self.load(11, &self.expr_allocation(*end)),
+                    self.load(12, &self.length_allocation(*base)),
+                    index_out_of_bounds(location),
+                    self.store(11, &self.offset_allocation(allocation, 8, 4)),
+                    self.base_address(*base),
+                    immediate(12, 4, element_size as u32),
+                    self.store(9, &self.offset_allocation(allocation, 0, 8)),
+                )
+            }
+            InstData::NewVec(elements, type_) => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Vec { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let mut inst_asm = String::new();
+
+                // The runtime copies the elements from the stack.
+                let elements_address = if elements.is_empty() {
+                    Allocation::Immediate(0)
+                } else {
+                    let elements_allocation = self.reserve_stack_allocation(
+                        element_size * elements.len() as u64,
+                        &mut frame.stack_size,
+                    );
+
+                    for (i, element) in elements.iter().enumerate() {
+                        inst_asm.push_str(&self.move_(
+                            &self.expr_allocation(*element),
+                            &self.offset_allocation(
+                                elements_allocation,
+                                i as u64 * element_size,
+                                element_size,
+                            ),
+                        ));
+                    }
+
+                    inst_asm.push_str(&self.address(9, &elements_allocation));
+
+                    Allocation::X9
+                };
+
+                let (heap_offsets, heap_count) = match self.heap_offsets_table(*type_) {
+                    Some((table, count)) => {
+                        inst_asm.push_str(&format!(
+                            "  adrp x10, heap_offsets{table}\n  add x10, x10, :lo12:heap_offsets{table}\n"
+                        ));
+                        (
+                            Allocation::Register {
+                                register: 10,
+                                size: 8,
+                            },
+                            count,
+                        )
+                    }
+                    None => (Allocation::Immediate(0), 0),
+                };
+
+                inst_asm.push_str(&self.runtime_call(
+                    "builtin_vec_new",
+                    &[
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(elements.len() as u32),
+                        elements_address,
+                        heap_offsets,
+                        Allocation::Immediate(heap_count),
+                    ],
+                ));
+
+                inst_asm.push_str(&self.store(9, &self.expr_allocation(Expr::Inst(inst))));
+
+                inst_asm
+            }
+            InstData::Push { vec, value } => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_push",
+                    &[
+                        self.expr_allocation(*vec),
+                        Allocation::Immediate(element_size as u32),
+                    ],
+                );
+
+                inst_asm.push_str(&self.move_(
+                    &self.expr_allocation(*value),
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                ));
+
+                inst_asm
+            }
+            InstData::Pop { vec, location } => {
+                let element_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_pop",
+                    &[
+                        self.expr_allocation(*vec),
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(location.line),
+                        Allocation::Immediate(location.column),
+                    ],
+                );
+
+                inst_asm.push_str(&self.move_(
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                    &self.expr_allocation(Expr::Inst(inst)),
+                ));
+
+                inst_asm
+            }
+            InstData::Alloca(type_) => {
+                let pointee_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Pointer { pointee }) => self.type_size(*pointee),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let memory = self.reserve_stack_allocation(pointee_size, &mut frame.stack_size);
+
+                format!(
+                    "{}{}",
+                    self.address(9, &memory),
+                    self.store(9, &self.expr_allocation(Expr::Inst(inst))),
+                )
+            }
+            // Values already live in memory.
+            InstData::AddressOf(expr, _) => format!(
+                "{}{}",
+                self.address(9, &self.expr_allocation(*expr)),
+                self.store(9, &self.expr_allocation(Expr::Inst(inst))),
+            ),
+            InstData::LoadPointer(pointer) => {
+                let size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                format!(
+                    "{}{}",
+                    self.load(9, &self.expr_allocation(*pointer)),
+                    self.move_(
+                        &Allocation::Indirect { offset: 0, size },
+                        &self.expr_allocation(Expr::Inst(inst)),
+                    ),
+                )
+            }
+            InstData::StorePointer { pointer, value } => {
+                let size = self.type_size(self.expr_type(*value));
+
+                format!(
+                    "{}{}",
+                    self.load(9, &self.expr_allocation(*pointer)),
+                    self.move_(
+                        &self.expr_allocation(*value),
+                        &Allocation::Indirect { offset: 0, size }
+                    ),
+                )
+            }
+            InstData::Retain(vec) => {
+                self.runtime_call("builtin_vec_retain", &[self.expr_allocation(*vec)])
+            }
+            InstData::Release(vec) => {
+                self.runtime_call("builtin_vec_release", &[self.expr_allocation(*vec)])
+            }
+            InstData::Equal(lhs, rhs) => {
+                self.binary_operation(inst, "cmp w10, w11\n  cset w10, eq", *lhs, *rhs)
+            }
+            InstData::Add(lhs, rhs) => self.binary_operation(inst, "add w10, w10, w11", *lhs, *rhs),
+            InstData::Sub(lhs, rhs) => self.binary_operation(inst, "sub w10, w10, w11", *lhs, *rhs),
+            InstData::Mul(lhs, rhs) => self.binary_operation(inst, "mul w10, w10, w11", *lhs, *rhs),
+            // Dividing by zero would give zero.
+            InstData::Div(lhs, rhs) if self.ssa.divisor_may_be_zero(*rhs) => {
+                let inst_number = inst.as_u32();
+                self.binary_operation(
+                    inst,
+                    &format!(
+                        "cbnz w11, i{inst_number}_nonzero\n  bl builtin_division_by_zero\ni{inst_number}_nonzero:\n  udiv w10, w10, w11"
+                    ),
+                    *lhs,
+                    *rhs,
+                )
+            }
+            InstData::Div(lhs, rhs) => {
+                self.binary_operation(inst, "udiv w10, w10, w11", *lhs, *rhs)
+            }
+            InstData::Call { function, argument } => {
+                let mut inst_asm = self.call_arguments(*function, *argument, false, frame);
+
+                let (BlockData::ExternFunction { name, ret, .. }
+                | BlockData::Function { name, ret, .. }) = &self.ssa.blocks[*function]
+                else {
+                    panic!()
+                };
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                // The callee writes the returned value in our stack slot.
+                if let Class::Memory = self.classify(*ret) {
+                    inst_asm.push_str(&self.address(8, &allocation));
+                }
+
+                inst_asm.push_str(&format!("  bl f{}_{name}\n", function.as_u32()));
+
+                if let Class::Registers = self.classify(*ret) {
+                    for (register, doubleword) in self
+                        .doublewords(allocation, self.type_size(*ret))
+                        .iter()
+                        .enumerate()
+                    {
+                        inst_asm.push_str(&self.store(register as u8, doubleword));
+                    }
+                }
+
+                inst_asm.push('\n');
+
+                inst_asm
+            }
+            InstData::Jump { block, argument } => {
+                let mut inst_asm = self.move_(
+                    &self.expr_allocation(*argument),
+                    &self.expr_allocation(Expr::BlockArg(*block)),
+                );
+
+                inst_asm.push_str(&format!("  b block{}\n\n", block.as_u32()));
+
+                inst_asm
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => format!(
+                "{}  cbz w10, block{}\n  b block{}\n\n",
+                self.load(10, &self.expr_allocation(*condition)),
+                else_.as_u32(),
+                then.as_u32(),
+            ),
+            InstData::Return(expr) => {
+                let mut inst_asm = String::new();
+
+                let allocation = self.expr_allocation(*expr);
+                let size = self.type_size(frame.return_type);
+
+                match self.classify(frame.return_type) {
+                    Class::None => {
+                        // The exit status of the program.
+                        if frame.name == "main" {
+                            inst_asm.push_str("  mov w0, #0\n");
+                        }
+                    }
+                    Class::Registers => {
+                        for (register, doubleword) in
+                            self.doublewords(allocation, size).iter().enumerate()
+                        {
+                            inst_asm.push_str(&self.load(register as u8, doubleword));
+                        }
+                    }
+                    Class::Memory => {
+                        inst_asm.push_str(&self.load(9, &frame.return_pointer.unwrap()));
+                        inst_asm.push_str(
+                            &self.move_(&allocation, &Allocation::Indirect { offset: 0, size }),
+                        );
+                    }
+                }
+
+                inst_asm.push_str("  mov sp, x29\n  ldp x29, x30, [sp], #16\n  ret\n\n");
+
+                inst_asm
+            }
+            InstData::TailCall { function, argument } => {
+                let BlockData::Function { name, .. } = &self.ssa.blocks[*function] else {
+                    panic!()
+                };
+
+                // `main` keeps its frame, as it gives the exit status.
+                let jump = frame.name != "main";
+                let mut inst_asm = self.call_arguments(*function, *argument, jump, frame);
+
+                // The callee writes the returned value where our caller
+                // expects it.
+                if let Some(return_pointer) = frame.return_pointer {
+                    inst_asm.push_str(&self.load(8, &return_pointer));
+                }
+
+                if !jump {
+                    inst_asm.push_str(&format!("  bl f{}_{name}\n", function.as_u32()));
+                    if frame.name == "main" {
+                        inst_asm.push_str("  mov w0, #0\n");
+                    }
+                }
+
+                inst_asm.push_str("  mov sp, x29\n  ldp x29, x30, [sp], #16\n");
+                if jump {
+                    inst_asm.push_str(&format!("  b f{}_{name}\n\n", function.as_u32()));
+                } else {
+                    inst_asm.push_str("  ret\n\n");
+                }
+
+                inst_asm
+            }
+        }
+    }
+
+    // Put the argument of a call where the callee expects it, the returned
+    // value and the call itself are left to the caller. Tail calls give our
+    // argument area to the callee, other calls a new one.
+    fn call_arguments(
+        &mut self,
+        function: Block,
+        argument: Expr,
+        tail: bool,
+        frame: &mut Frame,
+    ) -> String {
+        let (BlockData::ExternFunction { arg, .. } | BlockData::Function { arg, .. }) =
+            &self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let mut asm = "\n".to_string();
+
+        let area_size = match self.ssa.blocks[function] {
+            BlockData::Function { .. } => self.argument_areas[function],
+            BlockData::ExternFunction { .. } | BlockData::Block { .. } => 0,
+        };
+        if area_size != 0 && tail {
+            asm.push_str(&self.load(0, &frame.argument_area.unwrap()));
+        } else if area_size != 0 {
+            let area = self.reserve_stack_allocation(area_size, &mut frame.stack_size);
+            asm.push_str(&self.address(0, &area));
+        }
+        let first_register = (area_size != 0) as u8;
+
+        let argument_allocation = self.expr_allocation(argument);
+        match self.classify(*arg) {
+            Class::None => {}
+            Class::Registers => {
+                for (register, doubleword) in self
+                    .doublewords(argument_allocation, self.type_size(*arg))
+                    .iter()
+                    .enumerate()
+                {
+                    asm.push_str(&self.load(first_register + register as u8, doubleword));
+                }
+            }
+            Class::Memory if area_size != 0 => {
+                asm.push_str("  mov x9, x0\n");
+                asm.push_str(&self.move_(
+                    &argument_allocation,
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: self.type_size(*arg),
+                    },
+                ));
+            }
+            // The callee copies the argument before writing anything, so the
+            // slot of the argument is given directly.
+            Class::Memory => asm.push_str(&self.address(0, &argument_allocation)),
+        }
+
+        asm
+    }
+
+    // Compute `operation` with `lhs` in `w10` and `rhs` in `w11`, the result is
+    // in `w10`.
+    fn binary_operation(&self, inst: Inst, operation: &str, lhs: Expr, rhs: Expr) -> String {
+        format!(
+            "{}{}  {operation}\n{}",
+            self.load(10, &self.expr_allocation(lhs)),
+            self.load(11, &self.expr_allocation(rhs)),
+            self.store(10, &self.expr_allocation(Expr::Inst(inst))),
+        )
+    }
+
+    fn move_(&self, source: &Allocation, destination: &Allocation) -> String {
+        match allocation_size(destination) {
+            _ if source == destination => String::new(),
+            0 => String::new(),
+            4 | 8 => match (source, destination) {
+                (_, Allocation::Register { register, .. }) => self.load(*register, source),
+                (Allocation::Register { register, .. }, _) => self.store(*register, destination),
+                _ => format!("{}{}", self.load(10, source), self.store(10, destination)),
+            },
+            size if size % 4 == 0 => (0..size / 4)
+                .map(|i| {
+                    self.move_(
+                        &self.offset_allocation(*source, i * 4, 4),
+                        &self.offset_allocation(*destination, i * 4, 4),
+                    )
+                })
+                .collect(),
+            _ => todo!(),
+        }
+    }
+
+    // Load `source` in the register `register`, of the size of `source`.
+    fn load(&self, register: u8, source: &Allocation) -> String {
+        let size = allocation_size(source);
+        let register_name = register_asm(register, size);
+
+        match source {
+            Allocation::Immediate(value) => immediate(register, 4, *value),
+            Allocation::Register {
+                register: source, ..
+            } if *source == register => String::new(),
+            Allocation::Register {
+                register: source,
+                size,
+            } => format!("  mov {register_name}, {}\n", register_asm(*source, *size)),
+            Allocation::Stack { .. } | Allocation::Indirect { .. } => {
+                let (setup, memory) = self.memory_asm(source);
+                format!("{setup}  ldr {register_name}, {memory}\n")
+            }
+        }
+    }
+
+    // Store the register `register`, of the size of `destination`.
+    fn store(&self, register: u8, destination: &Allocation) -> String {
+        let register_name = register_asm(register, allocation_size(destination));
+
+        match destination {
+            Allocation::Register {
+                register: destination,
+                size,
+            } if *destination != register => {
+                format!(
+                    "  mov {}, {register_name}\n",
+                    register_asm(*destination, *size)
+                )
+            }
+            Allocation::Register { .. } => String::new(),
+            Allocation::Stack { .. } | Allocation::Indirect { .. } => {
+                let (setup, memory) = self.memory_asm(destination);
+                format!("{setup}  str {register_name}, {memory}\n")
+            }
+            Allocation::Immediate(_) => panic!(),
+        }
+    }
+
+    // Put the address of a memory allocation in the register `register`.
+    fn address(&self, register: u8, allocation: &Allocation) -> String {
+        let (base, offset) = match allocation {
+            Allocation::Stack { offset, .. } => ("sp", *offset),
+            Allocation::Indirect { offset, .. } => ("x9", *offset),
+            Allocation::Register { .. } | Allocation::Immediate(_) => panic!(),
+        };
+
+        if offset <= 4095 {
+            format!("  add x{register}, {base}, #{offset}\n")
+        } else {
+            format!(
+                "{}  add x{register}, {base}, x17\n",
+                immediate(17, 8, offset as u32)
+            )
+        }
+    }
+
+    // Addressing of a memory allocation, with the code computing its address
+    // in `x17` when out of reach of the immediate offset of `ldr` and `str`.
+    fn memory_asm(&self, allocation: &Allocation) -> (String, String) {
+        let size = allocation_size(allocation);
+        let (base, offset) = match allocation {
+            Allocation::Stack { offset, .. } => ("sp", *offset),
+            Allocation::Indirect { offset, .. } => ("x9", *offset),
+            Allocation::Register { .. } | Allocation::Immediate(_) => panic!(),
+        };
+
+        if offset % size == 0 && offset / size <= 4095 {
+            (String::new(), format!("[{base}, #{offset}]"))
+        } else {
+            (
+                format!(
+                    "{}  add x17, {base}, x17\n",
+                    immediate(17, 8, offset as u32)
+                ),
+                "[x17]".to_string(),
+            )
+        }
+    }
+
+    // Call a function of the runtime following AAPCS64, arguments of 4 or 8
+    // bytes only. The returned value is left in `x9`.
+    fn runtime_call(&self, name: &str, arguments: &[Allocation]) -> String {
+        let mut asm = "\n".to_string();
+
+        // Arguments never live in the registers they are passed in.
+        for (register, argument) in arguments.iter().enumerate() {
+            asm.push_str(&self.load(register as u8, argument));
+        }
+
+        asm.push_str(&format!("  bl {name}\n  mov x9, x0\n\n"));
+
+        asm
+    }
+
+    // Table of the offsets of the vecs held by an element of the vec type
+    // `type_` and its length, as expected by `builtin_vec_new`. Elements
+    // holding no vec have no table.
+    fn heap_offsets_table(&mut self, type_: Type) -> Option<(usize, u32)> {
+        let Val::Value(&TypeData::Vec { element }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, 0, &mut offsets);
+
+        if offsets.is_empty() {
+            return None;
+        }
+
+        let count = offsets.len() as u32;
+        let table = match self.heap_offsets.iter().position(|table| *table == offsets) {
+            Some(table) => table,
+            None => {
+                self.heap_offsets.push(offsets);
+                self.heap_offsets.len() - 1
+            }
+        };
+
+        Some((table, count))
+    }
+
+    fn heap_offsets(&self, type_: Type, offset: u64, offsets: &mut Vec<u64>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(*field, offset + self.field_offset(type_, i), offsets);
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_size = self.type_size(element);
+                for i in 0..length as u64 {
+                    self.heap_offsets(element, offset + i * element_size, offsets);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.expr_type(expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    fn length_allocation(&self, base: Expr) -> Allocation {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(&TypeData::Array { length, .. }) => Allocation::Immediate(length),
+            Val::Value(TypeData::Slice { .. }) => {
+                self.offset_allocation(self.expr_allocation(base), 8, 4)
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Load in `x9` the address of the first element of an array or slice.
+    fn base_address(&self, base: Expr) -> String {
+        let base_allocation = self.expr_allocation(base);
+
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(TypeData::Array { .. }) => self.address(9, &base_allocation),
+            Val::Value(TypeData::Slice { .. }) => {
+                self.load(9, &self.offset_allocation(base_allocation, 0, 8))
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Load in `x9` the address of the element `index` of an array, a slice or
+    // a vec, checking the bounds.
+    fn element_address(
+        &self,
+        inst: Inst,
+        base: Expr,
+        index: Expr,
+        element_size: u64,
+        location: &Location,
+    ) -> String {
+        if self.is_vec(base) {
+            return self.runtime_call(
+                "builtin_vec_get",
+                &[
+                    self.expr_allocation(base),
+                    self.expr_allocation(index),
+                    Allocation::Immediate(element_size as u32),
+                    Allocation::Immediate(location.line),
+                    Allocation::Immediate(location.column),
+                ],
+            );
+        }
+
+        let inst_number = inst.as_u32();
+
+        format!(
+            "{}{}  cmp w11, w10\n  b.lo i{inst_number}_in_bounds\n{}i{inst_number}_in_bounds:\n{}{}  umaddl x9, w11, w12, x9\n",
+            self.load(11, &self.expr_allocation(index)),
+            self.load(10, &self.length_allocation(base)),
+            index_out_of_bounds(location),
+            self.base_address(base),
+            immediate(12, 4, element_size as u32),
+        )
+    }
+
+    fn classify(&self, type_: Type) -> Class {
+        match self.type_size(type_) {
+            0 => Class::None,
+            1..=16 => Class::Registers,
+            _ => Class::Memory,
+        }
+    }
+
+    // Split a value of the registers class in the parts passed in each
+    // register.
+    fn doublewords(&self, allocation: Allocation, size: u64) -> Vec<Allocation> {
+        if let Allocation::Register { .. } | Allocation::Immediate(_) = allocation {
+            return vec![allocation];
+        }
+
+        (0..size.div_ceil(8))
+            .map(|i| self.offset_allocation(allocation, i * 8, (size - i * 8).min(8)))
+            .collect()
+    }
+
+    #[track_caller]
+    fn expr_allocation(&self, expr: Expr) -> Allocation {
+        match expr {
+            Expr::Inst(inst) => self.insts_allocations[inst].unwrap(),
+            Expr::BlockArg(block) => self.args_allocations[block].unwrap(),
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => panic!(),
+                Val::Sentinel(sentinel) => match sentinel {
+                    ConstSentinel::Unit | ConstSentinel::False => Allocation::Immediate(0),
+                    ConstSentinel::True => Allocation::Immediate(1),
+                },
+                Val::Value(const_data) => match const_data {
+                    ConstData::Uint32(value) => Allocation::Immediate(*value),
+                    ConstData::Product(_, _) => panic!(),
+                },
+            },
+        }
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    fn reserve_stack_allocation(&mut self, size: u64, stack_size: &mut u64) -> Allocation {
+        *stack_size = stack_size.next_multiple_of(if size >= 8 { 8 } else { 4 });
+
+        let allocation = Allocation::Stack {
+            offset: *stack_size,
+            size,
+        };
+        *stack_size += size;
+        allocation
+    }
+
+    fn offset_allocation(&self, allocation: Allocation, offset: u64, size: u64) -> Allocation {
+        match allocation {
+            Allocation::Stack {
+                offset: base_offset,
+                ..
+            } => Allocation::Stack {
+                offset: base_offset + offset,
+                size,
+            },
+            Allocation::Indirect {
+                offset: base_offset,
+                ..
+            } => Allocation::Indirect {
+                offset: base_offset + offset,
+                size,
+            },
+            _ => panic!(),
+        }
+    }
+
+    fn type_size(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => 4,
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .fold(0u64, |offset, (_, field)| {
+                        offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+                    })
+                    .next_multiple_of(self.type_align(type_)),
+                TypeData::Array { element, length } => self.type_size(*element) * *length as u64,
+                // A pointer to the first element followed by the length, padded
+                // to keep the size a multiple of 8.
+                TypeData::Slice { .. } => 16,
+                // A pointer to the vec allocated by the runtime.
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    // Types are laid out as in C, so that values can be shared with C
+    // functions.
+    fn type_align(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit | TypeSentinel::Never => 1,
+                TypeSentinel::Bool
+                | TypeSentinel::False
+                | TypeSentinel::True
+                | TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .map(|(_, field)| self.type_align(*field))
+                    .max()
+                    .unwrap_or(1),
+                TypeData::Array { element, .. } => self.type_align(*element),
+                TypeData::Slice { .. } | TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn field_offset(&self, type_: Type, field: usize) -> u64 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .fold(0u64, |offset, (_, field)| {
+                offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+            })
+            .next_multiple_of(self.type_align(fields[field].1))
+    }
+}
+
+fn register_asm(register: u8, size: u64) -> String {
+    match size {
+        4 => format!("w{register}"),
+        8 => format!("x{register}"),
+        _ => panic!(),
+    }
+}
+
+fn allocation_size(allocation: &Allocation) -> u64 {
+    match allocation {
+        Allocation::Stack { size, .. }
+        | Allocation::Indirect { size, .. }
+        | Allocation::Register { size, .. } => *size,
+        Allocation::Immediate(_) => 4,
+    }
+}
+
+// Put `value` in the register `register`, 16 bits at a time.
+fn immediate(register: u8, size: u64, value: u32) -> String {
+    let register = register_asm(register, size);
+
+    let mut asm = format!("  mov {register}, #{}\n", value & 0xffff);
+    if value >> 16 != 0 {
+        asm.push_str(&format!("  movk {register}, #{}, lsl #16\n", value >> 16));
+    }
+    asm
+}
+
+// Report the failed bounds check, the runtime never returns.
+fn index_out_of_bounds(location: &Location) -> String {
+    format!(
+        "{}{}  bl builtin_index_out_of_bounds\n",
+        immediate(0, 4, location.line),
+        immediate(1, 4, location.column),
+    )
+}
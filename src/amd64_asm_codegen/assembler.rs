@@ -0,0 +1,321 @@
+// Assembler of the AT&T syntax emitted by the code generation into an ELF
+// relocatable object, without the system assembler.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::elf::{Object, Relocation, RelocationKind, SectionKind, Symbol};
+
+use super::encoder::{self, Encoder, Operand};
+
+pub fn assemble(asm: &str) -> Object {
+    let mut assembler = Assembler {
+        object: Object::default(),
+        encoders: Vec::new(),
+        section: 0,
+        symbols: HashMap::new(),
+        globals: HashSet::new(),
+        aliases: HashMap::new(),
+    };
+
+    assembler.section(SectionKind::Text);
+
+    for line in asm.lines() {
+        assembler.line(line);
+    }
+
+    assembler.finish()
+}
+
+struct Assembler {
+    object: Object,
+    // Encoders of the sections of `object`, at the same indices.
+    encoders: Vec<Encoder>,
+    section: usize,
+    symbols: HashMap<String, usize>,
+    globals: HashSet<String>,
+    aliases: HashMap<String, String>,
+}
+
+impl Assembler {
+    fn line(&mut self, line: &str) {
+        let line = match line.split_once('#') {
+            Some((line, _comment)) => line.trim(),
+            None => line.trim(),
+        };
+
+        if line.is_empty() {
+            return;
+        }
+
+        if let Some(string) = line.strip_prefix(".ascii ") {
+            let string = string
+                .trim()
+                .strip_prefix('"')
+                .and_then(|string| string.strip_suffix('"'))
+                .unwrap_or_else(|| panic!("invalid string {string}"))
+                .replace("\\n", "\n");
+            self.code().extend(string.as_bytes());
+            return;
+        }
+
+        if let Some(label) = line.strip_suffix(':') {
+            let offset = self.code().len() as u64;
+            self.define(label.to_string(), self.section, offset);
+            return;
+        }
+
+        let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, split_operands(operands.trim())),
+            None => (line, Vec::new()),
+        };
+
+        match (mnemonic, operands.as_slice()) {
+            (".code64", []) => {}
+            (".text", []) => self.section(SectionKind::Text),
+            (".data", []) => self.section(SectionKind::Data),
+            (".section", [".rodata"]) => self.section(SectionKind::Rodata),
+            (".global" | ".globl", [name]) => {
+                self.globals.insert(name.to_string());
+            }
+            (".set", [name, target]) => {
+                self.aliases.insert(name.to_string(), target.to_string());
+            }
+            (".byte", values) => {
+                for value in values {
+                    let value = immediate(value) as u8;
+                    self.code().push(value);
+                }
+            }
+            (".long", values) => {
+                for value in values {
+                    let value = immediate(value) as u32;
+                    self.code().extend(value.to_le_bytes());
+                }
+            }
+            (".quad", values) => {
+                for value in values {
+                    match value.parse::<i64>() {
+                        Ok(value) => self.code().extend(value.to_le_bytes()),
+                        Err(_) => {
+                            let symbol = self.reference(value);
+                            let offset = self.code().len() as u64;
+                            self.object.sections[self.section]
+                                .relocations
+                                .push(Relocation {
+                                    offset,
+                                    symbol,
+                                    kind: RelocationKind::Absolute64,
+                                    addend: 0,
+                                });
+                            self.code().extend([0; 8]);
+                        }
+                    }
+                }
+            }
+            (directive, _) if directive.starts_with('.') => {
+                panic!("unsupported directive {line}")
+            }
+            (mnemonic, operands) => {
+                let operands = operands
+                    .iter()
+                    .map(|operand| parse_operand(operand))
+                    .collect::<Vec<_>>();
+                self.encoders[self.section].encode(mnemonic, &operands);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Object {
+        let mut aliases = self.aliases.keys().cloned().collect::<Vec<_>>();
+        aliases.sort();
+        for alias in aliases {
+            let target = self.resolve(&alias).to_string();
+            if let Some(&symbol) = self.symbols.get(&target) {
+                let Symbol { section, value, .. } = self.object.symbols[symbol];
+                if let Some(section) = section {
+                    self.define(alias, section, value);
+                }
+            }
+        }
+
+        for (section, encoder) in std::mem::take(&mut self.encoders).into_iter().enumerate() {
+            let mut code = encoder.code;
+
+            for fixup in encoder.fixups {
+                let symbol = self.reference(&fixup.label);
+                let offset = fixup.offset as u64;
+
+                match self.object.symbols[symbol] {
+                    // Displacements within a section are known.
+                    Symbol {
+                        section: Some(other_section),
+                        value,
+                        ..
+                    } if other_section == section => {
+                        let displacement = value as i64 - (offset as i64 + 4);
+                        code[fixup.offset..fixup.offset + 4]
+                            .copy_from_slice(&(displacement as i32).to_le_bytes());
+                    }
+                    Symbol {
+                        section: other_section,
+                        ..
+                    } => {
+                        let kind = match other_section {
+                            Some(_) => RelocationKind::Pc32,
+                            None => RelocationKind::Plt32,
+                        };
+                        self.object.sections[section].relocations.push(Relocation {
+                            offset,
+                            symbol,
+                            kind,
+                            addend: -4,
+                        });
+                    }
+                }
+            }
+
+            self.object.sections[section].data = code;
+        }
+
+        for symbol in &mut self.object.symbols {
+            symbol.global = symbol.section.is_none() || self.globals.contains(&symbol.name);
+        }
+
+        self.object
+    }
+
+    fn section(&mut self, kind: SectionKind) {
+        self.section = self.object.section(kind);
+        if self.encoders.len() < self.object.sections.len() {
+            self.encoders.push(Encoder::default());
+        }
+    }
+
+    fn code(&mut self) -> &mut Vec<u8> {
+        &mut self.encoders[self.section].code
+    }
+
+    fn define(&mut self, name: String, section: usize, value: u64) {
+        match self.symbols.get(&name) {
+            Some(&symbol) => {
+                let symbol = &mut self.object.symbols[symbol];
+                if symbol.section.is_some() {
+                    panic!("symbol {name} defined twice");
+                }
+                symbol.section = Some(section);
+                symbol.value = value;
+            }
+            None => {
+                self.symbols.insert(name.clone(), self.object.symbols.len());
+                self.object.symbols.push(Symbol {
+                    name,
+                    section: Some(section),
+                    value,
+                    global: false,
+                });
+            }
+        }
+    }
+
+    // Index of the symbol referenced by `name`, undefined symbols are global
+    // and resolved by the linker.
+    fn reference(&mut self, name: &str) -> usize {
+        let name = self.resolve(name).to_string();
+
+        match self.symbols.get(&name) {
+            Some(&symbol) => symbol,
+            None => {
+                self.symbols.insert(name.clone(), self.object.symbols.len());
+                self.object.symbols.push(Symbol {
+                    name,
+                    section: None,
+                    value: 0,
+                    global: true,
+                });
+                self.object.symbols.len() - 1
+            }
+        }
+    }
+
+    fn resolve<'a>(&'a self, mut name: &'a str) -> &'a str {
+        while let Some(target) = self.aliases.get(name) {
+            name = target;
+        }
+        name
+    }
+}
+
+// Split at the commas outside of parentheses.
+fn split_operands(operands: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (index, character) in operands.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(operands[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    split.push(operands[start..].trim());
+    split
+}
+
+fn parse_operand(operand: &str) -> Operand {
+    if let Some(name) = operand.strip_prefix('%') {
+        return encoder::register(name).unwrap_or_else(|| panic!("unknown register {operand}"));
+    }
+
+    if let Some(value) = operand.strip_prefix('$') {
+        return Operand::Immediate(immediate(value));
+    }
+
+    if let Some(label) = operand.strip_suffix("(%rip)") {
+        return Operand::RipRelative(label.to_string());
+    }
+
+    if let Some((displacement, base)) = operand.split_once('(') {
+        let base = base
+            .strip_prefix('%')
+            .and_then(|base| base.strip_suffix(')'))
+            .and_then(encoder::register);
+
+        let Some(Operand::Register { number, size: 8 }) = base else {
+            panic!("unsupported memory operand {operand}");
+        };
+
+        let displacement = match displacement {
+            "" => 0,
+            displacement => immediate(displacement) as i32,
+        };
+
+        return Operand::Memory {
+            base: number,
+            displacement,
+        };
+    }
+
+    Operand::Label(operand.to_string())
+}
+
+// Parse a decimal or `0x` hexadecimal integer.
+fn immediate(value: &str) -> i64 {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+
+    let value = match value.strip_prefix("0x") {
+        Some(value) => i64::from_str_radix(value, 16),
+        None => value.parse(),
+    }
+    .unwrap_or_else(|_| panic!("invalid integer {value}"));
+
+    if negative { -value } else { value }
+}
@@ -0,0 +1,348 @@
+// Encoder for the subset of x86-64 instructions emitted by the code
+// generation, with operands in the AT&T order.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    // Registers are numbered as in their encoding, sizes are in bytes.
+    Register { number: u8, size: u8 },
+    Immediate(i64),
+    Memory { base: u8, displacement: i32 },
+    // Address of a label relative to `%rip`, only in instructions without an
+    // immediate as its displacement is relative to the end of the
+    // instruction.
+    RipRelative(String),
+    Label(String),
+}
+
+// A 32 bits displacement relative to the end of its instruction, patched once
+// the address of `label` is known.
+#[derive(Debug)]
+pub struct Fixup {
+    pub offset: usize,
+    pub label: String,
+}
+
+#[derive(Default)]
+pub struct Encoder {
+    pub code: Vec<u8>,
+    pub fixups: Vec<Fixup>,
+}
+
+const RSP: u8 = 4;
+const RBP: u8 = 5;
+
+// Mnemonics taking an optional `l` or `q` suffix for the size of their
+// operands.
+const SIZED_MNEMONICS: [&str; 16] = [
+    "mov", "add", "sub", "and", "xor", "cmp", "test", "mul", "div", "imul", "shl", "shr", "bsf",
+    "lea", "push", "pop",
+];
+
+impl Encoder {
+    pub fn encode(&mut self, mnemonic: &str, operands: &[Operand]) {
+        let (mnemonic, size) = operation_size(mnemonic, operands);
+
+        match (mnemonic, operands) {
+            ("ret", []) => self.code.push(0xc3),
+            ("syscall", []) => self.code.extend([0x0f, 0x05]),
+            ("call", [Operand::Label(label)]) => {
+                self.code.push(0xe8);
+                self.rel32(label);
+            }
+            ("jmp", [Operand::Label(label)]) => {
+                self.code.push(0xe9);
+                self.rel32(label);
+            }
+            (jump, [Operand::Label(label)]) if condition_code(jump).is_some() => {
+                self.code
+                    .extend([0x0f, 0x80 | condition_code(jump).unwrap()]);
+                self.rel32(label);
+            }
+            ("push", [Operand::Register { number, size: 8 }]) => {
+                self.rex(false, 0, *number, false);
+                self.code.push(0x50 | (number & 7));
+            }
+            ("push", [Operand::Immediate(value)]) => match i8::try_from(*value) {
+                Ok(value) => self.code.extend([0x6a, value as u8]),
+                Err(_) => {
+                    self.code.push(0x68);
+                    self.code.extend(imm32(*value));
+                }
+            },
+            ("push", [memory @ Operand::Memory { .. }]) => {
+                self.modrm(&[0xff], false, 6, memory);
+            }
+            ("pop", [Operand::Register { number, size: 8 }]) => {
+                self.rex(false, 0, *number, false);
+                self.code.push(0x58 | (number & 7));
+            }
+            ("mov", [source, destination]) => self.mov(size, source, destination),
+            (
+                "movzbl",
+                [
+                    source @ (Operand::Register { size: 1, .. } | Operand::Memory { .. }),
+                    Operand::Register {
+                        number: destination,
+                        size: 4,
+                    },
+                ],
+            ) => self.modrm(&[0x0f, 0xb6], false, *destination, source),
+            (
+                "lea",
+                [
+                    memory @ (Operand::Memory { .. } | Operand::RipRelative(_)),
+                    Operand::Register { number, size: 8 },
+                ],
+            ) => {
+                self.modrm(&[0x8d], true, *number, memory);
+            }
+            ("add", [source, destination]) => self.arithmetic(0, size, source, destination),
+            ("and", [source, destination]) => self.arithmetic(4, size, source, destination),
+            ("sub", [source, destination]) => self.arithmetic(5, size, source, destination),
+            ("xor", [source, destination]) => self.arithmetic(6, size, source, destination),
+            ("cmp", [source, destination]) => self.arithmetic(7, size, source, destination),
+            (
+                "imul",
+                [
+                    Operand::Immediate(value),
+                    register @ Operand::Register { number, .. },
+                ],
+            ) => match i8::try_from(*value) {
+                Ok(value) => {
+                    self.modrm(&[0x6b], size == 8, *number, register);
+                    self.code.push(value as u8);
+                }
+                Err(_) => {
+                    self.modrm(&[0x69], size == 8, *number, register);
+                    self.code.extend(imm32(*value));
+                }
+            },
+            ("imul", [source, Operand::Register { number, .. }]) => {
+                self.modrm(&[0x0f, 0xaf], size == 8, *number, source);
+            }
+            ("shl", [Operand::Immediate(value), destination]) => {
+                self.modrm(&[0xc1], size == 8, 4, destination);
+                self.code.push(*value as u8);
+            }
+            ("shr", [Operand::Immediate(value), destination]) => {
+                self.modrm(&[0xc1], size == 8, 5, destination);
+                self.code.push(*value as u8);
+            }
+            ("bsf", [source, Operand::Register { number, .. }]) => {
+                self.modrm(&[0x0f, 0xbc], size == 8, *number, source);
+            }
+            ("mul", [operand]) => self.modrm(&[0xf7], size == 8, 4, operand),
+            ("div", [operand]) => self.modrm(&[0xf7], size == 8, 6, operand),
+            ("sete", [register @ Operand::Register { size: 1, .. }]) => {
+                self.modrm(&[0x0f, 0x94], false, 0, register);
+            }
+            ("test", [Operand::Register { number, .. }, operand]) => {
+                self.modrm(&[0x85], size == 8, *number, operand);
+            }
+            _ => panic!("unsupported instruction {mnemonic} {operands:?}"),
+        }
+    }
+
+    fn mov(&mut self, size: u8, source: &Operand, destination: &Operand) {
+        let wide = size == 8;
+
+        match (source, destination) {
+            (Operand::Register { number, .. }, destination) if size == 1 => {
+                self.modrm(&[0x88], false, *number, destination);
+            }
+            (Operand::Immediate(value), destination) if size == 1 => {
+                self.modrm(&[0xc6], false, 0, destination);
+                self.code.push(*value as u8);
+            }
+            (Operand::Register { number, .. }, destination) => {
+                self.modrm(&[0x89], wide, *number, destination);
+            }
+            (source @ Operand::Memory { .. }, Operand::Register { number, .. }) => {
+                self.modrm(&[0x8b], wide, *number, source);
+            }
+            (Operand::Immediate(value), Operand::Register { number, size: 4 }) => {
+                self.rex(false, 0, *number, false);
+                self.code.push(0xb8 | (number & 7));
+                self.code.extend(imm32(*value));
+            }
+            // Sign extended to 64 bits for wide moves.
+            (Operand::Immediate(value), destination) => {
+                self.modrm(&[0xc7], wide, 0, destination);
+                self.code.extend(imm32(*value));
+            }
+            _ => panic!("unsupported mov {source:?}, {destination:?}"),
+        }
+    }
+
+    // `operation` is the number of the operation in the encoding of the
+    // arithmetic instructions, as in the `/digit` of their immediate form.
+    fn arithmetic(&mut self, operation: u8, size: u8, source: &Operand, destination: &Operand) {
+        let wide = size == 8;
+
+        match (source, destination) {
+            (Operand::Immediate(value), destination) => match i8::try_from(*value) {
+                Ok(value) => {
+                    self.modrm(&[0x83], wide, operation, destination);
+                    self.code.push(value as u8);
+                }
+                Err(_) => {
+                    self.modrm(&[0x81], wide, operation, destination);
+                    self.code.extend(imm32(*value));
+                }
+            },
+            (Operand::Register { number, .. }, destination) => {
+                self.modrm(&[operation << 3 | 0x01], wide, *number, destination);
+            }
+            (source @ Operand::Memory { .. }, Operand::Register { number, .. }) => {
+                self.modrm(&[operation << 3 | 0x03], wide, *number, source);
+            }
+            _ => panic!("unsupported arithmetic {source:?}, {destination:?}"),
+        }
+    }
+
+    // Emit `opcode` followed by the ModRM byte addressing `operand`, with
+    // `reg` in the middle field and the REX prefix when needed.
+    fn modrm(&mut self, opcode: &[u8], wide: bool, reg: u8, operand: &Operand) {
+        match operand {
+            Operand::Register { number, size } => {
+                // Without a REX prefix the byte registers 4 to 7 are `%ah` to
+                // `%bh`.
+                let byte_register = *size == 1 && (4..8).contains(number);
+                self.rex(wide, reg, *number, byte_register);
+                self.code.extend(opcode);
+                self.code.push(0xc0 | (reg & 7) << 3 | (number & 7));
+            }
+            Operand::Memory { base, displacement } => {
+                self.rex(wide, reg, *base, false);
+                self.code.extend(opcode);
+
+                // `%rbp` and `%r13` as a base always need a displacement.
+                let mode = match i8::try_from(*displacement) {
+                    Ok(0) if base & 7 != RBP => 0b00,
+                    Ok(_) => 0b01,
+                    Err(_) => 0b10,
+                };
+
+                self.code.push(mode << 6 | (reg & 7) << 3 | (base & 7));
+
+                // `%rsp` and `%r12` as a base need a SIB byte.
+                if base & 7 == RSP {
+                    self.code.push(0x24);
+                }
+
+                match mode {
+                    0b01 => self.code.push(*displacement as u8),
+                    0b10 => self.code.extend(displacement.to_le_bytes()),
+                    _ => {}
+                }
+            }
+            Operand::RipRelative(label) => {
+                self.rex(wide, reg, 0, false);
+                self.code.extend(opcode);
+                self.code.push((reg & 7) << 3 | RBP);
+                self.rel32(label);
+            }
+            Operand::Immediate(_) | Operand::Label(_) => panic!("{operand:?} is not addressable"),
+        }
+    }
+
+    fn rex(&mut self, wide: bool, reg: u8, base: u8, force: bool) {
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3 & 1) << 2 | (base >> 3 & 1);
+
+        if rex != 0x40 || force {
+            self.code.push(rex);
+        }
+    }
+
+    fn rel32(&mut self, label: &str) {
+        self.fixups.push(Fixup {
+            offset: self.code.len(),
+            label: label.to_string(),
+        });
+        self.code.extend([0; 4]);
+    }
+}
+
+// Size in bytes of the operands of an instruction, from the suffix of its
+// mnemonic or from its register operands.
+fn operation_size<'a>(mnemonic: &'a str, operands: &[Operand]) -> (&'a str, u8) {
+    for base in SIZED_MNEMONICS {
+        if let Some(suffix) = mnemonic.strip_prefix(base) {
+            match suffix {
+                "b" => return (base, 1),
+                "l" => return (base, 4),
+                "q" => return (base, 8),
+                _ => {}
+            }
+        }
+    }
+
+    // Unsuffixed memory operations without registers are 64 bits.
+    let size = operands
+        .iter()
+        .rev()
+        .find_map(|operand| match operand {
+            Operand::Register { size, .. } => Some(*size),
+            _ => None,
+        })
+        .unwrap_or(8);
+
+    (mnemonic, size)
+}
+
+fn condition_code(jump: &str) -> Option<u8> {
+    match jump {
+        "jb" => Some(0x2),
+        "jae" => Some(0x3),
+        "je" | "jz" => Some(0x4),
+        "jne" | "jnz" => Some(0x5),
+        "jbe" => Some(0x6),
+        "ja" => Some(0x7),
+        _ => None,
+    }
+}
+
+fn imm32(value: i64) -> [u8; 4] {
+    i32::try_from(value)
+        .or_else(|_| u32::try_from(value).map(|value| value as i32))
+        .unwrap()
+        .to_le_bytes()
+}
+
+// Parse a register name without its `%`.
+pub fn register(name: &str) -> Option<Operand> {
+    const NAMES: [[&str; 3]; 8] = [
+        ["rax", "eax", "al"],
+        ["rcx", "ecx", "cl"],
+        ["rdx", "edx", "dl"],
+        ["rbx", "ebx", "bl"],
+        ["rsp", "esp", "spl"],
+        ["rbp", "ebp", "bpl"],
+        ["rsi", "esi", "sil"],
+        ["rdi", "edi", "dil"],
+    ];
+
+    for (number, names) in NAMES.iter().enumerate() {
+        for (name_, size) in names.iter().zip([8, 4, 1]) {
+            if name == *name_ {
+                return Some(Operand::Register {
+                    number: number as u8,
+                    size,
+                });
+            }
+        }
+    }
+
+    let (number, size) = match name.strip_prefix('r')? {
+        name if name.ends_with('d') => (&name[..name.len() - 1], 4),
+        name if name.ends_with('b') => (&name[..name.len() - 1], 1),
+        name => (name, 8),
+    };
+
+    let number = number
+        .parse::<u8>()
+        .ok()
+        .filter(|number| (8..16).contains(number))?;
+
+    Some(Operand::Register { number, size })
+}
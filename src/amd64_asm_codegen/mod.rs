@@ -0,0 +1,1424 @@
+mod assembler;
+mod encoder;
+mod register_allocation;
+
+use std::borrow::Cow;
+
+pub use self::assembler::assemble;
+
+use crate::{
+    key_vec::{KeyVec, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+    ssa::{
+        Block, BlockData, BlockSentinel, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData,
+        InstSentinel, Location, Ssa,
+    },
+};
+
+pub fn generate(types: &Types, ssa: &Ssa) -> String {
+    let mut generator = Generator {
+        types,
+        ssa,
+        blocks: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| String::new()).collect()),
+        args_allocations: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        insts_allocations: KeyVec::from_vec((0..ssa.insts.len()).map(|_| None).collect()),
+        argument_areas: KeyVec::from_vec(vec![0; ssa.blocks.len()]),
+        heap_offsets: Vec::new(),
+    };
+
+    generator.generate();
+
+    generator.result()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Allocation {
+    Stack { offset: u64, size: u64 },
+    StackArgument { offset: u64, size: u64 },
+    // Argument or return value of a called function, relative to `%rsp`.
+    CallArea { offset: u64, size: u64 },
+    Register { register: Register, size: u64 },
+    // Memory pointed by `%r10`, only valid within the generated code of a
+    // single instruction.
+    Indirect { offset: u64, size: u64 },
+    Immediate(u32),
+}
+
+impl Allocation {
+    const EAX: Allocation = Allocation::Register {
+        register: Register::Rax,
+        size: 4,
+    };
+    const EDX: Allocation = Allocation::Register {
+        register: Register::Rdx,
+        size: 4,
+    };
+    // An address held in `%r10` itself.
+    const R10: Allocation = Allocation::Register {
+        register: Register::R10,
+        size: 8,
+    };
+    const R11: Allocation = Allocation::Register {
+        register: Register::R11,
+        size: 8,
+    };
+    const R11D: Allocation = Allocation::Register {
+        register: Register::R11,
+        size: 4,
+    };
+}
+
+// Class of an argument or a returned value in the System V ABI, keb has no
+// floating point values so every eightbyte is of the integer class.
+enum Class {
+    None,
+    // Passed in one register for each eightbyte.
+    Registers,
+    // Passed on the stack, or written to a pointer given by the caller when
+    // returned.
+    Memory,
+}
+
+const ARGUMENT_REGISTERS: [Register; 6] = [
+    Register::Rdi,
+    Register::Rsi,
+    Register::Rdx,
+    Register::Rcx,
+    Register::R8,
+    Register::R9,
+];
+
+const RETURN_REGISTERS: [Register; 2] = [Register::Rax, Register::Rdx];
+
+// `%r10` and `%r11` are scratch registers used by the generated code of a
+// single instruction, they are never reserved for a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Register {
+    Rax,
+    Rbx,
+    Rcx,
+    Rdx,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+struct Generator<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    blocks: KeyVec<BlockSentinel, String>,
+    args_allocations: KeyVec<BlockSentinel, Option<Allocation>>,
+    insts_allocations: KeyVec<InstSentinel, Option<Allocation>>,
+    // Size of the stack arguments given by the callers of each function.
+    argument_areas: KeyVec<BlockSentinel, u64>,
+    // Offsets of the vecs held by the elements of vecs, each table is at the
+    // label `heap_offsets{i}` of `.rodata`.
+    heap_offsets: Vec<Vec<u64>>,
+}
+
+struct Frame<'a> {
+    name: &'a str,
+    return_type: Type,
+    // Where to write the returned value when it is of the memory class.
+    return_pointer: Option<Allocation>,
+    // Callee saved registers used by the function, with the stack slot
+    // holding the value of the caller.
+    saved_registers: Vec<(Register, Allocation)>,
+    stack_size: u64,
+    call_area_size: u64,
+}
+
+impl Generator<'_> {
+    fn result(self) -> String {
+        let mut asm = String::new();
+
+        asm.push_str(".code64\n");
+        asm.push_str(".global main\n\n");
+        asm.extend(self.blocks.entries().flat_map(|(_, asm)| [asm, "\n"]));
+
+        if !self.heap_offsets.is_empty() {
+            asm.push_str(".section .rodata\n");
+            for (i, offsets) in self.heap_offsets.iter().enumerate() {
+                asm.push_str(&format!(
+                    "heap_offsets{i}:\n  .long {}\n",
+                    offsets
+                        .iter()
+                        .map(|offset| offset.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        // Read by the runtime to report the traps.
+        asm.push_str(&format!(
+            ".section .rodata\n.global builtin_source_path\nbuiltin_source_path:\n  .byte {}\n",
+            self.ssa
+                .path
+                .bytes()
+                .chain([0])
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+
+        asm
+    }
+
+    fn generate(&mut self) {
+        self.size_argument_areas();
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            match block_data {
+                BlockData::ExternFunction { name, .. } => {
+                    self.blocks[block] = format!(".set f{}_{name}, {name}\n", block.as_u32());
+                }
+                BlockData::Function { .. } => self.generate_function(block),
+                BlockData::Block { .. } => {}
+            }
+        }
+    }
+
+    // Tail calls write the arguments of the memory class where the caller
+    // wrote theirs, the callers give as much space as the arguments of the
+    // functions reached through tail calls.
+    fn size_argument_areas(&mut self) {
+        for (function, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::ExternFunction { arg, .. } | BlockData::Function { arg, .. } =
+                block_data
+                && let Class::Memory = self.classify(*arg)
+            {
+                self.argument_areas[function] = self.type_size(*arg);
+            }
+        }
+
+        let tail_calls = self.ssa.tail_calls();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(caller, callee) in &tail_calls {
+                if self.argument_areas[callee] > self.argument_areas[caller] {
+                    self.argument_areas[caller] = self.argument_areas[callee];
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn generate_function(&mut self, function: Block) {
+        let ssa = self.ssa;
+
+        let BlockData::Function {
+            name,
+            arg,
+            ret,
+            insts,
+        } = &ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let mut frame = Frame {
+            name,
+            return_type: *ret,
+            return_pointer: None,
+            saved_registers: Vec::new(),
+            stack_size: 0,
+            call_area_size: 0,
+        };
+
+        // Blocks are emitted in the order they were created, which is also
+        // the order in which their values are defined.
+        let cfg = Cfg::new(self.ssa, function);
+        let mut blocks = cfg.blocks()[1..].to_vec();
+        blocks.sort_by_key(|block| block.as_u32());
+
+        self.allocate_registers(function, &blocks, &cfg.liveness(self.ssa), &mut frame);
+
+        let mut body = String::new();
+
+        // The pointer to the returned value is saved before the argument is
+        // read, as the argument may be allocated to its register.
+        if let Class::Memory = self.classify(*ret) {
+            let allocation = self.reserve_stack_allocation(8, &mut frame.stack_size);
+            frame.return_pointer = Some(allocation);
+            body.push_str(&format!("  mov %rdi, {}\n", allocation_asm(&allocation)));
+        }
+
+        if let Class::Registers = self.classify(*arg) {
+            let allocation = self.expr_allocation(Expr::BlockArg(function));
+            let registers = self.argument_registers(*ret);
+
+            for (eightbyte, register) in self
+                .eightbytes(allocation, self.type_size(*arg))
+                .into_iter()
+                .zip(registers)
+            {
+                body.push_str(&self.move_(
+                    &Allocation::Register {
+                        register: *register,
+                        size: allocation_size(&eightbyte),
+                    },
+                    &eightbyte,
+                ));
+            }
+        }
+
+        for inst in insts {
+            body.push_str(&self.generate_inst(*inst, &mut frame));
+        }
+
+        for block in blocks {
+            let BlockData::Block { insts, .. } = &ssa.blocks[block] else {
+                panic!()
+            };
+
+            body.push_str(&format!("b{}:\n", block.as_u32()));
+            for inst in insts {
+                body.push_str(&self.generate_inst(*inst, &mut frame));
+            }
+        }
+
+        let mut asm = String::new();
+
+        if name == "main" {
+            asm.push_str(&format!(".set main, f{}_{name}\n", function.as_u32()));
+        }
+
+        asm.push_str(&format!("f{}_{name}:\n", function.as_u32()));
+
+        // The frame holds every value of the function and, at its bottom, the
+        // arguments and return values of the called functions. Its size is
+        // kept a multiple of 16 so that calls are aligned.
+        let frame_size = (frame.stack_size + frame.call_area_size).next_multiple_of(16);
+
+        asm.push_str("  push %rbp\n");
+        asm.push_str("  mov %rsp, %rbp\n");
+        asm.push_str(&format!("  sub ${frame_size}, %rsp\n"));
+        for (register, allocation) in &frame.saved_registers {
+            asm.push_str(&format!(
+                "  mov {}, {}\n",
+                register_asm(*register, 8),
+                allocation_asm(allocation)
+            ));
+        }
+        asm.push('\n');
+        asm.push_str(&body);
+
+        self.blocks[function] = asm;
+    }
+
+    fn generate_inst(&mut self, inst: Inst, frame: &mut Frame) -> String {
+        match &self.ssa.insts[inst] {
+            InstData::Field(expr, field) => {
+                let allocation = self.expr_allocation(*expr);
+
+                let expr_type = self.expr_type(*expr);
+
+                let field_offset = self.field_offset(expr_type, *field as usize);
+
+                let field_type = match self.types.get(expr_type) {
+                    Val::None => panic!(),
+                    Val::Sentinel(_) => panic!(),
+                    Val::Value(type_data) => match type_data {
+                        TypeData::Function { .. }
+                        | TypeData::Array { .. }
+                        | TypeData::Slice { .. }
+                        | TypeData::Pointer { .. }
+                        | TypeData::Vec { .. } => panic!(),
+                        TypeData::Product { fields } => fields[*field as usize].1,
+                    },
+                };
+
+                let field_size = self.type_size(field_type);
+                let field_allocation = self.expr_allocation(Expr::Inst(inst));
+
+                let source_allocation =
+                    self.offset_allocation(allocation, field_offset, field_size);
+
+                self.move_(&source_allocation, &field_allocation)
+            }
+            InstData::Record(fields, type_) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                let mut inst_asm = String::new();
+
+                for (i, field) in fields.iter().enumerate() {
+                    let field_allocation = self.expr_allocation(*field);
+                    let field_size = self.type_size(self.expr_type(*field));
+                    inst_asm.push_str(&self.move_(
+                        &field_allocation,
+                        &self.offset_allocation(
+                            allocation,
+                            self.field_offset(*type_, i),
+                            field_size,
+                        ),
+                    ));
+                }
+
+                inst_asm
+            }
+            InstData::Array(elements, _) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                let mut inst_asm = String::new();
+
+                let mut offset = 0;
+                for element in elements {
+                    let element_allocation = self.expr_allocation(*element);
+                    let element_size = self.type_size(self.expr_type(*element));
+                    inst_asm.push_str(&self.move_(
+                        &element_allocation,
+                        &self.offset_allocation(allocation, offset, element_size),
+                    ));
+
+                    offset += element_size;
+                }
+
+                inst_asm
+            }
+            InstData::Length(base) if self.is_vec(*base) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                // The length follows the pointer to the elements.
+                format!(
+                    "  mov {}, %r10\n{}",
+                    allocation_asm(&self.expr_allocation(*base)),
+                    self.move_(&Allocation::Indirect { offset: 8, size: 4 }, &allocation),
+                )
+            }
+            InstData::Length(base) => {
+                let base_allocation = self.expr_allocation(*base);
+                let length_allocation = self.length_allocation(*base, base_allocation);
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                self.move_(&length_allocation, &allocation)
+            }
+            InstData::Load {
+                base,
+                index,
+                location,
+            } if self.is_vec(*base) => {
+                let element_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_get",
+                    &[
+                        self.expr_allocation(*base),
+                        self.expr_allocation(*index),
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(location.line),
+                        Allocation::Immediate(location.column),
+                    ],
+                );
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                inst_asm.push_str(&self.move_(
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                    &allocation,
+                ));
+
+                inst_asm
+            }
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let element_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let mut inst_asm = String::new();
+                let base_allocation =
+                    self.memory_allocation(*base, &mut frame.stack_size, &mut inst_asm);
+
+                inst_asm.push_str(&self.element_address(
+                    inst,
+                    *base,
+                    base_allocation,
+                    *index,
+                    element_size,
+                    location,
+                ));
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                inst_asm.push_str(&self.move_(
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                    &allocation,
+                ));
+
+                inst_asm
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } if self.is_vec(*base) => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_get",
+                    &[
+                        self.expr_allocation(*base),
+                        self.expr_allocation(*index),
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(location.line),
+                        Allocation::Immediate(location.column),
+                    ],
+                );
+
+                inst_asm.push_str(&self.move_(
+                    &self.expr_allocation(*value),
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                ));
+
+                inst_asm
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let mut inst_asm = String::new();
+                let base_allocation =
+                    self.memory_allocation(*base, &mut frame.stack_size, &mut inst_asm);
+
+                inst_asm.push_str(&self.element_address(
+                    inst,
+                    *base,
+                    base_allocation,
+                    *index,
+                    element_size,
+                    location,
+                ));
+
+                inst_asm.push_str(&self.move_(
+                    &self.expr_allocation(*value),
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                ));
+
+                inst_asm
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            } => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Slice { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let mut inst_asm = String::new();
+                let base_allocation =
+                    self.memory_allocation(*base, &mut frame.stack_size, &mut inst_asm);
+                let length_allocation = self.length_allocation(*base, base_allocation);
+
+                let inst_number = inst.as_u32();
+
+                inst_asm.push_str(&format!(
+                    "  movl {}, %r10d\n{}  cmpl %r11d, %r10d\n  ja i{inst_number}_out_of_bounds\n  cmpl {}, %r11d\n  jbe i{inst_number}_in_bounds\ni{inst_number}_out_of_bounds:\n{}i{inst_number}_in_bounds:\n",
+                    allocation_asm(&self.expr_allocation(*start)),
+                    self.move_(&self.expr_allocation(*end), &Allocation::R11D),
+                    allocation_asm(&length_allocation),
+                    index_out_of_bounds(location),
+                ));
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                // The length is `end - start`, the pointer is the base
+                // address offsetted by `start` elements.
+                inst_asm.push_str(&format!(
+                    "  subl %r10d, %r11d\n  movl %r11d, {}\n  imul ${element_size}, %r10\n  mov %r10, %r11\n{}  add %r11, %r10\n  mov %r10, {}\n",
+                    allocation_asm(&self.offset_allocation(allocation, 8, 4)),
+                    self.base_address(*base, base_allocation),
+                    allocation_asm(&self.offset_allocation(allocation, 0, 8)),
+                ));
+
+                inst_asm
+            }
+            InstData::NewVec(elements, type_) => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Vec { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let mut inst_asm = String::new();
+
+                // The runtime copies the elements from the stack.
+                let elements_address = if elements.is_empty() {
+                    Allocation::Immediate(0)
+                } else {
+                    let elements_allocation = self.reserve_stack_allocation(
+                        element_size * elements.len() as u64,
+                        &mut frame.stack_size,
+                    );
+
+                    for (i, element) in elements.iter().enumerate() {
+                        inst_asm.push_str(&self.move_(
+                            &self.expr_allocation(*element),
+                            &self.offset_allocation(
+                                elements_allocation,
+                                i as u64 * element_size,
+                                element_size,
+                            ),
+                        ));
+                    }
+
+                    inst_asm.push_str(&format!(
+                        "  lea {}, %r10\n",
+                        allocation_asm(&elements_allocation)
+                    ));
+
+                    Allocation::R10
+                };
+
+                let (heap_offsets, heap_count) = match self.heap_offsets_table(*type_) {
+                    Some((table, count)) => {
+                        inst_asm.push_str(&format!("  lea heap_offsets{table}(%rip), %r11\n"));
+                        (Allocation::R11, count)
+                    }
+                    None => (Allocation::Immediate(0), 0),
+                };
+
+                inst_asm.push_str(&self.runtime_call(
+                    "builtin_vec_new",
+                    &[
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(elements.len() as u32),
+                        elements_address,
+                        heap_offsets,
+                        Allocation::Immediate(heap_count),
+                    ],
+                ));
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                inst_asm.push_str(&format!("  mov %r10, {}\n", allocation_asm(&allocation)));
+
+                inst_asm
+            }
+            InstData::Push { vec, value } => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_push",
+                    &[
+                        self.expr_allocation(*vec),
+                        Allocation::Immediate(element_size as u32),
+                    ],
+                );
+
+                inst_asm.push_str(&self.move_(
+                    &self.expr_allocation(*value),
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                ));
+
+                inst_asm
+            }
+            InstData::Pop { vec, location } => {
+                let element_size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let mut inst_asm = self.runtime_call(
+                    "builtin_vec_pop",
+                    &[
+                        self.expr_allocation(*vec),
+                        Allocation::Immediate(element_size as u32),
+                        Allocation::Immediate(location.line),
+                        Allocation::Immediate(location.column),
+                    ],
+                );
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                inst_asm.push_str(&self.move_(
+                    &Allocation::Indirect {
+                        offset: 0,
+                        size: element_size,
+                    },
+                    &allocation,
+                ));
+
+                inst_asm
+            }
+            InstData::Alloca(type_) => {
+                let pointee_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Pointer { pointee }) => self.type_size(*pointee),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let memory = self.reserve_stack_allocation(pointee_size, &mut frame.stack_size);
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                format!(
+                    "  lea {}, %r10\n  mov %r10, {}\n",
+                    allocation_asm(&memory),
+                    allocation_asm(&allocation),
+                )
+            }
+            InstData::AddressOf(expr, _) => {
+                let mut inst_asm = String::new();
+                let memory = self.memory_allocation(*expr, &mut frame.stack_size, &mut inst_asm);
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                inst_asm.push_str(&format!(
+                    "  lea {}, %r10\n  mov %r10, {}\n",
+                    allocation_asm(&memory),
+                    allocation_asm(&allocation),
+                ));
+
+                inst_asm
+            }
+            InstData::LoadPointer(pointer) => {
+                let size = self.type_size(self.expr_type(Expr::Inst(inst)));
+
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                format!(
+                    "  mov {}, %r10\n{}",
+                    allocation_asm(&self.expr_allocation(*pointer)),
+                    self.move_(&Allocation::Indirect { offset: 0, size }, &allocation),
+                )
+            }
+            InstData::StorePointer { pointer, value } => {
+                let size = self.type_size(self.expr_type(*value));
+
+                format!(
+                    "  mov {}, %r10\n{}",
+                    allocation_asm(&self.expr_allocation(*pointer)),
+                    self.move_(
+                        &self.expr_allocation(*value),
+                        &Allocation::Indirect { offset: 0, size }
+                    ),
+                )
+            }
+            InstData::Retain(vec) => {
+                self.runtime_call("builtin_vec_retain", &[self.expr_allocation(*vec)])
+            }
+            InstData::Release(vec) => {
+                self.runtime_call("builtin_vec_release", &[self.expr_allocation(*vec)])
+            }
+            InstData::Equal(lhs, rhs) => format!(
+                "{}  cmpl {}, %r11d\n  sete %r11b\n  movzbl %r11b, %r11d\n{}",
+                self.move_(&self.expr_allocation(*lhs), &Allocation::R11D),
+                allocation_asm(&self.expr_allocation(*rhs)),
+                self.move_(&Allocation::R11D, &self.expr_allocation(Expr::Inst(inst))),
+            ),
+            InstData::Add(lhs, rhs) => self.binary_operation(inst, "addl", *lhs, *rhs),
+            InstData::Sub(lhs, rhs) => self.binary_operation(inst, "subl", *lhs, *rhs),
+            InstData::Mul(lhs, rhs) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                format!(
+                    "{}{}  mull %r11d\n{}",
+                    self.move_(&self.expr_allocation(*rhs), &Allocation::R11D),
+                    self.move_(&self.expr_allocation(*lhs), &Allocation::EAX),
+                    self.move_(&Allocation::EAX, &allocation),
+                )
+            }
+            InstData::Div(lhs, rhs) => {
+                let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                // Dividing by zero would raise SIGFPE.
+                let check = if self.ssa.divisor_may_be_zero(*rhs) {
+                    let inst_number = inst.as_u32();
+                    format!(
+                        "  testl %r11d, %r11d\n  jnz i{inst_number}_nonzero\n  and $-16, %rsp\n  call builtin_division_by_zero\ni{inst_number}_nonzero:\n"
+                    )
+                } else {
+                    String::new()
+                };
+
+                format!(
+                    "{}{check}{}{}  divl %r11d\n{}",
+                    self.move_(&self.expr_allocation(*rhs), &Allocation::R11D),
+                    self.move_(&self.expr_allocation(*lhs), &Allocation::EAX),
+                    self.move_(&Allocation::Immediate(0), &Allocation::EDX),
+                    self.move_(&Allocation::EAX, &allocation),
+                )
+            }
+            InstData::Call { function, argument } => {
+                let mut inst_asm = self.call_arguments(*function, *argument, false, frame);
+
+                let (BlockData::ExternFunction { name, ret, .. }
+                | BlockData::Function { name, ret, .. }) = &self.ssa.blocks[*function]
+                else {
+                    panic!()
+                };
+
+                // The callee writes the returned value in our stack slot.
+                if let Class::Memory = self.classify(*ret) {
+                    inst_asm.push_str(&format!(
+                        "  lea {}, %rdi\n",
+                        allocation_asm(&self.expr_allocation(Expr::Inst(inst)))
+                    ));
+                }
+
+                inst_asm.push_str(&format!("  call f{}_{name}\n", function.as_u32()));
+
+                if let Class::Registers = self.classify(*ret) {
+                    let allocation = self.expr_allocation(Expr::Inst(inst));
+
+                    for (eightbyte, register) in self
+                        .eightbytes(allocation, self.type_size(*ret))
+                        .into_iter()
+                        .zip(RETURN_REGISTERS)
+                    {
+                        inst_asm.push_str(&self.move_(
+                            &Allocation::Register {
+                                register,
+                                size: allocation_size(&eightbyte),
+                            },
+                            &eightbyte,
+                        ));
+                    }
+                }
+
+                inst_asm.push('\n');
+
+                inst_asm
+            }
+            InstData::Jump { block, argument } => {
+                let mut inst_asm = String::new();
+
+                if let Some(allocation) = self.args_allocations[*block] {
+                    inst_asm.push_str(&self.move_(&self.expr_allocation(*argument), &allocation));
+                }
+
+                inst_asm.push_str(&format!("  jmp b{}\n\n", block.as_u32()));
+
+                inst_asm
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => format!(
+                "{}  test %r11d, %r11d\n  jz b{}\n  jmp b{}\n\n",
+                self.move_(&self.expr_allocation(*condition), &Allocation::R11D),
+                else_.as_u32(),
+                then.as_u32(),
+            ),
+            InstData::Return(expr) => {
+                let mut inst_asm = String::new();
+
+                match self.classify(frame.return_type) {
+                    Class::None => {
+                        // The exit status of the program.
+                        if frame.name == "main" {
+                            inst_asm.push_str("  movl $0, %eax\n");
+                        }
+                    }
+                    Class::Registers => {
+                        let allocation = self.expr_allocation(*expr);
+
+                        for (eightbyte, register) in self
+                            .eightbytes(allocation, self.type_size(frame.return_type))
+                            .into_iter()
+                            .zip(RETURN_REGISTERS)
+                        {
+                            inst_asm.push_str(&self.move_(
+                                &eightbyte,
+                                &Allocation::Register {
+                                    register,
+                                    size: allocation_size(&eightbyte),
+                                },
+                            ));
+                        }
+                    }
+                    Class::Memory => {
+                        let size = self.type_size(frame.return_type);
+
+                        inst_asm.push_str(&format!(
+                            "  mov {}, %r10\n{}  mov %r10, %rax\n",
+                            allocation_asm(&frame.return_pointer.unwrap()),
+                            self.move_(
+                                &self.expr_allocation(*expr),
+                                &Allocation::Indirect { offset: 0, size }
+                            ),
+                        ));
+                    }
+                }
+
+                for (register, allocation) in &frame.saved_registers {
+                    inst_asm.push_str(&format!(
+                        "  mov {}, {}\n",
+                        allocation_asm(allocation),
+                        register_asm(*register, 8)
+                    ));
+                }
+
+                inst_asm.push_str("  mov %rbp, %rsp\n  pop %rbp\n  ret\n\n");
+
+                inst_asm
+            }
+            InstData::TailCall { function, argument } => {
+                let BlockData::Function { name, .. } = &self.ssa.blocks[*function] else {
+                    panic!()
+                };
+
+                // `main` keeps its frame, as it gives the exit status.
+                let jump = frame.name != "main";
+                let mut inst_asm = self.call_arguments(*function, *argument, jump, frame);
+
+                // The callee writes the returned value where our caller
+                // expects it.
+                if let Some(return_pointer) = frame.return_pointer {
+                    inst_asm.push_str(&format!(
+                        "  mov {}, %rdi\n",
+                        allocation_asm(&return_pointer)
+                    ));
+                }
+
+                if !jump {
+                    inst_asm.push_str(&format!("  call f{}_{name}\n", function.as_u32()));
+                    if frame.name == "main" {
+                        inst_asm.push_str("  movl $0, %eax\n");
+                    }
+                }
+
+                for (register, allocation) in &frame.saved_registers {
+                    inst_asm.push_str(&format!(
+                        "  mov {}, {}\n",
+                        allocation_asm(allocation),
+                        register_asm(*register, 8)
+                    ));
+                }
+
+                inst_asm.push_str("  mov %rbp, %rsp\n  pop %rbp\n");
+                if jump {
+                    inst_asm.push_str(&format!("  jmp f{}_{name}\n\n", function.as_u32()));
+                } else {
+                    inst_asm.push_str("  ret\n\n");
+                }
+
+                inst_asm
+            }
+        }
+    }
+
+    // Put the argument of a call where the callee expects it, the returned
+    // value and the call itself are left to the caller. Tail calls write the
+    // arguments on the stack over ours.
+    fn call_arguments(
+        &mut self,
+        function: Block,
+        argument: Expr,
+        tail: bool,
+        frame: &mut Frame,
+    ) -> String {
+        let (BlockData::ExternFunction { arg, ret, .. } | BlockData::Function { arg, ret, .. }) =
+            &self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let mut asm = "\n".to_string();
+
+        match self.classify(*arg) {
+            Class::None => {}
+            Class::Registers => {
+                let allocation = self.expr_allocation(argument);
+                let registers = self.argument_registers(*ret);
+
+                for (eightbyte, register) in self
+                    .eightbytes(allocation, self.type_size(*arg))
+                    .into_iter()
+                    .zip(registers)
+                {
+                    asm.push_str(&self.move_(
+                        &eightbyte,
+                        &Allocation::Register {
+                            register: *register,
+                            size: allocation_size(&eightbyte),
+                        },
+                    ));
+                }
+            }
+            Class::Memory if tail => {
+                asm.push_str(&self.move_(
+                    &self.expr_allocation(argument),
+                    &Allocation::StackArgument {
+                        offset: 0,
+                        size: self.type_size(*arg),
+                    },
+                ));
+            }
+            Class::Memory => {
+                asm.push_str(&self.move_(
+                    &self.expr_allocation(argument),
+                    &Allocation::CallArea {
+                        offset: 0,
+                        size: self.type_size(*arg),
+                    },
+                ));
+            }
+        }
+
+        if !tail {
+            let size = self.argument_areas[function].next_multiple_of(8);
+            frame.call_area_size = frame.call_area_size.max(size);
+        }
+
+        asm
+    }
+
+    // Compute `lhs <operation> rhs` in `%r11d`, as the result may share its
+    // register with `rhs`.
+    fn binary_operation(&self, inst: Inst, operation: &str, lhs: Expr, rhs: Expr) -> String {
+        format!(
+            "{}  {operation} {}, %r11d\n{}",
+            self.move_(&self.expr_allocation(lhs), &Allocation::R11D),
+            allocation_asm(&self.expr_allocation(rhs)),
+            self.move_(&Allocation::R11D, &self.expr_allocation(Expr::Inst(inst))),
+        )
+    }
+
+    fn move_(&self, source: &Allocation, destination: &Allocation) -> String {
+        match allocation_size(destination) {
+            _ if source == destination => String::new(),
+            4 if is_memory(source) && is_memory(destination) => format!(
+                "  movl {}, %r11d\n  movl %r11d, {}\n",
+                allocation_asm(source),
+                allocation_asm(destination)
+            ),
+            4 => format!(
+                "  movl {}, {}\n",
+                allocation_asm(source),
+                allocation_asm(destination)
+            ),
+            8 if !is_memory(source) || !is_memory(destination) => format!(
+                "  mov {}, {}\n",
+                allocation_asm(source),
+                allocation_asm(destination)
+            ),
+            size if size % 4 == 0 => (0..size / 4)
+                .map(|i| {
+                    self.move_(
+                        &self.offset_allocation(*source, i * 4, 4),
+                        &self.offset_allocation(*destination, i * 4, 4),
+                    )
+                })
+                .collect(),
+            _ => todo!(),
+        }
+    }
+
+    // Call a function of the runtime following the System V calling
+    // convention, arguments of 4 or 8 bytes only. The returned value is left
+    // in `%r10`.
+    fn runtime_call(&self, name: &str, arguments: &[Allocation]) -> String {
+        const REGISTERS: [&str; 5] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8"];
+
+        let mut asm = "\n".to_string();
+
+        // Arguments may be held by the registers they are passed in, they go
+        // through the stack so that none is overwritten before being read.
+        for argument in arguments {
+            let argument = match argument {
+                Allocation::Register { register, .. } => Cow::Borrowed(register_asm(*register, 8)),
+                _ => allocation_asm(argument),
+            };
+            asm.push_str(&format!("  pushq {argument}\n"));
+        }
+
+        for register in REGISTERS[..arguments.len()].iter().rev() {
+            asm.push_str(&format!("  pop {register}\n"));
+        }
+
+        // Values living across the call are never held by caller saved
+        // registers.
+        asm.push_str(&format!("  call {name}\n  mov %rax, %r10\n\n"));
+
+        asm
+    }
+
+    // Table of the offsets of the vecs held by an element of the vec type
+    // `type_` and its length, as expected by `builtin_vec_new`. Elements
+    // holding no vec have no table.
+    fn heap_offsets_table(&mut self, type_: Type) -> Option<(usize, u32)> {
+        let Val::Value(&TypeData::Vec { element }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, 0, &mut offsets);
+
+        if offsets.is_empty() {
+            return None;
+        }
+
+        let count = offsets.len() as u32;
+        let table = match self.heap_offsets.iter().position(|table| *table == offsets) {
+            Some(table) => table,
+            None => {
+                self.heap_offsets.push(offsets);
+                self.heap_offsets.len() - 1
+            }
+        };
+
+        Some((table, count))
+    }
+
+    fn heap_offsets(&self, type_: Type, offset: u64, offsets: &mut Vec<u64>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(*field, offset + self.field_offset(type_, i), offsets);
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_size = self.type_size(element);
+                for i in 0..length as u64 {
+                    self.heap_offsets(element, offset + i * element_size, offsets);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.expr_type(expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    // Put `base` in memory, so that its address can be taken.
+    fn memory_allocation(
+        &mut self,
+        base: Expr,
+        stack_size: &mut u64,
+        asm: &mut String,
+    ) -> Allocation {
+        let allocation = self.expr_allocation(base);
+
+        if is_memory(&allocation) {
+            return allocation;
+        }
+
+        let memory = self.reserve_stack_allocation(allocation_size(&allocation), stack_size);
+        asm.push_str(&self.move_(&allocation, &memory));
+        memory
+    }
+
+    fn length_allocation(&self, base: Expr, base_allocation: Allocation) -> Allocation {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(&TypeData::Array { length, .. }) => Allocation::Immediate(length),
+            Val::Value(TypeData::Slice { .. }) => self.offset_allocation(base_allocation, 8, 4),
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Load in `%r10` the address of the first element of an array or slice.
+    fn base_address(&self, base: Expr, base_allocation: Allocation) -> String {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(TypeData::Array { .. }) => {
+                format!("  lea {}, %r10\n", allocation_asm(&base_allocation))
+            }
+            Val::Value(TypeData::Slice { .. }) => format!(
+                "  mov {}, %r10\n",
+                allocation_asm(&self.offset_allocation(base_allocation, 0, 8))
+            ),
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Load in `%r10` the address of the element `index` of an array or
+    // slice, checking the bounds.
+    fn element_address(
+        &self,
+        inst: Inst,
+        base: Expr,
+        base_allocation: Allocation,
+        index: Expr,
+        element_size: u64,
+        location: &Location,
+    ) -> String {
+        let inst_number = inst.as_u32();
+
+        format!(
+            "{}  cmpl {}, %r11d\n  jb i{inst_number}_in_bounds\n{}i{inst_number}_in_bounds:\n{}  imul ${element_size}, %r11\n  add %r11, %r10\n",
+            self.move_(&self.expr_allocation(index), &Allocation::R11D),
+            allocation_asm(&self.length_allocation(base, base_allocation)),
+            index_out_of_bounds(location),
+            self.base_address(base, base_allocation),
+        )
+    }
+
+    fn classify(&self, type_: Type) -> Class {
+        match self.type_size(type_) {
+            0 => Class::None,
+            1..=16 => Class::Registers,
+            _ => Class::Memory,
+        }
+    }
+
+    // Registers of the argument of a function, the first one holds the
+    // pointer to the returned value when it is of the memory class.
+    fn argument_registers(&self, return_type: Type) -> &'static [Register] {
+        match self.classify(return_type) {
+            Class::Memory => &ARGUMENT_REGISTERS[1..],
+            Class::None | Class::Registers => &ARGUMENT_REGISTERS,
+        }
+    }
+
+    // Split a value of the registers class in the parts passed in each
+    // register.
+    fn eightbytes(&self, allocation: Allocation, size: u64) -> Vec<Allocation> {
+        if !is_memory(&allocation) {
+            return vec![allocation];
+        }
+
+        (0..size.div_ceil(8))
+            .map(|i| self.offset_allocation(allocation, i * 8, (size - i * 8).min(8)))
+            .collect()
+    }
+
+    #[track_caller]
+    fn expr_allocation(&self, expr: Expr) -> Allocation {
+        match expr {
+            Expr::Inst(inst) => self.insts_allocations[inst].unwrap(),
+            Expr::BlockArg(block) => self.args_allocations[block].unwrap(),
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => panic!(),
+                Val::Sentinel(sentinel) => match sentinel {
+                    ConstSentinel::Unit | ConstSentinel::False => Allocation::Immediate(0),
+                    ConstSentinel::True => Allocation::Immediate(1),
+                },
+                Val::Value(const_data) => match const_data {
+                    ConstData::Uint32(value) => Allocation::Immediate(*value),
+                    ConstData::Product(_, _) => panic!(),
+                },
+            },
+        }
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    fn reserve_stack_allocation(&mut self, size: u64, stack_size: &mut u64) -> Allocation {
+        if size >= 8 {
+            *stack_size = stack_size.next_multiple_of(8);
+        }
+
+        let allocation = Allocation::Stack {
+            offset: size + *stack_size,
+            size,
+        };
+        *stack_size += size;
+        allocation
+    }
+
+    fn offset_allocation(&self, allocation: Allocation, offset: u64, size: u64) -> Allocation {
+        match allocation {
+            Allocation::Stack {
+                offset: base_offset,
+                ..
+            } => Allocation::Stack {
+                offset: base_offset - offset,
+                size,
+            },
+            Allocation::StackArgument {
+                offset: base_offset,
+                ..
+            } => Allocation::StackArgument {
+                offset: base_offset + offset,
+                size,
+            },
+            Allocation::CallArea {
+                offset: base_offset,
+                ..
+            } => Allocation::CallArea {
+                offset: base_offset + offset,
+                size,
+            },
+            Allocation::Indirect {
+                offset: base_offset,
+                ..
+            } => Allocation::Indirect {
+                offset: base_offset + offset,
+                size,
+            },
+            _ => panic!(),
+        }
+    }
+
+    fn type_size(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => 4,
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .fold(0u64, |offset, (_, field)| {
+                        offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+                    })
+                    .next_multiple_of(self.type_align(type_)),
+                TypeData::Array { element, length } => self.type_size(*element) * *length as u64,
+                // A pointer to the first element followed by the length, padded
+                // to keep the size a multiple of 8.
+                TypeData::Slice { .. } => 16,
+                // A pointer to the vec allocated by the runtime.
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+    // Types are laid out as in C, so that values can be shared with C
+    // functions.
+    fn type_align(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit | TypeSentinel::Never => 1,
+                TypeSentinel::Bool
+                | TypeSentinel::False
+                | TypeSentinel::True
+                | TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .map(|(_, field)| self.type_align(*field))
+                    .max()
+                    .unwrap_or(1),
+                TypeData::Array { element, .. } => self.type_align(*element),
+                TypeData::Slice { .. } | TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn field_offset(&self, type_: Type, field: usize) -> u64 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .fold(0u64, |offset, (_, field)| {
+                offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+            })
+            .next_multiple_of(self.type_align(fields[field].1))
+    }
+}
+
+fn allocation_asm(allocation: &Allocation) -> Cow<'static, str> {
+    match allocation {
+        Allocation::Stack { offset, .. } => Cow::Owned(format!("-{}(%rbp)", offset)),
+        Allocation::StackArgument { offset, size: _ } => {
+            Cow::Owned(format!("{}(%rbp)", offset + 16))
+        }
+        Allocation::CallArea { offset, .. } => Cow::Owned(format!("{offset}(%rsp)")),
+        Allocation::Register { register, size } => Cow::Borrowed(register_asm(*register, *size)),
+        Allocation::Indirect { offset, .. } => Cow::Owned(format!("{offset}(%r10)")),
+        Allocation::Immediate(value) => Cow::Owned(format!("${value}")),
+    }
+}
+
+fn register_asm(register: Register, size: u64) -> &'static str {
+    match (register, size) {
+        (Register::Rax, 4) => "%eax",
+        (Register::Rbx, 4) => "%ebx",
+        (Register::Rcx, 4) => "%ecx",
+        (Register::Rdx, 4) => "%edx",
+        (Register::Rsi, 4) => "%esi",
+        (Register::Rdi, 4) => "%edi",
+        (Register::R8, 4) => "%r8d",
+        (Register::R9, 4) => "%r9d",
+        (Register::R10, 4) => "%r10d",
+        (Register::R11, 4) => "%r11d",
+        (Register::R12, 4) => "%r12d",
+        (Register::R13, 4) => "%r13d",
+        (Register::R14, 4) => "%r14d",
+        (Register::R15, 4) => "%r15d",
+        (Register::Rax, 8) => "%rax",
+        (Register::Rbx, 8) => "%rbx",
+        (Register::Rcx, 8) => "%rcx",
+        (Register::Rdx, 8) => "%rdx",
+        (Register::Rsi, 8) => "%rsi",
+        (Register::Rdi, 8) => "%rdi",
+        (Register::R8, 8) => "%r8",
+        (Register::R9, 8) => "%r9",
+        (Register::R10, 8) => "%r10",
+        (Register::R11, 8) => "%r11",
+        (Register::R12, 8) => "%r12",
+        (Register::R13, 8) => "%r13",
+        (Register::R14, 8) => "%r14",
+        (Register::R15, 8) => "%r15",
+        _ => panic!(),
+    }
+}
+
+fn allocation_size(allocation: &Allocation) -> u64 {
+    match allocation {
+        Allocation::Stack { size, .. }
+        | Allocation::StackArgument { size, .. }
+        | Allocation::CallArea { size, .. }
+        | Allocation::Register { size, .. }
+        | Allocation::Indirect { size, .. } => *size,
+        Allocation::Immediate(_) => 4,
+    }
+}
+
+fn is_memory(allocation: &Allocation) -> bool {
+    matches!(
+        allocation,
+        Allocation::Stack { .. }
+            | Allocation::StackArgument { .. }
+            | Allocation::CallArea { .. }
+            | Allocation::Indirect { .. }
+    )
+}
+
+// Report the failed bounds check, the stack is realigned as we never return.
+fn index_out_of_bounds(location: &Location) -> String {
+    format!(
+        "  movl ${}, %edi\n  movl ${}, %esi\n  and $-16, %rsp\n  call builtin_index_out_of_bounds\n",
+        location.line, location.column,
+    )
+}
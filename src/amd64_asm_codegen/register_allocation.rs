@@ -0,0 +1,476 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Allocation, Class, Frame, Generator, Register, allocation_size};
+use crate::{
+    key_vec::Val,
+    semantic::{Type, TypeData},
+    ssa::{Block, BlockData, Expr, Inst, InstData, Liveness},
+};
+
+// Registers that may hold a value, the caller saved ones are preferred as they
+// don't need to be saved by the function.
+const REGISTERS: [Register; 12] = [
+    Register::Rax,
+    Register::Rcx,
+    Register::Rdx,
+    Register::Rsi,
+    Register::Rdi,
+    Register::R8,
+    Register::R9,
+    Register::Rbx,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+const CALLER_SAVED: [Register; 7] = [
+    Register::Rax,
+    Register::Rcx,
+    Register::Rdx,
+    Register::Rsi,
+    Register::Rdi,
+    Register::R8,
+    Register::R9,
+];
+
+const CALLEE_SAVED: [Register; 5] = [
+    Register::Rbx,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Value {
+    Inst(Inst),
+    Arg(Block),
+}
+
+// Positions over the function laid out as emitted, the instruction number `n`
+// reads its operands at `2n` and writes its result at `2n + 1`. Registers
+// clobbered by the instruction are overwritten in between, so that they can
+// still hold its operands and its result.
+#[derive(Debug)]
+struct Interval {
+    value: Value,
+    start: u32,
+    end: u32,
+    size: u64,
+    // Register where the value is expected, to avoid a move.
+    hint: Option<Register>,
+}
+
+// A function entry or a block, with its instructions.
+struct Node<'a> {
+    block: Block,
+    insts: &'a [Inst],
+    // Position of the start of the node, block arguments are live from there.
+    start: u32,
+}
+
+impl Generator<'_> {
+    // Assign a register or a stack slot to every value of the function, with
+    // a linear scan over the live intervals of the values.
+    pub(super) fn allocate_registers(
+        &mut self,
+        function: Block,
+        blocks: &[Block],
+        liveness: &Liveness,
+        frame: &mut Frame,
+    ) {
+        let ssa = self.ssa;
+
+        let BlockData::Function {
+            arg, ret, insts, ..
+        } = &ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        // Only values fitting a register are allocated, the others live in
+        // their own stack slot as slices and pointers may point to them.
+        self.args_allocations[function] = match self.classify(*arg) {
+            _ if self.fits_register(*arg) => None,
+            Class::None => None,
+            Class::Registers => {
+                Some(self.reserve_stack_allocation(self.type_size(*arg), &mut frame.stack_size))
+            }
+            Class::Memory => Some(Allocation::StackArgument {
+                offset: 0,
+                size: self.type_size(*arg),
+            }),
+        };
+
+        let mut nodes = vec![Node {
+            block: function,
+            insts,
+            start: 0,
+        }];
+
+        for block in blocks {
+            let BlockData::Block { arg, insts } = &ssa.blocks[*block] else {
+                panic!()
+            };
+
+            self.args_allocations[*block] = match self.type_size(*arg) {
+                0 => None,
+                _ if self.fits_register(*arg) => None,
+                size => Some(self.reserve_stack_allocation(size, &mut frame.stack_size)),
+            };
+
+            nodes.push(Node {
+                block: *block,
+                insts,
+                start: 0,
+            });
+        }
+
+        let mut intervals = HashMap::<Value, Interval>::new();
+        let mut clobbers = Vec::<(u32, &[Register])>::new();
+
+        let mut number = 0;
+        for node in &mut nodes {
+            node.start = 2 * number;
+            number += 1;
+
+            if node.block == function
+                && let Some(value) = self.value(Expr::BlockArg(function))
+            {
+                let hint = self.argument_registers(*ret)[0];
+                self.extend_interval(&mut intervals, value, 1, Some(hint));
+            }
+
+            for inst in node.insts {
+                let position = 2 * number;
+                number += 1;
+
+                let (operands, late_operands) = self.operands(*inst);
+                for (operand, offset) in operands
+                    .iter()
+                    .map(|operand| (operand, 0))
+                    .chain(late_operands.iter().map(|operand| (operand, 1)))
+                {
+                    let Some(value) = self.value(*operand) else {
+                        continue;
+                    };
+
+                    self.extend_interval(&mut intervals, value, position + offset, None);
+                }
+
+                let result = self.value(Expr::Inst(*inst));
+                if let Some(value) = result {
+                    let hint = self.result_hint(*inst);
+                    self.extend_interval(&mut intervals, value, position + 1, hint);
+                } else if self.type_size(self.expr_type(Expr::Inst(*inst))) != 0 {
+                    self.insts_allocations[*inst] = Some(self.reserve_stack_allocation(
+                        self.type_size(self.expr_type(Expr::Inst(*inst))),
+                        &mut frame.stack_size,
+                    ));
+                }
+
+                // The argument of the target block is written by the jump.
+                if let InstData::Jump { block, .. } = &ssa.insts[*inst]
+                    && let Some(value) = self.value(Expr::BlockArg(*block))
+                {
+                    self.extend_interval(&mut intervals, value, position + 1, None);
+                }
+
+                // Values returned in a register are hinted to it.
+                if let InstData::Return(expr) = &ssa.insts[*inst]
+                    && let Some(value) = self.value(*expr)
+                    && let Some(interval) = intervals.get_mut(&value)
+                {
+                    interval.hint = interval.hint.or(Some(Register::Rax));
+                }
+
+                let clobbered = self.clobbered_registers(*inst);
+                if !clobbered.is_empty() {
+                    clobbers.push((position, clobbered));
+                }
+            }
+        }
+
+        // Live values are extended to the start or the end of the nodes they
+        // flow through. The argument of a block is live from its start, as
+        // the block may be laid out before the jumps writing it.
+        for (i, node) in nodes.iter().enumerate() {
+            let end = nodes.get(i + 1).map_or(2 * number, |next| next.start) - 1;
+
+            let live_in = liveness.live_in[&node.block]
+                .iter()
+                .copied()
+                .chain([Expr::BlockArg(node.block)]);
+            for value in live_in.filter_map(|expr| self.value(expr)) {
+                self.extend_interval(&mut intervals, value, node.start, None);
+            }
+
+            let live_out = liveness.live_out[&node.block].iter().copied();
+            for value in live_out.filter_map(|expr| self.value(expr)) {
+                self.extend_interval(&mut intervals, value, end, None);
+            }
+        }
+
+        // Arguments passed to a block prefer the register of its argument.
+        let mut copies = HashMap::<Value, Vec<Value>>::new();
+        for node in &nodes {
+            for inst in node.insts {
+                if let InstData::Jump { block, argument } = &ssa.insts[*inst]
+                    && let Some(argument) = self.value(*argument)
+                    && let Some(block_argument) = self.value(Expr::BlockArg(*block))
+                {
+                    copies.entry(argument).or_default().push(block_argument);
+                    copies.entry(block_argument).or_default().push(argument);
+                }
+            }
+        }
+
+        let mut intervals = intervals.into_values().collect::<Vec<_>>();
+        intervals.sort_by_key(|interval| (interval.start, interval.end));
+
+        self.linear_scan(&intervals, &clobbers, &copies, frame);
+    }
+
+    fn linear_scan(
+        &mut self,
+        intervals: &[Interval],
+        clobbers: &[(u32, &[Register])],
+        copies: &HashMap<Value, Vec<Value>>,
+        frame: &mut Frame,
+    ) {
+        // Intervals currently in a register, and the ones spilled to a stack
+        // slot which is reused once they end.
+        let mut active = Vec::<(u32, Register, usize)>::new();
+        let mut spilled = Vec::<(u32, Allocation)>::new();
+        let mut free_slots = HashMap::<u64, Vec<Allocation>>::new();
+
+        let mut allocations = HashMap::<Value, Allocation>::new();
+        let mut used_registers = HashSet::new();
+
+        for (i, interval) in intervals.iter().enumerate() {
+            active.retain(|(end, _, _)| *end >= interval.start);
+            spilled.retain(|(end, slot)| {
+                let expired = *end < interval.start;
+                if expired {
+                    free_slots
+                        .entry(allocation_size(slot))
+                        .or_default()
+                        .push(*slot);
+                }
+                !expired
+            });
+
+            // Registers overwritten while the value is live.
+            let first_clobber =
+                clobbers.partition_point(|(position, _)| *position < interval.start);
+            let forbidden = clobbers[first_clobber..]
+                .iter()
+                .take_while(|(position, _)| *position < interval.end)
+                .flat_map(|(_, registers)| registers.iter().copied())
+                .collect::<HashSet<_>>();
+
+            let is_free = |register: &Register| {
+                !forbidden.contains(register)
+                    && active
+                        .iter()
+                        .all(|(_, active_register, _)| active_register != register)
+            };
+
+            let copy_registers = copies
+                .get(&interval.value)
+                .into_iter()
+                .flatten()
+                .filter_map(|copy| match allocations.get(copy) {
+                    Some(Allocation::Register { register, .. }) => Some(*register),
+                    _ => None,
+                });
+
+            let register = copy_registers
+                .chain(interval.hint)
+                .chain(REGISTERS)
+                .find(|register| REGISTERS.contains(register) && is_free(register));
+
+            let register = match register {
+                Some(register) => Some(register),
+                None => {
+                    // Spill the interval ending last, keeping registers for
+                    // the shorter ones.
+                    let candidate = active
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, (_, register, _))| !forbidden.contains(register))
+                        .max_by_key(|(_, (end, _, _))| *end)
+                        .map(|(index, active)| (index, *active));
+
+                    match candidate {
+                        Some((index, (end, register, spilled_interval))) if end > interval.end => {
+                            active.remove(index);
+
+                            let spilled_interval = &intervals[spilled_interval];
+                            let slot =
+                                self.spill_slot(spilled_interval.size, &mut free_slots, frame);
+                            allocations.insert(spilled_interval.value, slot);
+                            spilled.push((end, slot));
+
+                            Some(register)
+                        }
+                        _ => None,
+                    }
+                }
+            };
+
+            match register {
+                Some(register) => {
+                    used_registers.insert(register);
+                    active.push((interval.end, register, i));
+                    allocations.insert(
+                        interval.value,
+                        Allocation::Register {
+                            register,
+                            size: interval.size,
+                        },
+                    );
+                }
+                None => {
+                    let slot = self.spill_slot(interval.size, &mut free_slots, frame);
+                    allocations.insert(interval.value, slot);
+                    spilled.push((interval.end, slot));
+                }
+            }
+        }
+
+        for (value, allocation) in allocations {
+            match value {
+                Value::Inst(inst) => self.insts_allocations[inst] = Some(allocation),
+                Value::Arg(block) => self.args_allocations[block] = Some(allocation),
+            }
+        }
+
+        for register in CALLEE_SAVED {
+            if used_registers.contains(&register) {
+                let slot = self.reserve_stack_allocation(8, &mut frame.stack_size);
+                frame.saved_registers.push((register, slot));
+            }
+        }
+    }
+
+    fn spill_slot(
+        &mut self,
+        size: u64,
+        free_slots: &mut HashMap<u64, Vec<Allocation>>,
+        frame: &mut Frame,
+    ) -> Allocation {
+        free_slots
+            .get_mut(&size)
+            .and_then(|slots| slots.pop())
+            .unwrap_or_else(|| self.reserve_stack_allocation(size, &mut frame.stack_size))
+    }
+
+    fn extend_interval(
+        &self,
+        intervals: &mut HashMap<Value, Interval>,
+        value: Value,
+        position: u32,
+        hint: Option<Register>,
+    ) {
+        let size = match value {
+            Value::Inst(inst) => self.type_size(self.expr_type(Expr::Inst(inst))),
+            Value::Arg(block) => self.type_size(self.expr_type(Expr::BlockArg(block))),
+        };
+
+        let interval = intervals.entry(value).or_insert(Interval {
+            value,
+            start: position,
+            end: position,
+            size,
+            hint: None,
+        });
+
+        interval.start = interval.start.min(position);
+        interval.end = interval.end.max(position);
+        interval.hint = interval.hint.or(hint);
+    }
+
+    // The value computed by an expression, if it may be held by a register.
+    fn value(&self, expr: Expr) -> Option<Value> {
+        let value = match expr {
+            Expr::Const(_) => return None,
+            Expr::Inst(inst) => Value::Inst(inst),
+            Expr::BlockArg(block) => Value::Arg(block),
+        };
+
+        let type_ = self.expr_type(expr);
+
+        self.fits_register(type_).then_some(value)
+    }
+
+    fn fits_register(&self, type_: Type) -> bool {
+        match self.types.get(type_) {
+            Val::Sentinel(_) => matches!(self.type_size(type_), 4 | 8),
+            Val::Value(TypeData::Pointer { .. } | TypeData::Vec { .. }) => true,
+            Val::None | Val::Value(_) => false,
+        }
+    }
+
+    fn result_hint(&self, inst: Inst) -> Option<Register> {
+        match &self.ssa.insts[inst] {
+            InstData::Mul(..) | InstData::Div(..) => Some(Register::Rax),
+            InstData::Call { .. } => Some(Register::Rax),
+            _ => None,
+        }
+    }
+
+    // Operands read before the instruction clobbers any register, and the
+    // ones read after.
+    fn operands(&self, inst: Inst) -> (Vec<Expr>, Vec<Expr>) {
+        match &self.ssa.insts[inst] {
+            InstData::Field(expr, _)
+            | InstData::Length(expr)
+            | InstData::AddressOf(expr, _)
+            | InstData::LoadPointer(expr)
+            | InstData::Retain(expr)
+            | InstData::Release(expr)
+            | InstData::Return(expr) => (vec![*expr], Vec::new()),
+            InstData::Record(exprs, _) | InstData::Array(exprs, _) | InstData::NewVec(exprs, _) => {
+                (exprs.clone(), Vec::new())
+            }
+            InstData::Load { base, index, .. } => (vec![*base, *index], Vec::new()),
+            InstData::Store {
+                base, index, value, ..
+            } => (vec![*base, *index], vec![*value]),
+            InstData::Slice {
+                base, start, end, ..
+            } => (vec![*base, *start, *end], Vec::new()),
+            InstData::Push { vec, value } => (vec![*vec], vec![*value]),
+            InstData::Pop { vec, .. } => (vec![*vec], Vec::new()),
+            InstData::Alloca(_) => (Vec::new(), Vec::new()),
+            InstData::StorePointer { pointer, value } => (vec![*pointer, *value], Vec::new()),
+            InstData::Equal(lhs, rhs)
+            | InstData::Add(lhs, rhs)
+            | InstData::Sub(lhs, rhs)
+            | InstData::Mul(lhs, rhs)
+            | InstData::Div(lhs, rhs) => (vec![*lhs, *rhs], Vec::new()),
+            InstData::Call { argument, .. }
+            | InstData::TailCall { argument, .. }
+            | InstData::Jump { argument, .. } => (vec![*argument], Vec::new()),
+            InstData::JumpCondition { condition, .. } => (vec![*condition], Vec::new()),
+        }
+    }
+
+    fn clobbered_registers(&self, inst: Inst) -> &'static [Register] {
+        match &self.ssa.insts[inst] {
+            InstData::Load { base, .. } | InstData::Store { base, .. } if self.is_vec(*base) => {
+                &CALLER_SAVED
+            }
+            InstData::NewVec(..)
+            | InstData::Push { .. }
+            | InstData::Pop { .. }
+            | InstData::Retain(_)
+            | InstData::Release(_)
+            | InstData::Call { .. } => &CALLER_SAVED,
+            InstData::Mul(..) | InstData::Div(..) => &[Register::Rax, Register::Rdx],
+            _ => &[],
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use crate::{
     key_vec::Val,
+    runtime,
     semantic::{Type, TypeData, TypeSentinel, Types, types_equals},
-    ssa::{Block, BlockData, ConstData, ConstSentinel, Expr, Inst, InstData, Ssa},
+    ssa::{Block, BlockData, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData, Ssa},
 };
 
 pub fn generate(types: &Types, ssa: &Ssa) -> String {
@@ -12,6 +13,8 @@ pub fn generate(types: &Types, ssa: &Ssa) -> String {
         ssa,
         functions: String::new(),
         structs: Vec::new(),
+        heap_offsets: Vec::new(),
+        tail_groups: HashMap::new(),
     };
 
     generator.generate();
@@ -24,17 +27,38 @@ struct Generator<'a> {
     ssa: &'a Ssa,
     functions: String,
     structs: Vec<(Type, String)>,
+    // Offsets of the vecs held by the elements of vecs, each table is the
+    // static array `heap_offsets{i}`.
+    heap_offsets: Vec<String>,
+    // Functions tail calling each other, or themselves, with the first
+    // function of their group. Their tail calls are jumps.
+    tail_groups: HashMap<Block, Block>,
 }
 
 impl Generator<'_> {
     fn result(self) -> String {
         format!(
-            "#include<stdio.h>\n\nvoid builtin_print(unsigned int x) {{ printf(\"%u\\n\", x); }}\n\n{}\n\n{}int main() {{ f{}_main(); return 0; }}\n",
+            "{}\n{}\n\n{}const char builtin_source_path[] = {{ {} }};\n\n{}int main() {{ f{}_main(); return 0; }}\n",
+            runtime::SOURCE,
             self.structs
                 .into_iter()
                 .map(|(_, definition)| definition)
                 .collect::<Vec<String>>()
                 .join("\n\n"),
+            self.heap_offsets
+                .iter()
+                .enumerate()
+                .map(|(i, offsets)| {
+                    format!("static const uint32_t heap_offsets{i}[] = {{ {offsets} }};\n\n")
+                })
+                .collect::<String>(),
+            self.ssa
+                .path
+                .bytes()
+                .chain([0])
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
             self.functions,
             self.ssa
                 .blocks
@@ -50,6 +74,22 @@ impl Generator<'_> {
     }
 
     fn generate(&mut self) {
+        self.group_tail_calls();
+
+        // Functions may call the ones defined after them.
+        for (block, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::Function { .. } = block_data {
+                let head = self.function_head(block);
+                self.functions.push_str(&format!("{head};\n"));
+
+                if self.group(block).len() > 1 && self.tail_groups[&block] == block {
+                    let head = self.group_head(block);
+                    self.functions.push_str(&format!("{head};\n"));
+                }
+            }
+        }
+        self.functions.push('\n');
+
         for (block, block_data) in self.ssa.blocks.entries() {
             match block_data {
                 BlockData::ExternFunction { .. } => {
@@ -67,15 +107,74 @@ impl Generator<'_> {
         }
     }
 
-    fn generate_extern_function(&mut self, function: Block) -> String {
-        let BlockData::ExternFunction { name, arg, ret } = &self.ssa.blocks[function] else {
+    // Tail calls are made jumps, C compilers do not all guarantee them
+    // otherwise. Functions tail calling each other are generated in a single
+    // function, entered through the function of each.
+    fn group_tail_calls(&mut self) {
+        fn root(parents: &HashMap<Block, Block>, mut function: Block) -> Block {
+            while let Some(&parent) = parents.get(&function) {
+                function = parent;
+            }
+            function
+        }
+
+        let mut parents = HashMap::new();
+        let mut callers = Vec::new();
+        for (caller, function) in self.ssa.tail_calls() {
+            if self.function_type(function).1 == self.function_type(caller).1 {
+                let (caller, function) = (root(&parents, caller), root(&parents, function));
+                if caller != function {
+                    parents.insert(function, caller);
+                }
+                callers.push(caller);
+            }
+        }
+
+        let roots: Vec<_> = callers
+            .into_iter()
+            .map(|caller| root(&parents, caller))
+            .collect();
+        for (function, block_data) in self.ssa.blocks.entries() {
+            let root = root(&parents, function);
+            if let BlockData::Function { .. } = block_data
+                && roots.contains(&root)
+            {
+                self.tail_groups.insert(function, root);
+            }
+        }
+    }
+
+    // Functions of the group of `function`, in the order of their blocks.
+    fn group(&self, function: Block) -> Vec<Block> {
+        let Some(root) = self.tail_groups.get(&function) else {
+            return vec![function];
+        };
+
+        self.ssa
+            .blocks
+            .entries()
+            .map(|(block, _)| block)
+            .filter(|block| self.tail_groups.get(block) == Some(root))
+            .collect()
+    }
+
+    fn group_head(&mut self, root: Block) -> String {
+        let (_, return_type) = self.function_type(root);
+        format!(
+            "{return_type} g{}(unsigned int function, void *argument)",
+            root.as_u32()
+        )
+    }
+
+    fn function_head(&mut self, function: Block) -> String {
+        let BlockData::Function { name, arg, ret, .. } = &self.ssa.blocks[function] else {
             panic!()
         };
 
         let return_type = self.generate_type(*ret);
         let argument_type = self.generate_type(*arg);
 
-        let head = if argument_type == "void" {
+        if argument_type == "void" {
             format!("{} f{}_{name}()", return_type, function.as_u32())
         } else {
             format!(
@@ -85,25 +184,11 @@ impl Generator<'_> {
                 argument_type,
                 function.as_u32(),
             )
-        };
-
-        let body = if return_type == "void" {
-            format!("    {name}(a{});\n", function.as_u32())
-        } else {
-            format!("    return {name}(a{});\n", function.as_u32())
-        };
-
-        format!("{head} {{\n{body}}}")
+        }
     }
 
-    fn generate_function(&mut self, function: Block) -> String {
-        let BlockData::Function {
-            name,
-            arg,
-            ret,
-            insts,
-        } = &self.ssa.blocks[function]
-        else {
+    fn generate_extern_function(&mut self, function: Block) -> String {
+        let BlockData::ExternFunction { name, arg, ret } = &self.ssa.blocks[function] else {
             panic!()
         };
 
@@ -122,11 +207,84 @@ impl Generator<'_> {
             )
         };
 
+        let body = if return_type == "void" {
+            format!("    {name}(a{});\n", function.as_u32())
+        } else {
+            format!("    return {name}(a{});\n", function.as_u32())
+        };
+
+        format!("{head} {{\n{body}}}")
+    }
+
+    fn generate_function(&mut self, function: Block) -> String {
+        let head = self.function_head(function);
+        let group = self.group(function);
+
+        if group.len() == 1 {
+            // The label is the target of the tail calls to itself.
+            let label = if self.tail_groups.contains_key(&function) {
+                format!("b{}:\n", function.as_u32())
+            } else {
+                String::new()
+            };
+            let body = self.function_body(function);
+            return format!("{head} {{\n{label}{body}}}");
+        }
+
+        let root = self.tail_groups[&function];
+        let (argument_type, return_type) = self.function_type(function);
+        let argument = if argument_type == "void" {
+            "NULL".to_string()
+        } else {
+            format!("&a{}", function.as_u32())
+        };
+        let call = format!("g{}({}, {argument})", root.as_u32(), function.as_u32());
+        let mut text = if return_type == "void" {
+            format!("{head} {{\n    {call};\n}}")
+        } else {
+            format!("{head} {{\n    return {call};\n}}")
+        };
+
+        if root == function {
+            let mut declarations = String::new();
+            let mut entries = String::new();
+            let mut bodies = String::new();
+            for member in group {
+                let (argument_type, _) = self.function_type(member);
+                let number = member.as_u32();
+                if argument_type == "void" {
+                    entries.push_str(&format!("    case {number}: goto b{number};\n"));
+                } else {
+                    declarations.push_str(&format!("    {argument_type} a{number};\n"));
+                    entries.push_str(&format!(
+                        "    case {number}: a{number} = *({argument_type} *)argument; goto b{number};\n"
+                    ));
+                }
+                bodies.push_str(&format!("b{number}:\n{}", self.function_body(member)));
+            }
+
+            let head = self.group_head(root);
+            text.push_str(&format!(
+                "\n\n{head} {{\n{declarations}    switch (function) {{\n{entries}    }}\n{bodies}}}"
+            ));
+        }
+
+        text
+    }
+
+    fn function_body(&mut self, function: Block) -> String {
+        let BlockData::Function { insts, .. } = &self.ssa.blocks[function] else {
+            panic!()
+        };
+
         let mut body = String::new();
 
-        let blocks = self.function_blocks(function);
+        // Every block comes after the blocks dominating it, which keeps C
+        // declarations before uses.
+        let cfg = Cfg::new(self.ssa, function);
+        let blocks = &cfg.blocks()[1..];
 
-        for block in &blocks {
+        for block in blocks {
             let BlockData::Block { arg, insts: _ } = &self.ssa.blocks[*block] else {
                 panic!()
             };
@@ -143,53 +301,25 @@ impl Generator<'_> {
             }
         }
 
-        body.push_str(&self.generate_statements(insts.iter().copied()));
+        body.push_str(&self.generate_statements(function, insts.iter().copied()));
 
-        for block in &blocks {
+        for block in blocks {
             let BlockData::Block { insts, .. } = &self.ssa.blocks[*block] else {
                 panic!()
             };
 
             body.push_str(&format!("b{}:\n", block.as_u32()));
-            body.push_str(&self.generate_statements(insts.iter().copied()));
-        }
-
-        format!("{head} {{\n{body}}}")
-    }
-
-    fn function_blocks(&mut self, function: Block) -> HashSet<Block> {
-        let BlockData::Function { insts, .. } = &self.ssa.blocks[function] else {
-            panic!()
-        };
-
-        let mut blocks = HashSet::new();
-
-        match &self.ssa.insts[*insts.last().unwrap()] {
-            InstData::Jump { block, .. } => blocks.extend([*block]),
-            InstData::JumpCondition { then, else_, .. } => blocks.extend([*then, *else_]),
-            _ => {}
-        }
-
-        let mut checked_blocks = HashSet::new();
-
-        while let Some(&block) = blocks.difference(&checked_blocks).next() {
-            checked_blocks.insert(block);
-
-            let BlockData::Block { insts, .. } = &self.ssa.blocks[block] else {
-                panic!();
-            };
-
-            match &self.ssa.insts[*insts.last().unwrap()] {
-                InstData::Jump { block, .. } => blocks.extend([*block]),
-                InstData::JumpCondition { then, else_, .. } => blocks.extend([*then, *else_]),
-                _ => {}
-            }
+            body.push_str(&self.generate_statements(function, insts.iter().copied()));
         }
 
-        blocks
+        body
     }
 
-    fn generate_statements(&mut self, insts: impl IntoIterator<Item = Inst>) -> String {
+    fn generate_statements(
+        &mut self,
+        caller: Block,
+        insts: impl IntoIterator<Item = Inst>,
+    ) -> String {
         let mut body = String::new();
 
         for inst in insts {
@@ -212,6 +342,162 @@ impl Generator<'_> {
                         .map(|field| format!("{}, ", self.generate_expr(*field)))
                         .collect::<String>()
                 )),
+                InstData::Array(elements, _) => body.push_str(&format!(
+                    "{{ {{ {} }} }}",
+                    elements
+                        .iter()
+                        .map(|element| format!("{}, ", self.generate_expr(*element)))
+                        .collect::<String>()
+                )),
+                InstData::Length(base) => body.push_str(&self.generate_length(*base)),
+                InstData::Load {
+                    base,
+                    index,
+                    location,
+                } if self.is_vec(*base) => {
+                    let element_type = self.generate_type(type_);
+                    body.push_str(&format!(
+                        "*({element_type} *)builtin_vec_get({}, {}, sizeof({element_type}), {}, {})",
+                        self.generate_expr(*base),
+                        self.generate_expr(*index),
+                        location.line,
+                        location.column,
+                    ))
+                }
+                InstData::Load {
+                    base,
+                    index,
+                    location,
+                } => body.push_str(&format!(
+                    "{}.items[builtin_bounds_check({}, {}, {}, {})]",
+                    self.generate_expr(*base),
+                    self.generate_expr(*index),
+                    self.generate_length(*base),
+                    location.line,
+                    location.column,
+                )),
+                InstData::Store {
+                    base,
+                    index,
+                    value,
+                    location,
+                } if self.is_vec(*base) => {
+                    let element_type =
+                        self.generate_type(self.ssa.expression_type(self.types, *value));
+                    body.push_str(&format!(
+                        "*({element_type} *)builtin_vec_get({}, {}, sizeof({element_type}), {}, {}) = {}",
+                        self.generate_expr(*base),
+                        self.generate_expr(*index),
+                        location.line,
+                        location.column,
+                        self.generate_expr(*value),
+                    ))
+                }
+                InstData::Store {
+                    base,
+                    index,
+                    value,
+                    location,
+                } => body.push_str(&format!(
+                    "{}.items[builtin_bounds_check({}, {}, {}, {})] = {}",
+                    self.generate_expr(*base),
+                    self.generate_expr(*index),
+                    self.generate_length(*base),
+                    location.line,
+                    location.column,
+                    self.generate_expr(*value),
+                )),
+                InstData::Slice {
+                    base,
+                    start,
+                    end,
+                    location,
+                    ..
+                } => body.push_str(&format!(
+                    "{{ &{}.items[builtin_slice_check({}, {}, {}, {}, {})], {} - {} }}",
+                    self.generate_expr(*base),
+                    self.generate_expr(*start),
+                    self.generate_expr(*end),
+                    self.generate_length(*base),
+                    location.line,
+                    location.column,
+                    self.generate_expr(*end),
+                    self.generate_expr(*start),
+                )),
+                InstData::NewVec(elements, vec_type) => {
+                    let Val::Value(&TypeData::Vec { element }) = self.types.get(*vec_type) else {
+                        panic!()
+                    };
+                    let element_type = self.generate_type(element);
+
+                    let elements_text = if elements.is_empty() {
+                        "NULL".to_string()
+                    } else {
+                        format!(
+                            "({element_type}[]){{ {} }}",
+                            elements
+                                .iter()
+                                .map(|element| format!("{}, ", self.generate_expr(*element)))
+                                .collect::<String>()
+                        )
+                    };
+
+                    let heap_offsets = self.heap_offsets_table(element);
+
+                    body.push_str(&format!(
+                        "builtin_vec_new(sizeof({element_type}), {}, {elements_text}, {heap_offsets})",
+                        elements.len(),
+                    ))
+                }
+                InstData::Push { vec, value } => {
+                    let element_type =
+                        self.generate_type(self.ssa.expression_type(self.types, *value));
+                    body.push_str(&format!(
+                        "*({element_type} *)builtin_vec_push({}, sizeof({element_type})) = {}",
+                        self.generate_expr(*vec),
+                        self.generate_expr(*value),
+                    ))
+                }
+                InstData::Pop { vec, location } => body.push_str(&format!(
+                    "*({c_type} *)builtin_vec_pop({}, sizeof({c_type}), {}, {})",
+                    self.generate_expr(*vec),
+                    location.line,
+                    location.column,
+                )),
+                InstData::Alloca(pointer_type) => {
+                    let Val::Value(&TypeData::Pointer { pointee }) = self.types.get(*pointer_type)
+                    else {
+                        panic!()
+                    };
+
+                    // Compound literals live until the end of the function.
+                    body.push_str(&format!("&({}){{ 0 }}", self.generate_type(pointee)))
+                }
+                InstData::AddressOf(expr @ Expr::Const(_), _) => {
+                    let expr_type = self.generate_type(self.ssa.expression_type(self.types, *expr));
+                    body.push_str(&format!(
+                        "&({expr_type}){{ {} }}",
+                        self.generate_expr(*expr)
+                    ))
+                }
+                InstData::AddressOf(expr, _) => {
+                    body.push_str(&format!("&{}", self.generate_expr(*expr)))
+                }
+                InstData::LoadPointer(pointer) => {
+                    body.push_str(&format!("*{}", self.generate_expr(*pointer)))
+                }
+                InstData::StorePointer { pointer, value } => body.push_str(&format!(
+                    "*{} = {}",
+                    self.generate_expr(*pointer),
+                    self.generate_expr(*value),
+                )),
+                InstData::Retain(vec) => {
+                    body.push_str(&format!("builtin_vec_retain({})", self.generate_expr(*vec)))
+                }
+                InstData::Release(vec) => body.push_str(&format!(
+                    "builtin_vec_release({})",
+                    self.generate_expr(*vec)
+                )),
                 InstData::Equal(lhs, rhs) => body.push_str(&format!(
                     "{} == {}",
                     self.generate_expr(*lhs),
@@ -232,26 +518,22 @@ impl Generator<'_> {
                     self.generate_expr(*lhs),
                     self.generate_expr(*rhs),
                 )),
+                // Dividing by zero is undefined behavior in C.
+                InstData::Div(lhs, rhs) if self.ssa.divisor_may_be_zero(*rhs) => {
+                    body.push_str(&format!(
+                        "{} / builtin_divisor_check({})",
+                        self.generate_expr(*lhs),
+                        self.generate_expr(*rhs),
+                    ))
+                }
                 InstData::Div(lhs, rhs) => body.push_str(&format!(
                     "{} / {}",
                     self.generate_expr(*lhs),
                     self.generate_expr(*rhs),
                 )),
-                InstData::Call { function, argument } => match &self.ssa.blocks[*function] {
-                    BlockData::ExternFunction { name, .. } | BlockData::Function { name, .. } => {
-                        let argument_text = match argument {
-                            Expr::Const(const_)
-                                if const_.sentinel() == Some(ConstSentinel::Unit) =>
-                            {
-                                ""
-                            }
-                            _ => &self.generate_expr(*argument),
-                        };
-
-                        body.push_str(&format!("f{}_{name}({argument_text})", function.as_u32(),));
-                    }
-                    BlockData::Block { .. } => panic!(),
-                },
+                InstData::Call { function, argument } => {
+                    body.push_str(&self.generate_call(*function, *argument))
+                }
                 InstData::Jump { block, argument } => {
                     let argument_type = match &self.ssa.blocks[*block] {
                         BlockData::ExternFunction { arg, .. }
@@ -288,6 +570,25 @@ impl Generator<'_> {
                         body.push_str(&format!("return {}", self.generate_expr(*expr)))
                     }
                 }
+                InstData::TailCall { function, argument }
+                    if self.tail_groups.contains_key(function)
+                        && self.tail_groups.get(function) == self.tail_groups.get(&caller) =>
+                {
+                    if self.function_type(*function).0 != "void" {
+                        body.push_str(&format!(
+                            "a{} = {}; ",
+                            function.as_u32(),
+                            self.generate_expr(*argument)
+                        ));
+                    }
+                    body.push_str(&format!("goto b{}", function.as_u32()));
+                }
+                InstData::TailCall { function, argument } => {
+                    body.push_str(&format!(
+                        "return {}",
+                        self.generate_call(*function, *argument)
+                    ));
+                }
             }
 
             body.push_str(";\n");
@@ -296,14 +597,41 @@ impl Generator<'_> {
         body
     }
 
+    fn generate_call(&mut self, function: Block, argument: Expr) -> String {
+        let (BlockData::ExternFunction { name, .. } | BlockData::Function { name, .. }) =
+            &self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let argument_text = match argument {
+            Expr::Const(const_) if const_.sentinel() == Some(ConstSentinel::Unit) => "",
+            _ => &self.generate_expr(argument),
+        };
+
+        format!("f{}_{name}({argument_text})", function.as_u32())
+    }
+
+    // Argument and return types of a function, as declared in C.
+    fn function_type(&mut self, function: Block) -> (String, String) {
+        let (BlockData::ExternFunction { arg, ret, .. } | BlockData::Function { arg, ret, .. }) =
+            self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        (self.generate_type(arg), self.generate_type(ret))
+    }
+
     fn generate_type(&mut self, type_: Type) -> String {
         match self.types.get(type_) {
             Val::None => panic!(),
             Val::Sentinel(sentinel) => match sentinel {
                 TypeSentinel::Unknown => panic!(),
-                TypeSentinel::Unit => "void".to_string(),
+                TypeSentinel::Unit | TypeSentinel::Never => "void".to_string(),
                 TypeSentinel::Uint32 => "unsigned int".to_string(),
                 TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => "bool".to_string(),
+                TypeSentinel::Pointer => "void *".to_string(),
             },
             Val::Value(type_data) => match type_data {
                 TypeData::Function { .. } => todo!(),
@@ -331,10 +659,118 @@ impl Generator<'_> {
 
                     format!("struct t{}", type_.as_u32())
                 }
+                TypeData::Array { element, length } => {
+                    if let Some((ty, _)) = self
+                        .structs
+                        .iter()
+                        .find(|(ty, _)| types_equals(self.types, type_, *ty))
+                    {
+                        return format!("struct t{}", ty.as_u32());
+                    }
+
+                    let value = format!(
+                        "struct t{} {{ {} items[{length}]; }};",
+                        type_.as_u32(),
+                        self.generate_type(*element),
+                    );
+
+                    self.structs.push((type_, value));
+
+                    format!("struct t{}", type_.as_u32())
+                }
+                TypeData::Slice { element } => {
+                    if let Some((ty, _)) = self
+                        .structs
+                        .iter()
+                        .find(|(ty, _)| types_equals(self.types, type_, *ty))
+                    {
+                        return format!("struct t{}", ty.as_u32());
+                    }
+
+                    let value = format!(
+                        "struct t{} {{ {} *items; unsigned int length; }};",
+                        type_.as_u32(),
+                        self.generate_type(*element),
+                    );
+
+                    self.structs.push((type_, value));
+
+                    format!("struct t{}", type_.as_u32())
+                }
+                TypeData::Pointer { pointee } => format!("{} *", self.generate_type(*pointee)),
+                // The runtime only deals with bytes, elements are cast to
+                // their type where they are read or written.
+                TypeData::Vec { .. } => "struct builtin_vec *".to_string(),
             },
         }
     }
 
+    // Table of the offsets of the vecs held by an element and its length, as
+    // expected by `builtin_vec_new`.
+    fn heap_offsets_table(&mut self, element: Type) -> String {
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, "0".to_string(), &mut offsets);
+
+        if offsets.is_empty() {
+            return "NULL, 0".to_string();
+        }
+
+        let count = offsets.len();
+        let offsets = offsets.join(", ");
+        let table = match self.heap_offsets.iter().position(|table| *table == offsets) {
+            Some(table) => table,
+            None => {
+                self.heap_offsets.push(offsets);
+                self.heap_offsets.len() - 1
+            }
+        };
+
+        format!("heap_offsets{table}, {count}")
+    }
+
+    fn heap_offsets(&mut self, type_: Type, offset: String, offsets: &mut Vec<String>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                let struct_type = self.generate_type(type_);
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(
+                        *field,
+                        format!("{offset} + offsetof({struct_type}, f{i})"),
+                        offsets,
+                    );
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_type = self.generate_type(element);
+                for i in 0..length {
+                    self.heap_offsets(
+                        element,
+                        format!("{offset} + {i} * sizeof({element_type})"),
+                        offsets,
+                    );
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.ssa.expression_type(self.types, expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    fn generate_length(&mut self, base: Expr) -> String {
+        match self.types.get(self.ssa.expression_type(self.types, base)) {
+            Val::Value(TypeData::Array { length, .. }) => length.to_string(),
+            Val::Value(TypeData::Slice { .. }) => format!("{}.length", self.generate_expr(base)),
+            Val::Value(TypeData::Vec { .. }) => format!("{}->length", self.generate_expr(base)),
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
     fn generate_expr(&mut self, expr: Expr) -> String {
         match expr {
             Expr::Const(const_) => match self.ssa.consts.get(const_) {
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use super::object::{Object, RelocationKind, SectionKind};
+
+// Address at which executables are loaded.
+const BASE_ADDRESS: u64 = 0x400000;
+const PAGE_SIZE: u64 = 0x1000;
+
+const HEADER_SIZE: u64 = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+
+const PT_LOAD: u32 = 1;
+const PT_GNU_STACK: u32 = 0x6474e551;
+
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+// Link `objects` into a static executable starting at the symbol `entry`.
+//
+// The first segment is read only and executable, it holds the headers, the
+// `.text` and the `.rodata` sections. The second segment holds the `.data`
+// sections, at the next page. The last program header makes the stack non
+// executable.
+pub fn link(objects: &[Object], entry: &str) -> Vec<u8> {
+    let segments = 3;
+    let mut bytes = vec![0; (HEADER_SIZE + segments * PROGRAM_HEADER_SIZE) as usize];
+
+    // File offset of every section of every object, the address of a section
+    // is its offset from the base address.
+    let mut offsets = objects
+        .iter()
+        .map(|object| vec![0; object.sections.len()])
+        .collect::<Vec<_>>();
+
+    let mut text_end = 0;
+    let mut data_offset = 0;
+
+    for kind in [SectionKind::Text, SectionKind::Rodata, SectionKind::Data] {
+        if kind == SectionKind::Data {
+            text_end = bytes.len() as u64;
+            data_offset = text_end.next_multiple_of(PAGE_SIZE);
+            bytes.resize(data_offset as usize, 0);
+        }
+
+        for (object, offsets) in objects.iter().zip(&mut offsets) {
+            for (section, offset) in object.sections.iter().zip(offsets.iter_mut()) {
+                if section.kind != kind {
+                    continue;
+                }
+
+                *offset = (bytes.len() as u64).next_multiple_of(16);
+                bytes.resize(*offset as usize, 0);
+                bytes.extend(&section.data);
+            }
+        }
+    }
+
+    let mut globals = HashMap::new();
+    for (object, offsets) in objects.iter().zip(&offsets) {
+        for symbol in &object.symbols {
+            if let (true, Some(section)) = (symbol.global, symbol.section) {
+                let address = BASE_ADDRESS + offsets[section] + symbol.value;
+                if globals.insert(symbol.name.as_str(), address).is_some() {
+                    panic!("symbol {} defined twice", symbol.name);
+                }
+            }
+        }
+    }
+
+    for (object, offsets) in objects.iter().zip(&offsets) {
+        for (section, &offset) in object.sections.iter().zip(offsets) {
+            for relocation in &section.relocations {
+                let symbol = &object.symbols[relocation.symbol];
+                let address = match symbol.section {
+                    Some(section) if !symbol.global => {
+                        BASE_ADDRESS + offsets[section] + symbol.value
+                    }
+                    _ => *globals
+                        .get(symbol.name.as_str())
+                        .unwrap_or_else(|| panic!("undefined symbol {}", symbol.name)),
+                };
+
+                let position = (offset + relocation.offset) as usize;
+                let value = address.wrapping_add_signed(relocation.addend);
+
+                match relocation.kind {
+                    RelocationKind::Absolute64 => {
+                        bytes[position..position + 8].copy_from_slice(&value.to_le_bytes());
+                    }
+                    RelocationKind::Pc32 | RelocationKind::Plt32 => {
+                        let place = BASE_ADDRESS + offset + relocation.offset;
+                        let displacement = i32::try_from(value.wrapping_sub(place) as i64)
+                            .unwrap_or_else(|_| panic!("relocation to {} overflows", symbol.name));
+                        bytes[position..position + 4].copy_from_slice(&displacement.to_le_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    let entry = *globals
+        .get(entry)
+        .unwrap_or_else(|| panic!("undefined entry point {entry}"));
+
+    let mut header = Vec::new();
+    // Magic, 64 bits, little endian, version 1, System V ABI.
+    header.extend([0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.extend([0; 8]);
+    // Executable, x86-64, version 1.
+    header.extend(2u16.to_le_bytes());
+    header.extend(62u16.to_le_bytes());
+    header.extend(1u32.to_le_bytes());
+    header.extend(entry.to_le_bytes());
+    header.extend(HEADER_SIZE.to_le_bytes());
+    // No section headers.
+    header.extend(0u64.to_le_bytes());
+    header.extend(0u32.to_le_bytes());
+    header.extend((HEADER_SIZE as u16).to_le_bytes());
+    header.extend((PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    header.extend((segments as u16).to_le_bytes());
+    header.extend(64u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+
+    for (kind, flags, offset, size) in [
+        (PT_LOAD, PF_R | PF_X, 0, text_end),
+        (
+            PT_LOAD,
+            PF_R | PF_W,
+            data_offset,
+            bytes.len() as u64 - data_offset,
+        ),
+        (PT_GNU_STACK, PF_R | PF_W, 0, 0),
+    ] {
+        header.extend(kind.to_le_bytes());
+        header.extend(flags.to_le_bytes());
+        header.extend(offset.to_le_bytes());
+        header.extend((BASE_ADDRESS + offset).to_le_bytes());
+        header.extend((BASE_ADDRESS + offset).to_le_bytes());
+        header.extend(size.to_le_bytes());
+        header.extend(size.to_le_bytes());
+        header.extend(PAGE_SIZE.to_le_bytes());
+    }
+
+    bytes[..header.len()].copy_from_slice(&header);
+
+    bytes
+}
@@ -0,0 +1,8 @@
+//! Writer of ELF64 relocatable object files for x86-64, and a static linker
+//! combining them into executables for Linux.
+
+mod linker;
+mod object;
+
+pub use self::linker::link;
+pub use self::object::{Object, Relocation, RelocationKind, Section, SectionKind, Symbol};
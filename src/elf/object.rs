@@ -0,0 +1,347 @@
+#[derive(Debug, Default)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Debug)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub data: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionKind {
+    Text,
+    Data,
+    Rodata,
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    // Index in `Object::sections`, `None` for undefined symbols.
+    pub section: Option<usize>,
+    pub value: u64,
+    pub global: bool,
+}
+
+#[derive(Debug)]
+pub struct Relocation {
+    pub offset: u64,
+    // Index in `Object::symbols`.
+    pub symbol: usize,
+    pub kind: RelocationKind,
+    pub addend: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelocationKind {
+    Absolute64,
+    Pc32,
+    Plt32,
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+
+const HEADER_SIZE: u64 = 64;
+const SECTION_HEADER_SIZE: u64 = 64;
+const SYMBOL_SIZE: u64 = 24;
+const RELOCATION_SIZE: u64 = 24;
+
+impl SectionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SectionKind::Text => ".text",
+            SectionKind::Data => ".data",
+            SectionKind::Rodata => ".rodata",
+        }
+    }
+
+    fn flags(self) -> u64 {
+        match self {
+            SectionKind::Text => SHF_ALLOC | SHF_EXECINSTR,
+            SectionKind::Data => SHF_ALLOC | SHF_WRITE,
+            SectionKind::Rodata => SHF_ALLOC,
+        }
+    }
+
+    fn align(self) -> u64 {
+        match self {
+            SectionKind::Text => 16,
+            SectionKind::Data | SectionKind::Rodata => 8,
+        }
+    }
+}
+
+impl RelocationKind {
+    fn number(self) -> u64 {
+        match self {
+            RelocationKind::Absolute64 => 1,
+            RelocationKind::Pc32 => 2,
+            RelocationKind::Plt32 => 4,
+        }
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entry_size: u64,
+}
+
+impl Object {
+    pub fn section(&mut self, kind: SectionKind) -> usize {
+        match self
+            .sections
+            .iter()
+            .position(|section| section.kind == kind)
+        {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section {
+                    kind,
+                    data: Vec::new(),
+                    relocations: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        }
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        self.symbols.iter().position(|symbol| symbol.name == name)
+    }
+
+    // Layout of the file: header, contents of the sections, then section
+    // headers. Section 0 is the null section, followed by the sections of the
+    // object, their relocations, the symbol table, its string table, the
+    // string table of the section names and an empty `.note.GNU-stack` to
+    // mark the stack as non executable.
+    pub fn write(&self) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE as usize];
+        let mut headers = vec![SectionHeader {
+            name: 0,
+            kind: 0,
+            flags: 0,
+            offset: 0,
+            size: 0,
+            link: 0,
+            info: 0,
+            align: 0,
+            entry_size: 0,
+        }];
+        let mut section_names = StringTable::default();
+
+        let relocated_sections = self
+            .sections
+            .iter()
+            .filter(|section| !section.relocations.is_empty())
+            .count();
+        let symtab_index = 1 + self.sections.len() + relocated_sections;
+
+        for section in &self.sections {
+            let offset = append_aligned(&mut bytes, &section.data, section.kind.align());
+            headers.push(SectionHeader {
+                name: section_names.insert(section.kind.name()),
+                kind: SHT_PROGBITS,
+                flags: section.kind.flags(),
+                offset,
+                size: section.data.len() as u64,
+                link: 0,
+                info: 0,
+                align: section.kind.align(),
+                entry_size: 0,
+            });
+        }
+
+        // Locals must precede globals in the symbol table.
+        let mut order = (0..self.symbols.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&symbol| self.symbols[symbol].global);
+        let mut symbol_indices = vec![0; self.symbols.len()];
+        for (index, &symbol) in order.iter().enumerate() {
+            symbol_indices[symbol] = index + 1;
+        }
+
+        for (index, section) in self.sections.iter().enumerate() {
+            if section.relocations.is_empty() {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            for relocation in &section.relocations {
+                let symbol = symbol_indices[relocation.symbol] as u64;
+                data.extend(relocation.offset.to_le_bytes());
+                data.extend((symbol << 32 | relocation.kind.number()).to_le_bytes());
+                data.extend(relocation.addend.to_le_bytes());
+            }
+
+            let offset = append_aligned(&mut bytes, &data, 8);
+            headers.push(SectionHeader {
+                name: section_names.insert(&format!(".rela{}", section.kind.name())),
+                kind: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                offset,
+                size: data.len() as u64,
+                link: symtab_index as u32,
+                info: index as u32 + 1,
+                align: 8,
+                entry_size: RELOCATION_SIZE,
+            });
+        }
+
+        let mut names = StringTable::default();
+        let mut symtab = vec![0; SYMBOL_SIZE as usize];
+        for &symbol in &order {
+            let symbol = &self.symbols[symbol];
+            let bind = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+            let section = symbol.section.map_or(0, |section| section as u16 + 1);
+
+            symtab.extend(names.insert(&symbol.name).to_le_bytes());
+            symtab.push(bind << 4);
+            symtab.push(0);
+            symtab.extend(section.to_le_bytes());
+            symtab.extend(symbol.value.to_le_bytes());
+            symtab.extend(0u64.to_le_bytes());
+        }
+
+        let first_global = 1 + self.symbols.iter().filter(|symbol| !symbol.global).count();
+        let offset = append_aligned(&mut bytes, &symtab, 8);
+        headers.push(SectionHeader {
+            name: section_names.insert(".symtab"),
+            kind: SHT_SYMTAB,
+            flags: 0,
+            offset,
+            size: symtab.len() as u64,
+            link: symtab_index as u32 + 1,
+            info: first_global as u32,
+            align: 8,
+            entry_size: SYMBOL_SIZE,
+        });
+
+        let offset = append_aligned(&mut bytes, &names.bytes, 1);
+        headers.push(SectionHeader {
+            name: section_names.insert(".strtab"),
+            kind: SHT_STRTAB,
+            flags: 0,
+            offset,
+            size: names.bytes.len() as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entry_size: 0,
+        });
+
+        let note_name = section_names.insert(".note.GNU-stack");
+        let shstrtab_name = section_names.insert(".shstrtab");
+        let offset = append_aligned(&mut bytes, &section_names.bytes, 1);
+        headers.push(SectionHeader {
+            name: shstrtab_name,
+            kind: SHT_STRTAB,
+            flags: 0,
+            offset,
+            size: section_names.bytes.len() as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entry_size: 0,
+        });
+        let shstrtab_index = headers.len() - 1;
+
+        headers.push(SectionHeader {
+            name: note_name,
+            kind: SHT_PROGBITS,
+            flags: 0,
+            offset,
+            size: 0,
+            link: 0,
+            info: 0,
+            align: 1,
+            entry_size: 0,
+        });
+
+        let section_headers_offset = append_aligned(&mut bytes, &[], 8);
+        for header in &headers {
+            bytes.extend(header.name.to_le_bytes());
+            bytes.extend(header.kind.to_le_bytes());
+            bytes.extend(header.flags.to_le_bytes());
+            bytes.extend(0u64.to_le_bytes());
+            bytes.extend(header.offset.to_le_bytes());
+            bytes.extend(header.size.to_le_bytes());
+            bytes.extend(header.link.to_le_bytes());
+            bytes.extend(header.info.to_le_bytes());
+            bytes.extend(header.align.to_le_bytes());
+            bytes.extend(header.entry_size.to_le_bytes());
+        }
+
+        let mut header = Vec::new();
+        // Magic, 64 bits, little endian, version 1, System V ABI.
+        header.extend([0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        header.extend([0; 8]);
+        // Relocatable, x86-64, version 1.
+        header.extend(1u16.to_le_bytes());
+        header.extend(62u16.to_le_bytes());
+        header.extend(1u32.to_le_bytes());
+        // No entry point and no program headers.
+        header.extend(0u64.to_le_bytes());
+        header.extend(0u64.to_le_bytes());
+        header.extend(section_headers_offset.to_le_bytes());
+        header.extend(0u32.to_le_bytes());
+        header.extend((HEADER_SIZE as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend((SECTION_HEADER_SIZE as u16).to_le_bytes());
+        header.extend((headers.len() as u16).to_le_bytes());
+        header.extend((shstrtab_index as u16).to_le_bytes());
+
+        bytes[..HEADER_SIZE as usize].copy_from_slice(&header);
+
+        bytes
+    }
+}
+
+// Append `data` at the next multiple of `align` and return its offset.
+fn append_aligned(bytes: &mut Vec<u8>, data: &[u8], align: u64) -> u64 {
+    let offset = (bytes.len() as u64).next_multiple_of(align);
+    bytes.resize(offset as usize, 0);
+    bytes.extend(data);
+    offset
+}
+
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl Default for StringTable {
+    // Strings tables start with the empty string.
+    fn default() -> Self {
+        StringTable { bytes: vec![0] }
+    }
+}
+
+impl StringTable {
+    fn insert(&mut self, string: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend(string.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
@@ -0,0 +1,1279 @@
+mod runtime;
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use cranelift_codegen::{
+    ir::{
+        self, AbiParam, InstBuilder, MemFlags, Opcode, StackSlot, StackSlotData, StackSlotKind,
+        condcodes::IntCC, types,
+    },
+    isa::CallConv,
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{DataDescription, FuncId, Linkage, Module, default_libcall_names};
+
+use crate::{
+    key_vec::{KeyVec, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+    ssa::{
+        Block, BlockData, BlockSentinel, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData,
+        InstSentinel, Location, Ssa,
+    },
+};
+
+// Why the program stopped before the end of `main`.
+#[derive(Debug)]
+pub enum Error {
+    // A check failed, reported as the other runtimes do.
+    Trap {
+        reason: &'static str,
+        path: String,
+        location: Location,
+    },
+    DivisionByZero,
+    Output(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Trap {
+                reason,
+                path,
+                location,
+            } => write!(
+                f,
+                "{reason} at {path}:{}:{}",
+                location.line, location.column
+            ),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::Output(error) => write!(f, "failed to write the output: {error}"),
+        }
+    }
+}
+
+// Compile the program to machine code in memory with Cranelift and run its
+// `main`, writing what it prints to `output`.
+pub fn run(types: &Types, ssa: &Ssa, output: &mut dyn Write) -> Result<(), Error> {
+    // Tail calls rely on frame pointers.
+    let mut flags = settings::builder();
+    flags.set("preserve_frame_pointers", "true").unwrap();
+
+    let isa = cranelift_native::builder()
+        .unwrap()
+        .finish(settings::Flags::new(flags))
+        .unwrap();
+
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbols(runtime::symbols());
+
+    let mut generator = Generator {
+        types,
+        ssa,
+        module: JITModule::new(builder),
+        functions: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        runtime: HashMap::new(),
+        args_values: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        argument_areas: KeyVec::from_vec(vec![0; ssa.blocks.len()]),
+        insts_values: KeyVec::from_vec((0..ssa.insts.len()).map(|_| None).collect()),
+        trapped: runtime::trapped_address() as i64,
+    };
+
+    let main = generator.generate();
+    generator.module.finalize_definitions().unwrap();
+
+    let main = generator.module.get_finalized_function(main);
+    // The value returned by `main` is ignored.
+    let result = runtime::run(&ssa.path, output, || unsafe {
+        std::mem::transmute::<*const u8, extern "C" fn()>(main)()
+    });
+
+    unsafe { generator.module.free_memory() };
+
+    result
+}
+
+// Values of a type with a Cranelift equivalent are in variables, products,
+// arrays and slices are in stack slots. Slices are a pointer followed by the
+// length.
+#[derive(Clone, Copy)]
+enum Value {
+    None,
+    Variable(Variable),
+    Slot(StackSlot),
+}
+
+// Memory at an offset from an address.
+#[derive(Clone, Copy)]
+struct Place {
+    base: ir::Value,
+    offset: i32,
+}
+
+struct Generator<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    module: JITModule,
+    functions: KeyVec<BlockSentinel, Option<FuncId>>,
+    runtime: HashMap<&'static str, FuncId>,
+    args_values: KeyVec<BlockSentinel, Option<Value>>,
+    // Size of the area where the callers of each function write the
+    // arguments in memory.
+    argument_areas: KeyVec<BlockSentinel, u32>,
+    insts_values: KeyVec<InstSentinel, Option<Value>>,
+    // Address of the flag set by the runtime when a check failed.
+    trapped: i64,
+}
+
+struct Frame<'a> {
+    builder: FunctionBuilder<'a>,
+    blocks: HashMap<Block, ir::Block>,
+    // Where to write the returned value when it lives in memory.
+    return_pointer: Option<ir::Value>,
+    // Where the caller wrote the argument, reused by tail calls.
+    argument_area: Option<ir::Value>,
+    // Returns without a meaningful value once the runtime trapped, up to
+    // `main`.
+    trap_block: ir::Block,
+    variables: u32,
+    functions: HashMap<FuncId, ir::FuncRef>,
+}
+
+impl Generator<'_> {
+    // Declare every function and define those of the program, returning
+    // `main`.
+    fn generate(&mut self) -> FuncId {
+        let pointer = self.module.target_config().pointer_type();
+
+        for (name, params, returns) in [
+            (
+                "builtin_index_out_of_bounds",
+                &[types::I32, types::I32][..],
+                None,
+            ),
+            ("builtin_division_by_zero", &[], None),
+            (
+                "builtin_vec_new",
+                &[types::I32, types::I32, pointer, pointer, types::I32],
+                Some(pointer),
+            ),
+            ("builtin_vec_retain", &[pointer], None),
+            ("builtin_vec_release", &[pointer], None),
+            ("builtin_vec_push", &[pointer, types::I32], Some(pointer)),
+            (
+                "builtin_vec_pop",
+                &[pointer, types::I32, types::I32, types::I32],
+                Some(pointer),
+            ),
+            (
+                "builtin_vec_get",
+                &[pointer, types::I32, types::I32, types::I32, types::I32],
+                Some(pointer),
+            ),
+        ] {
+            let mut signature = self.module.make_signature();
+            signature
+                .params
+                .extend(params.iter().copied().map(AbiParam::new));
+            signature.returns.extend(returns.map(AbiParam::new));
+
+            let function = self
+                .module
+                .declare_function(name, Linkage::Import, &signature)
+                .unwrap();
+            self.runtime.insert(name, function);
+        }
+
+        self.size_argument_areas();
+
+        let mut main = None;
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            let function = match block_data {
+                // Extern functions are looked up by their name in the
+                // runtime.
+                BlockData::ExternFunction { name, arg, ret } => {
+                    let signature = self.signature(*arg, *ret);
+                    self.module
+                        .declare_function(name, Linkage::Import, &signature)
+                        .unwrap()
+                }
+                BlockData::Function { name, .. } => {
+                    let signature = self.function_signature(block);
+                    let function = self
+                        .module
+                        .declare_function(
+                            &format!("f{}_{name}", block.as_u32()),
+                            Linkage::Local,
+                            &signature,
+                        )
+                        .unwrap();
+
+                    if name == "main" {
+                        main = Some(function);
+                    }
+
+                    function
+                }
+                BlockData::Block { .. } => continue,
+            };
+
+            self.functions[block] = Some(function);
+        }
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::Function { .. } = block_data {
+                self.generate_function(block);
+            }
+        }
+
+        main.expect("no main function")
+    }
+
+    // Values with a Cranelift type are passed and returned as is, others are
+    // passed by address. The caller gives the address where to write the
+    // returned value as the first parameter.
+    fn signature(&self, arg: Type, ret: Type) -> ir::Signature {
+        let pointer = self.module.target_config().pointer_type();
+
+        let mut signature = self.module.make_signature();
+        if self.is_memory(ret) {
+            signature.params.push(AbiParam::new(pointer));
+        }
+        if self.is_memory(arg) {
+            signature.params.push(AbiParam::new(pointer));
+        }
+        signature
+            .params
+            .extend(self.scalar_type(arg).map(AbiParam::new));
+        signature
+            .returns
+            .extend(self.scalar_type(ret).map(AbiParam::new));
+        signature
+    }
+
+    // Arguments in memory are written to an area given by the caller, which
+    // the function reuses for the arguments of its tail calls. The area is as
+    // large as the arguments of the functions reached through tail calls.
+    fn size_argument_areas(&mut self) {
+        for (function, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::Function { arg, .. } = block_data
+                && self.is_memory(*arg)
+            {
+                self.argument_areas[function] = self.type_size(*arg);
+            }
+        }
+
+        let tail_calls = self.ssa.tail_calls();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(caller, callee) in &tail_calls {
+                if self.argument_areas[callee] > self.argument_areas[caller] {
+                    self.argument_areas[caller] = self.argument_areas[callee];
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    // Functions of the program can make tail calls between them, except
+    // `main` which is called by the host. Those with an argument area take
+    // its address in place of the address of an argument in memory.
+    fn function_signature(&self, function: Block) -> ir::Signature {
+        let BlockData::Function { name, arg, ret, .. } = &self.ssa.blocks[function] else {
+            panic!()
+        };
+
+        let mut signature = self.signature(*arg, *ret);
+        if self.argument_areas[function] != 0 && !self.is_memory(*arg) {
+            let pointer = self.module.target_config().pointer_type();
+            signature
+                .params
+                .insert(self.is_memory(*ret) as usize, AbiParam::new(pointer));
+        }
+        if name != "main" {
+            signature.call_conv = CallConv::Tail;
+        }
+        signature
+    }
+
+    fn generate_function(&mut self, function: Block) {
+        let ssa = self.ssa;
+
+        let BlockData::Function { arg, ret, .. } = &ssa.blocks[function] else {
+            panic!()
+        };
+
+        let mut context = self.module.make_context();
+        context.func.signature = self.function_signature(function);
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+
+        let trap_block = builder.create_block();
+        let mut frame = Frame {
+            builder,
+            blocks: HashMap::new(),
+            return_pointer: None,
+            argument_area: None,
+            trap_block,
+            variables: 0,
+            functions: HashMap::new(),
+        };
+
+        let mut blocks = Cfg::new(self.ssa, function).blocks()[1..].to_vec();
+        blocks.sort_by_key(|block| block.as_u32());
+        blocks.insert(0, function);
+
+        for &block in &blocks {
+            let cranelift_block = frame.builder.create_block();
+            frame.blocks.insert(block, cranelift_block);
+        }
+
+        self.reserve_values(&blocks, &mut frame);
+
+        let entry = frame.blocks[&function];
+        frame.builder.append_block_params_for_function_params(entry);
+        frame.builder.switch_to_block(entry);
+
+        let mut params = frame.builder.block_params(entry).to_vec().into_iter();
+        frame.return_pointer = self.is_memory(*ret).then(|| params.next().unwrap());
+        frame.argument_area = (self.argument_areas[function] != 0).then(|| params.next().unwrap());
+
+        // The argument is copied out of the area when it lives in memory, so
+        // that tail calls can write theirs there.
+        match self.args_values[function].unwrap() {
+            Value::Slot(slot) => {
+                let address = self.slot_address(slot, &mut frame);
+                let area = frame.argument_area.unwrap();
+                self.copy(address, area, self.type_size(*arg), &mut frame);
+            }
+            Value::Variable(variable) => {
+                frame.builder.def_var(variable, params.next().unwrap());
+            }
+            Value::None => {}
+        }
+
+        for &block in &blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            if block != function {
+                frame.builder.switch_to_block(frame.blocks[&block]);
+            }
+            for inst in insts {
+                self.generate_inst(*inst, &mut frame);
+            }
+        }
+
+        frame.builder.switch_to_block(frame.trap_block);
+        let values = self
+            .scalar_type(*ret)
+            .map(|type_| frame.builder.ins().iconst(type_, 0));
+        frame.builder.ins().return_(values.as_slice());
+
+        frame.builder.seal_all_blocks();
+        frame.builder.finalize();
+
+        self.module
+            .define_function(self.functions[function].unwrap(), &mut context)
+            .unwrap();
+    }
+
+    // Give a variable or a stack slot to every value of the function.
+    fn reserve_values(&mut self, blocks: &[Block], frame: &mut Frame) {
+        let ssa = self.ssa;
+
+        for &block in blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            let arg_type = self.expr_type(Expr::BlockArg(block));
+            self.args_values[block] = Some(self.reserve_value(arg_type, frame));
+
+            for inst in insts {
+                let inst_type = self.expr_type(Expr::Inst(*inst));
+                self.insts_values[*inst] = Some(self.reserve_value(inst_type, frame));
+            }
+        }
+    }
+
+    fn reserve_value(&self, type_: Type, frame: &mut Frame) -> Value {
+        if self.is_memory(type_) {
+            Value::Slot(self.reserve_slot(self.type_size(type_), frame))
+        } else if let Some(scalar_type) = self.scalar_type(type_) {
+            let variable = Variable::from_u32(frame.variables);
+            frame.variables += 1;
+            frame.builder.declare_var(variable, scalar_type);
+            Value::Variable(variable)
+        } else {
+            Value::None
+        }
+    }
+
+    fn reserve_slot(&self, size: u32, frame: &mut Frame) -> StackSlot {
+        frame.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size,
+            3,
+        ))
+    }
+
+    fn generate_inst(&mut self, inst: Inst, frame: &mut Frame) {
+        let value = self.insts_values[inst].unwrap();
+
+        match &self.ssa.insts[inst] {
+            InstData::Field(expr, field) => {
+                let field_offset = self.field_offset(self.expr_type(*expr), *field as usize);
+                let field_type = self.expr_type(Expr::Inst(inst));
+
+                let place = Place {
+                    base: self.address(*expr, frame),
+                    offset: field_offset as i32,
+                };
+                self.read(place, value, field_type, frame);
+            }
+            InstData::Record(fields, type_) => {
+                let Value::Slot(slot) = value else { panic!() };
+                let base = self.slot_address(slot, frame);
+
+                for (i, field) in fields.iter().enumerate() {
+                    let place = Place {
+                        base,
+                        offset: self.field_offset(*type_, i) as i32,
+                    };
+                    self.write(*field, place, frame);
+                }
+            }
+            InstData::Array(elements, _) => {
+                let Value::Slot(slot) = value else { panic!() };
+                let base = self.slot_address(slot, frame);
+
+                let mut offset = 0;
+                for element in elements {
+                    self.write(*element, Place { base, offset }, frame);
+                    offset += self.type_size(self.expr_type(*element)) as i32;
+                }
+            }
+            InstData::Length(base) if self.is_vec(*base) => {
+                // The length follows the pointer to the elements.
+                let vec = self.value(*base, frame).unwrap();
+                let length = frame
+                    .builder
+                    .ins()
+                    .load(types::I32, MemFlags::trusted(), vec, 8);
+                self.set(value, length, frame);
+            }
+            InstData::Length(base) => {
+                let length = self.length(*base, frame);
+                self.set(value, length, frame);
+            }
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let element_type = self.expr_type(Expr::Inst(inst));
+
+                let place = Place {
+                    base: self.element_address(*base, *index, element_type, location, frame),
+                    offset: 0,
+                };
+                self.read(place, value, element_type, frame);
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let element_type = self.expr_type(*value);
+
+                let place = Place {
+                    base: self.element_address(*base, *index, element_type, location, frame),
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            } => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Slice { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let Value::Slot(slot) = value else { panic!() };
+
+                let start = self.value(*start, frame).unwrap();
+                let end = self.value(*end, frame).unwrap();
+                let length = self.length(*base, frame);
+
+                let reversed = frame
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedGreaterThan, start, end);
+                let past_end = frame
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedGreaterThan, end, length);
+                let out_of_bounds = frame.builder.ins().bor(reversed, past_end);
+                self.trap_if(out_of_bounds, location, frame);
+
+                // The pointer is the base address offsetted by `start`
+                // elements, the length is `end - start`.
+                let base_address = self.base_address(*base, frame);
+                let offset = self.offset(start, element_size, frame);
+                let pointer = frame.builder.ins().iadd(base_address, offset);
+                let length = frame.builder.ins().isub(end, start);
+
+                frame.builder.ins().stack_store(pointer, slot, 0);
+                frame.builder.ins().stack_store(length, slot, 8);
+            }
+            InstData::NewVec(elements, type_) => {
+                let element_type = match self.types.get(*type_) {
+                    Val::Value(TypeData::Vec { element }) => *element,
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+                let element_size = self.type_size(element_type);
+
+                // The runtime copies the elements from the frame.
+                let elements_address = if elements.is_empty() {
+                    frame.builder.ins().iconst(self.pointer_type(), 0)
+                } else {
+                    let slot = self.reserve_slot(element_size * elements.len() as u32, frame);
+                    let base = self.slot_address(slot, frame);
+
+                    for (i, element) in elements.iter().enumerate() {
+                        let place = Place {
+                            base,
+                            offset: (i as u32 * element_size) as i32,
+                        };
+                        self.write(*element, place, frame);
+                    }
+
+                    base
+                };
+
+                let (heap_offsets, heap_count) = self.heap_offsets_table(element_type, frame);
+
+                let arguments = [
+                    frame.builder.ins().iconst(types::I32, element_size as i64),
+                    frame
+                        .builder
+                        .ins()
+                        .iconst(types::I32, elements.len() as i64),
+                    elements_address,
+                    heap_offsets,
+                    heap_count,
+                ];
+                let vec = self
+                    .call_runtime("builtin_vec_new", &arguments, frame)
+                    .unwrap();
+                self.set(value, vec, frame);
+            }
+            InstData::Push { vec, value } => {
+                let element_size = self.type_size(self.expr_type(*value));
+
+                let arguments = [
+                    self.value(*vec, frame).unwrap(),
+                    frame.builder.ins().iconst(types::I32, element_size as i64),
+                ];
+                let address = self
+                    .call_runtime("builtin_vec_push", &arguments, frame)
+                    .unwrap();
+
+                let place = Place {
+                    base: address,
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Pop { vec, location } => {
+                let element_type = self.expr_type(Expr::Inst(inst));
+
+                let arguments = [
+                    self.value(*vec, frame).unwrap(),
+                    frame
+                        .builder
+                        .ins()
+                        .iconst(types::I32, self.type_size(element_type) as i64),
+                    frame.builder.ins().iconst(types::I32, location.line as i64),
+                    frame
+                        .builder
+                        .ins()
+                        .iconst(types::I32, location.column as i64),
+                ];
+                let address = self
+                    .call_runtime("builtin_vec_pop", &arguments, frame)
+                    .unwrap();
+                self.check_trap(frame);
+
+                let place = Place {
+                    base: address,
+                    offset: 0,
+                };
+                self.read(place, value, element_type, frame);
+            }
+            InstData::Alloca(type_) => {
+                let pointee_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Pointer { pointee }) => self.type_size(*pointee),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let slot = self.reserve_slot(pointee_size, frame);
+                let address = self.slot_address(slot, frame);
+                self.set(value, address, frame);
+            }
+            InstData::AddressOf(expr, _) => {
+                let address = match self.expr_value(*expr) {
+                    Value::Slot(slot) => self.slot_address(slot, frame),
+                    // Values in variables are copied to memory.
+                    Value::None | Value::Variable(_) => {
+                        let size = self.type_size(self.expr_type(*expr));
+                        let slot = self.reserve_slot(size, frame);
+                        let base = self.slot_address(slot, frame);
+                        self.write(*expr, Place { base, offset: 0 }, frame);
+                        base
+                    }
+                };
+
+                self.set(value, address, frame);
+            }
+            InstData::LoadPointer(pointer) => {
+                let pointee_type = self.expr_type(Expr::Inst(inst));
+
+                let place = Place {
+                    base: self.value(*pointer, frame).unwrap(),
+                    offset: 0,
+                };
+                self.read(place, value, pointee_type, frame);
+            }
+            InstData::StorePointer { pointer, value } => {
+                let place = Place {
+                    base: self.value(*pointer, frame).unwrap(),
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Retain(vec) => {
+                let vec = self.value(*vec, frame).unwrap();
+                self.call_runtime("builtin_vec_retain", &[vec], frame);
+            }
+            InstData::Release(vec) => {
+                let vec = self.value(*vec, frame).unwrap();
+                self.call_runtime("builtin_vec_release", &[vec], frame);
+            }
+            InstData::Equal(lhs, rhs) => {
+                let lhs = self.value(*lhs, frame).unwrap();
+                let rhs = self.value(*rhs, frame).unwrap();
+                let result = frame.builder.ins().icmp(IntCC::Equal, lhs, rhs);
+                self.set(value, result, frame);
+            }
+            InstData::Add(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Opcode::Iadd, frame)
+            }
+            InstData::Sub(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Opcode::Isub, frame)
+            }
+            InstData::Mul(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Opcode::Imul, frame)
+            }
+            InstData::Div(lhs, rhs) => {
+                // Dividing by zero would raise a signal in the process
+                // running the program.
+                if self.ssa.divisor_may_be_zero(*rhs) {
+                    let divisor = self.value(*rhs, frame).unwrap();
+                    let zero = frame.builder.ins().icmp_imm(IntCC::Equal, divisor, 0);
+                    self.trap_with_if(zero, "builtin_division_by_zero", &[], frame);
+                }
+
+                self.binary_operation(value, *lhs, *rhs, Opcode::Udiv, frame)
+            }
+            InstData::Call { function, argument } => {
+                // The callee writes the returned value in our frame.
+                let mut arguments = Vec::new();
+                if let Value::Slot(slot) = value {
+                    arguments.push(self.slot_address(slot, frame));
+                }
+                arguments.extend(self.argument(*function, *argument, None, frame));
+
+                let result = self.call(self.functions[*function].unwrap(), &arguments, frame);
+                if let (Value::Variable(variable), Some(result)) = (value, result) {
+                    frame.builder.def_var(variable, result);
+                }
+
+                // Only `builtin_print` may fail among the extern functions.
+                self.check_trap(frame);
+            }
+            InstData::Jump {
+                block: target,
+                argument,
+            } => {
+                match self.args_values[*target].unwrap() {
+                    Value::None => {}
+                    Value::Variable(variable) => {
+                        let argument = self.value(*argument, frame).unwrap();
+                        frame.builder.def_var(variable, argument);
+                    }
+                    Value::Slot(slot) => {
+                        let base = self.slot_address(slot, frame);
+                        self.write(*argument, Place { base, offset: 0 }, frame);
+                    }
+                }
+
+                frame.builder.ins().jump(frame.blocks[target], &[]);
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => {
+                let condition = self.value(*condition, frame).unwrap();
+                frame.builder.ins().brif(
+                    condition,
+                    frame.blocks[then],
+                    &[],
+                    frame.blocks[else_],
+                    &[],
+                );
+            }
+            InstData::Return(expr) => {
+                let mut values = Vec::new();
+
+                match frame.return_pointer {
+                    Some(return_pointer) => {
+                        let place = Place {
+                            base: return_pointer,
+                            offset: 0,
+                        };
+                        self.write(*expr, place, frame);
+                    }
+                    None => values.extend(self.value(*expr, frame)),
+                }
+
+                frame.builder.ins().return_(&values);
+            }
+            InstData::TailCall { function, argument } => {
+                // The callee writes the returned value where our caller
+                // expects it.
+                let mut arguments = Vec::new();
+                arguments.extend(frame.return_pointer);
+
+                // `main` has no tail calls, as it follows the calling
+                // convention of the host, nor do the extern functions.
+                let tail = frame.builder.func.signature.call_conv == CallConv::Tail
+                    && matches!(self.ssa.blocks[*function], BlockData::Function { .. });
+                let area = if tail { frame.argument_area } else { None };
+                arguments.extend(self.argument(*function, *argument, area, frame));
+
+                let function = self.functions[*function].unwrap();
+                if !tail {
+                    let result = self.call(function, &arguments, frame);
+                    frame.builder.ins().return_(result.as_slice());
+                } else {
+                    let function = *frame.functions.entry(function).or_insert_with(|| {
+                        self.module
+                            .declare_func_in_func(function, frame.builder.func)
+                    });
+                    frame.builder.ins().return_call(function, &arguments);
+                }
+            }
+        }
+    }
+
+    fn binary_operation(
+        &self,
+        value: Value,
+        lhs: Expr,
+        rhs: Expr,
+        opcode: Opcode,
+        frame: &mut Frame,
+    ) {
+        let lhs = self.value(lhs, frame).unwrap();
+        let rhs = self.value(rhs, frame).unwrap();
+        let type_ = frame.builder.func.dfg.value_type(lhs);
+        let (inst, dfg) = frame.builder.ins().Binary(opcode, type_, lhs, rhs);
+        let result = dfg.first_result(inst);
+        self.set(value, result, frame);
+    }
+
+    // The argument of a call to `function`, with the area where it is written
+    // when the function takes one: `area` for a tail call, or else a new one.
+    fn argument(
+        &self,
+        function: Block,
+        argument: Expr,
+        area: Option<ir::Value>,
+        frame: &mut Frame,
+    ) -> Vec<ir::Value> {
+        let size = match self.ssa.blocks[function] {
+            BlockData::Function { .. } => self.argument_areas[function],
+            BlockData::ExternFunction { .. } | BlockData::Block { .. } => 0,
+        };
+        if size == 0 {
+            return self.value(argument, frame).into_iter().collect();
+        }
+
+        let area = area.unwrap_or_else(|| {
+            let slot = self.reserve_slot(size, frame);
+            self.slot_address(slot, frame)
+        });
+
+        if self.is_memory(self.expr_type(argument)) {
+            self.write(
+                argument,
+                Place {
+                    base: area,
+                    offset: 0,
+                },
+                frame,
+            );
+            vec![area]
+        } else {
+            let mut arguments = vec![area];
+            arguments.extend(self.value(argument, frame));
+            arguments
+        }
+    }
+
+    fn call(
+        &mut self,
+        function: FuncId,
+        arguments: &[ir::Value],
+        frame: &mut Frame,
+    ) -> Option<ir::Value> {
+        let function = *frame.functions.entry(function).or_insert_with(|| {
+            self.module
+                .declare_func_in_func(function, frame.builder.func)
+        });
+
+        let call = frame.builder.ins().call(function, arguments);
+        frame.builder.inst_results(call).first().copied()
+    }
+
+    fn call_runtime(
+        &mut self,
+        name: &str,
+        arguments: &[ir::Value],
+        frame: &mut Frame,
+    ) -> Option<ir::Value> {
+        self.call(self.runtime[name], arguments, frame)
+    }
+
+    // Return early if the runtime trapped during the last call.
+    fn check_trap(&self, frame: &mut Frame) {
+        let trapped = frame
+            .builder
+            .ins()
+            .iconst(self.pointer_type(), self.trapped);
+        let trapped = frame
+            .builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), trapped, 0);
+
+        let next = frame.builder.create_block();
+        frame
+            .builder
+            .ins()
+            .brif(trapped, frame.trap_block, &[], next, &[]);
+        frame.builder.switch_to_block(next);
+    }
+
+    // Report the failed bounds check and return early if `condition` holds.
+    fn trap_if(&mut self, condition: ir::Value, location: &Location, frame: &mut Frame) {
+        self.trap_with_if(
+            condition,
+            "builtin_index_out_of_bounds",
+            &[location.line, location.column],
+            frame,
+        );
+    }
+
+    // Call the runtime function `builtin` with the `u32` constants
+    // `arguments` when `condition` is true, it records the trap.
+    fn trap_with_if(
+        &mut self,
+        condition: ir::Value,
+        builtin: &'static str,
+        arguments: &[u32],
+        frame: &mut Frame,
+    ) {
+        let failed = frame.builder.create_block();
+        let next = frame.builder.create_block();
+        frame.builder.ins().brif(condition, failed, &[], next, &[]);
+
+        frame.builder.switch_to_block(failed);
+        let arguments = arguments
+            .iter()
+            .map(|argument| frame.builder.ins().iconst(types::I32, *argument as i64))
+            .collect::<Vec<_>>();
+        self.call_runtime(builtin, &arguments, frame);
+        frame.builder.ins().jump(frame.trap_block, &[]);
+
+        frame.builder.switch_to_block(next);
+    }
+
+    // The scalar value of `expr`, or the address of a value in memory.
+    fn value(&self, expr: Expr, frame: &mut Frame) -> Option<ir::Value> {
+        match expr {
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => panic!(),
+                Val::Sentinel(sentinel) => match sentinel {
+                    ConstSentinel::Unit => None,
+                    ConstSentinel::False => Some(frame.builder.ins().iconst(types::I8, 0)),
+                    ConstSentinel::True => Some(frame.builder.ins().iconst(types::I8, 1)),
+                },
+                Val::Value(const_data) => match const_data {
+                    ConstData::Uint32(value) => {
+                        Some(frame.builder.ins().iconst(types::I32, *value as i64))
+                    }
+                    ConstData::Product(_, _) => todo!(),
+                },
+            },
+            Expr::Inst(_) | Expr::BlockArg(_) => match self.expr_value(expr) {
+                Value::None => None,
+                Value::Variable(variable) => Some(frame.builder.use_var(variable)),
+                Value::Slot(slot) => Some(self.slot_address(slot, frame)),
+            },
+        }
+    }
+
+    fn address(&self, expr: Expr, frame: &mut Frame) -> ir::Value {
+        let Value::Slot(slot) = self.expr_value(expr) else {
+            panic!()
+        };
+        self.slot_address(slot, frame)
+    }
+
+    fn slot_address(&self, slot: StackSlot, frame: &mut Frame) -> ir::Value {
+        frame.builder.ins().stack_addr(self.pointer_type(), slot, 0)
+    }
+
+    fn set(&self, value: Value, result: ir::Value, frame: &mut Frame) {
+        let Value::Variable(variable) = value else {
+            panic!()
+        };
+        frame.builder.def_var(variable, result);
+    }
+
+    // Read a value of type `type_` at `place` in `value`.
+    fn read(&self, place: Place, value: Value, type_: Type, frame: &mut Frame) {
+        match value {
+            Value::None => {}
+            Value::Variable(variable) => {
+                let result = frame.builder.ins().load(
+                    self.scalar_type(type_).unwrap(),
+                    MemFlags::trusted(),
+                    place.base,
+                    place.offset,
+                );
+                frame.builder.def_var(variable, result);
+            }
+            Value::Slot(slot) => {
+                let address = self.slot_address(slot, frame);
+                let source = self.place_address(place, frame);
+                self.copy(address, source, self.type_size(type_), frame);
+            }
+        }
+    }
+
+    // Write the value of `expr` at `place`.
+    fn write(&self, expr: Expr, place: Place, frame: &mut Frame) {
+        let expr_type = self.expr_type(expr);
+
+        if self.is_memory(expr_type) {
+            let destination = self.place_address(place, frame);
+            let source = self.value(expr, frame).unwrap();
+            self.copy(destination, source, self.type_size(expr_type), frame);
+        } else if let Some(value) = self.value(expr, frame) {
+            frame
+                .builder
+                .ins()
+                .store(MemFlags::trusted(), value, place.base, place.offset);
+        }
+    }
+
+    fn place_address(&self, place: Place, frame: &mut Frame) -> ir::Value {
+        match place.offset {
+            0 => place.base,
+            offset => frame.builder.ins().iadd_imm(place.base, offset as i64),
+        }
+    }
+
+    fn copy(&self, destination: ir::Value, source: ir::Value, size: u32, frame: &mut Frame) {
+        if size == 0 {
+            return;
+        }
+
+        let size = frame.builder.ins().iconst(self.pointer_type(), size as i64);
+        frame
+            .builder
+            .call_memmove(self.module.target_config(), destination, source, size);
+    }
+
+    fn length(&self, base: Expr, frame: &mut Frame) -> ir::Value {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(&TypeData::Array { length, .. }) => {
+                frame.builder.ins().iconst(types::I32, length as i64)
+            }
+            Val::Value(TypeData::Slice { .. }) => {
+                let slice = self.address(base, frame);
+                frame
+                    .builder
+                    .ins()
+                    .load(types::I32, MemFlags::trusted(), slice, 8)
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Address of the first element of an array or slice.
+    fn base_address(&self, base: Expr, frame: &mut Frame) -> ir::Value {
+        let address = self.address(base, frame);
+
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(TypeData::Array { .. }) => address,
+            Val::Value(TypeData::Slice { .. }) => {
+                frame
+                    .builder
+                    .ins()
+                    .load(self.pointer_type(), MemFlags::trusted(), address, 0)
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Offset in bytes of the element `index`.
+    fn offset(&self, index: ir::Value, element_size: u32, frame: &mut Frame) -> ir::Value {
+        let index = frame.builder.ins().uextend(self.pointer_type(), index);
+        frame.builder.ins().imul_imm(index, element_size as i64)
+    }
+
+    // Address of the element `index` of an array, a slice or a vec, checking
+    // the bounds.
+    fn element_address(
+        &mut self,
+        base: Expr,
+        index: Expr,
+        element_type: Type,
+        location: &Location,
+        frame: &mut Frame,
+    ) -> ir::Value {
+        let element_size = self.type_size(element_type);
+        let index = self.value(index, frame).unwrap();
+
+        if self.is_vec(base) {
+            let arguments = [
+                self.value(base, frame).unwrap(),
+                index,
+                frame.builder.ins().iconst(types::I32, element_size as i64),
+                frame.builder.ins().iconst(types::I32, location.line as i64),
+                frame
+                    .builder
+                    .ins()
+                    .iconst(types::I32, location.column as i64),
+            ];
+            let address = self
+                .call_runtime("builtin_vec_get", &arguments, frame)
+                .unwrap();
+            self.check_trap(frame);
+            return address;
+        }
+
+        let length = self.length(base, frame);
+        let out_of_bounds =
+            frame
+                .builder
+                .ins()
+                .icmp(IntCC::UnsignedGreaterThanOrEqual, index, length);
+        self.trap_if(out_of_bounds, location, frame);
+
+        let base_address = self.base_address(base, frame);
+        let offset = self.offset(index, element_size, frame);
+        frame.builder.ins().iadd(base_address, offset)
+    }
+
+    // Table of the offsets of the vecs held by an element and its length, as
+    // expected by `builtin_vec_new`.
+    fn heap_offsets_table(&mut self, element: Type, frame: &mut Frame) -> (ir::Value, ir::Value) {
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, 0, &mut offsets);
+
+        let table = if offsets.is_empty() {
+            frame.builder.ins().iconst(self.pointer_type(), 0)
+        } else {
+            let data = self.module.declare_anonymous_data(false, false).unwrap();
+            let mut description = DataDescription::new();
+            description.define(
+                offsets
+                    .iter()
+                    .flat_map(|offset| offset.to_ne_bytes())
+                    .collect(),
+            );
+            description.set_align(4);
+            self.module.define_data(data, &description).unwrap();
+
+            let global = self.module.declare_data_in_func(data, frame.builder.func);
+            frame
+                .builder
+                .ins()
+                .global_value(self.pointer_type(), global)
+        };
+        let count = frame.builder.ins().iconst(types::I32, offsets.len() as i64);
+
+        (table, count)
+    }
+
+    fn heap_offsets(&self, type_: Type, offset: u32, offsets: &mut Vec<u32>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(*field, offset + self.field_offset(type_, i), offsets);
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_size = self.type_size(element);
+                for i in 0..length {
+                    self.heap_offsets(element, offset + i * element_size, offsets);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.expr_type(expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    fn is_memory(&self, type_: Type) -> bool {
+        matches!(
+            self.types.get(type_),
+            Val::Value(TypeData::Product { .. } | TypeData::Array { .. } | TypeData::Slice { .. })
+        )
+    }
+
+    // Type of the variables holding values of `type_`, booleans are bytes
+    // holding `0` or `1`.
+    fn scalar_type(&self, type_: Type) -> Option<ir::Type> {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit | TypeSentinel::Never => None,
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => Some(types::I8),
+                TypeSentinel::Uint32 => Some(types::I32),
+                TypeSentinel::Pointer => Some(self.pointer_type()),
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { .. } | TypeData::Array { .. } | TypeData::Slice { .. } => None,
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => Some(self.pointer_type()),
+            },
+        }
+    }
+
+    fn pointer_type(&self) -> ir::Type {
+        self.module.target_config().pointer_type()
+    }
+
+    #[track_caller]
+    fn expr_value(&self, expr: Expr) -> Value {
+        match expr {
+            Expr::Inst(inst) => self.insts_values[inst].unwrap(),
+            Expr::BlockArg(block) => self.args_values[block].unwrap(),
+            Expr::Const(_) => panic!(),
+        }
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    // Sizes in the layout of C for 64 bits targets, as expected by the
+    // runtime.
+    fn type_size(&self, type_: Type) -> u32 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => 1,
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .fold(0u32, |offset, (_, field)| {
+                        offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+                    })
+                    .next_multiple_of(self.type_align(type_)),
+                TypeData::Array { element, length } => self.type_size(*element) * length,
+                TypeData::Slice { .. } => 16,
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn type_align(&self, type_: Type) -> u32 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit
+                | TypeSentinel::Never
+                | TypeSentinel::Bool
+                | TypeSentinel::False
+                | TypeSentinel::True => 1,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .map(|(_, field)| self.type_align(*field))
+                    .max()
+                    .unwrap_or(1),
+                TypeData::Array { element, .. } => self.type_align(*element),
+                TypeData::Slice { .. } | TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn field_offset(&self, type_: Type, field: usize) -> u32 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .fold(0u32, |offset, (_, field)| {
+                offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+            })
+            .next_multiple_of(self.type_align(fields[field].1))
+    }
+}
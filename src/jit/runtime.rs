@@ -0,0 +1,271 @@
+// Builtins called by the code compiled in memory, behaving as `runtime.c`
+// except for traps: they are recorded in the state of the thread and the
+// generated code returns up to `main` once one happened.
+
+use std::{
+    alloc::{Layout, alloc, dealloc, handle_alloc_error},
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::Write,
+    ptr::null_mut,
+};
+
+use super::Error;
+use crate::ssa::Location;
+
+thread_local! {
+    // Read by the generated code after every call that may trap.
+    static TRAPPED: Cell<bool> = const { Cell::new(false) };
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+struct State {
+    path: String,
+    // Only valid during `run`.
+    output: *mut dyn Write,
+    error: Option<Error>,
+    // Layout of every allocation not yet freed, they are all freed at the
+    // end of the run, even if it trapped.
+    allocations: HashMap<*mut u8, Layout>,
+}
+
+// Every value is at most 8 bytes aligned.
+const ALIGN: usize = 8;
+
+pub fn trapped_address() -> *const bool {
+    TRAPPED.with(|trapped| trapped.as_ptr().cast_const())
+}
+
+// Run `main` with the builtins printing to `output`, traps are reported at
+// `path`.
+pub fn run(path: &str, output: &mut dyn Write, main: impl FnOnce()) -> Result<(), Error> {
+    // SAFETY: The output outlives the state, which is cleared before
+    // returning.
+    let output =
+        unsafe { std::mem::transmute::<*mut (dyn Write + '_), *mut (dyn Write + 'static)>(output) };
+
+    TRAPPED.with(|trapped| trapped.set(false));
+    STATE.with(|state| {
+        *state.borrow_mut() = Some(State {
+            path: path.to_string(),
+            output,
+            error: None,
+            allocations: HashMap::new(),
+        })
+    });
+
+    main();
+
+    let state = STATE.with(|state| state.borrow_mut().take()).unwrap();
+    for (pointer, layout) in state.allocations {
+        unsafe { dealloc(pointer, layout) };
+    }
+
+    match state.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    STATE.with(|state| f(state.borrow_mut().as_mut().unwrap()))
+}
+
+fn trap(error: Error) {
+    TRAPPED.with(|trapped| trapped.set(true));
+    with_state(|state| state.error = Some(error));
+}
+
+pub fn symbols() -> Vec<(&'static str, *const u8)> {
+    vec![
+        ("builtin_print", builtin_print as *const u8),
+        ("builtin_alloc", builtin_alloc as *const u8),
+        ("builtin_free", builtin_free as *const u8),
+        (
+            "builtin_index_out_of_bounds",
+            builtin_index_out_of_bounds as *const u8,
+        ),
+        (
+            "builtin_division_by_zero",
+            builtin_division_by_zero as *const u8,
+        ),
+        ("builtin_vec_new", builtin_vec_new as *const u8),
+        ("builtin_vec_retain", builtin_vec_retain as *const u8),
+        ("builtin_vec_release", builtin_vec_release as *const u8),
+        ("builtin_vec_push", builtin_vec_push as *const u8),
+        ("builtin_vec_pop", builtin_vec_pop as *const u8),
+        ("builtin_vec_get", builtin_vec_get as *const u8),
+    ]
+}
+
+extern "C" fn builtin_print(value: u32) {
+    let result = with_state(|state| writeln!(unsafe { &mut *state.output }, "{value}"));
+    if let Err(error) = result {
+        trap(Error::Output(error));
+    }
+}
+
+extern "C" fn builtin_alloc(size: u32) -> *mut u8 {
+    let layout = Layout::from_size_align(size.max(1) as usize, ALIGN).unwrap();
+    let pointer = unsafe { alloc(layout) };
+    if pointer.is_null() {
+        handle_alloc_error(layout);
+    }
+    with_state(|state| state.allocations.insert(pointer, layout));
+    pointer
+}
+
+unsafe extern "C" fn builtin_free(pointer: *mut u8) {
+    if pointer.is_null() {
+        return;
+    }
+    let layout = with_state(|state| state.allocations.remove(&pointer)).unwrap();
+    unsafe { dealloc(pointer, layout) };
+}
+
+extern "C" fn builtin_index_out_of_bounds(line: u32, column: u32) {
+    trap(Error::Trap {
+        reason: "index out of bounds",
+        path: with_state(|state| state.path.clone()),
+        location: Location { line, column },
+    });
+}
+
+// Division has no location, as in the interpreter.
+extern "C" fn builtin_division_by_zero() {
+    trap(Error::DivisionByZero);
+}
+
+// Same layout as `struct builtin_vec` of the C runtime.
+#[repr(C)]
+struct BuiltinVec {
+    items: *mut u8,
+    length: u32,
+    capacity: u32,
+    references: u32,
+    element_size: u32,
+    heap_offsets: *const u32,
+    heap_count: u32,
+}
+
+unsafe extern "C" fn builtin_vec_new(
+    element_size: u32,
+    length: u32,
+    elements: *const u8,
+    heap_offsets: *const u32,
+    heap_count: u32,
+) -> *mut BuiltinVec {
+    let vec = builtin_alloc(size_of::<BuiltinVec>() as u32).cast::<BuiltinVec>();
+    let items = builtin_alloc(element_size * length);
+    unsafe {
+        vec.write(BuiltinVec {
+            items,
+            length,
+            capacity: length,
+            references: 1,
+            element_size,
+            heap_offsets,
+            heap_count,
+        });
+        if length != 0 {
+            items.copy_from_nonoverlapping(elements, (element_size * length) as usize);
+        }
+    }
+    vec
+}
+
+unsafe extern "C" fn builtin_vec_retain(vec: *mut BuiltinVec) {
+    unsafe { (*vec).references += 1 };
+}
+
+unsafe extern "C" fn builtin_vec_release(vec: *mut BuiltinVec) {
+    let vec = unsafe { &mut *vec };
+
+    vec.references -= 1;
+    if vec.references != 0 {
+        return;
+    }
+
+    for i in 0..vec.length {
+        let element = unsafe { vec.items.add((vec.element_size * i) as usize) };
+        for j in 0..vec.heap_count {
+            unsafe {
+                let offset = vec.heap_offsets.add(j as usize).read();
+                let field = element
+                    .add(offset as usize)
+                    .cast::<*mut BuiltinVec>()
+                    .read_unaligned();
+                builtin_vec_release(field);
+            }
+        }
+    }
+
+    unsafe {
+        builtin_free(vec.items);
+        builtin_free((vec as *mut BuiltinVec).cast());
+    }
+}
+
+// Returns where the pushed element must be written.
+unsafe extern "C" fn builtin_vec_push(vec: *mut BuiltinVec, element_size: u32) -> *mut u8 {
+    let vec = unsafe { &mut *vec };
+
+    if vec.length == vec.capacity {
+        let capacity = if vec.capacity == 0 {
+            4
+        } else {
+            vec.capacity * 2
+        };
+        let items = builtin_alloc(element_size * capacity);
+        unsafe {
+            if vec.length != 0 {
+                items.copy_from_nonoverlapping(vec.items, (element_size * vec.length) as usize);
+            }
+            builtin_free(vec.items);
+        }
+        vec.items = items;
+        vec.capacity = capacity;
+    }
+
+    vec.length += 1;
+    unsafe { vec.items.add((element_size * (vec.length - 1)) as usize) }
+}
+
+// Returns where the popped element is, it stays valid until the next push.
+unsafe extern "C" fn builtin_vec_pop(
+    vec: *mut BuiltinVec,
+    element_size: u32,
+    line: u32,
+    column: u32,
+) -> *mut u8 {
+    let vec = unsafe { &mut *vec };
+
+    if vec.length == 0 {
+        trap(Error::Trap {
+            reason: "pop from empty vec",
+            path: with_state(|state| state.path.clone()),
+            location: Location { line, column },
+        });
+        return null_mut();
+    }
+
+    vec.length -= 1;
+    unsafe { vec.items.add((element_size * vec.length) as usize) }
+}
+
+unsafe extern "C" fn builtin_vec_get(
+    vec: *mut BuiltinVec,
+    index: u32,
+    element_size: u32,
+    line: u32,
+    column: u32,
+) -> *mut u8 {
+    let vec = unsafe { &mut *vec };
+
+    if index >= vec.length {
+        builtin_index_out_of_bounds(line, column);
+        return null_mut();
+    }
+
+    unsafe { vec.items.add((element_size * index) as usize) }
+}
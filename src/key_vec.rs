@@ -63,6 +63,10 @@ impl<S: Sentinel, V> KeyVec<S, V> {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn from_vec(vec: Vec<V>) -> Self {
         Self(vec, PhantomData)
     }
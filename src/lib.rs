@@ -1,10 +1,16 @@
 #![feature(macro_derive)]
 
+pub mod aarch64_asm_codegen;
 pub mod amd64_asm_codegen;
 pub mod c_codegen;
 pub mod diagnotic;
+pub mod elf;
+pub mod jit;
 pub mod key_vec;
+pub mod llvm_ir_codegen;
+pub mod runtime;
 pub mod semantic;
 pub mod ssa;
 pub mod syntax;
 pub mod token;
+pub mod wasm_codegen;
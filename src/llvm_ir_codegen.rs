@@ -0,0 +1,1086 @@
+use std::{collections::HashMap, process::Command};
+
+use crate::{
+    key_vec::Val,
+    semantic::{Type, TypeData, TypeSentinel, Types, types_equals},
+    ssa::{Block, BlockData, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData, Location, Ssa},
+};
+
+// Generate a module of LLVM IR in its textual form, with opaque pointers, to
+// be linked with `runtime.c`.
+pub fn generate(types: &Types, ssa: &Ssa) -> String {
+    let mut generator = Generator {
+        types,
+        ssa,
+        functions: String::new(),
+        structs: Vec::new(),
+        heap_offsets: Vec::new(),
+        allocas: String::new(),
+        temporaries: 0,
+        result: None,
+    };
+
+    generator.generate();
+
+    generator.result()
+}
+
+const RUNTIME_DECLARATIONS: &str = "\
+%builtin_vec = type { ptr, i32, i32, i32, i32, ptr, i32 }
+
+declare i32 @builtin_bounds_check(i32, i32, i32, i32)
+declare i32 @builtin_slice_check(i32, i32, i32, i32, i32)
+declare i32 @builtin_divisor_check(i32)
+declare ptr @builtin_vec_new(i32, i32, ptr, ptr, i32)
+declare void @builtin_vec_retain(ptr)
+declare void @builtin_vec_release(ptr)
+declare ptr @builtin_vec_push(ptr, i32)
+declare ptr @builtin_vec_pop(ptr, i32, i32, i32)
+declare ptr @builtin_vec_get(ptr, i32, i32, i32, i32)
+";
+
+// Flags of `llc` to compile the generated IR: opaque pointers are the default
+// from LLVM 15, they must be enabled before.
+pub fn llc_flags() -> &'static [&'static str] {
+    let major = Command::new("llc")
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|version| {
+            let version = String::from_utf8(version.stdout).ok()?;
+            let (_, version) = version.split_once("LLVM version ")?;
+            version.split('.').next()?.parse::<u32>().ok()
+        });
+
+    match major {
+        Some(major) if major < 15 => &["-opaque-pointers"],
+        _ => &[],
+    }
+}
+
+struct Generator<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    functions: String,
+    structs: Vec<(Type, String)>,
+    // Offsets of the vecs held by the elements of vecs, each table is the
+    // constant `@heap_offsets{i}`.
+    heap_offsets: Vec<Vec<u64>>,
+    // Allocas of the function being generated, all in its entry block.
+    allocas: String,
+    temporaries: u32,
+    // Pointer to the result of the function being generated, when it is
+    // returned through memory.
+    result: Option<String>,
+}
+
+impl Generator<'_> {
+    fn result(self) -> String {
+        format!(
+            "{RUNTIME_DECLARATIONS}\n{}\n\n{}@builtin_source_path = constant [{} x i8] [{}]\n\n{}define i32 @main() {{\n  call tailcc void @f{}_main()\n  ret i32 0\n}}\n",
+            self.structs
+                .into_iter()
+                .map(|(_, definition)| definition)
+                .collect::<Vec<String>>()
+                .join("\n"),
+            self.heap_offsets
+                .iter()
+                .enumerate()
+                .map(|(i, offsets)| format!(
+                    "@heap_offsets{i} = private unnamed_addr constant [{} x i32] [{}]\n\n",
+                    offsets.len(),
+                    offsets
+                        .iter()
+                        .map(|offset| format!("i32 {offset}"))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ))
+                .collect::<String>(),
+            self.ssa.path.len() + 1,
+            self.ssa
+                .path
+                .bytes()
+                .chain([0])
+                .map(|byte| format!("i8 {byte}"))
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.functions,
+            self.ssa
+                .blocks
+                .entries()
+                .find(|(_, block_data)| matches!(
+                    block_data,
+                    BlockData::Function { name, .. } if name == "main",
+                ))
+                .unwrap()
+                .0
+                .as_u32()
+        )
+    }
+
+    fn generate(&mut self) {
+        for (block, block_data) in self.ssa.blocks.entries() {
+            match block_data {
+                BlockData::ExternFunction { .. } => {
+                    let function = self.generate_extern_function(block);
+                    self.functions.push_str(&function);
+                    self.functions.push_str("\n\n");
+                }
+                BlockData::Function { .. } => {
+                    let function = self.generate_function(block);
+                    self.functions.push_str(&function);
+                    self.functions.push_str("\n\n");
+                }
+                BlockData::Block { .. } => {}
+            }
+        }
+    }
+
+    // Extern functions are called directly by their name.
+    fn generate_extern_function(&mut self, function: Block) -> String {
+        let BlockData::ExternFunction { name, arg, ret } = &self.ssa.blocks[function] else {
+            panic!()
+        };
+
+        let return_type = self.generate_type(*ret).unwrap_or("void".to_string());
+        let argument_type = self.generate_type(*arg).unwrap_or_default();
+
+        format!("declare {return_type} @{name}({argument_type})")
+    }
+
+    fn generate_function(&mut self, function: Block) -> String {
+        let BlockData::Function {
+            name,
+            arg,
+            ret,
+            insts,
+        } = &self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        let function_number = function.as_u32();
+
+        self.allocas.clear();
+        self.temporaries = 0;
+
+        // Records and arrays are returned through a pointer given by the
+        // caller, which tail calls pass on.
+        let (return_type, mut parameters) = if self.returns_in_memory(function) {
+            self.result = Some(format!("%r{function_number}"));
+            ("void".to_string(), vec![format!("ptr %r{function_number}")])
+        } else {
+            self.result = None;
+            (
+                self.generate_type(*ret).unwrap_or("void".to_string()),
+                Vec::new(),
+            )
+        };
+
+        // Arrays live in memory so that their elements can be indexed and
+        // stored to, the argument is copied to its own alloca.
+        match self.generate_type(*arg) {
+            None => {}
+            Some(argument_type) if self.is_array(*arg) => {
+                self.allocas.push_str(&format!(
+                    "  %a{function_number} = alloca {argument_type}\n  store {argument_type} %a{function_number}.value, ptr %a{function_number}\n"
+                ));
+                parameters.push(format!("{argument_type} %a{function_number}.value"));
+            }
+            Some(argument_type) => parameters.push(format!("{argument_type} %a{function_number}")),
+        }
+
+        let head = format!(
+            "define tailcc {return_type} @f{function_number}_{name}({})",
+            parameters.join(", ")
+        );
+
+        let mut blocks = Cfg::new(self.ssa, function).blocks()[1..].to_vec();
+        blocks.sort_by_key(|block| block.as_u32());
+
+        let predecessors = self.predecessors(function, &blocks);
+
+        let mut body = self.generate_block(insts);
+
+        for block in &blocks {
+            let BlockData::Block { arg, insts } = &self.ssa.blocks[*block] else {
+                panic!()
+            };
+
+            let block_number = block.as_u32();
+            body.push_str(&format!("\nb{block_number}:\n"));
+
+            // Block arguments are phi nodes with the values given by the
+            // jumps to the block, other predecessors leave them undefined.
+            if let Some(argument_type) = self.generate_type(*arg) {
+                let incoming = predecessors
+                    .get(block)
+                    .into_iter()
+                    .flatten()
+                    .map(|(predecessor, value)| {
+                        format!(
+                            "[ {}, %b{} ]",
+                            value.as_deref().unwrap_or("undef"),
+                            predecessor.as_u32()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                if self.is_array(*arg) {
+                    body.push_str(&format!(
+                        "  %a{block_number}.value = phi {argument_type} {incoming}\n  store {argument_type} %a{block_number}.value, ptr %a{block_number}\n"
+                    ));
+                    self.allocas
+                        .push_str(&format!("  %a{block_number} = alloca {argument_type}\n"));
+                } else {
+                    body.push_str(&format!(
+                        "  %a{block_number} = phi {argument_type} {incoming}\n"
+                    ));
+                }
+            }
+
+            body.push_str(&self.generate_block(insts));
+        }
+
+        format!("{head} {{\nb{function_number}:\n{}{body}}}", self.allocas)
+    }
+
+    // Predecessors of every block with the value of its argument given by the
+    // predecessor, named as in `generate_jump`.
+    fn predecessors(
+        &self,
+        function: Block,
+        blocks: &[Block],
+    ) -> HashMap<Block, Vec<(Block, Option<String>)>> {
+        let mut predecessors = HashMap::<Block, Vec<_>>::new();
+
+        for &block in [function].iter().chain(blocks) {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &self.ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            let terminator = *insts.last().unwrap();
+            match &self.ssa.insts[terminator] {
+                InstData::Jump {
+                    block: target,
+                    argument,
+                } => {
+                    let value = match argument {
+                        _ if self.is_array(self.expr_type(*argument)) => {
+                            Some(format!("%i{}.argument", terminator.as_u32()))
+                        }
+                        Expr::Const(const_) if const_.sentinel() == Some(ConstSentinel::Unit) => {
+                            None
+                        }
+                        _ => Some(self.generate_expr(*argument)),
+                    };
+                    predecessors
+                        .entry(*target)
+                        .or_default()
+                        .push((block, value));
+                }
+                InstData::JumpCondition { then, else_, .. } => {
+                    for target in [then, else_] {
+                        predecessors.entry(*target).or_default().push((block, None));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        predecessors
+    }
+
+    fn generate_block(&mut self, insts: &[Inst]) -> String {
+        let mut body = String::new();
+
+        for inst in insts {
+            body.push_str(&self.generate_inst(*inst));
+        }
+
+        body
+    }
+
+    fn generate_inst(&mut self, inst: Inst) -> String {
+        let inst_number = inst.as_u32();
+        let type_ = self.ssa.instruction_type(self.types, inst);
+
+        match &self.ssa.insts[inst] {
+            InstData::Field(_, _) if self.generate_type(type_).is_none() => String::new(),
+            InstData::Field(expr, field) => {
+                let record_type = self.generate_type(self.expr_type(*expr)).unwrap();
+                let (mut body, value) = self.generate_value(*expr);
+                body.push_str(&self.define(
+                    inst,
+                    &format!("extractvalue {record_type} {value}, {field}"),
+                ));
+                body
+            }
+            InstData::Record(fields, record_type) => {
+                let Some(llvm_type) = self.generate_type(*record_type) else {
+                    return String::new();
+                };
+
+                let mut body = String::new();
+                let mut record = "undef".to_string();
+
+                // Fields without a representation are empty structs.
+                for (i, field) in fields.iter().enumerate() {
+                    let (field_type, value) = match self.generate_type(self.expr_type(*field)) {
+                        Some(field_type) => {
+                            let (field_body, value) = self.generate_value(*field);
+                            body.push_str(&field_body);
+                            (field_type, value)
+                        }
+                        None => ("{}".to_string(), "zeroinitializer".to_string()),
+                    };
+
+                    let instruction =
+                        format!("insertvalue {llvm_type} {record}, {field_type} {value}, {i}");
+
+                    if i + 1 == fields.len() {
+                        body.push_str(&self.define(inst, &instruction));
+                    } else {
+                        record = self.temporary();
+                        body.push_str(&format!("  {record} = {instruction}\n"));
+                    }
+                }
+
+                body
+            }
+            InstData::Array(elements, array_type) => {
+                let llvm_type = self.generate_type(*array_type).unwrap();
+                self.allocas
+                    .push_str(&format!("  %i{inst_number} = alloca {llvm_type}\n"));
+
+                let mut body = String::new();
+                for (i, element) in elements.iter().enumerate() {
+                    let Some(element_type) = self.generate_type(self.expr_type(*element)) else {
+                        continue;
+                    };
+                    let (element_body, value) = self.generate_value(*element);
+                    body.push_str(&element_body);
+
+                    let temporary = self.temporary();
+                    body.push_str(&format!(
+                        "  {temporary} = getelementptr {llvm_type}, ptr %i{inst_number}, i32 0, i32 {i}\n  store {element_type} {value}, ptr {temporary}\n"
+                    ));
+                }
+                body
+            }
+            InstData::Length(base) => match self.types.get(self.expr_type(*base)) {
+                Val::Value(TypeData::Array { length, .. }) => {
+                    self.define(inst, &format!("add i32 {length}, 0"))
+                }
+                Val::Value(TypeData::Slice { .. }) => self.define(
+                    inst,
+                    &format!(
+                        "extractvalue {{ ptr, i32 }} {}, 1",
+                        self.generate_expr(*base)
+                    ),
+                ),
+                Val::Value(TypeData::Vec { .. }) => {
+                    let temporary = self.temporary();
+                    format!(
+                        "  {temporary} = getelementptr %builtin_vec, ptr {}, i32 0, i32 1\n{}",
+                        self.generate_expr(*base),
+                        self.define(inst, &format!("load i32, ptr {temporary}"))
+                    )
+                }
+                Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+            },
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let (mut body, address) = self.element_address(*base, *index, type_, location);
+                if let Some(element_type) = self.generate_type(type_) {
+                    body.push_str(
+                        &self.define(inst, &format!("load {element_type}, ptr {address}")),
+                    );
+                }
+                body
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let value_type = self.expr_type(*value);
+                let (mut body, address) = self.element_address(*base, *index, value_type, location);
+                if let Some(element_type) = self.generate_type(value_type) {
+                    let (value_body, value) = self.generate_value(*value);
+                    body.push_str(&value_body);
+                    body.push_str(&format!("  store {element_type} {value}, ptr {address}\n"));
+                }
+                body
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_: slice_type,
+                location,
+            } => {
+                let Val::Value(&TypeData::Slice { element }) = self.types.get(*slice_type) else {
+                    panic!()
+                };
+                let element_type = self.generate_type(element).unwrap_or("{}".to_string());
+
+                let (mut body, items, length) = self.items_and_length(*base);
+                let checked = self.temporary();
+                let pointer = self.temporary();
+                let slice_length = self.temporary();
+                let slice = self.temporary();
+
+                body.push_str(&format!(
+                    "  {checked} = call i32 @builtin_slice_check(i32 {}, i32 {}, i32 {length}, i32 {}, i32 {})\n",
+                    self.generate_expr(*start),
+                    self.generate_expr(*end),
+                    location.line,
+                    location.column,
+                ));
+                body.push_str(&format!(
+                    "  {pointer} = getelementptr {element_type}, ptr {items}, i32 {checked}\n"
+                ));
+                body.push_str(&format!(
+                    "  {slice_length} = sub i32 {}, {}\n",
+                    self.generate_expr(*end),
+                    self.generate_expr(*start),
+                ));
+                body.push_str(&format!(
+                    "  {slice} = insertvalue {{ ptr, i32 }} undef, ptr {pointer}, 0\n"
+                ));
+                body.push_str(&self.define(
+                    inst,
+                    &format!("insertvalue {{ ptr, i32 }} {slice}, i32 {slice_length}, 1"),
+                ));
+                body
+            }
+            InstData::NewVec(elements, vec_type) => {
+                let Val::Value(&TypeData::Vec { element }) = self.types.get(*vec_type) else {
+                    panic!()
+                };
+                let element_size = self.type_size(element);
+                let (heap_offsets, heap_count) = self.heap_offsets_table(element);
+
+                let mut body = String::new();
+
+                // The runtime copies the elements from an alloca.
+                let elements_pointer = match self.generate_type(element) {
+                    Some(element_type) if !elements.is_empty() => {
+                        let length = elements.len();
+                        self.allocas.push_str(&format!(
+                            "  %i{inst_number}.elements = alloca [{length} x {element_type}]\n"
+                        ));
+
+                        for (i, element) in elements.iter().enumerate() {
+                            let (element_body, value) = self.generate_value(*element);
+                            body.push_str(&element_body);
+
+                            let temporary = self.temporary();
+                            body.push_str(&format!(
+                                "  {temporary} = getelementptr [{length} x {element_type}], ptr %i{inst_number}.elements, i32 0, i32 {i}\n  store {element_type} {value}, ptr {temporary}\n"
+                            ));
+                        }
+
+                        format!("%i{inst_number}.elements")
+                    }
+                    _ => "null".to_string(),
+                };
+
+                body.push_str(&self.define(
+                    inst,
+                    &format!(
+                        "call ptr @builtin_vec_new(i32 {element_size}, i32 {}, ptr {elements_pointer}, ptr {heap_offsets}, i32 {heap_count})",
+                        elements.len()
+                    ),
+                ));
+                body
+            }
+            InstData::Push { vec, value } => {
+                let value_type = self.expr_type(*value);
+                let slot = self.temporary();
+
+                let mut body = format!(
+                    "  {slot} = call ptr @builtin_vec_push(ptr {}, i32 {})\n",
+                    self.generate_expr(*vec),
+                    self.type_size(value_type),
+                );
+                if let Some(element_type) = self.generate_type(value_type) {
+                    let (value_body, value) = self.generate_value(*value);
+                    body.push_str(&value_body);
+                    body.push_str(&format!("  store {element_type} {value}, ptr {slot}\n"));
+                }
+                body
+            }
+            InstData::Pop { vec, location } => {
+                let slot = self.temporary();
+
+                let mut body = format!(
+                    "  {slot} = call ptr @builtin_vec_pop(ptr {}, i32 {}, i32 {}, i32 {})\n",
+                    self.generate_expr(*vec),
+                    self.type_size(type_),
+                    location.line,
+                    location.column,
+                );
+                if let Some(element_type) = self.generate_type(type_) {
+                    body.push_str(&self.define(inst, &format!("load {element_type}, ptr {slot}")));
+                }
+                body
+            }
+            InstData::Alloca(pointer_type) => {
+                let Val::Value(&TypeData::Pointer { pointee }) = self.types.get(*pointer_type)
+                else {
+                    panic!()
+                };
+
+                let pointee_type = self.generate_type(pointee).unwrap_or("{}".to_string());
+                self.allocas
+                    .push_str(&format!("  %i{inst_number} = alloca {pointee_type}\n"));
+                String::new()
+            }
+            InstData::AddressOf(expr, _) => {
+                let expr_type = self.generate_type(self.expr_type(*expr));
+                let allocated_type = expr_type.clone().unwrap_or("{}".to_string());
+                self.allocas
+                    .push_str(&format!("  %i{inst_number} = alloca {allocated_type}\n"));
+
+                match expr_type {
+                    Some(expr_type) => {
+                        let (mut body, value) = self.generate_value(*expr);
+                        body.push_str(&format!(
+                            "  store {expr_type} {value}, ptr %i{inst_number}\n"
+                        ));
+                        body
+                    }
+                    None => String::new(),
+                }
+            }
+            InstData::LoadPointer(pointer) => match self.generate_type(type_) {
+                Some(pointee_type) => self.define(
+                    inst,
+                    &format!("load {pointee_type}, ptr {}", self.generate_expr(*pointer)),
+                ),
+                None => String::new(),
+            },
+            InstData::StorePointer { pointer, value } => {
+                match self.generate_type(self.expr_type(*value)) {
+                    Some(value_type) => {
+                        let (mut body, value) = self.generate_value(*value);
+                        body.push_str(&format!(
+                            "  store {value_type} {value}, ptr {}\n",
+                            self.generate_expr(*pointer)
+                        ));
+                        body
+                    }
+                    None => String::new(),
+                }
+            }
+            InstData::Retain(vec) => format!(
+                "  call void @builtin_vec_retain(ptr {})\n",
+                self.generate_expr(*vec)
+            ),
+            InstData::Release(vec) => format!(
+                "  call void @builtin_vec_release(ptr {})\n",
+                self.generate_expr(*vec)
+            ),
+            InstData::Equal(lhs, rhs) => self.binary_operation(inst, "icmp eq", *lhs, *rhs),
+            InstData::Add(lhs, rhs) => self.binary_operation(inst, "add", *lhs, *rhs),
+            InstData::Sub(lhs, rhs) => self.binary_operation(inst, "sub", *lhs, *rhs),
+            InstData::Mul(lhs, rhs) => self.binary_operation(inst, "mul", *lhs, *rhs),
+            // Dividing by zero is undefined behavior in LLVM.
+            InstData::Div(lhs, rhs) if self.ssa.divisor_may_be_zero(*rhs) => {
+                let checked = self.temporary();
+                let mut body = format!(
+                    "  {checked} = call i32 @builtin_divisor_check(i32 {})\n",
+                    self.generate_expr(*rhs)
+                );
+                body.push_str(&self.define(
+                    inst,
+                    &format!("udiv i32 {}, {checked}", self.generate_expr(*lhs)),
+                ));
+                body
+            }
+            InstData::Div(lhs, rhs) => self.binary_operation(inst, "udiv", *lhs, *rhs),
+            InstData::Call { function, argument } if self.returns_in_memory(*function) => {
+                let result = format!("%i{inst_number}.result");
+                let (mut body, call) = self.generate_call(*function, *argument, Some(&result));
+                let return_type = self.function_type(*function).1.unwrap();
+
+                self.allocas
+                    .push_str(&format!("  {result} = alloca {return_type}\n"));
+                body.push_str(&format!("  call tailcc void {call}\n"));
+                body.push_str(&self.define(inst, &format!("load {return_type}, ptr {result}")));
+                body
+            }
+            InstData::Call { function, argument } => {
+                let (mut body, call) = self.generate_call(*function, *argument, None);
+                let convention = self.convention(*function);
+
+                match self.function_type(*function).1 {
+                    Some(return_type) => body.push_str(
+                        &self.define(inst, &format!("call {convention}{return_type} {call}")),
+                    ),
+                    None => body.push_str(&format!("  call {convention}void {call}\n")),
+                }
+                body
+            }
+            InstData::Jump { block, argument } => {
+                let mut body = String::new();
+
+                // Arrays are loaded from memory to be given to the phi node.
+                if self.is_array(self.expr_type(*argument)) {
+                    body.push_str(&format!(
+                        "  %i{inst_number}.argument = load {}, ptr {}\n",
+                        self.generate_type(self.expr_type(*argument)).unwrap(),
+                        self.generate_expr(*argument),
+                    ));
+                }
+
+                body.push_str(&format!("  br label %b{}\n", block.as_u32()));
+                body
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => {
+                let mut body = String::new();
+                let mut condition_value = self.generate_expr(*condition);
+
+                // Conditions of other types than bool are compared to zero.
+                let condition_type = self.generate_type(self.expr_type(*condition)).unwrap();
+                if condition_type != "i1" {
+                    let temporary = self.temporary();
+                    body.push_str(&format!(
+                        "  {temporary} = icmp ne {condition_type} {condition_value}, 0\n"
+                    ));
+                    condition_value = temporary;
+                }
+
+                body.push_str(&format!(
+                    "  br i1 {condition_value}, label %b{}, label %b{}\n",
+                    then.as_u32(),
+                    else_.as_u32(),
+                ));
+                body
+            }
+            InstData::Return(expr) => match self.generate_type(self.expr_type(*expr)) {
+                Some(return_type) if self.result.is_some() => {
+                    let (mut body, value) = self.generate_value(*expr);
+                    body.push_str(&format!(
+                        "  store {return_type} {value}, ptr {}\n  ret void\n",
+                        self.result.as_ref().unwrap()
+                    ));
+                    body
+                }
+                Some(return_type) => {
+                    let (mut body, value) = self.generate_value(*expr);
+                    body.push_str(&format!("  ret {return_type} {value}\n"));
+                    body
+                }
+                None => "  ret void\n".to_string(),
+            },
+            // Tail calls between functions of `tailcc` are guaranteed whatever
+            // their signatures, the ones to the runtime are only hinted.
+            InstData::TailCall { function, argument } if self.returns_in_memory(*function) => {
+                let result = self.result.clone().unwrap();
+                let (mut body, call) = self.generate_call(*function, *argument, Some(&result));
+                body.push_str(&format!("  tail call tailcc void {call}\n  ret void\n"));
+                body
+            }
+            InstData::TailCall { function, argument } => {
+                let (mut body, call) = self.generate_call(*function, *argument, None);
+                let convention = self.convention(*function);
+
+                match self.function_type(*function).1 {
+                    Some(return_type) => {
+                        let temporary = self.temporary();
+                        body.push_str(&format!(
+                            "  {temporary} = tail call {convention}{return_type} {call}\n  ret {return_type} {temporary}\n"
+                        ));
+                    }
+                    None => body.push_str(&format!(
+                        "  tail call {convention}void {call}\n  ret void\n"
+                    )),
+                }
+                body
+            }
+        }
+    }
+
+    // Code computing the argument, followed by the callee with the pointer to
+    // its result and its argument.
+    fn generate_call(
+        &mut self,
+        function: Block,
+        argument: Expr,
+        result: Option<&str>,
+    ) -> (String, String) {
+        let callee = match &self.ssa.blocks[function] {
+            BlockData::ExternFunction { name, .. } => name.clone(),
+            BlockData::Function { name, .. } => format!("f{}_{name}", function.as_u32()),
+            BlockData::Block { .. } => panic!(),
+        };
+
+        let mut arguments: Vec<_> = result
+            .map(|result| format!("ptr {result}"))
+            .into_iter()
+            .collect();
+        let mut body = String::new();
+        if let Some(argument_type) = self.generate_type(self.expr_type(argument)) {
+            let value;
+            (body, value) = self.generate_value(argument);
+            arguments.push(format!("{argument_type} {value}"));
+        }
+
+        (body, format!("@{callee}({})", arguments.join(", ")))
+    }
+
+    fn returns_in_memory(&self, function: Block) -> bool {
+        match self.ssa.blocks[function] {
+            BlockData::Function { ret, .. } => matches!(
+                self.types.get(ret),
+                Val::Value(TypeData::Product { .. } | TypeData::Array { .. })
+            ),
+            BlockData::ExternFunction { .. } | BlockData::Block { .. } => false,
+        }
+    }
+
+    // Functions of the program use `tailcc` so that their tail calls are
+    // guaranteed, the runtime uses the C convention.
+    fn convention(&self, function: Block) -> &'static str {
+        match self.ssa.blocks[function] {
+            BlockData::Function { .. } => "tailcc ",
+            BlockData::ExternFunction { .. } | BlockData::Block { .. } => "",
+        }
+    }
+
+    fn function_type(&mut self, function: Block) -> (Option<String>, Option<String>) {
+        let (BlockData::ExternFunction { arg, ret, .. } | BlockData::Function { arg, ret, .. }) =
+            self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        (self.generate_type(arg), self.generate_type(ret))
+    }
+
+    fn binary_operation(&mut self, inst: Inst, operation: &str, lhs: Expr, rhs: Expr) -> String {
+        let operand_type = self.generate_type(self.expr_type(lhs)).unwrap();
+
+        self.define(
+            inst,
+            &format!(
+                "{operation} {operand_type} {}, {}",
+                self.generate_expr(lhs),
+                self.generate_expr(rhs),
+            ),
+        )
+    }
+
+    // Define the value of an instruction, arrays are stored to their alloca.
+    fn define(&mut self, inst: Inst, instruction: &str) -> String {
+        let inst_number = inst.as_u32();
+        let type_ = self.ssa.instruction_type(self.types, inst);
+
+        if self.is_array(type_) {
+            let llvm_type = self.generate_type(type_).unwrap();
+            self.allocas
+                .push_str(&format!("  %i{inst_number} = alloca {llvm_type}\n"));
+
+            format!(
+                "  %i{inst_number}.value = {instruction}\n  store {llvm_type} %i{inst_number}.value, ptr %i{inst_number}\n"
+            )
+        } else {
+            format!("  %i{inst_number} = {instruction}\n")
+        }
+    }
+
+    // The value of an expression, loaded from memory for arrays.
+    fn generate_value(&mut self, expr: Expr) -> (String, String) {
+        let expr_type = self.expr_type(expr);
+
+        if self.is_array(expr_type) {
+            let temporary = self.temporary();
+            (
+                format!(
+                    "  {temporary} = load {}, ptr {}\n",
+                    self.generate_type(expr_type).unwrap(),
+                    self.generate_expr(expr)
+                ),
+                temporary,
+            )
+        } else {
+            (String::new(), self.generate_expr(expr))
+        }
+    }
+
+    // Pointer to the first element and length of an array or slice.
+    fn items_and_length(&mut self, base: Expr) -> (String, String, String) {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(TypeData::Array { length, .. }) => {
+                (String::new(), self.generate_expr(base), length.to_string())
+            }
+            Val::Value(TypeData::Slice { .. }) => {
+                let items = self.temporary();
+                let length = self.temporary();
+                let base = self.generate_expr(base);
+                (
+                    format!(
+                        "  {items} = extractvalue {{ ptr, i32 }} {base}, 0\n  {length} = extractvalue {{ ptr, i32 }} {base}, 1\n"
+                    ),
+                    items,
+                    length,
+                )
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Address of the element `index` of an array, a slice or a vec, checking
+    // the bounds.
+    fn element_address(
+        &mut self,
+        base: Expr,
+        index: Expr,
+        element_type: Type,
+        location: &Location,
+    ) -> (String, String) {
+        let address = self.temporary();
+
+        if self.is_vec(base) {
+            return (
+                format!(
+                    "  {address} = call ptr @builtin_vec_get(ptr {}, i32 {}, i32 {}, i32 {}, i32 {})\n",
+                    self.generate_expr(base),
+                    self.generate_expr(index),
+                    self.type_size(element_type),
+                    location.line,
+                    location.column,
+                ),
+                address,
+            );
+        }
+
+        let llvm_element_type = self.generate_type(element_type).unwrap_or("{}".to_string());
+        let (mut body, items, length) = self.items_and_length(base);
+        let checked = self.temporary();
+
+        body.push_str(&format!(
+            "  {checked} = call i32 @builtin_bounds_check(i32 {}, i32 {length}, i32 {}, i32 {})\n",
+            self.generate_expr(index),
+            location.line,
+            location.column,
+        ));
+        body.push_str(&format!(
+            "  {address} = getelementptr {llvm_element_type}, ptr {items}, i32 {checked}\n"
+        ));
+
+        (body, address)
+    }
+
+    fn temporary(&mut self) -> String {
+        self.temporaries += 1;
+        format!("%t{}", self.temporaries - 1)
+    }
+
+    // The type of values of `type_`, or `None` for values without a
+    // representation.
+    fn generate_type(&mut self, type_: Type) -> Option<String> {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit | TypeSentinel::Never => None,
+                TypeSentinel::Uint32 => Some("i32".to_string()),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => {
+                    Some("i1".to_string())
+                }
+                TypeSentinel::Pointer => Some("ptr".to_string()),
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => todo!(),
+                TypeData::Product { fields } if fields.is_empty() => None,
+                TypeData::Product { fields } => {
+                    if let Some((ty, _)) = self
+                        .structs
+                        .iter()
+                        .find(|(ty, _)| types_equals(self.types, type_, *ty))
+                    {
+                        return Some(format!("%t{}", ty.as_u32()));
+                    }
+
+                    let value = format!(
+                        "%t{} = type {{ {} }}",
+                        type_.as_u32(),
+                        fields
+                            .iter()
+                            .map(|(_, type_)| self
+                                .generate_type(*type_)
+                                .unwrap_or("{}".to_string()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+
+                    self.structs.push((type_, value));
+
+                    Some(format!("%t{}", type_.as_u32()))
+                }
+                TypeData::Array { element, length } => Some(format!(
+                    "[{length} x {}]",
+                    self.generate_type(*element).unwrap_or("{}".to_string())
+                )),
+                // A pointer to the first element followed by the length.
+                TypeData::Slice { .. } => Some("{ ptr, i32 }".to_string()),
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => Some("ptr".to_string()),
+            },
+        }
+    }
+
+    // Table of the offsets of the vecs held by an element and its length, as
+    // expected by `builtin_vec_new`.
+    fn heap_offsets_table(&mut self, element: Type) -> (String, usize) {
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, 0, &mut offsets);
+
+        if offsets.is_empty() {
+            return ("null".to_string(), 0);
+        }
+
+        let count = offsets.len();
+        let table = match self.heap_offsets.iter().position(|table| *table == offsets) {
+            Some(table) => table,
+            None => {
+                self.heap_offsets.push(offsets);
+                self.heap_offsets.len() - 1
+            }
+        };
+
+        (format!("@heap_offsets{table}"), count)
+    }
+
+    fn heap_offsets(&self, type_: Type, offset: u64, offsets: &mut Vec<u64>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(*field, offset + self.field_offset(type_, i), offsets);
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_size = self.type_size(element);
+                for i in 0..length as u64 {
+                    self.heap_offsets(element, offset + i * element_size, offsets);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.expr_type(expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    fn is_array(&self, type_: Type) -> bool {
+        matches!(self.types.get(type_), Val::Value(TypeData::Array { .. }))
+    }
+
+    fn generate_expr(&self, expr: Expr) -> String {
+        match expr {
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => panic!(),
+                Val::Sentinel(sentinel) => match sentinel {
+                    ConstSentinel::Unit => panic!(),
+                    ConstSentinel::False => "false".to_string(),
+                    ConstSentinel::True => "true".to_string(),
+                },
+                Val::Value(value) => match value {
+                    ConstData::Uint32(value) => value.to_string(),
+                    ConstData::Product(_, _) => todo!(),
+                },
+            },
+            Expr::Inst(inst) => format!("%i{}", inst.as_u32()),
+            Expr::BlockArg(block) => format!("%a{}", block.as_u32()),
+        }
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    // Sizes in the layout of LLVM for x86-64, the same as in C.
+    fn type_size(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => 1,
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .fold(0u64, |offset, (_, field)| {
+                        offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+                    })
+                    .next_multiple_of(self.type_align(type_)),
+                TypeData::Array { element, length } => self.type_size(*element) * *length as u64,
+                TypeData::Slice { .. } => 16,
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn type_align(&self, type_: Type) -> u64 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit
+                | TypeSentinel::Never
+                | TypeSentinel::Bool
+                | TypeSentinel::False
+                | TypeSentinel::True => 1,
+                TypeSentinel::Uint32 => 4,
+                TypeSentinel::Pointer => 8,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .map(|(_, field)| self.type_align(*field))
+                    .max()
+                    .unwrap_or(1),
+                TypeData::Array { element, .. } => self.type_align(*element),
+                TypeData::Slice { .. } | TypeData::Pointer { .. } | TypeData::Vec { .. } => 8,
+            },
+        }
+    }
+
+    fn field_offset(&self, type_: Type, field: usize) -> u64 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .fold(0u64, |offset, (_, field)| {
+                offset.next_multiple_of(self.type_align(*field)) + self.type_size(*field)
+            })
+            .next_multiple_of(self.type_align(fields[field].1))
+    }
+}
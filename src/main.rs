@@ -1,98 +1,458 @@
-use std::{process::Command, time::Instant};
+use std::{
+    env::temp_dir,
+    fs::Permissions,
+    io::Write,
+    os::unix::{fs::PermissionsExt, process::ExitStatusExt},
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, ExitStatus, Stdio},
+    time::Instant,
+};
 
 use colored::Colorize;
 use keb::{
-    amd64_asm_codegen, c_codegen,
+    aarch64_asm_codegen, amd64_asm_codegen, c_codegen, elf, jit, llvm_ir_codegen, runtime,
     semantic::{self, Types},
     ssa::{self, Ssa},
-    syntax, token,
+    syntax, token, wasm_codegen,
 };
 
-fn main() {
-    let source = std::fs::read_to_string("input.keb").unwrap();
+const USAGE: &str = "\
+Usage: keb <command> <input> [options]
+
+Commands:
+    build    Compile the input to an executable
+    run      Compile the input and run it
+    check    Check the input without generating code
+
+Options:
+    -o <path>              Where to write the executable, `a.out` by default
+    --backend <backend>    c, amd64, aarch64, llvm, wasm, jit or interpreter, amd64 by default
+    --emit <stage>         Print tokens, syntax, sem, ssa, counts, c or asm, can be repeated,
+                           asm is for aarch64 with the aarch64 backend and amd64 otherwise
+    -O                     Optimize the ssa, `--emit ssa` prints it after each pass and
+                           `--emit counts` the number of instructions left by each pass
+    --quiet                Only print what the program prints
+";
+
+// Usage errors exit with 2, compilation errors with 1 and `run` exits with
+// the code of the program.
+const USAGE_ERROR: u8 = 2;
+const COMPILE_ERROR: u8 = 1;
+// Exit code of a program that trapped, as with `abort`.
+const TRAP: u8 = 134;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Subcommand {
+    Build,
+    Run,
+    Check,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    C,
+    Amd64,
+    // Cross compiled with the C toolchain of aarch64 and run under
+    // `qemu-aarch64`.
+    Aarch64,
+    LlvmIr,
+    Wasm,
+    // Only run programs, in the process of the compiler.
+    Jit,
+    Interpreter,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Tokens,
+    Syntax,
+    Sem,
+    Ssa,
+    // Number of ssa instructions.
+    Counts,
+    C,
+    Asm,
+}
+
+struct Options {
+    subcommand: Subcommand,
+    input: PathBuf,
+    output: Option<PathBuf>,
+    backend: Backend,
+    emit: Vec<Stage>,
+    optimize: bool,
+    quiet: bool,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+
+    let source = match std::fs::read_to_string(&options.input) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: cannot read {}: {error}", options.input.display());
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+
+    // Errors in the program are reported by panics of the compiler.
+    let Ok((types, ssa)) = catch_unwind(AssertUnwindSafe(|| compile_to_ssa(&source, &options)))
+    else {
+        return ExitCode::from(COMPILE_ERROR);
+    };
+
+    if options.emit.contains(&Stage::C) {
+        debug_header("C", &options);
+        print!("{}", c_codegen::generate(&types, &ssa));
+    }
+
+    if options.emit.contains(&Stage::Asm) {
+        debug_header("ASSEMBLY", &options);
+        match options.backend {
+            Backend::Aarch64 => print!("{}", aarch64_asm_codegen::generate(&types, &ssa)),
+            _ => print!("{}", amd64_asm_codegen::generate(&types, &ssa)),
+        }
+    }
 
-    let (types, ssa) = compile_to_ssa(&source);
+    let result = match options.subcommand {
+        Subcommand::Check => Ok(ExitCode::SUCCESS),
+        Subcommand::Build => {
+            let output = options
+                .output
+                .clone()
+                .unwrap_or_else(|| default_output(options.backend));
+            build(&types, &ssa, options.backend, &output).map(|()| ExitCode::SUCCESS)
+        }
+        Subcommand::Run => run(&types, &ssa, &options),
+    };
 
-    // run_ssa_with_c_codegen(&types, &ssa);
-    run_ssa_with_amd64_asm_codegen(&types, &ssa);
+    result.unwrap_or_else(|message| {
+        eprintln!("error: {message}");
+        ExitCode::from(COMPILE_ERROR)
+    })
 }
 
-fn compile_to_ssa(source: &str) -> (Types, Ssa) {
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let subcommand = match args.next().as_deref() {
+        Some("build") => Subcommand::Build,
+        Some("run") => Subcommand::Run,
+        Some("check") => Subcommand::Check,
+        Some(subcommand) => return Err(format!("unknown command `{subcommand}`")),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut input = None;
+    let mut output = None;
+    let mut backend = Backend::Amd64;
+    let mut emit = Vec::new();
+    let mut optimize = false;
+    let mut quiet = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{option}`"))
+        };
+
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(value("-o")?)),
+            "--backend" => {
+                backend = match value("--backend")?.as_str() {
+                    "c" => Backend::C,
+                    "amd64" => Backend::Amd64,
+                    "aarch64" => Backend::Aarch64,
+                    "llvm" => Backend::LlvmIr,
+                    "wasm" => Backend::Wasm,
+                    "jit" => Backend::Jit,
+                    "interpreter" => Backend::Interpreter,
+                    backend => return Err(format!("unknown backend `{backend}`")),
+                }
+            }
+            "--emit" => emit.push(match value("--emit")?.as_str() {
+                "tokens" => Stage::Tokens,
+                "syntax" => Stage::Syntax,
+                "sem" => Stage::Sem,
+                "ssa" => Stage::Ssa,
+                "counts" => Stage::Counts,
+                "c" => Stage::C,
+                "asm" => Stage::Asm,
+                stage => return Err(format!("unknown stage `{stage}`")),
+            }),
+            "-O" => optimize = true,
+            "--quiet" => quiet = true,
+            option if option.starts_with('-') => {
+                return Err(format!("unknown option `{option}`"));
+            }
+            _ if input.is_some() => return Err(format!("unexpected argument `{arg}`")),
+            _ => input = Some(PathBuf::from(arg)),
+        }
+    }
+
+    if subcommand == Subcommand::Build && matches!(backend, Backend::Jit | Backend::Interpreter) {
+        return Err("the jit and the interpreter can only run programs".to_string());
+    }
+
+    Ok(Options {
+        subcommand,
+        input: input.ok_or("missing input")?,
+        output,
+        backend,
+        emit,
+        optimize,
+        quiet,
+    })
+}
+
+fn compile_to_ssa(source: &str, options: &Options) -> (Types, Ssa) {
     let start = Instant::now();
-    let tokens = token::lex(&source);
-    debug_header_duration("SOURCE (colored based on tokens)", start);
-    token::debug(&source, &tokens);
+    let tokens = token::lex(source);
+    if options.emit.contains(&Stage::Tokens) {
+        debug_header_duration("SOURCE (colored based on tokens)", start, options);
+        token::debug(source, &tokens);
+    }
 
     let start = Instant::now();
     let syntax = syntax::parse(&tokens.kinds);
-    debug_header_duration("SYNTAX", start);
-    syntax::debug(&syntax);
+    if options.emit.contains(&Stage::Syntax) {
+        debug_header_duration("SYNTAX", start, options);
+        syntax::debug(&syntax);
+    }
 
     let start = Instant::now();
-    let (mut semantic, mut types) = semantic::parse(&source, &tokens.offsets, &syntax);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
     semantic::infer_types(&mut semantic, &mut types);
-    debug_header_duration("SEMANTIC", start);
-    semantic::debug(&semantic, &types);
+    if options.emit.contains(&Stage::Sem) {
+        debug_header_duration("SEMANTIC", start, options);
+        semantic::debug(&semantic, &types);
+    }
 
     let start = Instant::now();
-    let ssa = ssa::generate(&source, &tokens.offsets, &semantic, &mut types);
-    debug_header_duration("SSA", start);
-    ssa::debug(&types, &ssa);
+    let mut ssa = ssa::generate(
+        &options.input.display().to_string(),
+        source,
+        &tokens.offsets,
+        &semantic,
+        &mut types,
+    );
+    if options.emit.contains(&Stage::Ssa) {
+        debug_header_duration("SSA", start, options);
+        ssa::debug(&types, &ssa);
+    }
+
+    let mut counts = vec![("generation", ssa.inst_count())];
+
+    if options.optimize {
+        let mut start = Instant::now();
+        ssa::opt::optimize(&mut types, &mut ssa, |pass, types, ssa| {
+            if options.emit.contains(&Stage::Ssa) {
+                debug_header_duration(&format!("SSA AFTER {pass}"), start, options);
+                ssa::debug(types, ssa);
+            }
+            counts.push((pass, ssa.inst_count()));
+            start = Instant::now();
+        });
+    }
+
+    if options.emit.contains(&Stage::Counts) {
+        debug_header("INSTRUCTION COUNTS", options);
+        let mut previous = counts[0].1;
+        for (stage, count) in counts {
+            let difference = count as isize - previous as isize;
+            println!("{stage:<20}{count:>8}{difference:>+8}");
+            previous = count;
+        }
+    }
 
     (types, ssa)
 }
 
-fn run_ssa_with_c_codegen(types: &Types, ssa: &Ssa) {
-    let c_code = c_codegen::generate(&types, &ssa);
-    std::fs::write("output.c", c_code).unwrap();
+fn default_output(backend: Backend) -> PathBuf {
+    match backend {
+        Backend::Wasm => PathBuf::from("a.wasm"),
+        _ => PathBuf::from("a.out"),
+    }
+}
 
-    debug_header("CLANG");
-    let clang_exit_status = Command::new("clang")
-        .args(["output.c", "-std=c23"])
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
+// Write the executable to `output`, intermediate files are in the temporary
+// directory.
+fn build(types: &Types, ssa: &Ssa, backend: Backend, output: &Path) -> Result<(), String> {
+    match backend {
+        Backend::C => {
+            let mut clang = Command::new("clang")
+                .args(["-xc", "-std=c23", "-", "-o"])
+                .arg(output)
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|error| format!("cannot run clang: {error}"))?;
 
-    if !clang_exit_status.success() {
-        return;
+            let stdin = clang.stdin.as_mut().unwrap();
+            stdin
+                .write_all(c_codegen::generate(types, ssa).as_bytes())
+                .unwrap();
+
+            check_status("clang", clang.wait())
+        }
+        // Assembled and linked in-crate, without a toolchain.
+        Backend::Amd64 => {
+            let asm = amd64_asm_codegen::generate(types, ssa);
+            let objects = [
+                amd64_asm_codegen::assemble(&asm),
+                amd64_asm_codegen::assemble(runtime::ASSEMBLY),
+            ];
+            write(output, &elf::link(&objects, "_start"))?;
+            std::fs::set_permissions(output, Permissions::from_mode(0o755))
+                .map_err(|error| format!("cannot write {}: {error}", output.display()))
+        }
+        Backend::Aarch64 => {
+            let name = format!("keb-{}", std::process::id());
+            let asm_path = temp_dir().join(format!("{name}.s"));
+            let runtime_path = temp_dir().join(format!("{name}-runtime.c"));
+            write(
+                &asm_path,
+                aarch64_asm_codegen::generate(types, ssa).as_bytes(),
+            )?;
+            write(&runtime_path, runtime::SOURCE.as_bytes())?;
+
+            let gcc = Command::new("aarch64-linux-gnu-gcc")
+                .arg("-static")
+                .arg(&asm_path)
+                .arg(&runtime_path)
+                .arg("-o")
+                .arg(output)
+                .status();
+
+            for path in [asm_path, runtime_path] {
+                let _ = std::fs::remove_file(path);
+            }
+
+            check_status("aarch64-linux-gnu-gcc", gcc)
+        }
+        Backend::LlvmIr => {
+            let name = format!("keb-{}", std::process::id());
+            let ir_path = temp_dir().join(format!("{name}.ll"));
+            let object_path = temp_dir().join(format!("{name}.o"));
+            let runtime_path = temp_dir().join(format!("{name}-runtime.c"));
+            write(&ir_path, llvm_ir_codegen::generate(types, ssa).as_bytes())?;
+            write(&runtime_path, runtime::SOURCE.as_bytes())?;
+
+            let llc = Command::new("llc")
+                .args(llvm_ir_codegen::llc_flags())
+                .args(["-filetype=obj", "-relocation-model=pic", "-o"])
+                .arg(&object_path)
+                .arg(&ir_path)
+                .status();
+
+            let gcc = check_status("llc", llc).and_then(|()| {
+                let gcc = Command::new("gcc")
+                    .arg(&object_path)
+                    .arg(&runtime_path)
+                    .args(["-Xlinker", "-z", "-Xlinker", "noexecstack", "-o"])
+                    .arg(output)
+                    .status();
+                check_status("gcc", gcc)
+            });
+
+            for path in [ir_path, object_path, runtime_path] {
+                let _ = std::fs::remove_file(path);
+            }
+
+            gcc
+        }
+        Backend::Wasm => write(output, &wasm_codegen::generate(types, ssa)),
+        Backend::Jit | Backend::Interpreter => panic!(),
     }
+}
+
+fn run(types: &Types, ssa: &Ssa, options: &Options) -> Result<ExitCode, String> {
+    debug_header("OUTPUT", options);
 
-    debug_header("OUTPUT");
-    Command::new("./a.out").spawn().unwrap().wait().unwrap();
+    let result = match options.backend {
+        Backend::Jit => {
+            jit::run(types, ssa, &mut std::io::stdout()).map_err(|error| error.to_string())
+        }
+        Backend::Interpreter => ssa::interpret(types, ssa, &mut std::io::stdout(), u64::MAX)
+            .map_err(|error| error.to_string()),
+        _ => {
+            // The executable is only kept when asked for.
+            let output = options
+                .output
+                .clone()
+                .unwrap_or_else(|| temp_dir().join(format!("keb-{}", std::process::id())));
+            build(types, ssa, options.backend, &output)?;
+
+            let mut command = match options.backend {
+                Backend::Aarch64 => {
+                    let mut command = Command::new("qemu-aarch64");
+                    command.arg(&output);
+                    command
+                }
+                Backend::Wasm => {
+                    let mut command = Command::new("node");
+                    command
+                        .args(["--no-warnings", "-e", wasm_codegen::WASI_RUNNER])
+                        .arg(&output);
+                    command
+                }
+                _ => Command::new(&output),
+            };
+            let status = command.status();
+
+            if options.output.is_none() {
+                let _ = std::fs::remove_file(&output);
+            }
+
+            let status = status.map_err(|error| format!("cannot run the program: {error}"))?;
+            if !options.quiet && !status.success() {
+                eprintln!("Program exited with {status}");
+            }
+
+            return Ok(exit_code(status));
+        }
+    };
+
+    match result {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(error) => {
+            eprintln!("{error}");
+            Ok(ExitCode::from(TRAP))
+        }
+    }
 }
 
-fn run_ssa_with_amd64_asm_codegen(types: &Types, ssa: &Ssa) {
-    let asm = amd64_asm_codegen::generate(&types, &ssa);
-    std::fs::write("output.s", asm).unwrap();
-
-    debug_header("GCC");
-    let gcc_exit_status = Command::new("gcc")
-        .args([
-            "-O0",
-            "output.s",
-            "-m64",
-            "-Xlinker",
-            "-z",
-            "-Xlinker",
-            "noexecstack",
-        ])
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
-
-    if !gcc_exit_status.success() {
-        return;
+// Programs killed by a signal exit as they would from a shell.
+fn exit_code(status: ExitStatus) -> ExitCode {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => ExitCode::from(code as u8),
+        (None, Some(signal)) => ExitCode::from(128 + signal as u8),
+        (None, None) => ExitCode::FAILURE,
     }
+}
 
-    debug_header("OUTPUT");
-    let exit_status = Command::new("./a.out").spawn().unwrap().wait().unwrap();
+fn check_status(program: &str, status: std::io::Result<ExitStatus>) -> Result<(), String> {
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{program} exited with {status}")),
+        Err(error) => Err(format!("cannot run {program}: {error}")),
+    }
+}
 
-    println!("Program exited with {exit_status}");
+fn write(path: &Path, contents: &[u8]) -> Result<(), String> {
+    std::fs::write(path, contents)
+        .map_err(|error| format!("cannot write {}: {error}", path.display()))
 }
 
-fn debug_header(text: &str) {
+fn debug_header(text: &str, options: &Options) {
+    if options.quiet {
+        return;
+    }
+
     println!(
         "\n{}\n",
         format!("======== {} ========", text.bright_green())
@@ -101,7 +461,11 @@ fn debug_header(text: &str) {
     );
 }
 
-fn debug_header_duration(text: &str, start: Instant) {
+fn debug_header_duration(text: &str, start: Instant, options: &Options) {
+    if options.quiet {
+        return;
+    }
+
     let duration = Instant::now().duration_since(start);
     println!(
         "\n{}\n",
@@ -0,0 +1,10 @@
+//! Implementations of the builtins called by generated code. The C backend
+//! includes the C runtime in its output and the assembly backends link against
+//! it with a C toolchain, the built-in linker uses the assembly runtime.
+
+/// Source of the runtime, it depends only on the C standard library.
+pub const SOURCE: &str = include_str!("runtime.c");
+
+/// Assembly of the runtime for x86-64 Linux, it depends on nothing and
+/// provides the `_start` entry point.
+pub const ASSEMBLY: &str = include_str!("runtime.s");
@@ -0,0 +1,123 @@
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+void builtin_print(uint32_t value) { printf("%u\n", value); }
+
+void *builtin_alloc(uint32_t size) {
+    void *pointer = malloc(size);
+    if (pointer == NULL && size != 0) {
+        fprintf(stderr, "out of memory\n");
+        abort();
+    }
+    return pointer;
+}
+
+void builtin_free(void *pointer) { free(pointer); }
+
+// Path of the source, defined by the generated code.
+extern const char builtin_source_path[];
+
+// What the program printed is flushed first, `abort` would lose it.
+void builtin_trap(const char *reason, uint32_t line, uint32_t column) {
+    fflush(stdout);
+    fprintf(stderr, "%s at %s:%u:%u\n", reason, builtin_source_path, line, column);
+    abort();
+}
+
+void builtin_index_out_of_bounds(uint32_t line, uint32_t column) {
+    builtin_trap("index out of bounds", line, column);
+}
+
+uint32_t builtin_bounds_check(uint32_t index, uint32_t length, uint32_t line, uint32_t column) {
+    if (index >= length) builtin_index_out_of_bounds(line, column);
+    return index;
+}
+
+// Division has no location, as in the interpreter.
+void builtin_division_by_zero(void) {
+    fflush(stdout);
+    fprintf(stderr, "division by zero\n");
+    abort();
+}
+
+uint32_t builtin_divisor_check(uint32_t divisor) {
+    if (divisor == 0) builtin_division_by_zero();
+    return divisor;
+}
+
+uint32_t builtin_slice_check(uint32_t start, uint32_t end, uint32_t length, uint32_t line, uint32_t column) {
+    if (start > end || end > length) builtin_index_out_of_bounds(line, column);
+    return start;
+}
+
+// Elements are stored as bytes, the size of an element is given to each
+// function so that a single implementation works for every element type.
+//
+// Vecs are reference counted. `heap_offsets` are the offsets of the
+// `heap_count` vecs held by an element, released with the elements.
+struct builtin_vec {
+    uint8_t *items;
+    uint32_t length;
+    uint32_t capacity;
+    uint32_t references;
+    uint32_t element_size;
+    const uint32_t *heap_offsets;
+    uint32_t heap_count;
+};
+
+struct builtin_vec *builtin_vec_new(uint32_t element_size, uint32_t length, const void *elements, const uint32_t *heap_offsets, uint32_t heap_count) {
+    struct builtin_vec *vec = builtin_alloc(sizeof(struct builtin_vec));
+    vec->items = builtin_alloc(element_size * length);
+    vec->length = length;
+    vec->capacity = length;
+    vec->references = 1;
+    vec->element_size = element_size;
+    vec->heap_offsets = heap_offsets;
+    vec->heap_count = heap_count;
+    if (length != 0) memcpy(vec->items, elements, element_size * length);
+    return vec;
+}
+
+void builtin_vec_retain(struct builtin_vec *vec) { vec->references++; }
+
+void builtin_vec_release(struct builtin_vec *vec) {
+    if (--vec->references != 0) return;
+
+    for (uint32_t i = 0; i < vec->length; i++) {
+        uint8_t *element = vec->items + vec->element_size * i;
+        for (uint32_t j = 0; j < vec->heap_count; j++) {
+            struct builtin_vec *field;
+            memcpy(&field, element + vec->heap_offsets[j], sizeof(field));
+            builtin_vec_release(field);
+        }
+    }
+
+    builtin_free(vec->items);
+    builtin_free(vec);
+}
+
+// Returns where the pushed element must be written.
+void *builtin_vec_push(struct builtin_vec *vec, uint32_t element_size) {
+    if (vec->length == vec->capacity) {
+        uint32_t capacity = vec->capacity == 0 ? 4 : vec->capacity * 2;
+        uint8_t *items = builtin_alloc(element_size * capacity);
+        if (vec->length != 0) memcpy(items, vec->items, element_size * vec->length);
+        builtin_free(vec->items);
+        vec->items = items;
+        vec->capacity = capacity;
+    }
+    return vec->items + element_size * vec->length++;
+}
+
+// Returns where the popped element is, it stays valid until the next push.
+void *builtin_vec_pop(struct builtin_vec *vec, uint32_t element_size, uint32_t line, uint32_t column) {
+    if (vec->length == 0) builtin_trap("pop from empty vec", line, column);
+    return vec->items + element_size * --vec->length;
+}
+
+void *builtin_vec_get(struct builtin_vec *vec, uint32_t index, uint32_t element_size, uint32_t line, uint32_t column) {
+    return vec->items + element_size * builtin_bounds_check(index, vec->length, line, column);
+}
@@ -0,0 +1,351 @@
+# Runtime of the executables linked by keb, without the C standard library.
+# It implements the functions of `runtime.c` with Linux system calls, memory
+# is mapped for each allocation.
+
+.code64
+.global _start
+.global builtin_print
+.global builtin_alloc
+.global builtin_free
+.global builtin_index_out_of_bounds
+.global builtin_division_by_zero
+.global builtin_vec_new
+.global builtin_vec_retain
+.global builtin_vec_release
+.global builtin_vec_push
+.global builtin_vec_pop
+.global builtin_vec_get
+
+.text
+
+_start:
+  movl $0, %ebp
+  call main
+  movl %eax, %edi
+  movl $231, %eax
+  syscall
+
+# Write %edx bytes at %rsi to the file descriptor %edi.
+write:
+  movl $1, %eax
+  syscall
+  ret
+
+# Write the decimal digits of %eax before %rsi and move %rsi to the first
+# digit. Clobbers %eax, %ecx and %edx.
+decimal:
+  movl $10, %ecx
+decimal_digit:
+  movl $0, %edx
+  divl %ecx
+  addl $48, %edx
+  sub $1, %rsi
+  movb %dl, 0(%rsi)
+  test %eax, %eax
+  jnz decimal_digit
+  ret
+
+# Send SIGABRT to the process, as `abort` does.
+abort:
+  movl $39, %eax
+  syscall
+  movl %eax, %edi
+  movl $6, %esi
+  movl $62, %eax
+  syscall
+  movl $134, %edi
+  movl $231, %eax
+  syscall
+
+# Print the %esi bytes at %rdi followed by the location at line %edx and column
+# %ecx, then abort. The path of the source is the string `builtin_source_path`
+# defined by the generated code.
+trap:
+  movl %edx, %r12d
+  movl %ecx, %r13d
+  movl %esi, %edx
+  mov %rdi, %rsi
+  movl $2, %edi
+  call write
+  lea at(%rip), %rsi
+  movl $4, %edx
+  movl $2, %edi
+  call write
+  lea builtin_source_path(%rip), %rsi
+  mov %rsi, %rdx
+source_path_end:
+  movzbl 0(%rdx), %eax
+  test %eax, %eax
+  jz source_path_found
+  add $1, %rdx
+  jmp source_path_end
+source_path_found:
+  sub %rsi, %rdx
+  movl $2, %edi
+  call write
+  sub $32, %rsp
+  movb $10, 31(%rsp)
+  lea 31(%rsp), %rsi
+  movl %r13d, %eax
+  call decimal
+  sub $1, %rsi
+  movb $58, 0(%rsi)
+  movl %r12d, %eax
+  call decimal
+  sub $1, %rsi
+  movb $58, 0(%rsi)
+  lea 32(%rsp), %rdx
+  sub %rsi, %rdx
+  movl $2, %edi
+  call write
+  jmp abort
+
+# Copy %rdx bytes from %rsi to %rdi.
+copy:
+  test %rdx, %rdx
+  jz copy_done
+copy_byte:
+  movzbl 0(%rsi), %eax
+  movb %al, 0(%rdi)
+  add $1, %rsi
+  add $1, %rdi
+  sub $1, %rdx
+  jnz copy_byte
+copy_done:
+  ret
+
+builtin_print:
+  sub $24, %rsp
+  movb $10, 16(%rsp)
+  lea 16(%rsp), %rsi
+  movl %edi, %eax
+  call decimal
+  lea 17(%rsp), %rdx
+  sub %rsi, %rdx
+  movl $1, %edi
+  call write
+  add $24, %rsp
+  ret
+
+# The size of the mapping is stored before the returned pointer.
+builtin_alloc:
+  test %edi, %edi
+  jz alloc_empty
+  movl %edi, %esi
+  add $16, %rsi
+  movl $0, %edi
+  movl $3, %edx
+  movl $34, %r10d
+  mov $-1, %r8
+  movl $0, %r9d
+  movl $9, %eax
+  syscall
+  cmp $-4095, %rax
+  jae out_of_memory
+  mov %rsi, 0(%rax)
+  add $16, %rax
+  ret
+alloc_empty:
+  movl $0, %eax
+  ret
+out_of_memory:
+  lea out_of_memory_message(%rip), %rsi
+  movl $14, %edx
+  movl $2, %edi
+  call write
+  jmp abort
+
+builtin_free:
+  test %rdi, %rdi
+  jz free_null
+  sub $16, %rdi
+  mov 0(%rdi), %rsi
+  movl $11, %eax
+  syscall
+free_null:
+  ret
+
+builtin_index_out_of_bounds:
+  movl %esi, %ecx
+  movl %edi, %edx
+  lea index_out_of_bounds(%rip), %rdi
+  movl $19, %esi
+  jmp trap
+
+builtin_division_by_zero:
+  lea division_by_zero(%rip), %rsi
+  movl $17, %edx
+  movl $2, %edi
+  call write
+  jmp abort
+
+# Vecs have the layout of `struct builtin_vec`: items at 0, length at 8,
+# capacity at 12, references at 16, element size at 20, heap offsets at 24 and
+# heap count at 32.
+builtin_vec_new:
+  push %rbx
+  push %rbp
+  push %r12
+  push %r13
+  push %r14
+  push %r15
+  movl %edi, %r12d
+  movl %esi, %r13d
+  mov %rdx, %r14
+  mov %rcx, %r15
+  movl %r8d, %ebp
+  movl $40, %edi
+  call builtin_alloc
+  mov %rax, %rbx
+  movl %r12d, %edi
+  imul %r13d, %edi
+  call builtin_alloc
+  mov %rax, 0(%rbx)
+  movl %r13d, 8(%rbx)
+  movl %r13d, 12(%rbx)
+  movl $1, 16(%rbx)
+  movl %r12d, 20(%rbx)
+  mov %r15, 24(%rbx)
+  movl %ebp, 32(%rbx)
+  mov %rax, %rdi
+  mov %r14, %rsi
+  movl %r12d, %edx
+  imul %r13d, %edx
+  call copy
+  mov %rbx, %rax
+  pop %r15
+  pop %r14
+  pop %r13
+  pop %r12
+  pop %rbp
+  pop %rbx
+  ret
+
+builtin_vec_retain:
+  addl $1, 16(%rdi)
+  ret
+
+builtin_vec_release:
+  subl $1, 16(%rdi)
+  jnz release_done
+  push %rbx
+  push %r12
+  push %r13
+  mov %rdi, %rbx
+  movl $0, %r12d
+release_element:
+  cmpl 8(%rbx), %r12d
+  jae release_items
+  movl $0, %r13d
+release_field:
+  cmpl 32(%rbx), %r13d
+  jae release_next
+  movl %r13d, %eax
+  shl $2, %eax
+  add 24(%rbx), %rax
+  movl 0(%rax), %ecx
+  movl 20(%rbx), %eax
+  imul %r12d, %eax
+  add %ecx, %eax
+  mov 0(%rbx), %rdi
+  add %rax, %rdi
+  mov 0(%rdi), %rdi
+  call builtin_vec_release
+  add $1, %r13d
+  jmp release_field
+release_next:
+  add $1, %r12d
+  jmp release_element
+release_items:
+  mov 0(%rbx), %rdi
+  call builtin_free
+  mov %rbx, %rdi
+  call builtin_free
+  pop %r13
+  pop %r12
+  pop %rbx
+release_done:
+  ret
+
+# Returns where the pushed element must be written.
+builtin_vec_push:
+  movl 8(%rdi), %eax
+  cmpl 12(%rdi), %eax
+  jb push_element
+  push %rbx
+  push %r12
+  push %r13
+  push %r14
+  mov %rdi, %rbx
+  movl %esi, %r12d
+  movl 12(%rbx), %r13d
+  add %r13d, %r13d
+  test %r13d, %r13d
+  jnz push_grow
+  movl $4, %r13d
+push_grow:
+  movl %r12d, %edi
+  imul %r13d, %edi
+  call builtin_alloc
+  mov %rax, %r14
+  mov %rax, %rdi
+  mov 0(%rbx), %rsi
+  movl %r12d, %edx
+  imul 8(%rbx), %edx
+  call copy
+  mov 0(%rbx), %rdi
+  call builtin_free
+  mov %r14, 0(%rbx)
+  movl %r13d, 12(%rbx)
+  mov %rbx, %rdi
+  movl %r12d, %esi
+  pop %r14
+  pop %r13
+  pop %r12
+  pop %rbx
+push_element:
+  movl 8(%rdi), %eax
+  imul %esi, %eax
+  add 0(%rdi), %rax
+  addl $1, 8(%rdi)
+  ret
+
+# Returns where the popped element is, it stays valid until the next push.
+builtin_vec_pop:
+  movl 8(%rdi), %eax
+  test %eax, %eax
+  jz pop_empty
+  sub $1, %eax
+  movl %eax, 8(%rdi)
+  imul %esi, %eax
+  add 0(%rdi), %rax
+  ret
+pop_empty:
+  lea pop_from_empty_vec(%rip), %rdi
+  movl $18, %esi
+  jmp trap
+
+builtin_vec_get:
+  cmpl 8(%rdi), %esi
+  jae get_out_of_bounds
+  movl %esi, %eax
+  imul %edx, %eax
+  add 0(%rdi), %rax
+  ret
+get_out_of_bounds:
+  movl %ecx, %edi
+  movl %r8d, %esi
+  jmp builtin_index_out_of_bounds
+
+.section .rodata
+
+at:
+  .ascii " at "
+out_of_memory_message:
+  .ascii "out of memory\n"
+index_out_of_bounds:
+  .ascii "index out of bounds"
+pop_from_empty_vec:
+  .ascii "pop from empty vec"
+division_by_zero:
+  .ascii "division by zero\n"
@@ -1,6 +1,6 @@
 use std::fmt::{Debug, Display};
 
-use colored::Colorize as _;
+use colored::{ColoredString, Colorize as _};
 
 use crate::{
     key_vec::Val,
@@ -88,10 +88,52 @@ impl Debug for DebugSem<'_> {
                 "access",
                 &[&DebugUsingDisplay(field.bright_cyan()), &sem(*expr)],
             ),
+            SemKind::Index { expr, index, .. } => display("index", &[&sem(*expr), &sem(*index)]),
+            SemKind::Slice {
+                expr, start, end, ..
+            } => [Some(*expr), *start, *end]
+                .iter()
+                .fold(
+                    &mut f.debug_tuple("slice".bright_green().to_string().as_str()),
+                    |tuple, field| match field {
+                        Some(field) => tuple.field(&sem(*field)),
+                        None => tuple.field(&format_args!("_")),
+                    },
+                )
+                .finish(),
+            SemKind::Store {
+                expr, index, value, ..
+            } => display("store", &[&sem(*expr), &sem(*index), &sem(*value)]),
             SemKind::Application { function, argument } => {
                 display("application", &[&sem(*function), &sem(*argument)])
             }
-            SemKind::Loop(body) => display("loop", &[&sem(*body)]),
+            SemKind::Loop(body) => display(
+                "loop",
+                &[&DebugUsingDisplay(debug_loop(self.sem)), &sem(*body)],
+            ),
+            SemKind::Break { loop_, value } => match value {
+                Some(value) => display(
+                    "break",
+                    &[&DebugUsingDisplay(debug_loop(*loop_)), &sem(*value)],
+                ),
+                None => display("break", &[&DebugUsingDisplay(debug_loop(*loop_))]),
+            },
+            SemKind::Continue { loop_ } => {
+                display("continue", &[&DebugUsingDisplay(debug_loop(*loop_))])
+            }
+            SemKind::For {
+                name,
+                iterable,
+                body,
+            } => display(
+                "for",
+                &[
+                    &DebugUsingDisplay(debug_loop(self.sem)),
+                    &DebugUsingDisplay(name.bright_cyan()),
+                    &sem(*iterable),
+                    &sem(*body),
+                ],
+            ),
             SemKind::If { condition, then } => display("if", &[&sem(*condition), &sem(*then)]),
             SemKind::IfElse {
                 condition,
@@ -107,6 +149,22 @@ impl Debug for DebugSem<'_> {
                     },
                 )
                 .finish(),
+            SemKind::BuildArray { elements } => elements
+                .iter()
+                .fold(&mut f.debug_list(), |list, element| {
+                    list.entry(&sem(*element))
+                })
+                .finish(),
+            SemKind::RepeatArray { value, length } => display(
+                "repeat_array",
+                &[
+                    &sem(*value),
+                    &DebugUsingDisplay(length.to_string().bright_purple()),
+                ],
+            ),
+            SemKind::BuildVec(array) => display("build_vec", &[&sem(*array)]),
+            SemKind::Push { vec, value } => display("push", &[&sem(*vec), &sem(*value)]),
+            SemKind::Pop { vec, .. } => display("pop", &[&sem(*vec)]),
             SemKind::ChainOpen {
                 statements,
                 expression,
@@ -132,6 +190,10 @@ impl Debug for DebugSem<'_> {
     }
 }
 
+fn debug_loop(loop_: Sem) -> ColoredString {
+    format!("#{}", loop_.as_u32()).bright_yellow()
+}
+
 struct DebugUsingDisplay<T>(T);
 
 impl<T: Display> Debug for DebugUsingDisplay<T> {
@@ -151,6 +213,8 @@ pub fn debug_type(types: &Types, type_: Type) -> String {
                 TypeSentinel::Bool => "bool",
                 TypeSentinel::False => "false",
                 TypeSentinel::True => "true",
+                TypeSentinel::Never => "!",
+                TypeSentinel::Pointer => "pointer",
             };
 
             text.bright_blue().to_string()
@@ -180,6 +244,12 @@ pub fn debug_type(types: &Types, type_: Type) -> String {
 
                 text
             }
+            TypeData::Array { element, length } => {
+                format!("[{}; {length}]", debug_type(types, *element))
+            }
+            TypeData::Slice { element } => format!("[{}]", debug_type(types, *element)),
+            TypeData::Pointer { pointee } => format!("&{}", debug_type(types, *pointee)),
+            TypeData::Vec { element } => format!("vec {}", debug_type(types, *element)),
         },
     }
 }
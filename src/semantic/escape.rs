@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use crate::{
+    key_vec::Val,
+    semantic::{ROOT_SEM, Sem, SemKind, Semantic, Type, TypeData, Types},
+};
+
+// Slices view the elements of arrays living in the frame of a function, so a
+// slice must not outlive the binding of the array it views. Every expression
+// is given the depth of the innermost binding it may view an array of: 0 when
+// it views none of the function, 1 for its argument, and one more for each
+// `let` and `for` around the array. A value can only flow to a place of at
+// least its depth.
+pub fn check_escapes(semantic: &Semantic, types: &Types) {
+    let mut checker = Checker {
+        semantic,
+        types,
+        loops: HashMap::new(),
+    };
+
+    checker.region(&Scope::new(), 0, ROOT_SEM);
+}
+
+type Scope = HashMap<String, Binding>;
+
+#[derive(Clone, Copy)]
+struct Binding {
+    // Depth of the binding itself, the one of the arrays it holds.
+    depth: u32,
+    // Depth of the arrays viewed by the slices it holds.
+    region: u32,
+}
+
+struct Checker<'a> {
+    semantic: &'a Semantic,
+    types: &'a Types,
+    // Depth of the loops and of the values given to their `break`s.
+    loops: HashMap<Sem, (u32, u32)>,
+}
+
+impl Checker<'_> {
+    fn region(&mut self, scope: &Scope, depth: u32, sem: Sem) -> u32 {
+        let region = match &self.semantic.kinds[sem] {
+            SemKind::False(_) | SemKind::True(_) | SemKind::Number(_) => 0,
+            SemKind::Module { bindings } => {
+                let mut scope = scope.clone();
+                for (name, _) in bindings {
+                    scope.insert(name.clone(), Binding { depth, region: 0 });
+                }
+
+                for (_, value) in bindings {
+                    self.region(&scope, depth, *value);
+                }
+                0
+            }
+            SemKind::Function { argument, body } => {
+                let mut scope = scope.clone();
+                scope.insert(
+                    argument.clone(),
+                    Binding {
+                        depth: 1,
+                        region: 0,
+                    },
+                );
+
+                // Returned values outlive the frame.
+                if self.region(&scope, 1, *body) != 0 {
+                    escapes();
+                }
+                0
+            }
+            SemKind::Binding { name, value, body } | SemKind::MutBinding { name, value, body } => {
+                let region = self.region(scope, depth, *value);
+
+                let mut scope = scope.clone();
+                scope.insert(
+                    name.clone(),
+                    Binding {
+                        depth: depth + 1,
+                        region,
+                    },
+                );
+
+                let region = self.region(&scope, depth + 1, *body);
+                if region > depth {
+                    escapes();
+                }
+                region
+            }
+            SemKind::Assignment { binding, value } => {
+                if self.region(scope, depth, *value) > scope[binding].region {
+                    escapes();
+                }
+                0
+            }
+            SemKind::Reference { name } => scope.get(name).map_or(0, |binding| binding.region),
+            SemKind::Access { expr, .. } | SemKind::Pop { vec: expr, .. } => {
+                self.region(scope, depth, *expr)
+            }
+            SemKind::Index { expr, index, .. } => {
+                self.region(scope, depth, *index);
+                self.region(scope, depth, *expr)
+            }
+            SemKind::Slice {
+                expr, start, end, ..
+            } => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.region(scope, depth, *bound);
+                }
+
+                let region = self.region(scope, depth, *expr);
+                match self.types.get(self.semantic.types[*expr]) {
+                    Val::Value(TypeData::Array { .. }) => {
+                        region.max(self.place(scope, depth, *expr))
+                    }
+                    _ => region,
+                }
+            }
+            SemKind::Store {
+                expr, index, value, ..
+            } => {
+                self.region(scope, depth, *index);
+                if self.region(scope, depth, *value) > self.region(scope, depth, *expr) {
+                    escapes();
+                }
+                0
+            }
+            SemKind::Push { vec, value } => {
+                if self.region(scope, depth, *value) > self.region(scope, depth, *vec) {
+                    escapes();
+                }
+                0
+            }
+            // Functions can give back the slices of their argument.
+            SemKind::Application { function, argument } => {
+                self.region(scope, depth, *function);
+                self.region(scope, depth, *argument)
+            }
+            SemKind::Loop(body) => {
+                self.loops.insert(sem, (depth, 0));
+                self.region(scope, depth, *body);
+                self.loops[&sem].1
+            }
+            SemKind::Break { loop_, value } => {
+                if let Some(value) = value {
+                    let region = self.region(scope, depth, *value);
+
+                    let (loop_depth, loop_region) = self.loops.get_mut(loop_).unwrap();
+                    if region > *loop_depth {
+                        escapes();
+                    }
+                    *loop_region = region.max(*loop_region);
+                }
+                0
+            }
+            SemKind::Continue { .. } => 0,
+            SemKind::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let region = self.region(scope, depth, *iterable);
+
+                let mut scope = scope.clone();
+                scope.insert(
+                    name.clone(),
+                    Binding {
+                        depth: depth + 1,
+                        region,
+                    },
+                );
+
+                self.loops.insert(sem, (depth, 0));
+                self.region(&scope, depth + 1, *body);
+                0
+            }
+            SemKind::If { condition, then } => {
+                self.region(scope, depth, *condition);
+                self.region(scope, depth, *then);
+                0
+            }
+            SemKind::IfElse {
+                condition,
+                then,
+                else_,
+            } => {
+                self.region(scope, depth, *condition);
+                let then = self.region(scope, depth, *then);
+                let else_ = self.region(scope, depth, *else_);
+                then.max(else_)
+            }
+            SemKind::BuildStruct { fields } => fields
+                .iter()
+                .map(|(_, value)| self.region(scope, depth, *value))
+                .fold(0, u32::max),
+            SemKind::BuildArray { elements } => elements
+                .iter()
+                .map(|element| self.region(scope, depth, *element))
+                .fold(0, u32::max),
+            SemKind::RepeatArray { value, .. } | SemKind::BuildVec(value) => {
+                self.region(scope, depth, *value)
+            }
+            SemKind::ChainOpen {
+                statements,
+                expression,
+            } => {
+                for statement in statements {
+                    self.region(scope, depth, *statement);
+                }
+                self.region(scope, depth, *expression)
+            }
+            SemKind::ChainClosed { statements } => {
+                for statement in statements {
+                    self.region(scope, depth, *statement);
+                }
+                0
+            }
+        };
+
+        // Only slices view arrays.
+        if holds_slice(self.types, self.semantic.types[sem]) {
+            region
+        } else {
+            0
+        }
+    }
+
+    // Depth of the array `sem`, the one of its binding or else of a
+    // temporary.
+    fn place(&self, scope: &Scope, depth: u32, sem: Sem) -> u32 {
+        match &self.semantic.kinds[sem] {
+            SemKind::Reference { name } => scope.get(name).map_or(depth, |binding| binding.depth),
+            SemKind::Access { expr, .. } | SemKind::Index { expr, .. }
+                if matches!(
+                    self.types.get(self.semantic.types[*expr]),
+                    Val::Value(TypeData::Product { .. } | TypeData::Array { .. })
+                ) =>
+            {
+                self.place(scope, depth, *expr)
+            }
+            _ => depth,
+        }
+    }
+}
+
+fn holds_slice(types: &Types, type_: Type) -> bool {
+    match types.get(type_) {
+        Val::Value(TypeData::Slice { .. }) => true,
+        Val::Value(TypeData::Product { fields }) => {
+            fields.iter().any(|(_, field)| holds_slice(types, *field))
+        }
+        Val::Value(&TypeData::Array { element, .. } | &TypeData::Vec { element }) => {
+            holds_slice(types, element)
+        }
+        Val::None | Val::Sentinel(_) | Val::Value(_) => false,
+    }
+}
+
+fn escapes() -> ! {
+    panic!("slice outlives the array it views")
+}
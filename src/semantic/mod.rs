@@ -1,4 +1,5 @@
 mod debug;
+mod escape;
 mod parser;
 mod sem;
 mod r#type;
@@ -5,7 +5,7 @@ use crate::{
         combine_types,
     },
     syntax::{self, Syn, SynData, Syntax},
-    token::{self, TokenOffsets, parse_identifer},
+    token::{self, Token, TokenOffsets, parse_identifer},
 };
 
 pub fn parse(source: &str, tokens: &TokenOffsets, syntax: &Syntax) -> (Semantic, Types) {
@@ -18,6 +18,7 @@ pub fn parse(source: &str, tokens: &TokenOffsets, syntax: &Syntax) -> (Semantic,
             types: SemTypes::default(),
         },
         types: Types::default(),
+        loops: Vec::new(),
     };
     parser.parse_root();
     (parser.semantic, parser.types)
@@ -30,6 +31,9 @@ struct Parser<'a> {
 
     semantic: Semantic,
     types: Types,
+
+    // Enclosing loops with their optional label, innermost last.
+    loops: Vec<(Option<String>, Sem)>,
 }
 
 impl Parser<'_> {
@@ -81,6 +85,8 @@ impl Parser<'_> {
             SynData::True(token) => self.push(SemKind::True(*token)),
             SynData::Number(token) => self.push(SemKind::Number(*token)),
             SynData::Function { pattern, body } => {
+                let loops = std::mem::take(&mut self.loops);
+
                 let param = self.push(SemKind::Reference {
                     name: "__param".to_string(),
                 });
@@ -122,6 +128,8 @@ impl Parser<'_> {
 
                 self.add_type(sem, ty);
 
+                self.loops = loops;
+
                 sem
             }
             SynData::Equal(lhs, rhs) => self.parse_binary_operator(*lhs, *rhs, "builtin_equal"),
@@ -136,6 +144,96 @@ impl Parser<'_> {
                         binding: parse_identifer(self.source, self.tokens, token).to_string(),
                         value,
                     }),
+                    SynData::Index {
+                        syn,
+                        bracket,
+                        index,
+                    } => {
+                        let expr = self.parse_expression(syn);
+                        let index = self.parse_expression(index);
+                        self.push(SemKind::Store {
+                            expr,
+                            index,
+                            value,
+                            token: bracket,
+                        })
+                    }
+                    _ => panic!(),
+                }
+            }
+            SynData::Access { syn, key } => {
+                let expr = self.parse_expression(*syn);
+                let field = match self.syntax[*key] {
+                    SynData::Ident(token) => {
+                        parse_identifer(self.source, self.tokens, token).to_string()
+                    }
+                    SynData::Number(token) => {
+                        token::parse_u64(self.source, self.tokens, token).to_string()
+                    }
+                    _ => panic!(),
+                };
+                self.push(SemKind::Access { field, expr })
+            }
+            SynData::Index {
+                syn,
+                bracket,
+                index,
+            } => {
+                let expr = self.parse_expression(*syn);
+                let index = self.parse_expression(*index);
+                self.push(SemKind::Index {
+                    expr,
+                    index,
+                    token: *bracket,
+                })
+            }
+            SynData::Slice {
+                syn,
+                bracket,
+                start,
+                end,
+            } => {
+                let expr = self.parse_expression(*syn);
+                let start = start.map(|start| self.parse_expression(start));
+                let end = end.map(|end| self.parse_expression(end));
+                self.push(SemKind::Slice {
+                    expr,
+                    start,
+                    end,
+                    token: *bracket,
+                })
+            }
+            SynData::Application { function, argument }
+                if self.vec_builtin(*function).is_some() =>
+            {
+                let token = self.vec_builtin(*function).unwrap();
+
+                match token::parse_identifer(self.source, self.tokens, token) {
+                    "vec" => {
+                        let array = self.parse_expression(*argument);
+                        self.push(SemKind::BuildVec(array))
+                    }
+                    "push" => {
+                        let mut argument = *argument;
+                        while let SynData::Paren(inner) = self.syntax[argument] {
+                            argument = inner;
+                        }
+
+                        let SynData::Tuple(syns) = &self.syntax[argument] else {
+                            panic!("push takes a vec and a value")
+                        };
+                        let &[vec, value] = syns.as_slice() else {
+                            panic!("push takes a vec and a value")
+                        };
+
+                        let vec = self.parse_expression(vec);
+                        let value = self.parse_expression(value);
+                        self.push(SemKind::Push { vec, value })
+                    }
+                    "pop" => {
+                        let vec = self.parse_expression(*argument);
+                        self.push(SemKind::Pop { vec, token })
+                    }
                     _ => panic!(),
                 }
             }
@@ -144,11 +242,66 @@ impl Parser<'_> {
                 let argument = self.parse_expression(*argument);
                 self.push(SemKind::Application { function, argument })
             }
-            SynData::Loop(body) => {
+            SynData::Loop { label, body } => {
+                let loop_ = self.push(SemKind::Loop(Sem::from_u32_index(0)));
+
+                let label = label
+                    .map(|label| token::parse_label(self.source, self.tokens, label).to_string());
+
+                self.loops.push((label, loop_));
+                let body = self.parse_expression(*body);
+                self.loops.pop();
+
+                self.semantic.kinds[loop_] = SemKind::Loop(body);
+
+                loop_
+            }
+            SynData::Break { label, value } => {
+                let loop_ = self.find_loop(*label);
+                let value = value.map(|value| self.parse_expression(value));
+                self.push(SemKind::Break { loop_, value })
+            }
+            SynData::Continue { label } => {
+                let loop_ = self.find_loop(*label);
+                self.push(SemKind::Continue { loop_ })
+            }
+            SynData::For {
+                label,
+                pattern,
+                iterable,
+                body,
+            } => {
+                let SynData::Ident(token) = self.syntax[*pattern] else {
+                    panic!()
+                };
+
+                let name = token::parse_identifer(self.source, self.tokens, token).to_string();
+                let iterable = self.parse_expression(*iterable);
+
+                let for_ = self.push(SemKind::For {
+                    name: name.clone(),
+                    iterable,
+                    body: Sem::from_u32_index(0),
+                });
+
+                let label = label
+                    .map(|label| token::parse_label(self.source, self.tokens, label).to_string());
+
+                self.loops.push((label, for_));
                 let body = self.parse_expression(*body);
-                self.push(SemKind::Loop(body))
+                self.loops.pop();
+
+                self.semantic.kinds[for_] = SemKind::For {
+                    name,
+                    iterable,
+                    body,
+                };
+
+                for_
             }
             SynData::Match(curly) => {
+                let loops = std::mem::take(&mut self.loops);
+
                 let function = self.push(SemKind::Function {
                     argument: "__param".to_string(),
                     body: Sem::from_u32_index(0),
@@ -198,6 +351,8 @@ impl Parser<'_> {
                 });
                 self.add_type(function, function_type);
 
+                self.loops = loops;
+
                 function
             }
             SynData::If { condition, then } => {
@@ -222,6 +377,35 @@ impl Parser<'_> {
                 })
             }
             SynData::Paren(expr) => self.parse_expression(*expr),
+            SynData::EmptyBracket(_) => self.push(SemKind::BuildArray {
+                elements: Vec::new(),
+            }),
+            SynData::Bracket(content) => match &self.syntax[*content] {
+                SynData::Tuple(syns) => {
+                    let elements = syns.iter().map(|syn| self.parse_expression(*syn)).collect();
+                    self.push(SemKind::BuildArray { elements })
+                }
+                SynData::ChainOpen(syns) => {
+                    let &[value, length] = syns.as_slice() else {
+                        panic!()
+                    };
+
+                    let SynData::Number(length) = self.syntax[length] else {
+                        panic!("array length must be a number literal")
+                    };
+
+                    let value = self.parse_expression(value);
+                    let length = token::parse_u64(self.source, self.tokens, length) as u32;
+
+                    self.push(SemKind::RepeatArray { value, length })
+                }
+                _ => {
+                    let element = self.parse_expression(*content);
+                    self.push(SemKind::BuildArray {
+                        elements: vec![element],
+                    })
+                }
+            },
             SynData::Tuple(sems) => {
                 let fields = sems
                     .iter()
@@ -245,6 +429,42 @@ impl Parser<'_> {
         }
     }
 
+    // `vec`, `push` and `pop` work on any element type, they are built into
+    // the language instead of being functions.
+    fn vec_builtin(&self, function: Syn) -> Option<Token> {
+        let SynData::Ident(token) = self.syntax[function] else {
+            return None;
+        };
+
+        match token::parse_identifer(self.source, self.tokens, token) {
+            "vec" | "push" | "pop" => Some(token),
+            _ => None,
+        }
+    }
+
+    fn find_loop(&self, label: Option<Token>) -> Sem {
+        let Some(label) = label else {
+            let Some((_, loop_)) = self.loops.last() else {
+                panic!("`break` or `continue` outside of a loop");
+            };
+
+            return *loop_;
+        };
+
+        let label = token::parse_label(self.source, self.tokens, label);
+
+        let Some((_, loop_)) = self
+            .loops
+            .iter()
+            .rev()
+            .find(|(loop_label, _)| loop_label.as_deref() == Some(label))
+        else {
+            panic!("unknown loop label '{label}");
+        };
+
+        *loop_
+    }
+
     fn parse_binary_operator(&mut self, lhs: Syn, rhs: Syn, function: &str) -> Sem {
         let lhs = self.parse_expression(lhs);
         let rhs = self.parse_expression(rhs);
@@ -346,6 +566,9 @@ impl Parser<'_> {
                     body = field;
                 }
 
+                // Fields were sifted from the last one.
+                fields_types.reverse();
+
                 (
                     body,
                     self.types.push(TypeData::Product {
@@ -449,6 +672,20 @@ impl Parser<'_> {
             SynData::Ident(token) => {
                 match token::parse_identifer(self.source, self.tokens, *token) {
                     "u32" => TypeSentinel::Uint32.to_index(),
+                    "pointer" => TypeSentinel::Pointer.to_index(),
+                    _ => panic!("unknown type"),
+                }
+            }
+            SynData::Application { function, argument } => {
+                let SynData::Ident(token) = self.syntax[*function] else {
+                    panic!("unknown type")
+                };
+
+                match token::parse_identifer(self.source, self.tokens, token) {
+                    "vec" => {
+                        let element = self.parse_type(*argument);
+                        self.types.push(TypeData::Vec { element })
+                    }
                     _ => panic!("unknown type"),
                 }
             }
@@ -465,6 +702,26 @@ impl Parser<'_> {
             }
             SynData::EmptyParen(_) => TypeSentinel::Unit.to_index(),
             SynData::Paren(expr) => self.parse_type(*expr),
+            SynData::Bracket(content) => match &self.syntax[*content] {
+                SynData::ChainOpen(syns) => {
+                    let &[element, length] = syns.as_slice() else {
+                        panic!()
+                    };
+
+                    let SynData::Number(length) = self.syntax[length] else {
+                        panic!("array length must be a number literal")
+                    };
+
+                    let element = self.parse_type(element);
+                    let length = token::parse_u64(self.source, self.tokens, length) as u32;
+
+                    self.types.push(TypeData::Array { element, length })
+                }
+                _ => {
+                    let element = self.parse_type(*content);
+                    self.types.push(TypeData::Slice { element })
+                }
+            },
             SynData::Tuple(sems) => {
                 let fields = sems
                     .iter()
@@ -37,11 +37,40 @@ pub enum SemKind {
         field: String,
         expr: Sem,
     },
+    Index {
+        expr: Sem,
+        index: Sem,
+        token: Token,
+    },
+    Slice {
+        expr: Sem,
+        start: Option<Sem>,
+        end: Option<Sem>,
+        token: Token,
+    },
+    Store {
+        expr: Sem,
+        index: Sem,
+        value: Sem,
+        token: Token,
+    },
     Application {
         function: Sem,
         argument: Sem,
     },
     Loop(Sem),
+    Break {
+        loop_: Sem,
+        value: Option<Sem>,
+    },
+    Continue {
+        loop_: Sem,
+    },
+    For {
+        name: String,
+        iterable: Sem,
+        body: Sem,
+    },
     If {
         condition: Sem,
         then: Sem,
@@ -54,6 +83,23 @@ pub enum SemKind {
     BuildStruct {
         fields: Vec<(String, Sem)>,
     },
+    BuildArray {
+        elements: Vec<Sem>,
+    },
+    RepeatArray {
+        value: Sem,
+        length: u32,
+    },
+    // `vec [..]`, moves the elements of an array into a new vec.
+    BuildVec(Sem),
+    Push {
+        vec: Sem,
+        value: Sem,
+    },
+    Pop {
+        vec: Sem,
+        token: Token,
+    },
     ChainOpen {
         statements: Vec<Sem>,
         expression: Sem,
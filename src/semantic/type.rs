@@ -9,17 +9,39 @@ pub enum TypeData {
     Product {
         fields: Vec<(String, Type)>,
     },
+    Array {
+        element: Type,
+        length: u32,
+    },
+    Slice {
+        element: Type,
+    },
+    // Address of a value of type `pointee`, only created by the SSA
+    // generation.
+    Pointer {
+        pointee: Type,
+    },
+    // Growable array living on the heap, copying it shares the same elements.
+    Vec {
+        element: Type,
+    },
 }
 
 #[repr(u32)]
 #[derive(Sentinel, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TypeSentinel {
-    Unknown = u32::MAX - 5,
+    Unknown = u32::MAX - 7,
     Unit,
     Uint32,
     Bool,
     False,
     True,
+    // Type of expressions that never produce a value, like `break` or a loop
+    // without any `break`.
+    Never,
+    // Address returned by the runtime allocator, the type of what it points
+    // to is unknown.
+    Pointer,
 }
 
 pub type Type = Index<TypeSentinel>;
@@ -31,6 +53,8 @@ pub fn combine_types(types: &mut Types, lhs: Type, rhs: Type) -> Type {
     match (types.get(lhs), types.get(rhs)) {
         (Val::Sentinel(TypeSentinel::Unknown), _) => rhs,
         (_, Val::Sentinel(TypeSentinel::Unknown)) => lhs,
+        (Val::Sentinel(TypeSentinel::Never), _) => rhs,
+        (_, Val::Sentinel(TypeSentinel::Never)) => lhs,
         (Val::Sentinel(TypeSentinel::Unit), Val::Sentinel(TypeSentinel::Unit)) => {
             TypeSentinel::Unit.to_index()
         }
@@ -38,6 +62,14 @@ pub fn combine_types(types: &mut Types, lhs: Type, rhs: Type) -> Type {
             TypeSentinel::Uint32.to_index()
         }
         (Val::Sentinel(TypeSentinel::Bool), Val::Sentinel(TypeSentinel::Bool))
+        | (
+            Val::Sentinel(TypeSentinel::Bool),
+            Val::Sentinel(TypeSentinel::False | TypeSentinel::True),
+        )
+        | (
+            Val::Sentinel(TypeSentinel::False | TypeSentinel::True),
+            Val::Sentinel(TypeSentinel::Bool),
+        )
         | (Val::Sentinel(TypeSentinel::True), Val::Sentinel(TypeSentinel::False))
         | (Val::Sentinel(TypeSentinel::False), Val::Sentinel(TypeSentinel::True)) => {
             TypeSentinel::Bool.to_index()
@@ -48,6 +80,9 @@ pub fn combine_types(types: &mut Types, lhs: Type, rhs: Type) -> Type {
         (Val::Sentinel(TypeSentinel::True), Val::Sentinel(TypeSentinel::True)) => {
             TypeSentinel::True.to_index()
         }
+        (Val::Sentinel(TypeSentinel::Pointer), Val::Sentinel(TypeSentinel::Pointer)) => {
+            TypeSentinel::Pointer.to_index()
+        }
         (
             Val::Value(&TypeData::Function {
                 argument_type: lhs_arg,
@@ -64,6 +99,48 @@ pub fn combine_types(types: &mut Types, lhs: Type, rhs: Type) -> Type {
             };
             types.push(type_)
         }
+        (
+            Val::Value(&TypeData::Array {
+                element: lhs_element,
+                length: lhs_length,
+            }),
+            Val::Value(&TypeData::Array {
+                element: rhs_element,
+                length: rhs_length,
+            }),
+        ) if lhs_length == rhs_length => {
+            let type_ = TypeData::Array {
+                element: combine_types(types, lhs_element, rhs_element),
+                length: lhs_length,
+            };
+            types.push(type_)
+        }
+        (
+            Val::Value(&TypeData::Slice {
+                element: lhs_element,
+            }),
+            Val::Value(&TypeData::Slice {
+                element: rhs_element,
+            }),
+        ) => {
+            let type_ = TypeData::Slice {
+                element: combine_types(types, lhs_element, rhs_element),
+            };
+            types.push(type_)
+        }
+        (
+            Val::Value(&TypeData::Vec {
+                element: lhs_element,
+            }),
+            Val::Value(&TypeData::Vec {
+                element: rhs_element,
+            }),
+        ) => {
+            let type_ = TypeData::Vec {
+                element: combine_types(types, lhs_element, rhs_element),
+            };
+            types.push(type_)
+        }
         // TODO: actually merge both products
         (Val::Value(TypeData::Product { .. }), Val::Value(TypeData::Product { .. })) => lhs,
         (a, b) => panic!("No rules to merge types {a:?} and {b:?}"),
@@ -78,12 +155,16 @@ pub fn types_equals(types: &Types, lhs: Type, rhs: Type) -> bool {
         (Val::Sentinel(TypeSentinel::Bool), Val::Sentinel(TypeSentinel::Bool)) => true,
         (Val::Sentinel(TypeSentinel::False), Val::Sentinel(TypeSentinel::False)) => true,
         (Val::Sentinel(TypeSentinel::True), Val::Sentinel(TypeSentinel::True)) => true,
+        (Val::Sentinel(TypeSentinel::Never), Val::Sentinel(TypeSentinel::Never)) => true,
+        (Val::Sentinel(TypeSentinel::Pointer), Val::Sentinel(TypeSentinel::Pointer)) => true,
         (
             Val::Sentinel(TypeSentinel::Unit)
             | Val::Sentinel(TypeSentinel::Uint32)
             | Val::Sentinel(TypeSentinel::Bool)
             | Val::Sentinel(TypeSentinel::False)
-            | Val::Sentinel(TypeSentinel::True),
+            | Val::Sentinel(TypeSentinel::True)
+            | Val::Sentinel(TypeSentinel::Never)
+            | Val::Sentinel(TypeSentinel::Pointer),
             _,
         ) => false,
         (
@@ -98,6 +179,40 @@ pub fn types_equals(types: &Types, lhs: Type, rhs: Type) -> bool {
                         types_equals(types, *lhs_field, *rhs_field)
                     })
         }
+        (
+            Val::Value(TypeData::Array {
+                element: lhs_element,
+                length: lhs_length,
+            }),
+            Val::Value(TypeData::Array {
+                element: rhs_element,
+                length: rhs_length,
+            }),
+        ) => lhs_length == rhs_length && types_equals(types, *lhs_element, *rhs_element),
+        (
+            Val::Value(TypeData::Slice {
+                element: lhs_element,
+            }),
+            Val::Value(TypeData::Slice {
+                element: rhs_element,
+            }),
+        ) => types_equals(types, *lhs_element, *rhs_element),
+        (
+            Val::Value(TypeData::Vec {
+                element: lhs_element,
+            }),
+            Val::Value(TypeData::Vec {
+                element: rhs_element,
+            }),
+        ) => types_equals(types, *lhs_element, *rhs_element),
+        (
+            Val::Value(TypeData::Pointer {
+                pointee: lhs_pointee,
+            }),
+            Val::Value(TypeData::Pointer {
+                pointee: rhs_pointee,
+            }),
+        ) => types_equals(types, *lhs_pointee, *rhs_pointee),
         (_, _) => false,
     }
 }
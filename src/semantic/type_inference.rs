@@ -2,13 +2,19 @@ use std::collections::HashMap;
 
 use crate::{
     key_vec::{Sentinel, Val},
-    semantic::{self, Sem, SemKind, Semantic, Type, TypeData, TypeSentinel, Types, combine_types},
+    semantic::{
+        self, Sem, SemKind, Semantic, Type, TypeData, TypeSentinel, Types, combine_types,
+        escape::check_escapes,
+    },
 };
 
 pub fn infer_types(semantic: &mut Semantic, types: &mut Types) {
     let mut inferrer = Inferrer { semantic, types };
 
     inferrer.infer_root();
+
+    // Needs the types to know which values hold slices.
+    check_escapes(semantic, types);
 }
 
 type Scope = HashMap<String, ScopeItem>;
@@ -32,7 +38,20 @@ impl Inferrer<'_> {
             return_type: TypeSentinel::Unit.to_index(),
         });
 
-        let mut scope = Scope::from([("print".to_string(), ScopeItem::Type(print))]);
+        let alloc = self.types.push(TypeData::Function {
+            argument_type: TypeSentinel::Uint32.to_index(),
+            return_type: TypeSentinel::Pointer.to_index(),
+        });
+        let free = self.types.push(TypeData::Function {
+            argument_type: TypeSentinel::Pointer.to_index(),
+            return_type: TypeSentinel::Unit.to_index(),
+        });
+
+        let mut scope = Scope::from([
+            ("print".to_string(), ScopeItem::Type(print)),
+            ("alloc".to_string(), ScopeItem::Type(alloc)),
+            ("free".to_string(), ScopeItem::Type(free)),
+        ]);
 
         let uint32_tuple = self.types.push(TypeData::Product {
             fields: vec![
@@ -45,13 +64,16 @@ impl Inferrer<'_> {
             return_type: TypeSentinel::Uint32.to_index(),
         });
 
-        for name in [
-            "builtin_equal",
-            "builtin_add",
-            "builtin_sub",
-            "builtin_mul",
-            "builtin_div",
-        ] {
+        let equal_function_type = self.types.push(TypeData::Function {
+            argument_type: uint32_tuple,
+            return_type: TypeSentinel::Bool.to_index(),
+        });
+        scope.insert(
+            "builtin_equal".to_string(),
+            ScopeItem::Type(equal_function_type),
+        );
+
+        for name in ["builtin_add", "builtin_sub", "builtin_mul", "builtin_div"] {
             scope.insert(name.to_string(), ScopeItem::Type(binary_function_type));
         }
 
@@ -62,6 +84,28 @@ impl Inferrer<'_> {
         self.semantic.types[sem] = combine_types(self.types, self.semantic.types[sem], type_);
     }
 
+    fn element_type(&self, type_: Type) -> Option<Type> {
+        match self.types.get(type_) {
+            Val::Value(
+                &TypeData::Array { element, .. }
+                | &TypeData::Slice { element }
+                | &TypeData::Vec { element },
+            ) => Some(element),
+            // TODO: Infer for unknown types
+            Val::Sentinel(TypeSentinel::Unknown) => None,
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // An `u32` condition is true when it is not zero.
+    fn infer_condition(&mut self, scope: &Scope, condition: Sem) {
+        self.infer_expression(scope, condition);
+
+        if let Some(TypeSentinel::Unknown) = self.semantic.types[condition].sentinel() {
+            self.add_type(condition, TypeSentinel::Bool.to_index());
+        }
+    }
+
     fn infer_expression(&mut self, scope: &Scope, i: Sem) {
         match &self.semantic.kinds[i] {
             SemKind::Number { .. } => self.add_type(i, TypeSentinel::Uint32.to_index()),
@@ -187,6 +231,8 @@ impl Inferrer<'_> {
                 };
 
                 self.infer_expression(scope, value);
+
+                self.add_type(i, TypeSentinel::Unit.to_index());
             }
             SemKind::Reference { name } => {
                 let type_ = match scope[name] {
@@ -217,9 +263,70 @@ impl Inferrer<'_> {
                             self.add_type(i, *field_type);
                         }
                     }
+                    Val::Value(
+                        TypeData::Array { .. } | TypeData::Slice { .. } | TypeData::Vec { .. },
+                    ) if field == "len" => self.add_type(i, TypeSentinel::Uint32.to_index()),
                     Val::Value(_) => panic!(),
                 }
             }
+            SemKind::Index { expr, index, .. } => {
+                let expr = *expr;
+                let index = *index;
+
+                self.infer_expression(scope, expr);
+                self.infer_expression(scope, index);
+                self.add_type(index, TypeSentinel::Uint32.to_index());
+
+                if let Some(element) = self.element_type(self.semantic.types[expr]) {
+                    self.add_type(i, element);
+                }
+            }
+            SemKind::Slice {
+                expr, start, end, ..
+            } => {
+                let expr = *expr;
+                let bounds = [*start, *end];
+
+                self.infer_expression(scope, expr);
+
+                if let Val::Value(TypeData::Vec { .. }) = self.types.get(self.semantic.types[expr])
+                {
+                    panic!("can only slice arrays and slices");
+                }
+
+                for bound in bounds.into_iter().flatten() {
+                    self.infer_expression(scope, bound);
+                    self.add_type(bound, TypeSentinel::Uint32.to_index());
+                }
+
+                if let Some(element) = self.element_type(self.semantic.types[expr]) {
+                    let type_ = self.types.push(TypeData::Slice { element });
+                    self.add_type(i, type_);
+                }
+            }
+            SemKind::Store {
+                expr, index, value, ..
+            } => {
+                let expr = *expr;
+                let index = *index;
+                let value = *value;
+
+                self.infer_expression(scope, expr);
+                self.infer_expression(scope, index);
+                self.infer_expression(scope, value);
+                self.add_type(index, TypeSentinel::Uint32.to_index());
+
+                match self.types.get(self.semantic.types[expr]) {
+                    Val::Value(&TypeData::Slice { element } | &TypeData::Vec { element }) => {
+                        self.add_type(value, element)
+                    }
+                    // Arrays are values, they can only be modified through a
+                    // slice.
+                    _ => panic!("can only store into a slice or a vec"),
+                }
+
+                self.add_type(i, TypeSentinel::Unit.to_index());
+            }
             SemKind::Application { function, argument } => {
                 let function = *function;
                 let argument = *argument;
@@ -237,14 +344,55 @@ impl Inferrer<'_> {
             SemKind::Loop(body) => {
                 let body = *body;
                 self.infer_expression(scope, body);
-                // TODO: When we add `break`, the type inference should infer based on them
+
+                // The type of a loop is the type of its `break`s, which have
+                // already been combined into it while inferring the body.
+                if let Some(TypeSentinel::Unknown) = self.semantic.types[i].sentinel() {
+                    self.add_type(i, TypeSentinel::Never.to_index());
+                }
+            }
+            SemKind::Break { loop_, value } => {
+                let loop_ = *loop_;
+
+                let value_type = match *value {
+                    Some(value) => {
+                        self.infer_expression(scope, value);
+                        self.semantic.types[value]
+                    }
+                    None => TypeSentinel::Unit.to_index(),
+                };
+
+                self.add_type(loop_, value_type);
+                self.add_type(i, TypeSentinel::Never.to_index());
+            }
+            SemKind::Continue { .. } => self.add_type(i, TypeSentinel::Never.to_index()),
+            SemKind::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let name = name.clone();
+                let iterable = *iterable;
+                let body = *body;
+
+                self.infer_expression(scope, iterable);
+
+                let Some(element) = self.element_type(self.semantic.types[iterable]) else {
+                    panic!("can only iterate over arrays, slices and vecs");
+                };
+
+                let mut scope = scope.clone();
+                scope.insert(name, ScopeItem::Type(element));
+
+                self.infer_expression(&scope, body);
+
                 self.add_type(i, TypeSentinel::Unit.to_index());
             }
             SemKind::If { condition, then } => {
                 let condition = *condition;
                 let then = *then;
 
-                self.add_type(condition, TypeSentinel::Bool.to_index());
+                self.infer_condition(scope, condition);
                 self.infer_expression(scope, then);
                 self.add_type(then, TypeSentinel::Unit.to_index());
                 self.add_type(i, TypeSentinel::Unit.to_index());
@@ -258,7 +406,7 @@ impl Inferrer<'_> {
                 let then = *then;
                 let else_ = *else_;
 
-                self.add_type(condition, TypeSentinel::Bool.to_index());
+                self.infer_condition(scope, condition);
 
                 self.infer_expression(scope, then);
                 self.infer_expression(scope, else_);
@@ -268,7 +416,7 @@ impl Inferrer<'_> {
 
                 self.add_type(then, else_type);
                 self.add_type(else_, then_type);
-                self.add_type(i, then_type);
+                self.add_type(i, self.semantic.types[then]);
             }
             SemKind::BuildStruct { fields } => {
                 let fields = fields.clone();
@@ -285,6 +433,84 @@ impl Inferrer<'_> {
 
                 self.add_type(i, type_);
             }
+            SemKind::BuildArray { elements } => {
+                let elements = elements.clone();
+
+                let mut element = TypeSentinel::Unknown.to_index();
+                for value in &elements {
+                    self.infer_expression(scope, *value);
+                    element = combine_types(self.types, element, self.semantic.types[*value]);
+                }
+
+                let type_ = self.types.push(TypeData::Array {
+                    element,
+                    length: elements.len() as u32,
+                });
+
+                self.add_type(i, type_);
+            }
+            SemKind::RepeatArray { value, length } => {
+                let value = *value;
+                let length = *length;
+
+                self.infer_expression(scope, value);
+
+                let type_ = self.types.push(TypeData::Array {
+                    element: self.semantic.types[value],
+                    length,
+                });
+
+                self.add_type(i, type_);
+            }
+            SemKind::BuildVec(array) => {
+                let array = *array;
+
+                self.infer_expression(scope, array);
+
+                // An ascription on an empty `vec []` gives its element type to
+                // the array.
+                if let Val::Value(&TypeData::Vec { element }) =
+                    self.types.get(self.semantic.types[i])
+                    && let Val::Value(&TypeData::Array { length, .. }) =
+                        self.types.get(self.semantic.types[array])
+                {
+                    let type_ = self.types.push(TypeData::Array { element, length });
+                    self.add_type(array, type_);
+                }
+
+                let Val::Value(&TypeData::Array { element, .. }) =
+                    self.types.get(self.semantic.types[array])
+                else {
+                    panic!("vec takes an array");
+                };
+
+                let type_ = self.types.push(TypeData::Vec { element });
+                self.add_type(i, type_);
+            }
+            SemKind::Push { vec, value } => {
+                let vec = *vec;
+                let value = *value;
+
+                self.infer_expression(scope, vec);
+                self.infer_expression(scope, value);
+
+                match self.types.get(self.semantic.types[vec]) {
+                    Val::Value(&TypeData::Vec { element }) => self.add_type(value, element),
+                    _ => panic!("can only push into a vec"),
+                }
+
+                self.add_type(i, TypeSentinel::Unit.to_index());
+            }
+            SemKind::Pop { vec, .. } => {
+                let vec = *vec;
+
+                self.infer_expression(scope, vec);
+
+                match self.types.get(self.semantic.types[vec]) {
+                    Val::Value(&TypeData::Vec { element }) => self.add_type(i, element),
+                    _ => panic!("can only pop from a vec"),
+                }
+            }
             SemKind::ChainOpen {
                 statements,
                 expression,
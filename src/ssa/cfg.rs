@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+// Control flow graph of a function, made of the function and the blocks it
+// can reach. There is an edge per jump target, so a conditional jump with the
+// same block on both sides gives two edges.
+pub struct Cfg {
+    // The function followed by its blocks, in reverse postorder.
+    blocks: Vec<Block>,
+    order: HashMap<Block, usize>,
+    successors: HashMap<Block, Vec<Block>>,
+    predecessors: HashMap<Block, Vec<Block>>,
+    // Immediate dominator of every block, the function is its own.
+    dominators: HashMap<Block, Block>,
+    // Blocks immediately dominated by every block, in reverse postorder.
+    dominated: HashMap<Block, Vec<Block>>,
+}
+
+// A natural loop, entered through its header.
+#[derive(Debug)]
+pub struct Loop {
+    pub header: Block,
+    // Blocks jumping back to the header.
+    pub latches: Vec<Block>,
+    // Blocks of the loop in reverse postorder, starting with the header.
+    pub blocks: Vec<Block>,
+}
+
+// Values used by a block or the blocks after it, before they are defined
+// again. Values are instructions and block arguments.
+#[derive(Debug, Default)]
+pub struct Liveness {
+    pub live_in: HashMap<Block, HashSet<Expr>>,
+    pub live_out: HashMap<Block, HashSet<Expr>>,
+}
+
+impl Cfg {
+    pub fn new(ssa: &Ssa, function: Block) -> Cfg {
+        let mut successors = HashMap::new();
+        let mut postorder = Vec::new();
+        let mut visited = HashSet::from([function]);
+        let mut stack = vec![(function, 0)];
+
+        while let Some((block, next)) = stack.last_mut() {
+            let block_successors = successors
+                .entry(*block)
+                .or_insert_with(|| block_successors(ssa, *block));
+
+            match block_successors.get(*next) {
+                Some(&successor) => {
+                    *next += 1;
+                    if visited.insert(successor) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    postorder.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+
+        let blocks = postorder.into_iter().rev().collect::<Vec<_>>();
+
+        let order = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (*block, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut predecessors = blocks
+            .iter()
+            .map(|block| (*block, Vec::new()))
+            .collect::<HashMap<_, _>>();
+        for block in &blocks {
+            for successor in &successors[block] {
+                predecessors.get_mut(successor).unwrap().push(*block);
+            }
+        }
+
+        let mut cfg = Cfg {
+            blocks,
+            order,
+            successors,
+            predecessors,
+            dominators: HashMap::new(),
+            dominated: HashMap::new(),
+        };
+        cfg.compute_dominators();
+        cfg
+    }
+
+    // As described in "A Simple, Fast Dominance Algorithm" by Cooper, Harvey
+    // and Kennedy.
+    fn compute_dominators(&mut self) {
+        let function = self.function();
+        let mut dominators = HashMap::from([(function, function)]);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for block in &self.blocks[1..] {
+                let mut dominator = None;
+                for predecessor in &self.predecessors[block] {
+                    if !dominators.contains_key(predecessor) {
+                        continue;
+                    }
+
+                    dominator = Some(match dominator {
+                        None => *predecessor,
+                        Some(mut lhs) => {
+                            let mut rhs = *predecessor;
+                            while lhs != rhs {
+                                while self.order[&lhs] > self.order[&rhs] {
+                                    lhs = dominators[&lhs];
+                                }
+                                while self.order[&rhs] > self.order[&lhs] {
+                                    rhs = dominators[&rhs];
+                                }
+                            }
+                            lhs
+                        }
+                    });
+                }
+
+                let dominator = dominator.unwrap();
+                if dominators.insert(*block, dominator) != Some(dominator) {
+                    changed = true;
+                }
+            }
+        }
+
+        for block in &self.blocks[1..] {
+            self.dominated
+                .entry(dominators[block])
+                .or_default()
+                .push(*block);
+        }
+        self.dominators = dominators;
+    }
+
+    pub fn function(&self) -> Block {
+        self.blocks[0]
+    }
+
+    // The function followed by its blocks, in reverse postorder. Every block
+    // comes after the blocks dominating it.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    pub fn contains(&self, block: Block) -> bool {
+        self.order.contains_key(&block)
+    }
+
+    // Position of the block in reverse postorder, an edge going to a block
+    // that is not after its source is a back edge in the graphs keb
+    // generates.
+    pub fn order(&self, block: Block) -> usize {
+        self.order[&block]
+    }
+
+    pub fn successors(&self, block: Block) -> &[Block] {
+        &self.successors[&block]
+    }
+
+    pub fn predecessors(&self, block: Block) -> &[Block] {
+        &self.predecessors[&block]
+    }
+
+    pub fn immediate_dominator(&self, block: Block) -> Block {
+        self.dominators[&block]
+    }
+
+    pub fn dominated(&self, block: Block) -> &[Block] {
+        self.dominated.get(&block).map_or(&[], Vec::as_slice)
+    }
+
+    // Whether every path from the function to `block` goes through
+    // `dominator`, blocks dominate themselves.
+    pub fn dominates(&self, dominator: Block, mut block: Block) -> bool {
+        if !self.contains(dominator) || !self.contains(block) {
+            return false;
+        }
+
+        loop {
+            if block == dominator {
+                return true;
+            }
+
+            let parent = self.dominators[&block];
+            if parent == block {
+                return false;
+            }
+            block = parent;
+        }
+    }
+
+    // Blocks where the dominance of each block stops, as described in the same
+    // paper as the dominators.
+    pub fn dominance_frontiers(&self) -> HashMap<Block, HashSet<Block>> {
+        let mut frontiers = self
+            .blocks
+            .iter()
+            .map(|block| (*block, HashSet::new()))
+            .collect::<HashMap<_, _>>();
+
+        for block in &self.blocks {
+            let predecessors = &self.predecessors[block];
+            if predecessors.len() < 2 {
+                continue;
+            }
+
+            for predecessor in predecessors {
+                let mut runner = *predecessor;
+                while runner != self.dominators[block] {
+                    frontiers.get_mut(&runner).unwrap().insert(*block);
+                    runner = self.dominators[&runner];
+                }
+            }
+        }
+
+        frontiers
+    }
+
+    // Natural loops, found from the back edges to a block dominating their
+    // source. Outer loops come before the loops they contain.
+    pub fn loops(&self) -> Vec<Loop> {
+        let mut loops = Vec::<Loop>::new();
+
+        for header in &self.blocks {
+            let latches = self.predecessors[header]
+                .iter()
+                .copied()
+                .filter(|predecessor| self.dominates(*header, *predecessor))
+                .collect::<Vec<_>>();
+
+            if latches.is_empty() {
+                continue;
+            }
+
+            // Blocks reaching a latch without going through the header.
+            let mut blocks = HashSet::from([*header]);
+            let mut stack = latches.clone();
+            while let Some(block) = stack.pop() {
+                if blocks.insert(block) {
+                    stack.extend(&self.predecessors[&block]);
+                }
+            }
+
+            let mut blocks = blocks.into_iter().collect::<Vec<_>>();
+            blocks.sort_by_key(|block| self.order[block]);
+
+            loops.push(Loop {
+                header: *header,
+                latches,
+                blocks,
+            });
+        }
+
+        loops
+    }
+
+    // Solved backwards until nothing changes, in postorder so that most
+    // successors are done before their predecessors.
+    pub fn liveness(&self, ssa: &Ssa) -> Liveness {
+        let mut uses = HashMap::new();
+        let mut definitions = HashMap::new();
+
+        for block in &self.blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[*block]
+            else {
+                panic!()
+            };
+
+            let block_definitions = insts
+                .iter()
+                .map(|inst| Expr::Inst(*inst))
+                .chain([Expr::BlockArg(*block)])
+                .collect::<HashSet<_>>();
+
+            let block_uses = insts
+                .iter()
+                .flat_map(|inst| ssa.insts[*inst].operands())
+                .filter(|operand| !matches!(operand, Expr::Const(_)))
+                .filter(|operand| !block_definitions.contains(operand))
+                .collect::<HashSet<_>>();
+
+            uses.insert(*block, block_uses);
+            definitions.insert(*block, block_definitions);
+        }
+
+        let mut liveness = Liveness::default();
+        for block in &self.blocks {
+            liveness.live_in.insert(*block, HashSet::new());
+            liveness.live_out.insert(*block, HashSet::new());
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for block in self.blocks.iter().rev() {
+                let live_out = self.successors[block]
+                    .iter()
+                    .flat_map(|successor| liveness.live_in[successor].iter().copied())
+                    .collect::<HashSet<_>>();
+
+                let live_in = uses[block]
+                    .iter()
+                    .copied()
+                    .chain(live_out.difference(&definitions[block]).copied())
+                    .collect::<HashSet<_>>();
+
+                if live_in != liveness.live_in[block] || live_out != liveness.live_out[block] {
+                    changed = true;
+                    liveness.live_in.insert(*block, live_in);
+                    liveness.live_out.insert(*block, live_out);
+                }
+            }
+        }
+
+        liveness
+    }
+}
+
+// Blocks jumped to from the block, jumps to functions are malformed and left
+// to the verifier.
+fn block_successors(ssa: &Ssa, block: Block) -> Vec<Block> {
+    let insts = match &ssa.blocks[block] {
+        BlockData::ExternFunction { .. } => return vec![],
+        BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts,
+    };
+
+    let mut successors = Vec::new();
+    for inst in insts {
+        match ssa.insts[*inst] {
+            InstData::Jump { block, .. } => successors.push(block),
+            InstData::JumpCondition { then, else_, .. } => successors.extend([then, else_]),
+            _ => {}
+        }
+    }
+
+    successors.retain(|block| matches!(ssa.blocks[*block], BlockData::Block { .. }));
+    successors
+}
@@ -63,6 +63,108 @@ pub fn debug(types: &Types, ssa: &Ssa) {
                         print!("{}", debug_expr(field));
                     }
                 }
+                InstData::Array(elements, _) => {
+                    print!("{} [", "array".bright_red().bold());
+                    for (i, element) in elements.iter().enumerate() {
+                        if i != 0 {
+                            print!(", ");
+                        }
+
+                        print!("{}", debug_expr(element));
+                    }
+                    print!("]");
+                }
+                InstData::Length(base) => {
+                    print!("{} {}", "length".bright_red().bold(), debug_expr(base))
+                }
+                InstData::Load {
+                    base,
+                    index,
+                    location,
+                } => print!(
+                    "{} {}[{}] {}",
+                    "load".bright_red().bold(),
+                    debug_expr(base),
+                    debug_expr(index),
+                    debug_location(location),
+                ),
+                InstData::Store {
+                    base,
+                    index,
+                    value,
+                    location,
+                } => print!(
+                    "{} {}[{}], {} {}",
+                    "store".bright_red().bold(),
+                    debug_expr(base),
+                    debug_expr(index),
+                    debug_expr(value),
+                    debug_location(location),
+                ),
+                InstData::Slice {
+                    base,
+                    start,
+                    end,
+                    location,
+                    ..
+                } => print!(
+                    "{} {}[{}..{}] {}",
+                    "slice".bright_red().bold(),
+                    debug_expr(base),
+                    debug_expr(start),
+                    debug_expr(end),
+                    debug_location(location),
+                ),
+                InstData::NewVec(elements, _) => {
+                    print!("{} [", "new_vec".bright_red().bold());
+                    for (i, element) in elements.iter().enumerate() {
+                        if i != 0 {
+                            print!(", ");
+                        }
+
+                        print!("{}", debug_expr(element));
+                    }
+                    print!("]");
+                }
+                InstData::Push { vec, value } => print!(
+                    "{} {}, {}",
+                    "push".bright_red().bold(),
+                    debug_expr(vec),
+                    debug_expr(value),
+                ),
+                InstData::Pop { vec, location } => print!(
+                    "{} {} {}",
+                    "pop".bright_red().bold(),
+                    debug_expr(vec),
+                    debug_location(location),
+                ),
+                InstData::Alloca(type_) => {
+                    print!(
+                        "{} {}",
+                        "alloca".bright_red().bold(),
+                        debug_type(types, *type_)
+                    )
+                }
+                InstData::AddressOf(expr, _) => {
+                    print!("{} {}", "address_of".bright_red().bold(), debug_expr(expr))
+                }
+                InstData::LoadPointer(pointer) => print!(
+                    "{} {}",
+                    "load_pointer".bright_red().bold(),
+                    debug_expr(pointer)
+                ),
+                InstData::StorePointer { pointer, value } => print!(
+                    "{} {}, {}",
+                    "store_pointer".bright_red().bold(),
+                    debug_expr(pointer),
+                    debug_expr(value),
+                ),
+                InstData::Retain(vec) => {
+                    print!("{} {}", "retain".bright_red().bold(), debug_expr(vec))
+                }
+                InstData::Release(vec) => {
+                    print!("{} {}", "release".bright_red().bold(), debug_expr(vec))
+                }
                 InstData::Equal(lhs, rhs) => print!(
                     "{} {}, {}",
                     "equal".bright_red().bold(),
@@ -121,6 +223,12 @@ pub fn debug(types: &Types, ssa: &Ssa) {
                 InstData::Return(value) => {
                     print!("{} {}", "return".bright_red().bold(), debug_expr(value))
                 }
+                InstData::TailCall { function, argument } => print!(
+                    "{} {}, {}",
+                    "tail_call".bright_red().bold(),
+                    format!("@{}", function.as_u32()).bright_yellow(),
+                    debug_expr(argument),
+                ),
             }
             println!("{}", ";".white());
         }
@@ -148,6 +256,13 @@ pub fn debug(types: &Types, ssa: &Ssa) {
     }
 }
 
+fn debug_location(location: &Location) -> String {
+    format!("at {}:{}", location.line, location.column)
+        .white()
+        .italic()
+        .to_string()
+}
+
 fn debug_expr(expr: &Expr) -> String {
     match expr {
         Expr::Const(const_) => {
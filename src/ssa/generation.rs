@@ -2,13 +2,14 @@ use std::collections::{HashMap, HashSet};
 
 use crate::{
     key_vec::{Sentinel, Val},
-    semantic::{self, Sem, SemKind, Semantic, TypeData, TypeSentinel, Types},
-    token::{self, TokenOffsets},
+    semantic::{self, Sem, SemKind, Semantic, Type, TypeData, TypeSentinel, Types},
+    token::{self, Token, TokenOffsets},
 };
 
 use super::*;
 
 pub fn generate(
+    path: &str,
     source: &str,
     tokens: &TokenOffsets,
     semantic: &Semantic,
@@ -19,10 +20,19 @@ pub fn generate(
         tokens,
         semantic,
         types,
-        ssa: Ssa::default(),
+        ssa: Ssa {
+            path: path.to_string(),
+            ..Ssa::default()
+        },
+        loops: HashMap::new(),
+        owned: Vec::new(),
+        by_reference: HashSet::new(),
     };
 
     generator.generate_module();
+    // Loops are often written as recursion, which must not grow the stack.
+    opt::tail_call(generator.types, &mut generator.ssa);
+    debug_verify(generator.types, &generator.ssa);
 
     generator.ssa
 }
@@ -33,6 +43,15 @@ struct Generator<'a> {
     semantic: &'a Semantic,
     types: &'a mut Types,
     ssa: Ssa,
+    // Entry and exit blocks of each loop being generated, with the number of
+    // `owned` values when entering its body.
+    loops: HashMap<Sem, (Block, Block, usize)>,
+    // Values holding vecs that are released when going out of scope, a
+    // `break` or `continue` releases the ones of the loop body.
+    owned: Vec<Expr>,
+    // Functions taking a pointer to their record argument, which callers put
+    // in memory.
+    by_reference: HashSet<Block>,
 }
 
 impl Generator<'_> {
@@ -41,14 +60,36 @@ impl Generator<'_> {
             panic!();
         };
 
-        let mut functions = HashMap::from([(
-            "print".to_string(),
-            self.ssa.extern_function(
-                "builtin_print".to_string(),
-                TypeSentinel::Uint32.to_index(),
-                TypeSentinel::Unit.to_index(),
+        let mut functions = HashMap::new();
+
+        // Implemented by the runtime, see `crate::runtime`. These are the only
+        // extern functions, other C functions cannot be called.
+        for (name, extern_name, arg, ret) in [
+            (
+                "print",
+                "builtin_print",
+                TypeSentinel::Uint32,
+                TypeSentinel::Unit,
+            ),
+            (
+                "alloc",
+                "builtin_alloc",
+                TypeSentinel::Uint32,
+                TypeSentinel::Pointer,
             ),
-        )]);
+            (
+                "free",
+                "builtin_free",
+                TypeSentinel::Pointer,
+                TypeSentinel::Unit,
+            ),
+        ] {
+            let function =
+                self.ssa
+                    .extern_function(extern_name.to_string(), arg.to_index(), ret.to_index());
+
+            functions.insert(name.to_string(), function);
+        }
 
         let u32_tuple = self.types.push(TypeData::Product {
             fields: vec![
@@ -57,19 +98,24 @@ impl Generator<'_> {
             ],
         });
 
-        for (name, inst_data) in [
+        for (name, inst_data, ret) in [
             (
                 "builtin_equal",
                 InstData::Equal as fn(Expr, Expr) -> InstData,
+                TypeSentinel::Bool,
             ),
-            ("builtin_add", InstData::Add as fn(Expr, Expr) -> InstData),
-            ("builtin_sub", InstData::Sub),
-            ("builtin_mul", InstData::Mul),
-            ("builtin_div", InstData::Div),
+            (
+                "builtin_add",
+                InstData::Add as fn(Expr, Expr) -> InstData,
+                TypeSentinel::Uint32,
+            ),
+            ("builtin_sub", InstData::Sub, TypeSentinel::Uint32),
+            ("builtin_mul", InstData::Mul, TypeSentinel::Uint32),
+            ("builtin_div", InstData::Div, TypeSentinel::Uint32),
         ] {
-            let function =
-                self.ssa
-                    .function(name.to_string(), u32_tuple, TypeSentinel::Uint32.to_index());
+            let function = self
+                .ssa
+                .function(name.to_string(), u32_tuple, ret.to_index());
             let lhs = self.ssa.inst_field(function, Expr::BlockArg(function), 0);
             let rhs = self.ssa.inst_field(function, Expr::BlockArg(function), 1);
             let result = self
@@ -93,11 +139,19 @@ impl Generator<'_> {
                 panic!()
             };
 
-            functions.insert(
-                name.clone(),
-                self.ssa
-                    .function(name.clone(), *argument_type, *return_type),
-            );
+            let (argument_type, return_type) = (*argument_type, *return_type);
+            let function = if self.passed_by_reference(argument_type) {
+                let pointer_type = self.types.push(TypeData::Pointer {
+                    pointee: argument_type,
+                });
+                let function = self.ssa.function(name.clone(), pointer_type, return_type);
+                self.by_reference.insert(function);
+                function
+            } else {
+                self.ssa.function(name.clone(), argument_type, return_type)
+            };
+
+            functions.insert(name.clone(), function);
         }
 
         for (name, value) in bindings {
@@ -106,14 +160,24 @@ impl Generator<'_> {
             };
 
             let mut block = functions[name.as_str()];
+            let value = if self.by_reference.contains(&block) {
+                Expr::Inst(self.ssa.inst_load_pointer(block, Expr::BlockArg(block)))
+            } else {
+                Expr::BlockArg(block)
+            };
+
             let mut scope = Scope {
                 parent: None,
                 mutable_bindings: HashSet::new(),
-                bindings: HashMap::from([(argument.clone(), Expr::BlockArg(block))]),
+                bindings: HashMap::from([(argument.clone(), value)]),
                 functions: functions.clone(),
             };
 
+            // Arguments are given to the function which releases them.
+            self.owned.push(value);
             let expr = self.generate_expression(&mut block, *body, &mut scope);
+            self.release_owned(block);
+
             self.ssa.inst_return(block, expr);
         }
     }
@@ -128,47 +192,161 @@ impl Generator<'_> {
             SemKind::True(_) => Expr::Const(ConstSentinel::True.to_index()),
             SemKind::Module { .. } => todo!(),
             SemKind::Function { .. } => todo!(),
-            SemKind::Binding { name, value, body } | SemKind::MutBinding { name, value, body } => {
+            SemKind::Binding { name, value, body } => {
                 let value = self.generate_expression(block, *value, scope);
 
-                let mutable_bindings = if let SemKind::MutBinding { .. } = self.semantic.kinds[sem]
-                {
-                    todo!("mutable bindings are not working whenever branching is involved")
-                    // HashSet::from([name.to_string()])
-                } else {
-                    HashSet::new()
+                let mut scope = Scope {
+                    parent: Some(scope),
+                    mutable_bindings: HashSet::new(),
+                    bindings: HashMap::from([(name.to_string(), value)]),
+                    functions: HashMap::new(),
                 };
 
+                self.owned.push(value);
+                let body = self.generate_expression(block, *body, &mut scope);
+                self.release_owned(*block);
+
+                body
+            }
+            // Mutable bindings live in memory so that assignments in a branch
+            // are seen after it.
+            SemKind::MutBinding { name, value, body } => {
+                let value = self.generate_expression(block, *value, scope);
+                let value_type = self.ssa.expression_type(self.types, value);
+
+                let slot = Expr::Inst(self.ssa.inst_alloca(self.types, *block, value_type));
+                self.ssa.inst_store_pointer(*block, slot, value);
+
                 let mut scope = Scope {
                     parent: Some(scope),
-                    mutable_bindings,
-                    bindings: HashMap::from([(name.to_string(), value)]),
+                    mutable_bindings: HashSet::from([name.to_string()]),
+                    bindings: HashMap::from([(name.to_string(), slot)]),
                     functions: HashMap::new(),
                 };
 
-                self.generate_expression(block, *body, &mut scope)
+                self.owned.push(slot);
+                let body = self.generate_expression(block, *body, &mut scope);
+                self.release_owned(*block);
+
+                body
             }
             SemKind::Assignment { binding, value } => {
-                // TODO: Does not work with branching, it will take the value
-                // from the last branch because we just replace the binding.
-                // Should we use block arguments?
                 assert!(scope.is_mutable(binding));
                 let value = self.generate_expression(block, *value, scope);
-                scope.bindings.insert(binding.clone(), value).unwrap();
+
+                let slot = scope.binding(binding).unwrap();
+                let previous = Expr::Inst(self.ssa.inst_load_pointer(*block, slot));
+                self.release(*block, previous);
+
+                self.ssa.inst_store_pointer(*block, slot, value);
+
                 Expr::Const(ConstSentinel::Unit.to_index())
             }
-            SemKind::Reference { name } => scope.binding(name).unwrap(),
+            SemKind::Reference { name } => {
+                let value = scope.binding(name).unwrap();
+
+                let value = if scope.is_mutable(name) {
+                    Expr::Inst(self.ssa.inst_load_pointer(*block, value))
+                } else {
+                    value
+                };
+
+                self.retain(*block, value);
+                value
+            }
             SemKind::Access { field, expr } => {
-                let field_index = match self.types.get(self.semantic.types[*expr]) {
+                let expr_type = self.semantic.types[*expr];
+                let expr = self.generate_expression(block, *expr, scope);
+
+                match self.types.get(expr_type) {
                     Val::Value(TypeData::Product { fields }) => {
-                        fields.iter().position(|(name, _)| field == name).unwrap()
+                        let field_index =
+                            fields.iter().position(|(name, _)| field == name).unwrap();
+
+                        let field =
+                            Expr::Inst(self.ssa.inst_field(*block, expr, field_index as u32));
+                        self.retain(*block, field);
+                        self.release(*block, expr);
+
+                        field
+                    }
+                    Val::Value(
+                        TypeData::Array { .. } | TypeData::Slice { .. } | TypeData::Vec { .. },
+                    ) if field == "len" => {
+                        let length = self.generate_length(*block, expr, expr_type);
+                        self.release(*block, expr);
+
+                        length
                     }
                     Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                }
+            }
+            SemKind::Index { expr, index, token } => {
+                let base = self.generate_expression(block, *expr, scope);
+                let index = self.generate_expression(block, *index, scope);
+                let location = self.location(*token);
+
+                let element = Expr::Inst(self.ssa.inst_load(*block, base, index, location));
+                self.retain(*block, element);
+                self.release(*block, base);
+
+                element
+            }
+            SemKind::Slice {
+                expr,
+                start,
+                end,
+                token,
+            } => {
+                let base_type = self.semantic.types[*expr];
+                let base = self.generate_expression(block, *expr, scope);
+
+                let start = match start {
+                    Some(start) => self.generate_expression(block, *start, scope),
+                    None => Expr::Const(self.ssa.const_u32(0)),
                 };
 
-                let expr = self.generate_expression(block, *expr, scope);
+                let end = match end {
+                    Some(end) => self.generate_expression(block, *end, scope),
+                    None => self.generate_length(*block, base, base_type),
+                };
+
+                let location = self.location(*token);
+
+                // Slices borrow the elements, they don't own them.
+                let slice = self.ssa.inst_slice(
+                    *block,
+                    base,
+                    start,
+                    end,
+                    self.semantic.types[sem],
+                    location,
+                );
+                self.release(*block, base);
 
-                Expr::Inst(self.ssa.inst_field(*block, expr, field_index as u32))
+                Expr::Inst(slice)
+            }
+            SemKind::Store {
+                expr,
+                index,
+                value,
+                token,
+            } => {
+                let base = self.generate_expression(block, *expr, scope);
+                let index = self.generate_expression(block, *index, scope);
+                let value = self.generate_expression(block, *value, scope);
+                let location = self.location(*token);
+
+                // The element being replaced is released.
+                if self.holds_vec(self.ssa.expression_type(self.types, value)) {
+                    let previous = Expr::Inst(self.ssa.inst_load(*block, base, index, location));
+                    self.release(*block, previous);
+                }
+
+                self.ssa.inst_store(*block, base, index, value, location);
+                self.release(*block, base);
+
+                Expr::Const(ConstSentinel::Unit.to_index())
             }
             SemKind::Application { function, argument } => {
                 let argument = self.generate_expression(block, *argument, scope);
@@ -177,13 +355,21 @@ impl Generator<'_> {
                     panic!();
                 };
 
-                Expr::Inst(
-                    self.ssa
-                        .inst_call(*block, scope.function(name).unwrap(), argument),
-                )
+                let function = scope.function(name).unwrap();
+                let argument = if self.by_reference.contains(&function) {
+                    Expr::Inst(self.ssa.inst_address_of(self.types, *block, argument))
+                } else {
+                    argument
+                };
+
+                Expr::Inst(self.ssa.inst_call(*block, function, argument))
             }
             SemKind::Loop(body) => {
                 let loop_block = self.ssa.basic_block(TypeSentinel::Unit.to_index());
+                let exit_block = self
+                    .ssa
+                    .basic_block(block_arg_type(self.semantic.types[sem]));
+
                 self.ssa.inst_jump(
                     *block,
                     loop_block,
@@ -191,12 +377,123 @@ impl Generator<'_> {
                 );
                 *block = loop_block;
 
-                self.generate_expression(block, *body, scope);
+                self.loops
+                    .insert(sem, (loop_block, exit_block, self.owned.len()));
+                let body = self.generate_expression(block, *body, scope);
+                self.release(*block, body);
+
+                self.ssa.inst_jump(
+                    *block,
+                    loop_block,
+                    Expr::Const(ConstSentinel::Unit.to_index()),
+                );
+
+                *block = exit_block;
+
+                Expr::BlockArg(exit_block)
+            }
+            SemKind::For {
+                name,
+                iterable,
+                body,
+            } => {
+                let iterable_type = self.semantic.types[*iterable];
+                let iterable = self.generate_expression(block, *iterable, scope);
+                let length = self.generate_length(*block, iterable, iterable_type);
+
+                let header_block = self.ssa.basic_block(TypeSentinel::Uint32.to_index());
+                let mut body_block = self.ssa.basic_block(TypeSentinel::Unit.to_index());
+                let step_block = self.ssa.basic_block(TypeSentinel::Unit.to_index());
+                let exit_block = self.ssa.basic_block(TypeSentinel::Unit.to_index());
+
+                let zero = self.ssa.const_u32(0);
+                self.ssa.inst_jump(*block, header_block, Expr::Const(zero));
+
+                let index = Expr::BlockArg(header_block);
+                let done = self.ssa.inst(header_block, InstData::Equal(index, length));
+                self.ssa.inst_jump_condition(
+                    header_block,
+                    Expr::Inst(done),
+                    exit_block,
+                    body_block,
+                );
+
+                // The index is always in bounds here, the location is never
+                // reported.
+                let location = Location { line: 0, column: 0 };
+                let element = Expr::Inst(self.ssa.inst_load(body_block, iterable, index, location));
+                self.retain(body_block, element);
+
+                let mut scope = Scope {
+                    parent: Some(scope),
+                    mutable_bindings: HashSet::new(),
+                    bindings: HashMap::from([(name.to_string(), element)]),
+                    functions: HashMap::new(),
+                };
+
+                // The iterable is kept until the exit block while the element
+                // is released at the end of each iteration.
+                self.owned.push(iterable);
+
+                // `continue` goes through the step block to increment the
+                // index.
+                self.loops
+                    .insert(sem, (step_block, exit_block, self.owned.len()));
+
+                self.owned.push(element);
+                let body = self.generate_expression(&mut body_block, *body, &mut scope);
+                self.release(body_block, body);
+                self.release_owned(body_block);
+
+                self.ssa.inst_jump(
+                    body_block,
+                    step_block,
+                    Expr::Const(ConstSentinel::Unit.to_index()),
+                );
 
+                let one = self.ssa.const_u32(1);
+                let next = self
+                    .ssa
+                    .inst(step_block, InstData::Add(index, Expr::Const(one)));
                 self.ssa
-                    .inst_jump(*block, *block, Expr::Const(ConstSentinel::Unit.to_index()));
+                    .inst_jump(step_block, header_block, Expr::Inst(next));
+
+                *block = exit_block;
+                self.release_owned(*block);
+
                 Expr::Const(ConstSentinel::Unit.to_index())
             }
+            SemKind::Break { loop_, value } => {
+                let value = match value {
+                    Some(value) => self.generate_expression(block, *value, scope),
+                    None => Expr::Const(ConstSentinel::Unit.to_index()),
+                };
+
+                let (_, exit_block, owned) = self.loops[loop_];
+                self.release_since(*block, owned);
+                self.ssa.inst_jump(*block, exit_block, value);
+
+                // Anything generated after a `break` is unreachable, it still
+                // needs a block to live in. The argument of that block is the
+                // never value given to what uses the `break`, like the join of
+                // an `if`, which accepts it whatever its type.
+                *block = self.ssa.basic_block(TypeSentinel::Never.to_index());
+
+                Expr::BlockArg(*block)
+            }
+            SemKind::Continue { loop_ } => {
+                let (loop_block, _, owned) = self.loops[loop_];
+                self.release_since(*block, owned);
+                self.ssa.inst_jump(
+                    *block,
+                    loop_block,
+                    Expr::Const(ConstSentinel::Unit.to_index()),
+                );
+
+                *block = self.ssa.basic_block(TypeSentinel::Never.to_index());
+
+                Expr::BlockArg(*block)
+            }
             SemKind::If { condition, then } => {
                 let condition = self.generate_expression(block, *condition, scope);
 
@@ -206,7 +503,8 @@ impl Generator<'_> {
                 self.ssa
                     .inst_jump_condition(*block, condition, then_block, after_block);
 
-                self.generate_expression(&mut then_block, *then, scope);
+                let then = self.generate_expression(&mut then_block, *then, scope);
+                self.release(then_block, then);
 
                 self.ssa.inst_jump(
                     then_block,
@@ -235,7 +533,9 @@ impl Generator<'_> {
 
                 let else_expr = self.generate_expression(&mut else_block, *else_, scope);
 
-                let after_block = self.ssa.basic_block(self.semantic.types[*then]);
+                let after_block = self
+                    .ssa
+                    .basic_block(block_arg_type(self.semantic.types[*then]));
                 self.ssa.inst_jump(then_block, after_block, then_expr);
                 self.ssa.inst_jump(else_block, after_block, else_expr);
 
@@ -251,25 +551,256 @@ impl Generator<'_> {
 
                 Expr::Inst(self.ssa.inst_product(self.types, *block, fields))
             }
+            SemKind::BuildArray { elements } => {
+                let elements = elements
+                    .iter()
+                    .map(|value| self.generate_expression(block, *value, scope))
+                    .collect();
+
+                Expr::Inst(
+                    self.ssa
+                        .inst_array(*block, elements, self.semantic.types[sem]),
+                )
+            }
+            SemKind::RepeatArray { value, length } => {
+                let value = self.generate_expression(block, *value, scope);
+                self.repeat(*block, value, *length);
+
+                Expr::Inst(self.ssa.inst_array(
+                    *block,
+                    vec![value; *length as usize],
+                    self.semantic.types[sem],
+                ))
+            }
+            SemKind::BuildVec(array) => {
+                // The array literal is generated as the elements of the vec
+                // directly instead of building an array to copy from.
+                let elements = match &self.semantic.kinds[*array] {
+                    SemKind::BuildArray { elements } => elements
+                        .iter()
+                        .map(|value| self.generate_expression(block, *value, scope))
+                        .collect(),
+                    SemKind::RepeatArray { value, length } => {
+                        let value = self.generate_expression(block, *value, scope);
+                        self.repeat(*block, value, *length);
+                        vec![value; *length as usize]
+                    }
+                    _ => {
+                        let array_type = self.semantic.types[*array];
+                        let array = self.generate_expression(block, *array, scope);
+
+                        let Val::Value(&TypeData::Array { length, .. }) =
+                            self.types.get(array_type)
+                        else {
+                            panic!()
+                        };
+
+                        let elements = (0..length)
+                            .map(|index| {
+                                let index = Expr::Const(self.ssa.const_u32(index));
+                                let location = Location { line: 0, column: 0 };
+                                let element =
+                                    Expr::Inst(self.ssa.inst_load(*block, array, index, location));
+                                self.retain(*block, element);
+                                element
+                            })
+                            .collect();
+                        self.release(*block, array);
+
+                        elements
+                    }
+                };
+
+                Expr::Inst(
+                    self.ssa
+                        .inst_new_vec(*block, elements, self.semantic.types[sem]),
+                )
+            }
+            SemKind::Push { vec, value } => {
+                let vec = self.generate_expression(block, *vec, scope);
+                let value = self.generate_expression(block, *value, scope);
+
+                self.ssa.inst_push(*block, vec, value);
+                self.release(*block, vec);
+
+                Expr::Const(ConstSentinel::Unit.to_index())
+            }
+            SemKind::Pop { vec, token } => {
+                let vec = self.generate_expression(block, *vec, scope);
+                let location = self.location(*token);
+
+                // The popped element is given by the vec, it is not retained.
+                let element = Expr::Inst(self.ssa.inst_pop(*block, vec, location));
+                self.release(*block, vec);
+
+                element
+            }
             SemKind::ChainOpen {
                 statements,
                 expression,
             } => {
                 for statement in statements {
-                    self.generate_expression(block, *statement, scope);
+                    let statement = self.generate_expression(block, *statement, scope);
+                    self.release(*block, statement);
                 }
 
                 self.generate_expression(block, *expression, scope)
             }
             SemKind::ChainClosed { statements } => {
                 for statement in statements {
-                    self.generate_expression(block, *statement, scope);
+                    let statement = self.generate_expression(block, *statement, scope);
+                    self.release(*block, statement);
                 }
 
                 Expr::Const(ConstSentinel::Unit.to_index())
             }
         }
     }
+
+    fn generate_length(&mut self, block: Block, expr: Expr, type_: Type) -> Expr {
+        match self.types.get(type_) {
+            Val::Value(&TypeData::Array { length, .. }) => Expr::Const(self.ssa.const_u32(length)),
+            Val::Value(TypeData::Slice { .. } | TypeData::Vec { .. }) => {
+                Expr::Inst(self.ssa.inst_length(block, expr))
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Values holding vecs are retained and released recursively through
+    // products and arrays, slices only borrow their elements.
+    fn retain(&mut self, block: Block, expr: Expr) {
+        self.retain_or_release(block, expr, InstData::Retain);
+    }
+
+    fn release(&mut self, block: Block, expr: Expr) {
+        self.retain_or_release(block, expr, InstData::Release);
+    }
+
+    fn retain_or_release(&mut self, block: Block, expr: Expr, inst_data: fn(Expr) -> InstData) {
+        let type_ = self.ssa.expression_type(self.types, expr);
+
+        if !self.holds_vec(type_) {
+            return;
+        }
+
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => {
+                self.ssa.inst(block, inst_data(expr));
+            }
+            Val::Value(TypeData::Product { fields }) => {
+                for field in 0..fields.len() {
+                    let field = Expr::Inst(self.ssa.inst_field(block, expr, field as u32));
+                    self.retain_or_release(block, field, inst_data);
+                }
+            }
+            Val::Value(&TypeData::Array { length, .. }) => {
+                for index in 0..length {
+                    let index = Expr::Const(self.ssa.const_u32(index));
+                    let location = Location { line: 0, column: 0 };
+                    let element = Expr::Inst(self.ssa.inst_load(block, expr, index, location));
+                    self.retain_or_release(block, element, inst_data);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+        }
+    }
+
+    // Records of more than 16 values are passed by reference, so that calls
+    // do not copy them. Calls given a reference are not tail calls, as it
+    // points into the frame of the caller.
+    fn passed_by_reference(&self, type_: Type) -> bool {
+        matches!(self.types.get(type_), Val::Value(TypeData::Product { .. }))
+            && self.values(type_) > 16
+    }
+
+    // Number of scalars making up a value of the type.
+    fn values(&self, type_: Type) -> u32 {
+        match self.types.get(type_) {
+            Val::Sentinel(TypeSentinel::Unit) => 0,
+            Val::Value(TypeData::Product { fields }) => {
+                fields.iter().map(|(_, field)| self.values(*field)).sum()
+            }
+            Val::Value(&TypeData::Array { element, length }) => length * self.values(element),
+            Val::Value(TypeData::Slice { .. }) => 2,
+            Val::None | Val::Sentinel(_) | Val::Value(_) => 1,
+        }
+    }
+
+    fn holds_vec(&self, type_: Type) -> bool {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => true,
+            Val::Value(TypeData::Product { fields }) => {
+                fields.iter().any(|(_, field)| self.holds_vec(*field))
+            }
+            Val::Value(&TypeData::Array { element, .. }) => self.holds_vec(element),
+            Val::None | Val::Sentinel(_) | Val::Value(_) => false,
+        }
+    }
+
+    // A value used `length` times needs `length - 1` more references.
+    fn repeat(&mut self, block: Block, value: Expr, length: u32) {
+        match length {
+            0 => self.release(block, value),
+            _ => {
+                for _ in 1..length {
+                    self.retain(block, value);
+                }
+            }
+        }
+    }
+
+    // Release the last owned value, which goes out of scope.
+    fn release_owned(&mut self, block: Block) {
+        let value = self.owned.pop().unwrap();
+        self.release_owned_value(block, value);
+    }
+
+    // Release the owned values of the loop being exited, without removing
+    // them as the code following a `break` or `continue` still refers to
+    // them.
+    fn release_since(&mut self, block: Block, owned: usize) {
+        let values = self.owned[owned..].to_vec();
+        for value in values.into_iter().rev() {
+            self.release_owned_value(block, value);
+        }
+    }
+
+    // Owned pointers are the memory of mutable bindings, the value they hold
+    // is released.
+    fn release_owned_value(&mut self, block: Block, value: Expr) {
+        match self.types.get(self.ssa.expression_type(self.types, value)) {
+            Val::Value(&TypeData::Pointer { pointee }) => {
+                if self.holds_vec(pointee) {
+                    let value = Expr::Inst(self.ssa.inst_load_pointer(block, value));
+                    self.release(block, value);
+                }
+            }
+            _ => self.release(block, value),
+        }
+    }
+
+    fn location(&self, token: Token) -> Location {
+        let offset = self.tokens[token];
+        let line_start = self.source[..offset]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        Location {
+            line: self.source[..offset].matches('\n').count() as u32 + 1,
+            column: self.source[line_start..offset].chars().count() as u32 + 1,
+        }
+    }
+}
+
+// A block taking a never argument can't be reached, it gets a unit argument
+// instead so that backends don't have to deal with never.
+fn block_arg_type(type_: Type) -> Type {
+    match type_.sentinel() {
+        Some(TypeSentinel::Never) => TypeSentinel::Unit.to_index(),
+        _ => type_,
+    }
 }
 
 struct Scope<'a> {
@@ -281,11 +812,13 @@ struct Scope<'a> {
 
 impl Scope<'_> {
     fn is_mutable(&self, name: &str) -> bool {
-        self.mutable_bindings.contains(name)
-            || self
-                .parent
-                .map(|parent| parent.is_mutable(name))
-                .unwrap_or(false)
+        if self.bindings.contains_key(name) {
+            return self.mutable_bindings.contains(name);
+        }
+
+        self.parent
+            .map(|parent| parent.is_mutable(name))
+            .unwrap_or(false)
     }
 
     fn binding(&self, name: &str) -> Option<Expr> {
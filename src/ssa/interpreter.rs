@@ -0,0 +1,758 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use crate::{
+    key_vec::{KeyVec, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+};
+
+use super::*;
+
+// Why the program stopped before the end of `main`.
+#[derive(Debug)]
+pub enum InterpretError {
+    // A check failed, reported as the runtimes do.
+    Trap {
+        reason: &'static str,
+        path: String,
+        location: Location,
+    },
+    DivisionByZero,
+    // A slice viewed the array of a frame that returned.
+    UseOfFreedMemory,
+    // More instructions than the limit were executed, the program probably
+    // does not terminate.
+    StepLimit,
+    Output(io::Error),
+}
+
+impl Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpretError::Trap {
+                reason,
+                path,
+                location,
+            } => write!(
+                f,
+                "{reason} at {path}:{}:{}",
+                location.line, location.column
+            ),
+            InterpretError::DivisionByZero => write!(f, "division by zero"),
+            InterpretError::UseOfFreedMemory => write!(f, "use of freed memory"),
+            InterpretError::StepLimit => write!(f, "step limit reached"),
+            InterpretError::Output(error) => write!(f, "failed to write the output: {error}"),
+        }
+    }
+}
+
+// Execute `main` without compiling it, printing to `output`, as a reference
+// for the backends. At most `step_limit` instructions are executed.
+pub fn interpret(
+    types: &Types,
+    ssa: &Ssa,
+    output: &mut dyn Write,
+    step_limit: u64,
+) -> Result<(), InterpretError> {
+    let main = ssa
+        .blocks
+        .entries()
+        .find(|(_, block_data)| {
+            matches!(block_data, BlockData::Function { name, .. } if name == "main")
+        })
+        .expect("no main function")
+        .0;
+
+    let mut interpreter = Interpreter {
+        types,
+        ssa,
+        output,
+        step_limit,
+        objects: Vec::new(),
+        inst_offsets: KeyVec::from_vec(vec![0; ssa.insts.len()]),
+        arg_offsets: KeyVec::from_vec(vec![0; ssa.blocks.len()]),
+        frame_sizes: KeyVec::from_vec(vec![None; ssa.blocks.len()]),
+        stack: Vec::new(),
+    };
+
+    interpreter.run(main)
+}
+
+// Values are made of cells holding a scalar each: products and arrays are the
+// cells of their fields and elements, slices are an address and a length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    Undefined,
+    Uint32(u32),
+    Bool(bool),
+    Pointer(Address),
+    // Object holding a vec.
+    Vec(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Address {
+    object: usize,
+    offset: u32,
+}
+
+enum Object {
+    Memory(Vec<Scalar>),
+    // The elements are in a memory object.
+    Vec {
+        items: usize,
+        length: u32,
+        references: u32,
+    },
+}
+
+struct Frame {
+    block: Block,
+    position: usize,
+    // Memory object of the values of the function.
+    memory: usize,
+}
+
+struct Interpreter<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    output: &'a mut dyn Write,
+    step_limit: u64,
+    // Freed objects are `None`.
+    objects: Vec<Option<Object>>,
+    // Offsets of the values in the frame of their function, in cells, and the
+    // size of the frames, set on the first call of each function.
+    inst_offsets: KeyVec<InstSentinel, u32>,
+    arg_offsets: KeyVec<BlockSentinel, u32>,
+    frame_sizes: KeyVec<BlockSentinel, Option<u32>>,
+    stack: Vec<Frame>,
+}
+
+impl Interpreter<'_> {
+    fn run(&mut self, main: Block) -> Result<(), InterpretError> {
+        self.call(main, Vec::new());
+
+        let mut steps = 0;
+
+        while let Some(frame) = self.stack.last_mut() {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &self.ssa.blocks[frame.block]
+            else {
+                panic!()
+            };
+
+            let inst = insts[frame.position];
+            frame.position += 1;
+
+            if steps == self.step_limit {
+                return Err(InterpretError::StepLimit);
+            }
+            steps += 1;
+
+            self.execute(inst)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, inst: Inst) -> Result<(), InterpretError> {
+        let expr = Expr::Inst(inst);
+
+        match &self.ssa.insts[inst] {
+            InstData::Field(record, field) => {
+                let record_type = self.expr_type(*record);
+                let offset = self.field_offset(record_type, *field as usize) as usize;
+                let size = self.type_cells(self.expr_type(expr)) as usize;
+
+                let cells = self.cells(*record);
+                self.define(expr, cells[offset..offset + size].to_vec());
+            }
+            InstData::Record(fields, _) | InstData::Array(fields, _) => {
+                let cells = fields.iter().flat_map(|field| self.cells(*field)).collect();
+                self.define(expr, cells);
+            }
+            InstData::Length(base) => {
+                let length = self.length(*base);
+                self.define(expr, vec![Scalar::Uint32(length)]);
+            }
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let element_cells = self.type_cells(self.expr_type(expr));
+                let address = self.element_address(*base, *index, element_cells, location)?;
+
+                let cells = self.load(address, element_cells);
+                self.define(expr, cells);
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let cells = self.cells(*value);
+                let address = self.element_address(*base, *index, cells.len() as u32, location)?;
+
+                self.store(address, cells);
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            } => {
+                let element_cells = match self.types.get(*type_) {
+                    Val::Value(TypeData::Slice { element }) => self.type_cells(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let start = self.uint32(*start);
+                let end = self.uint32(*end);
+                if start > end || end > self.length(*base) {
+                    return Err(InterpretError::Trap {
+                        reason: "index out of bounds",
+                        path: self.ssa.path.clone(),
+                        location: *location,
+                    });
+                }
+
+                let address = self.base_address(*base)?;
+                let pointer = Address {
+                    object: address.object,
+                    offset: address.offset + start * element_cells,
+                };
+                self.define(
+                    expr,
+                    vec![Scalar::Pointer(pointer), Scalar::Uint32(end - start)],
+                );
+            }
+            InstData::NewVec(elements, _) => {
+                let items = elements
+                    .iter()
+                    .flat_map(|element| self.cells(*element))
+                    .collect();
+                let items = self.allocate(Object::Memory(items));
+
+                let vec = self.allocate(Object::Vec {
+                    items,
+                    length: elements.len() as u32,
+                    references: 1,
+                });
+                self.define(expr, vec![Scalar::Vec(vec)]);
+            }
+            InstData::Push { vec, value } => {
+                let cells = self.cells(*value);
+                let element_cells = cells.len() as u32;
+
+                let Object::Vec { items, length, .. } = self.vec(*vec) else {
+                    panic!()
+                };
+                *length += 1;
+                let address = Address {
+                    object: *items,
+                    offset: (*length - 1) * element_cells,
+                };
+
+                self.store(address, cells);
+            }
+            InstData::Pop { vec, location } => {
+                let element_cells = self.type_cells(self.expr_type(expr));
+
+                let Object::Vec { items, length, .. } = self.vec(*vec) else {
+                    panic!()
+                };
+                if *length == 0 {
+                    return Err(InterpretError::Trap {
+                        reason: "pop from empty vec",
+                        path: self.ssa.path.clone(),
+                        location: *location,
+                    });
+                }
+                *length -= 1;
+                let address = Address {
+                    object: *items,
+                    offset: *length * element_cells,
+                };
+
+                let cells = self.load(address, element_cells);
+                self.truncate(address);
+                self.define(expr, cells);
+            }
+            // The memory follows the pointer in the frame.
+            InstData::Alloca(_) => {
+                let mut address = self.slot_address(expr);
+                address.offset += 1;
+                self.define(expr, vec![Scalar::Pointer(address)]);
+            }
+            // The value is copied to the memory following the pointer, as for
+            // `Alloca`.
+            InstData::AddressOf(value, _) => {
+                let mut address = self.slot_address(expr);
+                address.offset += 1;
+                let cells = self.cells(*value);
+                self.store(address, cells);
+                self.define(expr, vec![Scalar::Pointer(address)]);
+            }
+            InstData::LoadPointer(pointer) => {
+                let address = self.pointer(*pointer);
+                let cells = self.load(address, self.type_cells(self.expr_type(expr)));
+                self.define(expr, cells);
+            }
+            InstData::StorePointer { pointer, value } => {
+                let address = self.pointer(*pointer);
+                let cells = self.cells(*value);
+                self.store(address, cells);
+            }
+            InstData::Retain(vec) => {
+                let Object::Vec { references, .. } = self.vec(*vec) else {
+                    panic!()
+                };
+                *references += 1;
+            }
+            InstData::Release(vec) => {
+                let Scalar::Vec(vec) = self.scalar(*vec) else {
+                    panic!()
+                };
+                self.release(vec);
+            }
+            InstData::Equal(lhs, rhs) => {
+                let equal = self.scalar(*lhs) == self.scalar(*rhs);
+                self.define(expr, vec![Scalar::Bool(equal)]);
+            }
+            InstData::Add(lhs, rhs) => {
+                let result = self.uint32(*lhs).wrapping_add(self.uint32(*rhs));
+                self.define(expr, vec![Scalar::Uint32(result)]);
+            }
+            InstData::Sub(lhs, rhs) => {
+                let result = self.uint32(*lhs).wrapping_sub(self.uint32(*rhs));
+                self.define(expr, vec![Scalar::Uint32(result)]);
+            }
+            InstData::Mul(lhs, rhs) => {
+                let result = self.uint32(*lhs).wrapping_mul(self.uint32(*rhs));
+                self.define(expr, vec![Scalar::Uint32(result)]);
+            }
+            InstData::Div(lhs, rhs) => {
+                let result = self
+                    .uint32(*lhs)
+                    .checked_div(self.uint32(*rhs))
+                    .ok_or(InterpretError::DivisionByZero)?;
+                self.define(expr, vec![Scalar::Uint32(result)]);
+            }
+            InstData::Call { function, argument } => {
+                let argument = self.cells(*argument);
+
+                match &self.ssa.blocks[*function] {
+                    BlockData::ExternFunction { name, .. } => {
+                        let result = self.call_extern(name, argument)?;
+                        self.define(expr, result);
+                    }
+                    BlockData::Function { .. } => self.call(*function, argument),
+                    BlockData::Block { .. } => panic!(),
+                }
+            }
+            InstData::Jump { block, argument } => {
+                let argument = self.cells(*argument);
+                self.jump(*block);
+                self.define(Expr::BlockArg(*block), argument);
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => {
+                let condition = match self.scalar(*condition) {
+                    Scalar::Bool(condition) => condition,
+                    Scalar::Uint32(condition) => condition != 0,
+                    _ => panic!(),
+                };
+                self.jump(if condition { *then } else { *else_ });
+            }
+            InstData::Return(value) => {
+                let cells = self.cells(*value);
+
+                let frame = self.stack.pop().unwrap();
+                self.objects[frame.memory] = None;
+
+                // The value of the call is defined in the frame of the
+                // caller.
+                if let Some(caller) = self.stack.last() {
+                    let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                        &self.ssa.blocks[caller.block]
+                    else {
+                        panic!()
+                    };
+
+                    self.define(Expr::Inst(insts[caller.position - 1]), cells);
+                }
+            }
+            InstData::TailCall { function, argument } => {
+                let argument = self.cells(*argument);
+
+                // The callee returns to the caller, in place of this frame.
+                let frame = self.stack.pop().unwrap();
+                self.objects[frame.memory] = None;
+                self.call(*function, argument);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, function: Block, argument: Vec<Scalar>) {
+        let size = self.frame_size(function);
+        let memory = self.allocate(Object::Memory(vec![Scalar::Undefined; size as usize]));
+
+        self.stack.push(Frame {
+            block: function,
+            position: 0,
+            memory,
+        });
+
+        self.define(Expr::BlockArg(function), argument);
+    }
+
+    // Extern functions behave as the ones of the runtime.
+    fn call_extern(
+        &mut self,
+        name: &str,
+        argument: Vec<Scalar>,
+    ) -> Result<Vec<Scalar>, InterpretError> {
+        match (name, argument.as_slice()) {
+            ("builtin_print", [Scalar::Uint32(value)]) => {
+                writeln!(self.output, "{value}").map_err(InterpretError::Output)?;
+                Ok(Vec::new())
+            }
+            ("builtin_alloc", [Scalar::Uint32(_)]) => {
+                let object = self.allocate(Object::Memory(Vec::new()));
+                let address = Address { object, offset: 0 };
+                Ok(vec![Scalar::Pointer(address)])
+            }
+            ("builtin_free", [Scalar::Pointer(address)]) => {
+                assert!(self.objects[address.object].take().is_some(), "double free");
+                Ok(Vec::new())
+            }
+            _ => panic!("extern function {name} is not known to the interpreter"),
+        }
+    }
+
+    fn jump(&mut self, block: Block) {
+        let frame = self.stack.last_mut().unwrap();
+        frame.block = block;
+        frame.position = 0;
+    }
+
+    fn allocate(&mut self, object: Object) -> usize {
+        self.objects.push(Some(object));
+        self.objects.len() - 1
+    }
+
+    fn release(&mut self, vec: usize) {
+        let Some(Object::Vec {
+            items, references, ..
+        }) = &mut self.objects[vec]
+        else {
+            panic!("use of a freed vec")
+        };
+
+        *references -= 1;
+        if *references != 0 {
+            return;
+        }
+
+        let items = *items;
+        self.objects[vec] = None;
+
+        let Some(Object::Memory(cells)) = self.objects[items].take() else {
+            panic!()
+        };
+
+        for cell in cells {
+            if let Scalar::Vec(vec) = cell {
+                self.release(vec);
+            }
+        }
+    }
+
+    fn vec(&mut self, vec: Expr) -> &mut Object {
+        let Scalar::Vec(vec) = self.scalar(vec) else {
+            panic!()
+        };
+
+        self.objects[vec].as_mut().expect("use of a freed vec")
+    }
+
+    fn load(&self, address: Address, size: u32) -> Vec<Scalar> {
+        let Some(Object::Memory(cells)) = &self.objects[address.object] else {
+            panic!("use of freed memory")
+        };
+
+        cells[address.offset as usize..(address.offset + size) as usize].to_vec()
+    }
+
+    // Memory objects grow as needed, for the elements of vecs.
+    fn store(&mut self, address: Address, value: Vec<Scalar>) {
+        let Some(Object::Memory(cells)) = &mut self.objects[address.object] else {
+            panic!("use of freed memory")
+        };
+
+        let end = address.offset as usize + value.len();
+        if cells.len() < end {
+            cells.resize(end, Scalar::Undefined);
+        }
+        cells[address.offset as usize..end].copy_from_slice(&value);
+    }
+
+    // Free the cells of a memory object from `address`, so that only the
+    // elements of a vec are released with it.
+    fn truncate(&mut self, address: Address) {
+        let Some(Object::Memory(cells)) = &mut self.objects[address.object] else {
+            panic!("use of freed memory")
+        };
+
+        cells.truncate(address.offset as usize);
+    }
+
+    // Set the value of an instruction or a block argument of the current
+    // frame.
+    fn define(&mut self, expr: Expr, cells: Vec<Scalar>) {
+        if !cells.is_empty() {
+            let address = self.slot_address(expr);
+            self.store(address, cells);
+        }
+    }
+
+    // Value of `expr` in the current frame. Arguments of blocks jumped to
+    // conditionally are undefined.
+    fn cells(&self, expr: Expr) -> Vec<Scalar> {
+        match expr {
+            Expr::Const(const_) => self.const_cells(const_),
+            Expr::Inst(_) | Expr::BlockArg(_) => self.load(
+                self.slot_address(expr),
+                self.type_cells(self.expr_type(expr)),
+            ),
+        }
+    }
+
+    fn const_cells(&self, const_: Const) -> Vec<Scalar> {
+        match self.ssa.consts.get(const_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                ConstSentinel::Unit => Vec::new(),
+                ConstSentinel::False => vec![Scalar::Bool(false)],
+                ConstSentinel::True => vec![Scalar::Bool(true)],
+            },
+            Val::Value(const_data) => match const_data {
+                ConstData::Uint32(value) => vec![Scalar::Uint32(*value)],
+                ConstData::Product(fields, _) => fields
+                    .iter()
+                    .flat_map(|field| self.const_cells(*field))
+                    .collect(),
+            },
+        }
+    }
+
+    fn scalar(&self, expr: Expr) -> Scalar {
+        if let Expr::Const(const_) = expr {
+            let [scalar] = self.const_cells(const_)[..] else {
+                panic!()
+            };
+            return scalar;
+        }
+
+        let address = self.slot_address(expr);
+        let Some(Object::Memory(cells)) = &self.objects[address.object] else {
+            panic!("use of freed memory")
+        };
+        cells[address.offset as usize]
+    }
+
+    fn uint32(&self, expr: Expr) -> u32 {
+        let Scalar::Uint32(value) = self.scalar(expr) else {
+            panic!()
+        };
+        value
+    }
+
+    fn pointer(&self, expr: Expr) -> Address {
+        let Scalar::Pointer(address) = self.scalar(expr) else {
+            panic!()
+        };
+        address
+    }
+
+    // Address of the slot of a value in the frame.
+    fn slot_address(&self, expr: Expr) -> Address {
+        let offset = match expr {
+            Expr::Inst(inst) => self.inst_offsets[inst],
+            Expr::BlockArg(block) => self.arg_offsets[block],
+            Expr::Const(_) => panic!(),
+        };
+
+        Address {
+            object: self.stack.last().unwrap().memory,
+            offset,
+        }
+    }
+
+    fn length(&mut self, base: Expr) -> u32 {
+        match self.types.get(self.expr_type(base)) {
+            Val::Value(&TypeData::Array { length, .. }) => length,
+            Val::Value(TypeData::Slice { .. }) => {
+                let Scalar::Uint32(length) = self.cells(base)[1] else {
+                    panic!()
+                };
+                length
+            }
+            Val::Value(TypeData::Vec { .. }) => {
+                let Object::Vec { length, .. } = self.vec(base) else {
+                    panic!()
+                };
+                *length
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Address of the first element of an array, a slice or a vec.
+    fn base_address(&mut self, base: Expr) -> Result<Address, InterpretError> {
+        let address = match self.types.get(self.expr_type(base)) {
+            Val::Value(TypeData::Array { .. }) => self.slot_address(base),
+            Val::Value(TypeData::Slice { .. }) => {
+                let Scalar::Pointer(address) = self.cells(base)[0] else {
+                    panic!()
+                };
+                address
+            }
+            Val::Value(TypeData::Vec { .. }) => {
+                let Object::Vec { items, .. } = self.vec(base) else {
+                    panic!()
+                };
+                Address {
+                    object: *items,
+                    offset: 0,
+                }
+            }
+            _ => panic!(),
+        };
+
+        if self.objects[address.object].is_none() {
+            return Err(InterpretError::UseOfFreedMemory);
+        }
+        Ok(address)
+    }
+
+    // Address of the element `index` of an array, a slice or a vec, checking
+    // the bounds.
+    fn element_address(
+        &mut self,
+        base: Expr,
+        index: Expr,
+        element_cells: u32,
+        location: &Location,
+    ) -> Result<Address, InterpretError> {
+        let index = self.uint32(index);
+        if index >= self.length(base) {
+            return Err(InterpretError::Trap {
+                reason: "index out of bounds",
+                path: self.ssa.path.clone(),
+                location: *location,
+            });
+        }
+
+        let address = self.base_address(base)?;
+        Ok(Address {
+            object: address.object,
+            offset: address.offset + index * element_cells,
+        })
+    }
+
+    // Give a slot in the frame to every value of the function, followed by
+    // the memory of `Alloca` and `AddressOf`.
+    fn frame_size(&mut self, function: Block) -> u32 {
+        if let Some(size) = self.frame_sizes[function] {
+            return size;
+        }
+
+        let mut size = 0;
+
+        for &block in Cfg::new(self.ssa, function).blocks() {
+            let (BlockData::Function { arg, insts, .. } | BlockData::Block { arg, insts }) =
+                &self.ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            self.arg_offsets[block] = size;
+            size += self.type_cells(*arg);
+
+            for inst in insts {
+                self.inst_offsets[*inst] = size;
+                size += self.type_cells(self.expr_type(Expr::Inst(*inst)));
+
+                match &self.ssa.insts[*inst] {
+                    InstData::Alloca(type_) => {
+                        let Val::Value(TypeData::Pointer { pointee }) = self.types.get(*type_)
+                        else {
+                            panic!()
+                        };
+                        size += self.type_cells(*pointee);
+                    }
+                    InstData::AddressOf(value, _) => {
+                        size += self.type_cells(self.expr_type(*value))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.frame_sizes[function] = Some(size);
+        size
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    // Number of cells of a value of type `type_`.
+    fn type_cells(&self, type_: Type) -> u32 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32
+                | TypeSentinel::Bool
+                | TypeSentinel::False
+                | TypeSentinel::True
+                | TypeSentinel::Pointer => 1,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => fields
+                    .iter()
+                    .map(|(_, field)| self.type_cells(*field))
+                    .sum(),
+                TypeData::Array { element, length } => self.type_cells(*element) * length,
+                TypeData::Slice { .. } => 2,
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 1,
+            },
+        }
+    }
+
+    fn field_offset(&self, type_: Type, field: usize) -> u32 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .map(|(_, field)| self.type_cells(*field))
+            .sum()
+    }
+}
@@ -1,12 +1,22 @@
+mod cfg;
 mod debug;
 mod generation;
+mod interpreter;
+pub mod opt;
+#[allow(clippy::module_inception)]
 mod ssa;
+mod text;
+mod verify;
 
 pub use self::{
+    cfg::{Cfg, Liveness, Loop},
     debug::debug,
     generation::generate,
+    interpreter::{InterpretError, interpret},
     ssa::{
         Block, BlockData, BlockSentinel, Blocks, Const, ConstData, ConstSentinel, Consts, Expr,
-        Inst, InstData, InstSentinel, Insts, Ssa,
+        Inst, InstData, InstSentinel, Insts, Location, Ssa,
     },
+    text::{ParseError, parse, print},
+    verify::{VerifyError, debug_verify, verify},
 };
@@ -0,0 +1,117 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::key_vec::{Sentinel, Val};
+
+// Compute the arithmetic and comparisons of constants, wrapping as the
+// backends do. Divisions by zero are left to trap at runtime.
+pub fn const_fold(_types: &mut Types, ssa: &mut Ssa) {
+    let mut constants = Constants::new(ssa);
+
+    // The folded instructions are left unused for `dead_code`.
+    let mut folded = HashSet::new();
+
+    loop {
+        let mut replacements = HashMap::new();
+
+        for inst in block_insts(ssa) {
+            if folded.contains(&inst) {
+                continue;
+            }
+
+            let (InstData::Equal(lhs, rhs)
+            | InstData::Add(lhs, rhs)
+            | InstData::Sub(lhs, rhs)
+            | InstData::Mul(lhs, rhs)
+            | InstData::Div(lhs, rhs)) = ssa.insts[inst]
+            else {
+                continue;
+            };
+
+            if let (Some(lhs), Some(rhs)) = (constant(ssa, lhs), constant(ssa, rhs))
+                && let Some(value) = fold(&ssa.insts[inst], lhs, rhs)
+            {
+                folded.insert(inst);
+                replacements.insert(Expr::Inst(inst), constants.expr(ssa, value));
+            }
+        }
+
+        if replacements.is_empty() {
+            return;
+        }
+
+        replace_uses(ssa, &replacements);
+    }
+}
+
+// The value of the arithmetic or comparison of two constants, if it can be
+// computed.
+pub(super) fn fold(inst_data: &InstData, lhs: Constant, rhs: Constant) -> Option<Constant> {
+    if let InstData::Equal(..) = inst_data {
+        return Some(Constant::Bool(lhs == rhs));
+    }
+
+    let (Constant::Uint32(lhs), Constant::Uint32(rhs)) = (lhs, rhs) else {
+        return None;
+    };
+
+    match inst_data {
+        InstData::Add(..) => Some(lhs.wrapping_add(rhs)),
+        InstData::Sub(..) => Some(lhs.wrapping_sub(rhs)),
+        InstData::Mul(..) => Some(lhs.wrapping_mul(rhs)),
+        InstData::Div(..) => lhs.checked_div(rhs),
+        _ => None,
+    }
+    .map(Constant::Uint32)
+}
+
+// The `u32` constants of the ssa by value, so that the folded values reuse
+// them instead of adding one constant per instruction.
+pub(super) struct Constants(HashMap<u32, Const>);
+
+impl Constants {
+    pub(super) fn new(ssa: &Ssa) -> Constants {
+        let mut constants = HashMap::new();
+        for (const_, const_data) in ssa.consts.entries() {
+            if let ConstData::Uint32(value) = const_data {
+                constants.entry(*value).or_insert(const_);
+            }
+        }
+        Constants(constants)
+    }
+
+    pub(super) fn expr(&mut self, ssa: &mut Ssa, constant: Constant) -> Expr {
+        match constant {
+            Constant::Uint32(value) => {
+                Expr::Const(*self.0.entry(value).or_insert_with(|| ssa.const_u32(value)))
+            }
+            Constant::Bool(value) => boolean(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Constant {
+    Uint32(u32),
+    Bool(bool),
+}
+
+pub(super) fn constant(ssa: &Ssa, expr: Expr) -> Option<Constant> {
+    let Expr::Const(const_) = expr else {
+        return None;
+    };
+
+    match ssa.consts.get(const_) {
+        Val::Sentinel(ConstSentinel::False) => Some(Constant::Bool(false)),
+        Val::Sentinel(ConstSentinel::True) => Some(Constant::Bool(true)),
+        Val::Value(&ConstData::Uint32(value)) => Some(Constant::Uint32(value)),
+        Val::None | Val::Sentinel(ConstSentinel::Unit) | Val::Value(ConstData::Product(..)) => None,
+    }
+}
+
+pub(super) fn boolean(value: bool) -> Expr {
+    match value {
+        false => Expr::Const(ConstSentinel::False.to_index()),
+        true => Expr::Const(ConstSentinel::True.to_index()),
+    }
+}
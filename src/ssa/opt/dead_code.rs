@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+
+use super::*;
+
+// Remove the instructions without side effects whose value is never used.
+pub fn dead_code(_types: &mut Types, ssa: &mut Ssa) {
+    loop {
+        let mut used = HashSet::new();
+        for inst in block_insts(ssa) {
+            for operand in ssa.insts[inst].operands() {
+                if let Expr::Inst(operand) = operand {
+                    used.insert(operand);
+                }
+            }
+        }
+
+        let mut removed = false;
+        let blocks = ssa
+            .blocks
+            .entries()
+            .map(|(block, _)| block)
+            .collect::<Vec<_>>();
+
+        for block in blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &mut ssa.blocks[block]
+            else {
+                continue;
+            };
+
+            let length = insts.len();
+            insts.retain(|inst| used.contains(inst) || !is_pure(&ssa.insts[*inst]));
+            removed |= insts.len() != length;
+        }
+
+        if !removed {
+            return;
+        }
+    }
+}
+
+// Loads and divisions can trap, they are kept.
+fn is_pure(inst_data: &InstData) -> bool {
+    matches!(
+        inst_data,
+        InstData::Field(..)
+            | InstData::Record(..)
+            | InstData::Array(..)
+            | InstData::Length(_)
+            | InstData::Alloca(_)
+            | InstData::AddressOf(..)
+            | InstData::LoadPointer(_)
+            | InstData::Equal(..)
+            | InstData::Add(..)
+            | InstData::Sub(..)
+            | InstData::Mul(..)
+    )
+}
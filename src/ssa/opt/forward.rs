@@ -0,0 +1,55 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::key_vec::Val;
+
+// Copy propagation: use the operands of records instead of their fields, and
+// the argument of the only jump to a block instead of its parameter.
+pub fn forward(_types: &mut Types, ssa: &mut Ssa) {
+    // The forwarded instructions and parameters are left unused, for
+    // `dead_code` and `unreachable` to remove.
+    let mut forwarded = HashSet::new();
+
+    loop {
+        let mut replacements = HashMap::new();
+
+        for inst in block_insts(ssa) {
+            if forwarded.contains(&Expr::Inst(inst)) {
+                continue;
+            }
+
+            let InstData::Field(base, field) = ssa.insts[inst] else {
+                continue;
+            };
+
+            let value = match base {
+                Expr::Inst(record) => match &ssa.insts[record] {
+                    InstData::Record(fields, _) => fields[field as usize],
+                    _ => continue,
+                },
+                Expr::Const(const_) => match ssa.consts.get(const_) {
+                    Val::Value(ConstData::Product(fields, _)) => {
+                        Expr::Const(fields[field as usize])
+                    }
+                    _ => continue,
+                },
+                Expr::BlockArg(_) => continue,
+            };
+
+            forwarded.insert(Expr::Inst(inst));
+            replacements.insert(Expr::Inst(inst), value);
+        }
+
+        for (_, block, argument) in single_jumps(ssa) {
+            if forwarded.insert(Expr::BlockArg(block)) {
+                replacements.insert(Expr::BlockArg(block), argument);
+            }
+        }
+
+        if replacements.is_empty() {
+            return;
+        }
+
+        replace_uses(ssa, &replacements);
+    }
+}
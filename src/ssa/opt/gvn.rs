@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::{
+    key_vec::Val,
+    semantic::{Type, TypeData, types_equals},
+};
+
+// Global value numbering: an instruction computing the same value as an
+// instruction dominating it is replaced by it. Instructions are numbered by
+// their kind and operands, walking the dominator tree so that only the
+// numbers of the dominating blocks are visible.
+pub fn gvn(types: &mut Types, ssa: &mut Ssa) {
+    // Constants of the same value are the same number.
+    let mut u32_consts = HashMap::new();
+    let mut consts = HashMap::new();
+    for (const_, const_data) in ssa.consts.entries() {
+        if let ConstData::Uint32(value) = const_data {
+            consts.insert(const_, *u32_consts.entry(*value).or_insert(const_));
+        }
+    }
+
+    // Records of equal types are the same number, whichever of the equal
+    // types they were given.
+    let mut record_types = HashMap::new();
+    let mut distinct_types = Vec::new();
+    for (_, inst_data) in ssa.insts.entries() {
+        if let InstData::Record(_, type_) = inst_data {
+            let distinct_type = match distinct_types
+                .iter()
+                .find(|distinct_type| types_equals(types, **distinct_type, *type_))
+            {
+                Some(distinct_type) => *distinct_type,
+                None => {
+                    distinct_types.push(*type_);
+                    *type_
+                }
+            };
+            record_types.insert(*type_, distinct_type);
+        }
+    }
+
+    let functions = ssa
+        .blocks
+        .entries()
+        .filter(|(_, block_data)| matches!(block_data, BlockData::Function { .. }))
+        .map(|(function, _)| function)
+        .collect::<Vec<_>>();
+
+    let mut replacements = HashMap::new();
+
+    for function in functions {
+        let cfg = Cfg::new(ssa, function);
+        let mut numbers = HashMap::new();
+
+        // Blocks to enter, and the numbers to forget when leaving them.
+        let mut stack = vec![Walk::Enter(function)];
+        while let Some(walk) = stack.pop() {
+            let block = match walk {
+                Walk::Enter(block) => block,
+                Walk::Leave(keys) => {
+                    for key in keys {
+                        numbers.remove(&key);
+                    }
+                    continue;
+                }
+            };
+
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            let mut keys = Vec::new();
+            for inst in insts {
+                let Some(key) = key(types, ssa, &consts, &record_types, &replacements, *inst)
+                else {
+                    continue;
+                };
+
+                match numbers.get(&key) {
+                    Some(number) => {
+                        replacements.insert(Expr::Inst(*inst), Expr::Inst(*number));
+                    }
+                    None => {
+                        numbers.insert(key.clone(), *inst);
+                        keys.push(key);
+                    }
+                }
+            }
+
+            stack.push(Walk::Leave(keys));
+            for dominated in cfg.dominated(block).iter().rev() {
+                stack.push(Walk::Enter(*dominated));
+            }
+        }
+    }
+
+    if replacements.is_empty() {
+        return;
+    }
+
+    // The replaced instructions are pure, they are removed right away so
+    // that what the pass saves shows in the instruction counts.
+    replace_uses(ssa, &replacements);
+    for block in reachable_blocks(ssa) {
+        insts_mut(ssa, block).retain(|inst| !replacements.contains_key(&Expr::Inst(*inst)));
+    }
+}
+
+enum Walk {
+    Enter(Block),
+    Leave(Vec<Key>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Field(Expr, u32),
+    Record(Vec<Expr>, Type),
+    Equal(Expr, Expr),
+    Add(Expr, Expr),
+    Sub(Expr, Expr),
+    Mul(Expr, Expr),
+    Div(Expr, Expr),
+}
+
+// What the value of a pure instruction depends on, with its operands replaced
+// by their number. Arrays are not numbered, as stores through slices change
+// them.
+fn key(
+    types: &Types,
+    ssa: &Ssa,
+    consts: &HashMap<Const, Const>,
+    record_types: &HashMap<Type, Type>,
+    replacements: &HashMap<Expr, Expr>,
+    inst: Inst,
+) -> Option<Key> {
+    let number = |expr: Expr| match expr {
+        Expr::Const(const_) => Expr::Const(consts.get(&const_).copied().unwrap_or(const_)),
+        _ => replacements.get(&expr).copied().unwrap_or(expr),
+    };
+
+    // The operands of commutative instructions are sorted.
+    let sorted = |lhs: Expr, rhs: Expr| {
+        let (lhs, rhs) = (number(lhs), number(rhs));
+        match rank(lhs) <= rank(rhs) {
+            true => (lhs, rhs),
+            false => (rhs, lhs),
+        }
+    };
+
+    let key = match &ssa.insts[inst] {
+        &InstData::Field(expr, field) => Key::Field(number(expr), field),
+        InstData::Record(fields, type_) => Key::Record(
+            fields.iter().copied().map(number).collect(),
+            record_types[type_],
+        ),
+        &InstData::Equal(lhs, rhs) => {
+            let (lhs, rhs) = sorted(lhs, rhs);
+            Key::Equal(lhs, rhs)
+        }
+        &InstData::Add(lhs, rhs) => {
+            let (lhs, rhs) = sorted(lhs, rhs);
+            Key::Add(lhs, rhs)
+        }
+        &InstData::Mul(lhs, rhs) => {
+            let (lhs, rhs) = sorted(lhs, rhs);
+            Key::Mul(lhs, rhs)
+        }
+        &InstData::Sub(lhs, rhs) => Key::Sub(number(lhs), number(rhs)),
+        // A division by zero traps at the first one, the others are not
+        // reached.
+        &InstData::Div(lhs, rhs) => Key::Div(number(lhs), number(rhs)),
+        _ => return None,
+    };
+
+    match key {
+        Key::Field(..) | Key::Record(..)
+            if holds_array(types, ssa.instruction_type(types, inst)) =>
+        {
+            None
+        }
+        key => Some(key),
+    }
+}
+
+fn rank(expr: Expr) -> (u8, u32) {
+    match expr {
+        Expr::Const(const_) => (0, const_.as_u32()),
+        Expr::Inst(inst) => (1, inst.as_u32()),
+        Expr::BlockArg(block) => (2, block.as_u32()),
+    }
+}
+
+pub(super) fn holds_array(types: &Types, type_: Type) -> bool {
+    match types.get(type_) {
+        Val::Value(TypeData::Array { .. }) => true,
+        Val::Value(TypeData::Product { fields }) => {
+            fields.iter().any(|(_, field)| holds_array(types, *field))
+        }
+        Val::None | Val::Sentinel(_) | Val::Value(_) => false,
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+// Functions with at most this many instructions, in all their blocks, are
+// inlined.
+const MAX_SIZE: usize = 16;
+// The calls of inlined bodies are inlined at the next round.
+const MAX_ROUNDS: usize = 3;
+
+// Replace the calls of small functions by a copy of their body, the returns
+// become jumps to a block holding what followed the call. Recursive functions
+// are never inlined, nor are functions making tail calls as they would return
+// from the caller.
+pub fn inline(_types: &mut Types, ssa: &mut Ssa) {
+    for _ in 0..MAX_ROUNDS {
+        let recursive = recursive_functions(ssa);
+
+        let mut owners = HashMap::new();
+        let mut calls = Vec::new();
+        for (block, block_data) in ssa.blocks.entries() {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) = block_data
+            else {
+                continue;
+            };
+
+            for inst in insts {
+                owners.insert(*inst, block);
+
+                if let InstData::Call { function, .. } = ssa.insts[*inst]
+                    && let BlockData::Function { .. } = ssa.blocks[function]
+                    && !recursive.contains(&function)
+                    && !makes_tail_calls(ssa, function)
+                    && function_size(ssa, function) <= MAX_SIZE
+                {
+                    calls.push(*inst);
+                }
+            }
+        }
+
+        if calls.is_empty() {
+            return;
+        }
+
+        for call in calls {
+            inline_call(ssa, &mut owners, call);
+        }
+    }
+}
+
+fn inline_call(ssa: &mut Ssa, owners: &mut HashMap<Inst, Block>, call: Inst) {
+    let InstData::Call { function, argument } = ssa.insts[call] else {
+        panic!()
+    };
+    let BlockData::Function { ret, .. } = ssa.blocks[function] else {
+        panic!()
+    };
+
+    // What follows the call moves to a block receiving the returned value.
+    let block = owners[&call];
+    let continuation = ssa.basic_block(ret);
+
+    let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+        &mut ssa.blocks[block]
+    else {
+        panic!()
+    };
+    let position = insts.iter().position(|inst| *inst == call).unwrap();
+    let after = insts.split_off(position + 1);
+    insts.pop();
+
+    for inst in &after {
+        owners.insert(*inst, continuation);
+    }
+    let BlockData::Block { insts, .. } = &mut ssa.blocks[continuation] else {
+        panic!()
+    };
+    *insts = after;
+
+    // The function itself becomes a block receiving the argument.
+    let callee_blocks = function_blocks(ssa, function);
+    let mut blocks = HashMap::new();
+    for callee_block in &callee_blocks {
+        let (BlockData::Function { arg, .. } | BlockData::Block { arg, .. }) =
+            ssa.blocks[*callee_block]
+        else {
+            panic!()
+        };
+        blocks.insert(*callee_block, ssa.basic_block(arg));
+    }
+
+    let mut copies = HashMap::new();
+    let mut copied = Vec::new();
+    for callee_block in &callee_blocks {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            ssa.blocks[*callee_block].clone()
+        else {
+            panic!()
+        };
+
+        for inst in insts {
+            let copy = ssa.inst(blocks[callee_block], ssa.insts[inst].clone());
+            copies.insert(inst, copy);
+            copied.push(copy);
+        }
+    }
+
+    for copy in copied {
+        let inst_data = &mut ssa.insts[copy];
+
+        for operand in inst_data.operands_mut() {
+            match operand {
+                Expr::Inst(inst) => *inst = copies[inst],
+                Expr::BlockArg(block) => *block = blocks[block],
+                Expr::Const(_) => {}
+            }
+        }
+
+        match inst_data {
+            InstData::Jump { block, .. } => *block = blocks[block],
+            InstData::JumpCondition { then, else_, .. } => {
+                *then = blocks[then];
+                *else_ = blocks[else_];
+            }
+            &mut InstData::Return(value) => {
+                *inst_data = InstData::Jump {
+                    block: continuation,
+                    argument: value,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let jump = ssa.inst_jump(block, blocks[&function], argument);
+    owners.insert(jump, block);
+
+    replace_uses(
+        ssa,
+        &HashMap::from([(Expr::Inst(call), Expr::BlockArg(continuation))]),
+    );
+}
+
+fn function_size(ssa: &Ssa, function: Block) -> usize {
+    function_blocks(ssa, function)
+        .into_iter()
+        .map(|block| match &ssa.blocks[block] {
+            BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts.len(),
+            BlockData::ExternFunction { .. } => 0,
+        })
+        .sum()
+}
+
+fn makes_tail_calls(ssa: &Ssa, function: Block) -> bool {
+    function_blocks(ssa, function).into_iter().any(|block| {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[block]
+        else {
+            panic!()
+        };
+
+        insts
+            .iter()
+            .any(|inst| matches!(ssa.insts[*inst], InstData::TailCall { .. }))
+    })
+}
+
+// Functions calling themselves, directly or through other functions.
+fn recursive_functions(ssa: &Ssa) -> HashSet<Block> {
+    let callees = callees(ssa);
+
+    callees
+        .keys()
+        .copied()
+        .filter(|function| calls(&callees, *function, *function))
+        .collect()
+}
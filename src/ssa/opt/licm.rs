@@ -0,0 +1,61 @@
+use super::{gvn::holds_array, *};
+use crate::key_vec::Val;
+
+// Loop-invariant code motion: move the instructions computing the same value
+// at every iteration of a loop before it. Only instructions that cannot trap
+// are moved, as the loop may not run them.
+pub fn licm(types: &mut Types, ssa: &mut Ssa) {
+    for_each_loop(ssa, |ssa, cfg, loop_| {
+        let mut loop_insts = loop_insts(ssa, loop_);
+
+        // Blocks come after their dominators, so the operands of an
+        // instruction are moved before it.
+        let mut moved = Vec::new();
+        for block in &loop_.blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[*block]
+            else {
+                panic!()
+            };
+
+            for inst in insts {
+                if can_move(types, ssa, *inst)
+                    && ssa.insts[*inst]
+                        .operands()
+                        .into_iter()
+                        .all(|operand| is_invariant(operand, loop_, &loop_insts))
+                {
+                    loop_insts.remove(inst);
+                    moved.push(*inst);
+                }
+            }
+        }
+
+        if moved.is_empty() {
+            return;
+        }
+
+        for block in &loop_.blocks {
+            insts_mut(ssa, *block).retain(|inst| !moved.contains(inst));
+        }
+
+        let preheader = preheader(ssa, cfg, loop_);
+        insert_before_terminator(ssa, preheader, &moved);
+    });
+}
+
+// Arithmetic, comparisons, divisions by a constant other than zero, and
+// records and fields without arrays. Arrays may be viewed by slices, that a
+// store through changes.
+fn can_move(types: &Types, ssa: &Ssa, inst: Inst) -> bool {
+    match ssa.insts[inst] {
+        InstData::Equal(..) | InstData::Add(..) | InstData::Sub(..) | InstData::Mul(..) => true,
+        InstData::Div(_, Expr::Const(divisor)) => {
+            !matches!(ssa.consts.get(divisor), Val::Value(ConstData::Uint32(0)))
+        }
+        InstData::Record(..) | InstData::Field(..) => {
+            !holds_array(types, ssa.instruction_type(types, inst))
+        }
+        _ => false,
+    }
+}
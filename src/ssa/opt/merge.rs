@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use super::*;
+
+// Append the blocks jumped to from a single place to the block jumping there,
+// like the ones left by `inline`.
+pub fn merge(types: &mut Types, ssa: &mut Ssa) {
+    let mut owners = HashMap::new();
+    for (block, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { insts, .. } | BlockData::Block { insts, .. } = block_data {
+            for inst in insts {
+                owners.insert(*inst, block);
+            }
+        }
+    }
+
+    let mut replacements = HashMap::new();
+
+    for (jump, target, argument) in single_jumps(ssa) {
+        let BlockData::Block { insts, .. } = &mut ssa.blocks[target] else {
+            panic!()
+        };
+        let moved = std::mem::take(insts);
+
+        let block = owners[&jump];
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &mut ssa.blocks[block]
+        else {
+            panic!()
+        };
+
+        // The jump is the terminator of its block.
+        insts.pop();
+        for inst in &moved {
+            owners.insert(*inst, block);
+        }
+        insts.extend(moved);
+
+        replacements.insert(Expr::BlockArg(target), argument);
+    }
+
+    replace_uses(ssa, &replacements);
+
+    // The merged blocks are left empty.
+    unreachable(types, ssa);
+}
@@ -0,0 +1,341 @@
+// Passes rewriting the ssa into an equivalent one that is smaller or faster,
+// each of them leaves a valid ssa behind.
+
+mod const_fold;
+mod dead_code;
+mod forward;
+mod gvn;
+mod inline;
+mod licm;
+mod merge;
+mod sccp;
+mod strength_reduction;
+mod tail_call;
+mod unreachable;
+
+use std::collections::{HashMap, HashSet};
+
+pub use self::{
+    const_fold::const_fold, dead_code::dead_code, forward::forward, gvn::gvn, inline::inline,
+    licm::licm, merge::merge, sccp::sccp, strength_reduction::strength_reduction,
+    tail_call::tail_call, unreachable::unreachable,
+};
+use super::*;
+use crate::semantic::Types;
+
+pub struct Pass {
+    pub name: &'static str,
+    pub run: fn(&mut Types, &mut Ssa),
+}
+
+// Passes in the order they are run by `optimize`.
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "inline",
+        run: inline,
+    },
+    Pass {
+        name: "merge",
+        run: merge,
+    },
+    Pass {
+        name: "tail_call",
+        run: tail_call,
+    },
+    Pass {
+        name: "forward",
+        run: forward,
+    },
+    Pass {
+        name: "sccp",
+        run: sccp,
+    },
+    Pass {
+        name: "gvn",
+        run: gvn,
+    },
+    Pass {
+        name: "licm",
+        run: licm,
+    },
+    Pass {
+        name: "strength_reduction",
+        run: strength_reduction,
+    },
+    Pass {
+        name: "const_fold",
+        run: const_fold,
+    },
+    Pass {
+        name: "dead_code",
+        run: dead_code,
+    },
+    Pass {
+        name: "unreachable",
+        run: unreachable,
+    },
+];
+
+// Run every pass, `after_pass` is given the ssa after each of them.
+pub fn optimize(
+    types: &mut Types,
+    ssa: &mut Ssa,
+    mut after_pass: impl FnMut(&'static str, &Types, &Ssa),
+) {
+    for pass in PASSES {
+        (pass.run)(types, ssa);
+        debug_verify(types, ssa);
+        after_pass(pass.name, types, ssa);
+    }
+}
+
+// Instructions of every function and block, in the order of the blocks.
+fn block_insts(ssa: &Ssa) -> Vec<Inst> {
+    let mut all_insts = Vec::new();
+    for (_, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { insts, .. } | BlockData::Block { insts, .. } = block_data {
+            all_insts.extend(insts);
+        }
+    }
+    all_insts
+}
+
+// Replace every use of the keys by their value, which may itself be
+// replaced.
+fn replace_uses(ssa: &mut Ssa, replacements: &HashMap<Expr, Expr>) {
+    if replacements.is_empty() {
+        return;
+    }
+
+    for inst in block_insts(ssa) {
+        for operand in ssa.insts[inst].operands_mut() {
+            while let Some(replacement) = replacements.get(operand) {
+                *operand = *replacement;
+            }
+        }
+    }
+}
+
+// Functions and the blocks they can jump to, extern functions have no blocks.
+fn reachable_blocks(ssa: &Ssa) -> HashSet<Block> {
+    let mut reachable = HashSet::new();
+    for (function, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { .. } = block_data {
+            reachable.extend(function_blocks(ssa, function));
+        }
+    }
+    reachable
+}
+
+// The function followed by the blocks it can jump to, in reverse postorder.
+fn function_blocks(ssa: &Ssa, function: Block) -> Vec<Block> {
+    Cfg::new(ssa, function).blocks().to_vec()
+}
+
+// Reachable blocks jumped to from a single place, with the jump and the
+// argument it gives. That place dominates the block so the argument is
+// defined there.
+fn single_jumps(ssa: &Ssa) -> Vec<(Inst, Block, Expr)> {
+    let mut jumps = HashMap::<Block, Vec<Option<(Inst, Expr)>>>::new();
+
+    for block in reachable_blocks(ssa) {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[block]
+        else {
+            continue;
+        };
+
+        for inst in insts {
+            match ssa.insts[*inst] {
+                InstData::Jump {
+                    block: target,
+                    argument,
+                } => jumps
+                    .entry(target)
+                    .or_default()
+                    .push(Some((*inst, argument))),
+                // The targets of conditional jumps have no argument.
+                InstData::JumpCondition { then, else_, .. } => {
+                    jumps.entry(then).or_default().push(None);
+                    jumps.entry(else_).or_default().push(None);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut single_jumps = jumps
+        .into_iter()
+        .filter_map(|(target, jumps)| match jumps.as_slice() {
+            [Some((jump, argument))] => Some((*jump, target, *argument)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    single_jumps.sort_by_key(|(_, target, _)| target.as_u32());
+    single_jumps
+}
+
+// Functions called by each function.
+fn callees(ssa: &Ssa) -> HashMap<Block, HashSet<Block>> {
+    let mut callees = HashMap::<Block, HashSet<Block>>::new();
+
+    for (function, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { .. } = block_data {
+            for block in function_blocks(ssa, function) {
+                let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                    &ssa.blocks[block]
+                else {
+                    panic!()
+                };
+
+                for inst in insts {
+                    if let InstData::Call {
+                        function: callee, ..
+                    }
+                    | InstData::TailCall {
+                        function: callee, ..
+                    } = ssa.insts[*inst]
+                    {
+                        callees.entry(function).or_default().insert(callee);
+                    }
+                }
+            }
+        }
+    }
+
+    callees
+}
+
+// Whether `caller` calls `callee`, directly or through other functions.
+fn calls(callees: &HashMap<Block, HashSet<Block>>, caller: Block, callee: Block) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![caller];
+
+    while let Some(function) = stack.pop() {
+        for &next in callees.get(&function).into_iter().flatten() {
+            if next == callee {
+                return true;
+            }
+
+            if visited.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+
+    false
+}
+
+fn insts_mut(ssa: &mut Ssa, block: Block) -> &mut Vec<Inst> {
+    let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+        &mut ssa.blocks[block]
+    else {
+        panic!()
+    };
+    insts
+}
+
+// Give every loop to `f`, inner loops first so that what leaves them can
+// leave the loops around them too. The cfg is computed again for each loop,
+// as `f` may add blocks.
+fn for_each_loop(ssa: &mut Ssa, mut f: impl FnMut(&mut Ssa, &Cfg, &Loop)) {
+    let functions = ssa
+        .blocks
+        .entries()
+        .filter(|(_, block_data)| matches!(block_data, BlockData::Function { .. }))
+        .map(|(function, _)| function)
+        .collect::<Vec<_>>();
+
+    for function in functions {
+        let mut done = HashSet::new();
+
+        loop {
+            let cfg = Cfg::new(ssa, function);
+            let Some(loop_) = cfg
+                .loops()
+                .into_iter()
+                .rev()
+                .find(|loop_| !done.contains(&loop_.header))
+            else {
+                break;
+            };
+
+            done.insert(loop_.header);
+            f(ssa, &cfg, &loop_);
+        }
+    }
+}
+
+// The block every path entering the loop goes through last. A new block is
+// made when the loop is entered from several blocks or by a conditional jump.
+fn preheader(ssa: &mut Ssa, cfg: &Cfg, loop_: &Loop) -> Block {
+    let mut entries = cfg
+        .predecessors(loop_.header)
+        .iter()
+        .copied()
+        .filter(|block| !loop_.blocks.contains(block))
+        .collect::<Vec<_>>();
+    entries.dedup();
+
+    if let [entry] = entries[..]
+        && let Some(&terminator) = insts_mut(ssa, entry).last()
+        && let InstData::Jump { .. } = ssa.insts[terminator]
+    {
+        return entry;
+    }
+
+    let BlockData::Block { arg, .. } = ssa.blocks[loop_.header] else {
+        panic!()
+    };
+    let preheader = ssa.basic_block(arg);
+    ssa.inst_jump(preheader, loop_.header, Expr::BlockArg(preheader));
+
+    for entry in entries {
+        let terminator = *insts_mut(ssa, entry).last().unwrap();
+        match &mut ssa.insts[terminator] {
+            InstData::Jump { block, .. } => *block = preheader,
+            InstData::JumpCondition { then, else_, .. } => {
+                for target in [then, else_] {
+                    if *target == loop_.header {
+                        *target = preheader;
+                    }
+                }
+            }
+            _ => panic!(),
+        }
+    }
+
+    preheader
+}
+
+// Insert the instructions before the terminator of the block.
+fn insert_before_terminator(ssa: &mut Ssa, block: Block, new_insts: &[Inst]) {
+    let insts = insts_mut(ssa, block);
+    let terminator = insts.pop().unwrap();
+    insts.extend(new_insts);
+    insts.push(terminator);
+}
+
+// Instructions of the blocks of the loop.
+fn loop_insts(ssa: &Ssa, loop_: &Loop) -> HashSet<Inst> {
+    let mut loop_insts = HashSet::new();
+    for block in &loop_.blocks {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+        loop_insts.extend(insts);
+    }
+    loop_insts
+}
+
+// Whether the value is the same at every iteration of the loop, when
+// `loop_insts` are the instructions computed in the loop.
+fn is_invariant(expr: Expr, loop_: &Loop, loop_insts: &HashSet<Inst>) -> bool {
+    match expr {
+        Expr::Const(_) => true,
+        Expr::Inst(inst) => !loop_insts.contains(&inst),
+        Expr::BlockArg(block) => !loop_.blocks.contains(&block),
+    }
+}
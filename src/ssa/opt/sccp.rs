@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    const_fold::{Constant, Constants, constant, fold},
+    *,
+};
+use crate::{
+    key_vec::{Sentinel, Val},
+    semantic::TypeSentinel,
+};
+
+// Sparse conditional constant propagation: values are assumed constant until
+// a block that can run shows otherwise, and the targets of a conditional jump
+// on a constant are not both reached. The constants flow through the
+// arguments of blocks, conditional jumps on a constant become jumps and the
+// blocks no jump reaches anymore are removed.
+pub fn sccp(types: &mut Types, ssa: &mut Ssa) {
+    let functions = ssa
+        .blocks
+        .entries()
+        .filter(|(_, block_data)| matches!(block_data, BlockData::Function { .. }))
+        .map(|(function, _)| function)
+        .collect::<Vec<_>>();
+
+    let mut constants = Vec::new();
+    let mut jumps = Vec::new();
+
+    for function in functions {
+        let cfg = Cfg::new(ssa, function);
+        let solution = solve(types, ssa, &cfg);
+
+        for block in cfg.blocks() {
+            if !solution.executable.contains(block) {
+                continue;
+            }
+
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[*block]
+            else {
+                panic!()
+            };
+
+            let values = insts
+                .iter()
+                .map(|inst| Expr::Inst(*inst))
+                .chain([Expr::BlockArg(*block)]);
+            for expr in values {
+                if let Value::Constant(value) = solution.value(ssa, expr) {
+                    constants.push((expr, value));
+                }
+            }
+
+            for inst in insts {
+                if let InstData::JumpCondition {
+                    condition,
+                    then,
+                    else_,
+                } = ssa.insts[*inst]
+                    && let Value::Constant(condition) = solution.value(ssa, condition)
+                {
+                    jumps.push((*inst, if is_true(condition) { then } else { else_ }));
+                }
+            }
+        }
+    }
+
+    // The computed instructions are left unused for `dead_code`.
+    let mut interned = Constants::new(ssa);
+    let mut replacements = HashMap::new();
+    for (expr, value) in constants {
+        replacements.insert(expr, interned.expr(ssa, value));
+    }
+    replace_uses(ssa, &replacements);
+
+    for &(jump, target) in &jumps {
+        ssa.insts[jump] = InstData::Jump {
+            block: target,
+            argument: Expr::Const(ConstSentinel::Unit.to_index()),
+        };
+    }
+
+    // The folded jumps leave blocks jumped to from a single place, and blocks
+    // no jump reaches that `merge` removes.
+    if !jumps.is_empty() {
+        merge(types, ssa);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Value {
+    // Not computed by the blocks reached so far.
+    Unknown,
+    Constant(Constant),
+    Varying,
+}
+
+impl Value {
+    fn meet(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Unknown, value) | (value, Value::Unknown) => value,
+            (Value::Constant(lhs), Value::Constant(rhs)) if lhs == rhs => self,
+            _ => Value::Varying,
+        }
+    }
+}
+
+struct Solution {
+    // Blocks reached by jumps that can be taken.
+    executable: HashSet<Block>,
+    // Values of the instructions and block arguments.
+    values: HashMap<Expr, Value>,
+    // Instructions and block arguments of the `true` and `false` types, their
+    // value is known from their type alone.
+    singletons: HashSet<Expr>,
+}
+
+impl Solution {
+    // Booleans are known from the `true` and `false` constants and types, and
+    // the comparisons of constants.
+    fn value(&self, ssa: &Ssa, expr: Expr) -> Value {
+        match expr {
+            Expr::Const(_) => constant(ssa, expr).map_or(Value::Varying, Value::Constant),
+            Expr::Inst(_) | Expr::BlockArg(_) => {
+                self.values.get(&expr).copied().unwrap_or(Value::Unknown)
+            }
+        }
+    }
+
+    // Lower the value of the instruction or block argument, whether it
+    // changed.
+    fn update(&mut self, expr: Expr, value: Value) -> bool {
+        if self.singletons.contains(&expr) {
+            return false;
+        }
+
+        let old = self.values.get(&expr).copied().unwrap_or(Value::Unknown);
+        let new = old.meet(value);
+        self.values.insert(expr, new);
+        old != new
+    }
+}
+
+// Solved forwards until nothing changes, in reverse postorder so that the
+// instructions are mostly computed before their uses.
+fn solve(types: &Types, ssa: &Ssa, cfg: &Cfg) -> Solution {
+    let function = cfg.function();
+    let mut solution = Solution {
+        executable: HashSet::from([function]),
+        values: HashMap::from([(Expr::BlockArg(function), Value::Varying)]),
+        singletons: HashSet::new(),
+    };
+
+    for block in cfg.blocks() {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+
+        let exprs = insts
+            .iter()
+            .map(|inst| Expr::Inst(*inst))
+            .chain([Expr::BlockArg(*block)]);
+        for expr in exprs {
+            let value = match types.get(ssa.expression_type(types, expr)) {
+                Val::Sentinel(TypeSentinel::True) => true,
+                Val::Sentinel(TypeSentinel::False) => false,
+                Val::None | Val::Sentinel(_) | Val::Value(_) => continue,
+            };
+            solution
+                .values
+                .insert(expr, Value::Constant(Constant::Bool(value)));
+            solution.singletons.insert(expr);
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for block in cfg.blocks() {
+            if !solution.executable.contains(block) {
+                continue;
+            }
+
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[*block]
+            else {
+                panic!()
+            };
+
+            for inst in insts {
+                match ssa.insts[*inst] {
+                    InstData::Jump {
+                        block: target,
+                        argument,
+                    } => {
+                        let argument = solution.value(ssa, argument);
+                        changed |= solution.executable.insert(target);
+                        changed |= solution.update(Expr::BlockArg(target), argument);
+                    }
+                    InstData::JumpCondition {
+                        condition,
+                        then,
+                        else_,
+                    } => {
+                        let targets = match solution.value(ssa, condition) {
+                            Value::Unknown => vec![],
+                            Value::Constant(condition) if is_true(condition) => vec![then],
+                            Value::Constant(_) => vec![else_],
+                            Value::Varying => vec![then, else_],
+                        };
+
+                        // Conditional jumps give no argument to their targets.
+                        for target in targets {
+                            changed |= solution.executable.insert(target);
+                            changed |= solution.update(Expr::BlockArg(target), Value::Varying);
+                        }
+                    }
+                    ref inst_data => {
+                        let value = evaluate(ssa, &solution, inst_data);
+                        changed |= solution.update(Expr::Inst(*inst), value);
+                    }
+                }
+            }
+        }
+    }
+
+    solution
+}
+
+// Conditions of type `u32` are true when they are not zero.
+fn is_true(condition: Constant) -> bool {
+    match condition {
+        Constant::Bool(value) => value,
+        Constant::Uint32(value) => value != 0,
+    }
+}
+
+// Divisions by zero are left to trap at runtime.
+fn evaluate(ssa: &Ssa, solution: &Solution, inst_data: &InstData) -> Value {
+    let (InstData::Equal(lhs, rhs)
+    | InstData::Add(lhs, rhs)
+    | InstData::Sub(lhs, rhs)
+    | InstData::Mul(lhs, rhs)
+    | InstData::Div(lhs, rhs)) = *inst_data
+    else {
+        return Value::Varying;
+    };
+
+    match (solution.value(ssa, lhs), solution.value(ssa, rhs)) {
+        (Value::Constant(lhs), Value::Constant(rhs)) => {
+            fold(inst_data, lhs, rhs).map_or(Value::Varying, Value::Constant)
+        }
+        (Value::Unknown, _) | (_, Value::Unknown) => Value::Unknown,
+        _ => Value::Varying,
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::{
+    key_vec::{Sentinel, Val},
+    semantic::{TypeData, TypeSentinel},
+};
+
+// Induction variable strength reduction: when every store of a loop to a
+// mutable variable adds or subtracts an invariant step to it, the products of
+// the variable by an invariant factor are kept in a second variable. It is
+// computed once before the loop and updated after each store by adding the
+// step times the factor, so the loop loads it instead of multiplying. The
+// arguments of the header of a loop, or their fields, are induction variables
+// too when every jump back to the header adds or subtracts a step to them.
+pub fn strength_reduction(types: &mut Types, ssa: &mut Ssa) {
+    for_each_loop(ssa, |ssa, cfg, loop_| {
+        reduce_variables(types, ssa, cfg, loop_);
+    });
+    for_each_loop(ssa, |ssa, cfg, loop_| {
+        reduce_arguments(types, ssa, cfg, loop_);
+    });
+}
+
+fn reduce_variables(types: &mut Types, ssa: &mut Ssa, cfg: &Cfg, loop_: &Loop) {
+    let loop_insts = loop_insts(ssa, loop_);
+    let variables = variables(types, ssa, cfg)
+        .into_iter()
+        .filter(|variable| !loop_insts.contains(variable))
+        .collect::<HashSet<_>>();
+
+    // The stores to each variable with their step, `None` when the
+    // variable is not an induction variable.
+    let mut steps = HashMap::<Inst, Vec<(Inst, Option<Step>)>>::new();
+    // The multiplications of each variable by each factor, with the load
+    // of the variable.
+    let mut products = HashMap::<(Inst, Expr), Vec<(Inst, Inst)>>::new();
+
+    for block in &loop_.blocks {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+
+        for (position, inst) in insts.iter().enumerate() {
+            match ssa.insts[*inst] {
+                InstData::StorePointer {
+                    pointer: Expr::Inst(variable),
+                    value,
+                } if variables.contains(&variable) => {
+                    let step = step(ssa, &insts[..position], variable, value)
+                        .filter(|step| is_invariant(step.value, loop_, &loop_insts));
+                    steps.entry(variable).or_default().push((*inst, step));
+                }
+                InstData::Mul(lhs, rhs) => {
+                    for (load, factor) in [(lhs, rhs), (rhs, lhs)] {
+                        if let Expr::Inst(load) = load
+                            && loop_insts.contains(&load)
+                            && let InstData::LoadPointer(Expr::Inst(variable)) = ssa.insts[load]
+                            && variables.contains(&variable)
+                            && is_invariant(factor, loop_, &loop_insts)
+                        {
+                            products
+                                .entry((variable, factor))
+                                .or_default()
+                                .push((*inst, load));
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut products = products
+        .into_iter()
+        .filter(|((variable, _), _)| {
+            steps
+                .get(variable)
+                .is_some_and(|steps| steps.iter().all(|(_, step)| step.is_some()))
+        })
+        .collect::<Vec<_>>();
+    products.sort_by_key(|((variable, _), multiplications)| {
+        (variable.as_u32(), multiplications[0].0.as_u32())
+    });
+
+    if products.is_empty() {
+        return;
+    }
+
+    let preheader = preheader(ssa, cfg, loop_);
+
+    for ((variable, factor), multiplications) in products {
+        let InstData::Alloca(pointer_type) = ssa.insts[variable] else {
+            panic!()
+        };
+        let product = ssa.insts.push(InstData::Alloca(pointer_type));
+        insert_after(ssa, cfg, variable, &[product]);
+        let product = Expr::Inst(product);
+
+        let initial = ssa.insts.push(InstData::LoadPointer(Expr::Inst(variable)));
+        let start = ssa.insts.push(InstData::Mul(Expr::Inst(initial), factor));
+        let store = ssa.insts.push(InstData::StorePointer {
+            pointer: product,
+            value: Expr::Inst(start),
+        });
+        insert_before_terminator(ssa, preheader, &[initial, start, store]);
+
+        for &(store, step) in &steps[&variable] {
+            let Some(step) = step else { panic!() };
+
+            let increment = ssa.insts.push(InstData::Mul(step.value, factor));
+            insert_before_terminator(ssa, preheader, &[increment]);
+
+            let old = ssa.insts.push(InstData::LoadPointer(product));
+            let new = ssa.insts.push(match step.subtracted {
+                false => InstData::Add(Expr::Inst(old), Expr::Inst(increment)),
+                true => InstData::Sub(Expr::Inst(old), Expr::Inst(increment)),
+            });
+            let update = ssa.insts.push(InstData::StorePointer {
+                pointer: product,
+                value: Expr::Inst(new),
+            });
+            insert_after(ssa, cfg, store, &[old, new, update]);
+        }
+
+        // The multiplications are removed so that the loops around this
+        // one do not reduce them again.
+        let mut replacements = HashMap::new();
+        for (multiplication, load) in multiplications {
+            let loaded = ssa.insts.push(InstData::LoadPointer(product));
+            insert_after(ssa, cfg, load, &[loaded]);
+            replacements.insert(Expr::Inst(multiplication), Expr::Inst(loaded));
+        }
+        for block in &loop_.blocks {
+            insts_mut(ssa, *block).retain(|inst| !replacements.contains_key(&Expr::Inst(*inst)));
+        }
+        replace_uses(ssa, &replacements);
+    }
+}
+
+fn reduce_arguments(types: &mut Types, ssa: &mut Ssa, cfg: &Cfg, loop_: &Loop) {
+    let header = loop_.header;
+    let BlockData::Block { arg, .. } = ssa.blocks[header] else {
+        return;
+    };
+    let loop_insts = loop_insts(ssa, loop_);
+
+    // The argument itself when it is a `u32`, or its fields of type `u32`.
+    let variables = match types.get(arg) {
+        Val::Sentinel(TypeSentinel::Uint32) => vec![None],
+        Val::Value(TypeData::Product { fields }) => (0..fields.len() as u32)
+            .filter(|field| fields[*field as usize].1.sentinel() == Some(TypeSentinel::Uint32))
+            .map(Some)
+            .collect(),
+        Val::None | Val::Sentinel(_) | Val::Value(_) => return,
+    };
+
+    // The jumps back to the header with the value they give to the argument.
+    let mut jumps = Vec::new();
+    for block in &loop_.blocks {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+
+        if let Some(&jump) = insts.last()
+            && let InstData::Jump {
+                block: target,
+                argument,
+            } = ssa.insts[jump]
+            && target == header
+        {
+            jumps.push((*block, argument));
+        }
+    }
+
+    // The step given to each variable by each jump, `None` when the variable
+    // is not an induction variable.
+    let steps = variables
+        .iter()
+        .map(|&field| {
+            let steps = jumps
+                .iter()
+                .map(|&(block, argument)| {
+                    let value = match field {
+                        None => argument,
+                        Some(field) => match argument {
+                            Expr::Inst(record) => match &ssa.insts[record] {
+                                InstData::Record(fields, _) => fields[field as usize],
+                                _ => return None,
+                            },
+                            Expr::Const(_) | Expr::BlockArg(_) => return None,
+                        },
+                    };
+                    argument_step(ssa, header, field, value)
+                        .filter(|step| is_invariant(step.value, loop_, &loop_insts))
+                        .map(|step| (block, step))
+                })
+                .collect::<Option<Vec<_>>>();
+            (field, steps)
+        })
+        .filter_map(|(field, steps)| Some((field, steps?)))
+        .collect::<HashMap<_, _>>();
+
+    // The multiplications of each induction variable by each factor.
+    let mut products = HashMap::<(Option<u32>, Expr), Vec<Inst>>::new();
+    for block in &loop_.blocks {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+
+        for inst in insts {
+            let InstData::Mul(lhs, rhs) = ssa.insts[*inst] else {
+                continue;
+            };
+
+            for (variable, factor) in [(lhs, rhs), (rhs, lhs)] {
+                if let Some(field) = argument_field(ssa, header, variable)
+                    && steps.contains_key(&field)
+                    && is_invariant(factor, loop_, &loop_insts)
+                {
+                    products.entry((field, factor)).or_default().push(*inst);
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut products = products.into_iter().collect::<Vec<_>>();
+    products.sort_by_key(|(_, multiplications)| multiplications[0].as_u32());
+
+    if products.is_empty() {
+        return;
+    }
+
+    let preheader = preheader(ssa, cfg, loop_);
+    let jump = *insts_mut(ssa, preheader).last().unwrap();
+    let InstData::Jump {
+        argument: initial_argument,
+        ..
+    } = ssa.insts[jump]
+    else {
+        panic!()
+    };
+
+    for ((field, factor), multiplications) in products {
+        let pointer_type = types.push(TypeData::Pointer {
+            pointee: TypeSentinel::Uint32.to_index(),
+        });
+        let product = ssa.insts.push(InstData::Alloca(pointer_type));
+        insts_mut(ssa, cfg.function()).insert(0, product);
+        let product = Expr::Inst(product);
+
+        let mut start_insts = Vec::new();
+        let initial = match field {
+            None => initial_argument,
+            Some(field) => {
+                let initial = ssa.insts.push(InstData::Field(initial_argument, field));
+                start_insts.push(initial);
+                Expr::Inst(initial)
+            }
+        };
+        let start = ssa.insts.push(InstData::Mul(initial, factor));
+        let store = ssa.insts.push(InstData::StorePointer {
+            pointer: product,
+            value: Expr::Inst(start),
+        });
+        start_insts.extend([start, store]);
+        insert_before_terminator(ssa, preheader, &start_insts);
+
+        for &(block, step) in &steps[&field] {
+            let increment = ssa.insts.push(InstData::Mul(step.value, factor));
+            insert_before_terminator(ssa, preheader, &[increment]);
+
+            let old = ssa.insts.push(InstData::LoadPointer(product));
+            let new = ssa.insts.push(match step.subtracted {
+                false => InstData::Add(Expr::Inst(old), Expr::Inst(increment)),
+                true => InstData::Sub(Expr::Inst(old), Expr::Inst(increment)),
+            });
+            let update = ssa.insts.push(InstData::StorePointer {
+                pointer: product,
+                value: Expr::Inst(new),
+            });
+            insert_before_terminator(ssa, block, &[old, new, update]);
+        }
+
+        // The multiplications become loads, the loops around this one do not
+        // reduce them again.
+        for multiplication in multiplications {
+            ssa.insts[multiplication] = InstData::LoadPointer(product);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Step {
+    value: Expr,
+    subtracted: bool,
+}
+
+// Allocas of a `u32` only ever loaded and stored to, no other instruction can
+// change them.
+fn variables(types: &Types, ssa: &Ssa, cfg: &Cfg) -> HashSet<Inst> {
+    let mut variables = HashSet::new();
+    let mut escaping = HashSet::new();
+
+    for block in cfg.blocks() {
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &ssa.blocks[*block]
+        else {
+            panic!()
+        };
+
+        for inst in insts {
+            match &ssa.insts[*inst] {
+                InstData::Alloca(type_) => {
+                    if let Val::Value(TypeData::Pointer { pointee }) = types.get(*type_)
+                        && pointee.sentinel() == Some(TypeSentinel::Uint32)
+                    {
+                        variables.insert(*inst);
+                    }
+                }
+                InstData::LoadPointer(_) => {}
+                InstData::StorePointer { value, .. } => {
+                    escaping.insert(*value);
+                }
+                inst_data => escaping.extend(inst_data.operands()),
+            }
+        }
+    }
+
+    variables.retain(|variable| !escaping.contains(&Expr::Inst(*variable)));
+    variables
+}
+
+// The step of a value stored to the variable, when it is the value loaded
+// from the variable since its last store, plus or minus the step.
+fn step(ssa: &Ssa, before: &[Inst], variable: Inst, value: Expr) -> Option<Step> {
+    let Expr::Inst(value) = value else {
+        return None;
+    };
+
+    let (loaded, step) = match ssa.insts[value] {
+        InstData::Add(Expr::Inst(lhs), rhs) if is_load(ssa, lhs, variable) => (lhs, rhs),
+        InstData::Add(lhs, Expr::Inst(rhs)) if is_load(ssa, rhs, variable) => (rhs, lhs),
+        InstData::Sub(Expr::Inst(lhs), rhs) if is_load(ssa, lhs, variable) => (lhs, rhs),
+        _ => return None,
+    };
+
+    let position = before.iter().position(|inst| *inst == loaded)?;
+    let stored = before[position..].iter().any(|inst| {
+        matches!(
+            ssa.insts[*inst],
+            InstData::StorePointer { pointer, .. } if pointer == Expr::Inst(variable)
+        )
+    });
+
+    (!stored).then_some(Step {
+        value: step,
+        subtracted: matches!(ssa.insts[value], InstData::Sub(..)),
+    })
+}
+
+// The argument of the header, `None`, or the field of it the expression is.
+fn argument_field(ssa: &Ssa, header: Block, expr: Expr) -> Option<Option<u32>> {
+    match expr {
+        Expr::BlockArg(block) if block == header => Some(None),
+        Expr::Inst(inst) => match ssa.insts[inst] {
+            InstData::Field(Expr::BlockArg(block), field) if block == header => Some(Some(field)),
+            _ => None,
+        },
+        Expr::Const(_) | Expr::BlockArg(_) => None,
+    }
+}
+
+// The step of the value given back to the argument or its field, when it is
+// the argument or the field plus or minus the step.
+fn argument_step(ssa: &Ssa, header: Block, field: Option<u32>, value: Expr) -> Option<Step> {
+    let is_variable = |expr| argument_field(ssa, header, expr) == Some(field);
+    let Expr::Inst(value) = value else {
+        return None;
+    };
+
+    let (step, subtracted) = match ssa.insts[value] {
+        InstData::Add(lhs, rhs) if is_variable(lhs) => (rhs, false),
+        InstData::Add(lhs, rhs) if is_variable(rhs) => (lhs, false),
+        InstData::Sub(lhs, rhs) if is_variable(lhs) => (rhs, true),
+        _ => return None,
+    };
+
+    Some(Step {
+        value: step,
+        subtracted,
+    })
+}
+
+fn is_load(ssa: &Ssa, inst: Inst, variable: Inst) -> bool {
+    matches!(ssa.insts[inst], InstData::LoadPointer(pointer) if pointer == Expr::Inst(variable))
+}
+
+// Insert the instructions after `after`, in the block of the function holding
+// it.
+fn insert_after(ssa: &mut Ssa, cfg: &Cfg, after: Inst, new_insts: &[Inst]) {
+    for block in cfg.blocks() {
+        let insts = insts_mut(ssa, *block);
+        if let Some(position) = insts.iter().position(|inst| *inst == after) {
+            insts.splice(position + 1..position + 1, new_insts.iter().copied());
+            return;
+        }
+    }
+    panic!()
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::{
+    key_vec::Val,
+    semantic::{Type, TypeData},
+};
+
+// Calls whose value is returned right away, when they recurse. A call of the
+// function itself becomes a jump to the start of its body, and a call of a
+// function that calls it back becomes a `TailCall`, so that recursion does
+// not grow the stack.
+pub fn tail_call(types: &mut Types, ssa: &mut Ssa) {
+    let callees = callees(ssa);
+
+    let mut tail_calls = Vec::new();
+    for (function, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { .. } = block_data {
+            for block in function_blocks(ssa, function) {
+                if let Some((callee, argument)) = tail_call_of(types, ssa, block)
+                    && (callee == function || calls(&callees, callee, function))
+                {
+                    tail_calls.push((function, block, callee, argument));
+                }
+            }
+        }
+    }
+
+    // The body of a function calling itself moves to a block it can jump to.
+    let mut bodies = HashMap::new();
+
+    for (function, block, callee, argument) in tail_calls {
+        if callee == function && !bodies.contains_key(&function) {
+            bodies.insert(function, move_body(ssa, function));
+        }
+
+        let block = match bodies.get(&function) {
+            Some(&body) if block == function => body,
+            _ => block,
+        };
+
+        let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+            &mut ssa.blocks[block]
+        else {
+            panic!()
+        };
+        insts.truncate(insts.len() - 2);
+
+        if callee == function {
+            ssa.inst_jump(block, bodies[&function], argument);
+        } else {
+            ssa.inst_tail_call(block, callee, argument);
+        }
+    }
+}
+
+// The callee and the argument of a call ending the block, when its value is
+// returned, directly or by blocks only passing it on.
+fn tail_call_of(types: &Types, ssa: &Ssa, block: Block) -> Option<(Block, Expr)> {
+    let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) = &ssa.blocks[block]
+    else {
+        panic!()
+    };
+
+    let [.., call, terminator] = insts[..] else {
+        return None;
+    };
+
+    let InstData::Call { function, argument } = ssa.insts[call] else {
+        return None;
+    };
+
+    // Slices and pointers may point into the frame of the caller, which the
+    // callee reuses.
+    if !matches!(ssa.blocks[function], BlockData::Function { .. })
+        || points_into_frame(types, ssa.expression_type(types, argument))
+    {
+        return None;
+    }
+
+    let returned = match ssa.insts[terminator] {
+        InstData::Return(value) => value == Expr::Inst(call),
+        InstData::Jump { block, argument } => {
+            argument == Expr::Inst(call) && returns_argument(ssa, block)
+        }
+        _ => false,
+    };
+
+    returned.then_some((function, argument))
+}
+
+fn returns_argument(ssa: &Ssa, mut block: Block) -> bool {
+    let mut visited = HashSet::new();
+
+    while visited.insert(block) {
+        let BlockData::Block { insts, .. } = &ssa.blocks[block] else {
+            return false;
+        };
+
+        match insts[..] {
+            [inst] => match ssa.insts[inst] {
+                InstData::Return(value) => return value == Expr::BlockArg(block),
+                InstData::Jump {
+                    block: target,
+                    argument,
+                } if argument == Expr::BlockArg(block) => block = target,
+                _ => return false,
+            },
+            _ => return false,
+        }
+    }
+
+    false
+}
+
+fn points_into_frame(types: &Types, type_: Type) -> bool {
+    match types.get(type_) {
+        Val::Value(TypeData::Slice { .. } | TypeData::Pointer { .. }) => true,
+        Val::Value(TypeData::Product { fields }) => fields
+            .iter()
+            .any(|(_, field)| points_into_frame(types, *field)),
+        Val::Value(&TypeData::Array { element, .. } | &TypeData::Vec { element }) => {
+            points_into_frame(types, element)
+        }
+        Val::None | Val::Sentinel(_) | Val::Value(TypeData::Function { .. }) => false,
+    }
+}
+
+// Move the instructions of the function to a new block taking its argument,
+// the function only jumps to it.
+fn move_body(ssa: &mut Ssa, function: Block) -> Block {
+    let BlockData::Function { arg, insts, .. } = &mut ssa.blocks[function] else {
+        panic!()
+    };
+    let arg = *arg;
+    let insts = std::mem::take(insts);
+
+    let body = ssa.basic_block(arg);
+    let BlockData::Block {
+        insts: body_insts, ..
+    } = &mut ssa.blocks[body]
+    else {
+        panic!()
+    };
+    *body_insts = insts;
+
+    replace_uses(
+        ssa,
+        &HashMap::from([(Expr::BlockArg(function), Expr::BlockArg(body))]),
+    );
+    ssa.inst_jump(function, body, Expr::BlockArg(function));
+
+    body
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::key_vec::KeyVec;
+
+// Remove the blocks no function can jump to, like the ones generated after a
+// `break`. The remaining blocks are renumbered.
+pub fn unreachable(_types: &mut Types, ssa: &mut Ssa) {
+    let reachable = reachable_blocks(ssa);
+
+    let mut renamed = HashMap::new();
+    let mut blocks = Vec::new();
+    for (block, block_data) in ssa.blocks.entries() {
+        if reachable.contains(&block) || matches!(block_data, BlockData::ExternFunction { .. }) {
+            renamed.insert(block, Block::from_u32_index(blocks.len() as u32));
+            blocks.push(block_data.clone());
+        }
+    }
+
+    if blocks.len() == ssa.blocks.len() {
+        return;
+    }
+
+    ssa.blocks = KeyVec::from_vec(blocks);
+
+    for inst in block_insts(ssa) {
+        let inst_data = &mut ssa.insts[inst];
+
+        match inst_data {
+            InstData::Call {
+                function: block, ..
+            }
+            | InstData::TailCall {
+                function: block, ..
+            }
+            | InstData::Jump { block, .. } => *block = renamed[block],
+            InstData::JumpCondition { then, else_, .. } => {
+                *then = renamed[then];
+                *else_ = renamed[else_];
+            }
+            _ => {}
+        }
+
+        for operand in inst_data.operands_mut() {
+            if let Expr::BlockArg(block) = operand {
+                *block = renamed[block];
+            }
+        }
+    }
+}
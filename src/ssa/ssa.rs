@@ -1,10 +1,13 @@
 use crate::{
     key_vec::{Index, KeyVec, Sentinel, Val},
     semantic::{Type, TypeData, TypeSentinel, Types},
+    ssa::Cfg,
 };
 
 #[derive(Default, Debug)]
 pub struct Ssa {
+    // Path of the source, in the messages of the traps.
+    pub path: String,
     pub blocks: Blocks,
     pub insts: Insts,
     pub consts: Consts,
@@ -35,9 +38,26 @@ impl Ssa {
                     Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
                 }
             }
-            InstData::Record(_, ty) => *ty,
-            InstData::Equal(lhs, _)
-            | InstData::Add(lhs, _)
+            InstData::Record(_, ty) | InstData::Array(_, ty) => *ty,
+            InstData::Length(_) => TypeSentinel::Uint32.to_index(),
+            InstData::Load { base, .. } => self.element_type(types, *base),
+            InstData::Store { .. } => TypeSentinel::Unit.to_index(),
+            InstData::NewVec(_, ty) => *ty,
+            InstData::Push { .. }
+            | InstData::Retain(_)
+            | InstData::Release(_)
+            | InstData::StorePointer { .. } => TypeSentinel::Unit.to_index(),
+            InstData::Alloca(type_) | InstData::AddressOf(_, type_) => *type_,
+            InstData::LoadPointer(pointer) => {
+                match types.get(self.expression_type(types, *pointer)) {
+                    Val::Value(&TypeData::Pointer { pointee }) => pointee,
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                }
+            }
+            InstData::Pop { vec, .. } => self.element_type(types, *vec),
+            InstData::Slice { type_, .. } => *type_,
+            InstData::Equal(..) => TypeSentinel::Bool.to_index(),
+            InstData::Add(lhs, _)
             | InstData::Sub(lhs, _)
             | InstData::Mul(lhs, _)
             | InstData::Div(lhs, _) => self.expression_type(types, *lhs),
@@ -47,9 +67,21 @@ impl Ssa {
                 BlockData::ExternFunction { ret, .. } | BlockData::Function { ret, .. } => *ret,
                 BlockData::Block { .. } => TypeSentinel::Unit.to_index(),
             },
-            InstData::Jump { .. } | InstData::JumpCondition { .. } | InstData::Return(_) => {
-                TypeSentinel::Unit.to_index()
-            }
+            InstData::Jump { .. }
+            | InstData::JumpCondition { .. }
+            | InstData::Return(_)
+            | InstData::TailCall { .. } => TypeSentinel::Unit.to_index(),
+        }
+    }
+
+    fn element_type(&self, types: &Types, base: Expr) -> Type {
+        match types.get(self.expression_type(types, base)) {
+            Val::Value(
+                &TypeData::Array { element, .. }
+                | &TypeData::Slice { element }
+                | &TypeData::Vec { element },
+            ) => element,
+            Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
         }
     }
 
@@ -68,6 +100,65 @@ impl Ssa {
         }
     }
 
+    // Instructions of the functions and blocks, including the blocks no
+    // function can jump to.
+    pub fn inst_count(&self) -> usize {
+        self.blocks
+            .entries()
+            .map(|(_, block_data)| match block_data {
+                BlockData::ExternFunction { .. } => 0,
+                BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts.len(),
+            })
+            .sum()
+    }
+
+    // Tail calls of the functions to functions of the program, as pairs of
+    // the caller and the callee. Those of `main` are left out, the backends
+    // make calls in their place as `main` returns to the host.
+    pub fn tail_calls(&self) -> Vec<(Block, Block)> {
+        let mut tail_calls = Vec::new();
+
+        for (function, block_data) in self.blocks.entries() {
+            let BlockData::Function { name, .. } = block_data else {
+                continue;
+            };
+            if name == "main" {
+                continue;
+            }
+
+            for block in Cfg::new(self, function).blocks() {
+                let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                    &self.blocks[*block]
+                else {
+                    panic!()
+                };
+
+                for inst in insts {
+                    if let InstData::TailCall {
+                        function: callee, ..
+                    } = self.insts[*inst]
+                        && let BlockData::Function { .. } = self.blocks[callee]
+                    {
+                        tail_calls.push((function, callee));
+                    }
+                }
+            }
+        }
+
+        tail_calls
+    }
+
+    // Dividing by zero traps in every backend, only constant divisors other
+    // than zero are not checked.
+    pub fn divisor_may_be_zero(&self, divisor: Expr) -> bool {
+        match divisor {
+            Expr::Const(divisor) => {
+                matches!(self.consts.get(divisor), Val::Value(ConstData::Uint32(0)))
+            }
+            Expr::Inst(_) | Expr::BlockArg(_) => true,
+        }
+    }
+
     fn block(&mut self, block_data: BlockData) -> Block {
         self.blocks.push(block_data)
     }
@@ -148,6 +239,104 @@ impl Ssa {
         self.inst(block, InstData::Record(fields, type_))
     }
 
+    pub fn inst_array(&mut self, block: Block, elements: Vec<Expr>, type_: Type) -> Inst {
+        self.inst(block, InstData::Array(elements, type_))
+    }
+
+    pub fn inst_length(&mut self, block: Block, base: Expr) -> Inst {
+        self.inst(block, InstData::Length(base))
+    }
+
+    pub fn inst_load(&mut self, block: Block, base: Expr, index: Expr, location: Location) -> Inst {
+        self.inst(
+            block,
+            InstData::Load {
+                base,
+                index,
+                location,
+            },
+        )
+    }
+
+    pub fn inst_store(
+        &mut self,
+        block: Block,
+        base: Expr,
+        index: Expr,
+        value: Expr,
+        location: Location,
+    ) -> Inst {
+        self.inst(
+            block,
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            },
+        )
+    }
+
+    pub fn inst_slice(
+        &mut self,
+        block: Block,
+        base: Expr,
+        start: Expr,
+        end: Expr,
+        type_: Type,
+        location: Location,
+    ) -> Inst {
+        self.inst(
+            block,
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            },
+        )
+    }
+
+    pub fn inst_new_vec(&mut self, block: Block, elements: Vec<Expr>, type_: Type) -> Inst {
+        self.inst(block, InstData::NewVec(elements, type_))
+    }
+
+    pub fn inst_push(&mut self, block: Block, vec: Expr, value: Expr) -> Inst {
+        self.inst(block, InstData::Push { vec, value })
+    }
+
+    pub fn inst_pop(&mut self, block: Block, vec: Expr, location: Location) -> Inst {
+        self.inst(block, InstData::Pop { vec, location })
+    }
+
+    pub fn inst_retain(&mut self, block: Block, vec: Expr) -> Inst {
+        self.inst(block, InstData::Retain(vec))
+    }
+
+    pub fn inst_release(&mut self, block: Block, vec: Expr) -> Inst {
+        self.inst(block, InstData::Release(vec))
+    }
+
+    pub fn inst_alloca(&mut self, types: &mut Types, block: Block, pointee: Type) -> Inst {
+        let type_ = types.push(TypeData::Pointer { pointee });
+        self.inst(block, InstData::Alloca(type_))
+    }
+
+    pub fn inst_address_of(&mut self, types: &mut Types, block: Block, expr: Expr) -> Inst {
+        let pointee = self.expression_type(types, expr);
+        let type_ = types.push(TypeData::Pointer { pointee });
+        self.inst(block, InstData::AddressOf(expr, type_))
+    }
+
+    pub fn inst_load_pointer(&mut self, block: Block, pointer: Expr) -> Inst {
+        self.inst(block, InstData::LoadPointer(pointer))
+    }
+
+    pub fn inst_store_pointer(&mut self, block: Block, pointer: Expr, value: Expr) -> Inst {
+        self.inst(block, InstData::StorePointer { pointer, value })
+    }
+
     pub fn inst_call(&mut self, block: Block, target_function: Block, argument: Expr) -> Inst {
         self.inst(
             block,
@@ -188,6 +377,16 @@ impl Ssa {
     pub fn inst_return(&mut self, block: Block, expr: Expr) -> Inst {
         self.inst(block, InstData::Return(expr))
     }
+
+    pub fn inst_tail_call(&mut self, block: Block, target_function: Block, argument: Expr) -> Inst {
+        self.inst(
+            block,
+            InstData::TailCall {
+                function: target_function,
+                argument,
+            },
+        )
+    }
 }
 
 #[derive(Sentinel, Clone, Copy, Debug)]
@@ -212,8 +411,10 @@ pub type Blocks = KeyVec<BlockSentinel, BlockData>;
 pub type Insts = KeyVec<InstSentinel, InstData>;
 pub type Consts = KeyVec<ConstSentinel, ConstData>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BlockData {
+    // A function of the runtime, programs cannot declare their own so only
+    // the ones `generate_module` adds exist.
     ExternFunction {
         name: String,
         arg: Type,
@@ -231,10 +432,63 @@ pub enum BlockData {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InstData {
     Field(Expr, u32),
     Record(Vec<Expr>, Type),
+    Array(Vec<Expr>, Type),
+    // Length of a slice or a vec, the length of an array is known from its
+    // type.
+    Length(Expr),
+    // Read an element of an array, a slice or a vec, trapping if out of
+    // bounds.
+    Load {
+        base: Expr,
+        index: Expr,
+        location: Location,
+    },
+    // Write an element through a slice or a vec, trapping if out of bounds.
+    Store {
+        base: Expr,
+        index: Expr,
+        value: Expr,
+        location: Location,
+    },
+    // View `start..end` of an array or a slice, trapping if out of bounds.
+    Slice {
+        base: Expr,
+        start: Expr,
+        end: Expr,
+        type_: Type,
+        location: Location,
+    },
+    // Vec holding the given elements, allocated by the runtime.
+    NewVec(Vec<Expr>, Type),
+    Push {
+        vec: Expr,
+        value: Expr,
+    },
+    // Remove the last element of a vec, trapping if it is empty.
+    Pop {
+        vec: Expr,
+        location: Location,
+    },
+    // Uninitialized memory for a value in the frame of the function, the type
+    // is the pointer type.
+    Alloca(Type),
+    // Put a value in memory and take its address, the type is the pointer
+    // type.
+    AddressOf(Expr, Type),
+    LoadPointer(Expr),
+    StorePointer {
+        pointer: Expr,
+        value: Expr,
+    },
+    // Increment the reference count of a vec.
+    Retain(Expr),
+    // Decrement the reference count of a vec, freeing it and releasing its
+    // elements when it reaches zero.
+    Release(Expr),
     Equal(Expr, Expr),
     Add(Expr, Expr),
     Sub(Expr, Expr),
@@ -255,9 +509,123 @@ pub enum InstData {
         // TODO: Block arguments
     },
     Return(Expr),
+    // Return what the function returns, the frame of the caller is reused by
+    // the backends that can.
+    TailCall {
+        function: Block,
+        argument: Expr,
+    },
+}
+
+impl InstData {
+    // Values read by the instruction, the targets of calls and jumps are not
+    // values.
+    pub fn operands(&self) -> Vec<Expr> {
+        match self {
+            InstData::Field(expr, _)
+            | InstData::Length(expr)
+            | InstData::AddressOf(expr, _)
+            | InstData::LoadPointer(expr)
+            | InstData::Retain(expr)
+            | InstData::Release(expr)
+            | InstData::Pop { vec: expr, .. }
+            | InstData::Call { argument: expr, .. }
+            | InstData::TailCall { argument: expr, .. }
+            | InstData::Jump { argument: expr, .. }
+            | InstData::JumpCondition {
+                condition: expr, ..
+            }
+            | InstData::Return(expr) => vec![*expr],
+            InstData::Record(exprs, _) | InstData::Array(exprs, _) | InstData::NewVec(exprs, _) => {
+                exprs.clone()
+            }
+            InstData::Load { base, index, .. } => vec![*base, *index],
+            InstData::Store {
+                base, index, value, ..
+            } => vec![*base, *index, *value],
+            InstData::Slice {
+                base, start, end, ..
+            } => vec![*base, *start, *end],
+            InstData::Push {
+                vec: lhs,
+                value: rhs,
+            }
+            | InstData::StorePointer {
+                pointer: lhs,
+                value: rhs,
+            }
+            | InstData::Equal(lhs, rhs)
+            | InstData::Add(lhs, rhs)
+            | InstData::Sub(lhs, rhs)
+            | InstData::Mul(lhs, rhs)
+            | InstData::Div(lhs, rhs) => vec![*lhs, *rhs],
+            InstData::Alloca(_) => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            InstData::Field(expr, _)
+            | InstData::Length(expr)
+            | InstData::AddressOf(expr, _)
+            | InstData::LoadPointer(expr)
+            | InstData::Retain(expr)
+            | InstData::Release(expr)
+            | InstData::Pop { vec: expr, .. }
+            | InstData::Call { argument: expr, .. }
+            | InstData::TailCall { argument: expr, .. }
+            | InstData::Jump { argument: expr, .. }
+            | InstData::JumpCondition {
+                condition: expr, ..
+            }
+            | InstData::Return(expr) => vec![expr],
+            InstData::Record(exprs, _) | InstData::Array(exprs, _) | InstData::NewVec(exprs, _) => {
+                exprs.iter_mut().collect()
+            }
+            InstData::Load { base, index, .. } => vec![base, index],
+            InstData::Store {
+                base, index, value, ..
+            } => vec![base, index, value],
+            InstData::Slice {
+                base, start, end, ..
+            } => vec![base, start, end],
+            InstData::Push {
+                vec: lhs,
+                value: rhs,
+            }
+            | InstData::StorePointer {
+                pointer: lhs,
+                value: rhs,
+            }
+            | InstData::Equal(lhs, rhs)
+            | InstData::Add(lhs, rhs)
+            | InstData::Sub(lhs, rhs)
+            | InstData::Mul(lhs, rhs)
+            | InstData::Div(lhs, rhs) => vec![lhs, rhs],
+            InstData::Alloca(_) => vec![],
+        }
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            InstData::Jump { .. }
+                | InstData::JumpCondition { .. }
+                | InstData::Return(_)
+                | InstData::TailCall { .. }
+        )
+    }
 }
 
+// Position in the source reported when a bounds check fails, both starting
+// at `1`.
 #[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Expr {
     Const(Const),
     Inst(Inst),
@@ -0,0 +1,915 @@
+// Uncolored textual form of the ssa that can be parsed back, so tests can work
+// on the ssa directly and backend bugs can be reproduced by hand.
+//
+// Blocks are listed in order, followed by their instructions, then the
+// constants:
+//
+//   @0 extern builtin_print(u32) -> ()
+//   @1 fn main(()) -> ()
+//     %0 = add $0, $1;
+//     %1 = call @0, %0;
+//     %2 = jump @2, ();
+//
+//   @2 block(())
+//     %3 = return ();
+//
+//   $0 = 1_u32;
+//   $1 = 2_u32;
+//
+// Instructions:
+//
+//   field e, 0                      record e, e : type
+//   array [e, e] : type             new_vec [e, e] : type
+//   length e                        load e[e] at 1:2
+//   store e[e], e at 1:2            slice e[e..e] : type at 1:2
+//   push e, e                       pop e at 1:2
+//   alloca type                     address_of e : type
+//   load_pointer e                  store_pointer e, e
+//   retain e                        release e
+//   equal e, e                      add e, e
+//   sub e, e                        mul e, e
+//   div e, e                        call @0, e
+//   jump @0, e                      jump @0 if e else @1
+//   return e                        tail_call @0, e
+//
+// Expressions are `%inst`, `$const`, `param(@block)`, `()`, `false` or
+// `true`. Constants are `1_u32` or a product of constants `($0, true)`.
+//
+// Types are `()`, `u32`, `bool`, `false`, `true`, `!`, `pointer`,
+// `unknown`, `(a, name: b)`, `(a,)`, `[a; 4]`, `[a]`, `&a`, `vec a` and
+// `a -> b`, with parentheses for grouping.
+//
+// When printed, blocks and constants are named by their index and
+// instructions by their position in the text. When parsed, any name made of
+// letters, digits and `_` is accepted and `//` starts a comment.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Write as _},
+};
+
+use crate::{
+    key_vec::{Index, Sentinel, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+};
+
+use super::*;
+
+pub fn print(types: &Types, ssa: &Ssa) -> String {
+    // Instructions are numbered in the order they are printed, so the text
+    // of a parsed ssa is the same as the one it was parsed from.
+    let mut names = HashMap::new();
+    for (_, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { insts, .. } | BlockData::Block { insts, .. } = block_data {
+            for inst in insts {
+                names.insert(*inst, names.len());
+            }
+        }
+    }
+
+    let expr = |expr: &Expr| match expr {
+        Expr::Const(const_) => print_const(*const_),
+        Expr::Inst(inst) => format!("%{}", names[inst]),
+        Expr::BlockArg(block) => format!("param(@{})", block.as_u32()),
+    };
+    let exprs = |exprs: &[Expr]| exprs.iter().map(expr).collect::<Vec<_>>().join(", ");
+    let location = |location: &Location| format!("at {}:{}", location.line, location.column);
+
+    let mut text = String::new();
+
+    for (block, block_data) in ssa.blocks.entries() {
+        let insts = match block_data {
+            BlockData::ExternFunction { name, arg, ret } => {
+                writeln!(
+                    text,
+                    "@{} extern {name}({}) -> {}",
+                    block.as_u32(),
+                    print_type(types, *arg),
+                    print_type(types, *ret),
+                )
+                .unwrap();
+                continue;
+            }
+            BlockData::Function {
+                name,
+                arg,
+                ret,
+                insts,
+            } => {
+                writeln!(
+                    text,
+                    "@{} fn {name}({}) -> {}",
+                    block.as_u32(),
+                    print_type(types, *arg),
+                    print_type(types, *ret),
+                )
+                .unwrap();
+                insts
+            }
+            BlockData::Block { arg, insts } => {
+                writeln!(
+                    text,
+                    "@{} block({})",
+                    block.as_u32(),
+                    print_type(types, *arg)
+                )
+                .unwrap();
+                insts
+            }
+        };
+
+        for inst in insts {
+            let inst_text = match &ssa.insts[*inst] {
+                InstData::Field(base, field) => format!("field {}, {field}", expr(base)),
+                InstData::Record(fields, type_) => {
+                    let fields = exprs(fields);
+                    let separator = if fields.is_empty() { "" } else { " " };
+                    format!("record {fields}{separator}: {}", print_type(types, *type_))
+                }
+                InstData::Array(elements, type_) => format!(
+                    "array [{}] : {}",
+                    exprs(elements),
+                    print_type(types, *type_)
+                ),
+                InstData::Length(base) => format!("length {}", expr(base)),
+                InstData::Load {
+                    base,
+                    index,
+                    location: at,
+                } => format!("load {}[{}] {}", expr(base), expr(index), location(at)),
+                InstData::Store {
+                    base,
+                    index,
+                    value,
+                    location: at,
+                } => format!(
+                    "store {}[{}], {} {}",
+                    expr(base),
+                    expr(index),
+                    expr(value),
+                    location(at),
+                ),
+                InstData::Slice {
+                    base,
+                    start,
+                    end,
+                    type_,
+                    location: at,
+                } => format!(
+                    "slice {}[{}..{}] : {} {}",
+                    expr(base),
+                    expr(start),
+                    expr(end),
+                    print_type(types, *type_),
+                    location(at),
+                ),
+                InstData::NewVec(elements, type_) => format!(
+                    "new_vec [{}] : {}",
+                    exprs(elements),
+                    print_type(types, *type_)
+                ),
+                InstData::Push { vec, value } => format!("push {}, {}", expr(vec), expr(value)),
+                InstData::Pop { vec, location: at } => {
+                    format!("pop {} {}", expr(vec), location(at))
+                }
+                InstData::Alloca(type_) => format!("alloca {}", print_type(types, *type_)),
+                InstData::AddressOf(value, type_) => {
+                    format!("address_of {} : {}", expr(value), print_type(types, *type_))
+                }
+                InstData::LoadPointer(pointer) => format!("load_pointer {}", expr(pointer)),
+                InstData::StorePointer { pointer, value } => {
+                    format!("store_pointer {}, {}", expr(pointer), expr(value))
+                }
+                InstData::Retain(vec) => format!("retain {}", expr(vec)),
+                InstData::Release(vec) => format!("release {}", expr(vec)),
+                InstData::Equal(lhs, rhs) => format!("equal {}, {}", expr(lhs), expr(rhs)),
+                InstData::Add(lhs, rhs) => format!("add {}, {}", expr(lhs), expr(rhs)),
+                InstData::Sub(lhs, rhs) => format!("sub {}, {}", expr(lhs), expr(rhs)),
+                InstData::Mul(lhs, rhs) => format!("mul {}, {}", expr(lhs), expr(rhs)),
+                InstData::Div(lhs, rhs) => format!("div {}, {}", expr(lhs), expr(rhs)),
+                InstData::Call { function, argument } => {
+                    format!("call @{}, {}", function.as_u32(), expr(argument))
+                }
+                InstData::Jump { block, argument } => {
+                    format!("jump @{}, {}", block.as_u32(), expr(argument))
+                }
+                InstData::JumpCondition {
+                    condition,
+                    then,
+                    else_,
+                } => format!(
+                    "jump @{} if {} else @{}",
+                    then.as_u32(),
+                    expr(condition),
+                    else_.as_u32(),
+                ),
+                InstData::Return(value) => format!("return {}", expr(value)),
+                InstData::TailCall { function, argument } => {
+                    format!("tail_call @{}, {}", function.as_u32(), expr(argument))
+                }
+            };
+
+            writeln!(text, "  %{} = {inst_text};", names[inst]).unwrap();
+        }
+
+        text.push('\n');
+    }
+
+    for (const_, const_data) in ssa.consts.entries() {
+        let value = match const_data {
+            ConstData::Uint32(value) => format!("{value}_u32"),
+            ConstData::Product(fields, _) => {
+                let fields = fields.iter().map(|field| print_const(*field));
+                format!("({})", fields.collect::<Vec<_>>().join(", "))
+            }
+        };
+
+        writeln!(text, "${} = {value};", const_.as_u32()).unwrap();
+    }
+
+    text
+}
+
+fn print_const(const_: Const) -> String {
+    match const_.sentinel() {
+        Some(ConstSentinel::Unit) => "()".to_string(),
+        Some(ConstSentinel::False) => "false".to_string(),
+        Some(ConstSentinel::True) => "true".to_string(),
+        None => format!("${}", const_.as_u32()),
+    }
+}
+
+fn print_type(types: &Types, type_: Type) -> String {
+    match types.get(type_) {
+        Val::None => panic!(),
+        Val::Sentinel(sentinel) => match sentinel {
+            TypeSentinel::Unknown => "unknown",
+            TypeSentinel::Unit => "()",
+            TypeSentinel::Uint32 => "u32",
+            TypeSentinel::Bool => "bool",
+            TypeSentinel::False => "false",
+            TypeSentinel::True => "true",
+            TypeSentinel::Never => "!",
+            TypeSentinel::Pointer => "pointer",
+        }
+        .to_string(),
+        Val::Value(type_data) => match type_data {
+            TypeData::Function {
+                argument_type,
+                return_type,
+            } => format!(
+                "{} -> {}",
+                print_primary_type(types, *argument_type),
+                print_type(types, *return_type)
+            ),
+            TypeData::Product { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, field)| match name.as_str() {
+                        "" => print_type(types, *field),
+                        name => format!("{name}: {}", print_type(types, *field)),
+                    })
+                    .collect::<Vec<_>>();
+
+                match fields.as_slice() {
+                    // Without the comma it would only be parentheses.
+                    [field] => format!("({field},)"),
+                    fields => format!("({})", fields.join(", ")),
+                }
+            }
+            TypeData::Array { element, length } => {
+                format!("[{}; {length}]", print_type(types, *element))
+            }
+            TypeData::Slice { element } => format!("[{}]", print_type(types, *element)),
+            TypeData::Pointer { pointee } => format!("&{}", print_primary_type(types, *pointee)),
+            TypeData::Vec { element } => format!("vec {}", print_primary_type(types, *element)),
+        },
+    }
+}
+
+// Function types are put in parentheses where `->` would be ambiguous.
+fn print_primary_type(types: &Types, type_: Type) -> String {
+    match types.get(type_) {
+        Val::Value(TypeData::Function { .. }) => format!("({})", print_type(types, type_)),
+        Val::None | Val::Sentinel(_) | Val::Value(_) => print_type(types, type_),
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+pub fn parse(text: &str) -> Result<(Types, Ssa), ParseError> {
+    let mut parser = Parser {
+        tokens: lex(text)?,
+        position: 0,
+        blocks: HashMap::new(),
+        insts: HashMap::new(),
+        consts: HashMap::new(),
+        types: Types::default(),
+        ssa: Ssa::default(),
+    };
+
+    parser.collect_names()?;
+
+    while parser.peek() != &Token::End {
+        match parser.peek() {
+            Token::Block(_) => parser.parse_block()?,
+            Token::Const(_) => parser.parse_const()?,
+            _ => return Err(parser.error("expected a block or a constant")),
+        }
+    }
+
+    Ok((parser.types, parser.ssa))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Number(u32),
+    Block(&'a str),
+    Inst(&'a str),
+    Const(&'a str),
+    Punct(&'static str),
+    End,
+}
+
+const PUNCTS: [&str; 12] = ["->", "..", "(", ")", "[", "]", ",", ";", ":", "=", "&", "!"];
+
+fn lex(text: &str) -> Result<Vec<(Token<'_>, Location)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut location = Location { line: 1, column: 1 };
+
+    let is_name = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+
+    while offset < text.len() {
+        let rest = &text[offset..];
+        let c = rest.as_bytes()[0];
+
+        let length = if c == b'\n' {
+            offset += 1;
+            location.line += 1;
+            location.column = 1;
+            continue;
+        } else if c.is_ascii_whitespace() {
+            1
+        } else if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if c.is_ascii_digit() {
+            let length = rest.bytes().take_while(u8::is_ascii_digit).count();
+            let Ok(value) = rest[..length].parse() else {
+                return Err(ParseError {
+                    location,
+                    message: "number too large".to_string(),
+                });
+            };
+            tokens.push((Token::Number(value), location));
+            length
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let length = rest.bytes().take_while(|c| is_name(*c)).count();
+            tokens.push((Token::Ident(&rest[..length]), location));
+            length
+        } else if let b'@' | b'%' | b'$' = c {
+            let length = rest[1..].bytes().take_while(|c| is_name(*c)).count();
+            if length == 0 {
+                return Err(ParseError {
+                    location,
+                    message: format!("expected a name after `{}`", c as char),
+                });
+            }
+
+            let name = &rest[1..length + 1];
+            let token = match c {
+                b'@' => Token::Block(name),
+                b'%' => Token::Inst(name),
+                _ => Token::Const(name),
+            };
+            tokens.push((token, location));
+            length + 1
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+            tokens.push((Token::Punct(punct), location));
+            punct.len()
+        } else {
+            return Err(ParseError {
+                location,
+                message: format!("unexpected character `{}`", rest.chars().next().unwrap()),
+            });
+        };
+
+        offset += length;
+        location.column += rest[..length].chars().count() as u32;
+    }
+
+    tokens.push((Token::End, location));
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token<'a>, Location)>,
+    position: usize,
+    blocks: HashMap<&'a str, Block>,
+    insts: HashMap<&'a str, Inst>,
+    consts: HashMap<&'a str, Const>,
+    types: Types,
+    ssa: Ssa,
+}
+
+impl<'a> Parser<'a> {
+    // Names can be used before their definition, blocks, instructions and
+    // constants are indexed in the order they are defined.
+    fn collect_names(&mut self) -> Result<(), ParseError> {
+        for i in 1..self.tokens.len() {
+            let ((token, location), (next, _)) = (self.tokens[i - 1], self.tokens[i]);
+
+            let defined = match (token, next) {
+                (Token::Block(name), Token::Ident("extern" | "fn" | "block")) => {
+                    let block = Index::from_u32_index(self.blocks.len() as u32);
+                    self.blocks.insert(name, block).is_some()
+                }
+                (Token::Inst(name), Token::Punct("=")) => {
+                    let inst = Index::from_u32_index(self.insts.len() as u32);
+                    self.insts.insert(name, inst).is_some()
+                }
+                (Token::Const(name), Token::Punct("=")) => {
+                    let const_ = Index::from_u32_index(self.consts.len() as u32);
+                    self.consts.insert(name, const_).is_some()
+                }
+                _ => false,
+            };
+
+            if defined {
+                return Err(ParseError {
+                    location,
+                    message: "defined twice".to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn peek(&self) -> &Token<'a> {
+        &self.tokens[self.position].0
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let token = self.tokens[self.position].0;
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            location: self.tokens[self.position].1,
+            message: message.to_string(),
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let eaten = matches!(self.peek(), Token::Punct(p) if *p == punct);
+        if eaten {
+            self.next();
+        }
+        eaten
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        if !self.eat(punct) {
+            return Err(self.error(&format!("expected `{punct}`")));
+        }
+        Ok(())
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let eaten = self.peek() == &Token::Ident(keyword);
+        if eaten {
+            self.next();
+        }
+        eaten
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if !self.eat_keyword(keyword) {
+            return Err(self.error(&format!("expected `{keyword}`")));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<u32, ParseError> {
+        match self.peek() {
+            &Token::Number(value) => {
+                self.next();
+                Ok(value)
+            }
+            _ => Err(self.error("expected a number")),
+        }
+    }
+
+    fn block(&mut self) -> Result<Block, ParseError> {
+        match self.peek() {
+            Token::Block(name) => match self.blocks.get(name) {
+                Some(block) => {
+                    let block = *block;
+                    self.next();
+                    Ok(block)
+                }
+                None => Err(self.error(&format!("unknown block `@{name}`"))),
+            },
+            _ => Err(self.error("expected a block")),
+        }
+    }
+
+    fn parse_block(&mut self) -> Result<(), ParseError> {
+        self.next();
+
+        let block = match self.next() {
+            Token::Ident(kind @ ("extern" | "fn")) => {
+                let Token::Ident(name) = self.next() else {
+                    return Err(self.error("expected a name"));
+                };
+                self.expect("(")?;
+                let arg = self.parse_type()?;
+                self.expect(")")?;
+                self.expect("->")?;
+                let ret = self.parse_type()?;
+
+                if kind == "extern" {
+                    self.ssa.extern_function(name.to_string(), arg, ret);
+                    return Ok(());
+                }
+
+                self.ssa.function(name.to_string(), arg, ret)
+            }
+            Token::Ident("block") => {
+                self.expect("(")?;
+                let arg = self.parse_type()?;
+                self.expect(")")?;
+                self.ssa.basic_block(arg)
+            }
+            _ => return Err(self.error("expected `extern`, `fn` or `block`")),
+        };
+
+        while let Token::Inst(_) = self.peek() {
+            self.next();
+            self.expect("=")?;
+            let inst_data = self.parse_inst()?;
+            self.expect(";")?;
+            self.ssa.inst(block, inst_data);
+        }
+
+        Ok(())
+    }
+
+    fn parse_inst(&mut self) -> Result<InstData, ParseError> {
+        let Token::Ident(name) = self.next() else {
+            return Err(self.error("expected an instruction"));
+        };
+
+        let inst_data = match name {
+            "field" => {
+                let base = self.parse_expr()?;
+                self.expect(",")?;
+                InstData::Field(base, self.number()?)
+            }
+            "record" => {
+                let fields = self.parse_exprs(":")?;
+                self.expect(":")?;
+                InstData::Record(fields, self.parse_type()?)
+            }
+            "array" | "new_vec" => {
+                self.expect("[")?;
+                let elements = self.parse_exprs("]")?;
+                self.expect("]")?;
+                self.expect(":")?;
+                let type_ = self.parse_type()?;
+                match name {
+                    "array" => InstData::Array(elements, type_),
+                    _ => InstData::NewVec(elements, type_),
+                }
+            }
+            "length" => InstData::Length(self.parse_expr()?),
+            "load" => {
+                let base = self.parse_expr()?;
+                self.expect("[")?;
+                let index = self.parse_expr()?;
+                self.expect("]")?;
+                InstData::Load {
+                    base,
+                    index,
+                    location: self.parse_location()?,
+                }
+            }
+            "store" => {
+                let base = self.parse_expr()?;
+                self.expect("[")?;
+                let index = self.parse_expr()?;
+                self.expect("]")?;
+                self.expect(",")?;
+                let value = self.parse_expr()?;
+                InstData::Store {
+                    base,
+                    index,
+                    value,
+                    location: self.parse_location()?,
+                }
+            }
+            "slice" => {
+                let base = self.parse_expr()?;
+                self.expect("[")?;
+                let start = self.parse_expr()?;
+                self.expect("..")?;
+                let end = self.parse_expr()?;
+                self.expect("]")?;
+                self.expect(":")?;
+                let type_ = self.parse_type()?;
+                InstData::Slice {
+                    base,
+                    start,
+                    end,
+                    type_,
+                    location: self.parse_location()?,
+                }
+            }
+            "push" => {
+                let vec = self.parse_expr()?;
+                self.expect(",")?;
+                InstData::Push {
+                    vec,
+                    value: self.parse_expr()?,
+                }
+            }
+            "pop" => InstData::Pop {
+                vec: self.parse_expr()?,
+                location: self.parse_location()?,
+            },
+            "alloca" => InstData::Alloca(self.parse_type()?),
+            "address_of" => {
+                let value = self.parse_expr()?;
+                self.expect(":")?;
+                InstData::AddressOf(value, self.parse_type()?)
+            }
+            "load_pointer" => InstData::LoadPointer(self.parse_expr()?),
+            "store_pointer" => {
+                let pointer = self.parse_expr()?;
+                self.expect(",")?;
+                InstData::StorePointer {
+                    pointer,
+                    value: self.parse_expr()?,
+                }
+            }
+            "retain" => InstData::Retain(self.parse_expr()?),
+            "release" => InstData::Release(self.parse_expr()?),
+            "equal" | "add" | "sub" | "mul" | "div" => {
+                let lhs = self.parse_expr()?;
+                self.expect(",")?;
+                let rhs = self.parse_expr()?;
+                match name {
+                    "equal" => InstData::Equal(lhs, rhs),
+                    "add" => InstData::Add(lhs, rhs),
+                    "sub" => InstData::Sub(lhs, rhs),
+                    "mul" => InstData::Mul(lhs, rhs),
+                    _ => InstData::Div(lhs, rhs),
+                }
+            }
+            "call" | "tail_call" => {
+                let function = self.block()?;
+                self.expect(",")?;
+                let argument = self.parse_expr()?;
+                match name {
+                    "call" => InstData::Call { function, argument },
+                    _ => InstData::TailCall { function, argument },
+                }
+            }
+            "jump" => {
+                let block = self.block()?;
+                if self.eat(",") {
+                    InstData::Jump {
+                        block,
+                        argument: self.parse_expr()?,
+                    }
+                } else {
+                    self.expect_keyword("if")?;
+                    let condition = self.parse_expr()?;
+                    self.expect_keyword("else")?;
+                    InstData::JumpCondition {
+                        condition,
+                        then: block,
+                        else_: self.block()?,
+                    }
+                }
+            }
+            "return" => InstData::Return(self.parse_expr()?),
+            _ => {
+                self.position -= 1;
+                return Err(self.error(&format!("unknown instruction `{name}`")));
+            }
+        };
+
+        Ok(inst_data)
+    }
+
+    fn parse_location(&mut self) -> Result<Location, ParseError> {
+        self.expect_keyword("at")?;
+        let line = self.number()?;
+        self.expect(":")?;
+        Ok(Location {
+            line,
+            column: self.number()?,
+        })
+    }
+
+    // Comma separated expressions up to `end`, which is not consumed.
+    fn parse_exprs(&mut self, end: &'static str) -> Result<Vec<Expr>, ParseError> {
+        let mut exprs = Vec::new();
+        if self.peek() == &Token::Punct(end) {
+            return Ok(exprs);
+        }
+
+        loop {
+            exprs.push(self.parse_expr()?);
+            if !self.eat(",") {
+                return Ok(exprs);
+            }
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        match *self.peek() {
+            Token::Inst(name) => match self.insts.get(name) {
+                Some(inst) => {
+                    let inst = *inst;
+                    self.next();
+                    Ok(Expr::Inst(inst))
+                }
+                None => Err(self.error(&format!("unknown instruction `%{name}`"))),
+            },
+            Token::Ident("param") => {
+                self.next();
+                self.expect("(")?;
+                let block = self.block()?;
+                self.expect(")")?;
+                Ok(Expr::BlockArg(block))
+            }
+            _ => Ok(Expr::Const(self.parse_const_expr()?)),
+        }
+    }
+
+    fn parse_const_expr(&mut self) -> Result<Const, ParseError> {
+        let const_ = match *self.peek() {
+            Token::Const(name) => match self.consts.get(name) {
+                Some(const_) => *const_,
+                None => return Err(self.error(&format!("unknown constant `${name}`"))),
+            },
+            Token::Ident("false") => ConstSentinel::False.to_index(),
+            Token::Ident("true") => ConstSentinel::True.to_index(),
+            Token::Punct("(") => {
+                self.next();
+                if self.peek() != &Token::Punct(")") {
+                    return Err(self.error("expected `)`"));
+                }
+                ConstSentinel::Unit.to_index()
+            }
+            _ => return Err(self.error("expected an expression")),
+        };
+
+        self.next();
+        Ok(const_)
+    }
+
+    fn parse_const(&mut self) -> Result<(), ParseError> {
+        self.next();
+        self.expect("=")?;
+
+        if self.eat("(") {
+            let mut fields = Vec::new();
+            loop {
+                // The type of the product is computed from the fields.
+                let field = self.parse_const_expr()?;
+                if let Val::None = self.ssa.consts.get(field) {
+                    self.position -= 1;
+                    return Err(self.error("constant used before its definition"));
+                }
+                fields.push(field);
+
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+
+            self.ssa.const_product(&mut self.types, fields);
+        } else {
+            let value = self.number()?;
+            // The suffix is optional.
+            self.eat_keyword("_u32");
+            self.ssa.const_u32(value);
+        }
+
+        self.expect(";")
+    }
+
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let argument_type = self.parse_primary_type()?;
+
+        if !self.eat("->") {
+            return Ok(argument_type);
+        }
+
+        let return_type = self.parse_type()?;
+        Ok(self.types.push(TypeData::Function {
+            argument_type,
+            return_type,
+        }))
+    }
+
+    fn parse_primary_type(&mut self) -> Result<Type, ParseError> {
+        let type_data = match self.next() {
+            Token::Ident("unknown") => return Ok(TypeSentinel::Unknown.to_index()),
+            Token::Ident("u32") => return Ok(TypeSentinel::Uint32.to_index()),
+            Token::Ident("bool") => return Ok(TypeSentinel::Bool.to_index()),
+            Token::Ident("false") => return Ok(TypeSentinel::False.to_index()),
+            Token::Ident("true") => return Ok(TypeSentinel::True.to_index()),
+            Token::Ident("pointer") => return Ok(TypeSentinel::Pointer.to_index()),
+            Token::Punct("!") => return Ok(TypeSentinel::Never.to_index()),
+            Token::Ident("vec") => TypeData::Vec {
+                element: self.parse_primary_type()?,
+            },
+            Token::Punct("&") => TypeData::Pointer {
+                pointee: self.parse_primary_type()?,
+            },
+            Token::Punct("[") => {
+                let element = self.parse_type()?;
+                let type_data = if self.eat(";") {
+                    TypeData::Array {
+                        element,
+                        length: self.number()?,
+                    }
+                } else {
+                    TypeData::Slice { element }
+                };
+                self.expect("]")?;
+                type_data
+            }
+            Token::Punct("(") => {
+                if self.eat(")") {
+                    return Ok(TypeSentinel::Unit.to_index());
+                }
+
+                let mut fields = Vec::new();
+                let mut trailing_comma = false;
+                while !self.eat(")") {
+                    let name = match self.tokens[self.position..] {
+                        [(Token::Ident(name), _), (Token::Punct(":"), _), ..] => {
+                            self.position += 2;
+                            name.to_string()
+                        }
+                        // Fields of tuples are named by their position.
+                        [(Token::Number(position), _), (Token::Punct(":"), _), ..] => {
+                            self.position += 2;
+                            position.to_string()
+                        }
+                        _ => String::new(),
+                    };
+                    fields.push((name, self.parse_type()?));
+
+                    trailing_comma = self.eat(",");
+                    if !trailing_comma {
+                        self.expect(")")?;
+                        break;
+                    }
+                }
+
+                // Parentheses around a single type only group it.
+                if let [(name, type_)] = fields.as_slice()
+                    && name.is_empty()
+                    && !trailing_comma
+                {
+                    return Ok(*type_);
+                }
+
+                TypeData::Product { fields }
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error("expected a type"));
+            }
+        };
+
+        Ok(self.types.push(type_data))
+    }
+}
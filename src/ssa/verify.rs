@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use crate::{
+    key_vec::{Sentinel, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types, types_equals},
+};
+
+use super::*;
+
+// Why the ssa is malformed, instructions are designated by their position in
+// their block.
+#[derive(Debug)]
+pub enum VerifyError {
+    // The block does not end with a jump or a return.
+    MissingTerminator {
+        block: Block,
+    },
+    // A jump or a return is followed by other instructions.
+    EarlyTerminator {
+        block: Block,
+        position: usize,
+    },
+    // A jump to a function or an extern function, a call of a block or a tail
+    // call of anything but a function.
+    InvalidTarget {
+        block: Block,
+        position: usize,
+        target: Block,
+    },
+    // The argument given to a block or a function does not have the type of
+    // its parameter.
+    ArgumentType {
+        block: Block,
+        position: usize,
+        expected: Type,
+        found: Type,
+    },
+    // A field of something that is not a product or that it does not have.
+    InvalidField {
+        block: Block,
+        position: usize,
+        field: u32,
+    },
+    // An instruction or a block argument used where it is not always defined.
+    NotDominated {
+        block: Block,
+        position: usize,
+        value: Expr,
+    },
+    UndefinedConst {
+        block: Block,
+        position: usize,
+        const_: Const,
+    },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::MissingTerminator { block } => {
+                write!(f, "@{} does not end with a terminator", block.as_u32())
+            }
+            VerifyError::EarlyTerminator { block, position } => write!(
+                f,
+                "@{}, instruction {position}: terminator before the end of the block",
+                block.as_u32()
+            ),
+            VerifyError::InvalidTarget {
+                block,
+                position,
+                target,
+            } => write!(
+                f,
+                "@{}, instruction {position}: invalid target @{}",
+                block.as_u32(),
+                target.as_u32()
+            ),
+            VerifyError::ArgumentType {
+                block, position, ..
+            } => write!(
+                f,
+                "@{}, instruction {position}: argument does not have the type of the parameter",
+                block.as_u32()
+            ),
+            VerifyError::InvalidField {
+                block,
+                position,
+                field,
+            } => write!(
+                f,
+                "@{}, instruction {position}: no field {field}",
+                block.as_u32()
+            ),
+            VerifyError::NotDominated {
+                block, position, ..
+            } => write!(
+                f,
+                "@{}, instruction {position}: use not dominated by its definition",
+                block.as_u32()
+            ),
+            VerifyError::UndefinedConst {
+                block,
+                position,
+                const_,
+            } => write!(
+                f,
+                "@{}, instruction {position}: undefined constant ${}",
+                block.as_u32(),
+                const_.as_u32()
+            ),
+        }
+    }
+}
+
+// Check the invariants the backends rely on, reporting every error instead of
+// panicking on the first one.
+pub fn verify(types: &Types, ssa: &Ssa) -> Result<(), Vec<VerifyError>> {
+    let mut verifier = Verifier {
+        types,
+        ssa,
+        definitions: HashMap::new(),
+        value_types: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    for (block, block_data) in ssa.blocks.entries() {
+        if let BlockData::Function { insts, .. } | BlockData::Block { insts, .. } = block_data {
+            for (position, inst) in insts.iter().enumerate() {
+                verifier.definitions.insert(*inst, (block, position));
+            }
+        }
+    }
+
+    let cfgs = ssa
+        .blocks
+        .entries()
+        .filter(|(_, block_data)| matches!(block_data, BlockData::Function { .. }))
+        .map(|(function, _)| Cfg::new(ssa, function))
+        .collect::<Vec<_>>();
+
+    // Blocks not reachable from any function are only checked locally.
+    let mut block_cfgs = HashMap::new();
+    for cfg in &cfgs {
+        for block in cfg.blocks() {
+            block_cfgs.insert(*block, cfg);
+        }
+    }
+
+    for (block, block_data) in ssa.blocks.entries() {
+        let insts = match block_data {
+            BlockData::ExternFunction { .. } => continue,
+            BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts,
+        };
+
+        match insts.last() {
+            Some(inst) if ssa.insts[*inst].is_terminator() => {}
+            Some(_) | None => verifier
+                .errors
+                .push(VerifyError::MissingTerminator { block }),
+        }
+
+        for (position, inst) in insts.iter().enumerate() {
+            if position + 1 != insts.len() && ssa.insts[*inst].is_terminator() {
+                verifier
+                    .errors
+                    .push(VerifyError::EarlyTerminator { block, position });
+            }
+
+            verifier.verify_inst(block, position, *inst, &block_cfgs);
+        }
+    }
+
+    if verifier.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(verifier.errors)
+    }
+}
+
+// Panic on malformed ssa in debug builds, after the generation and after
+// each pass.
+pub fn debug_verify(types: &Types, ssa: &Ssa) {
+    if cfg!(debug_assertions)
+        && let Err(errors) = verify(types, ssa)
+    {
+        let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        panic!("malformed ssa:\n{}", errors.join("\n"));
+    }
+}
+
+struct Verifier<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    // Block and position of every instruction.
+    definitions: HashMap<Inst, (Block, usize)>,
+    // `None` for the values whose type cannot be known because they are
+    // malformed, the errors are reported where they are defined.
+    value_types: HashMap<Inst, Option<Type>>,
+    errors: Vec<VerifyError>,
+}
+
+impl Verifier<'_> {
+    fn verify_inst(
+        &mut self,
+        block: Block,
+        position: usize,
+        inst: Inst,
+        block_cfgs: &HashMap<Block, &Cfg>,
+    ) {
+        let inst_data = &self.ssa.insts[inst];
+
+        // Uses in unreachable blocks are only checked to be defined.
+        let cfg = block_cfgs.get(&block);
+        let reachable = cfg.is_some();
+        let dominates = |definition| cfg.is_some_and(|cfg| cfg.dominates(definition, block));
+
+        for value in inst_data.operands() {
+            let defined = match value {
+                Expr::Const(const_) => {
+                    if let Val::None = self.ssa.consts.get(const_) {
+                        self.errors.push(VerifyError::UndefinedConst {
+                            block,
+                            position,
+                            const_,
+                        });
+                    }
+                    continue;
+                }
+                Expr::Inst(used) => match self.definitions.get(&used) {
+                    None => false,
+                    Some(_) if !reachable => true,
+                    Some(&(definition, definition_position)) if definition == block => {
+                        definition_position < position
+                    }
+                    Some(&(definition, _)) => dominates(definition),
+                },
+                Expr::BlockArg(definition) => match self.ssa.blocks[definition] {
+                    BlockData::ExternFunction { .. } => false,
+                    BlockData::Function { .. } | BlockData::Block { .. } => {
+                        !reachable || dominates(definition)
+                    }
+                },
+            };
+
+            if !defined {
+                self.errors.push(VerifyError::NotDominated {
+                    block,
+                    position,
+                    value,
+                });
+            }
+        }
+
+        match inst_data {
+            InstData::Field(base, field) => {
+                if self.value_type(*base).is_some() && self.inst_type(inst).is_none() {
+                    self.errors.push(VerifyError::InvalidField {
+                        block,
+                        position,
+                        field: *field,
+                    });
+                }
+            }
+            &InstData::Call { function, argument } => match &self.ssa.blocks[function] {
+                BlockData::ExternFunction { arg, .. } | BlockData::Function { arg, .. } => {
+                    self.verify_argument(block, position, argument, *arg)
+                }
+                BlockData::Block { .. } => self.errors.push(VerifyError::InvalidTarget {
+                    block,
+                    position,
+                    target: function,
+                }),
+            },
+            &InstData::TailCall { function, argument } => match &self.ssa.blocks[function] {
+                BlockData::Function { arg, .. } => {
+                    self.verify_argument(block, position, argument, *arg)
+                }
+                BlockData::ExternFunction { .. } | BlockData::Block { .. } => {
+                    self.errors.push(VerifyError::InvalidTarget {
+                        block,
+                        position,
+                        target: function,
+                    })
+                }
+            },
+            &InstData::Jump {
+                block: target,
+                argument,
+            } => self.verify_jump(block, position, target, argument),
+            &InstData::JumpCondition { then, else_, .. } => {
+                // Conditional jumps give no argument to their targets.
+                let unit = Expr::Const(ConstSentinel::Unit.to_index());
+                self.verify_jump(block, position, then, unit);
+                self.verify_jump(block, position, else_, unit);
+            }
+            _ => {}
+        }
+    }
+
+    fn verify_jump(&mut self, block: Block, position: usize, target: Block, argument: Expr) {
+        match &self.ssa.blocks[target] {
+            BlockData::Block { arg, .. } => self.verify_argument(block, position, argument, *arg),
+            BlockData::ExternFunction { .. } | BlockData::Function { .. } => {
+                self.errors.push(VerifyError::InvalidTarget {
+                    block,
+                    position,
+                    target,
+                })
+            }
+        }
+    }
+
+    fn verify_argument(&mut self, block: Block, position: usize, argument: Expr, expected: Type) {
+        if let Some(found) = self.value_type(argument)
+            && !compatible(self.types, found, expected)
+        {
+            self.errors.push(VerifyError::ArgumentType {
+                block,
+                position,
+                expected,
+                found,
+            });
+        }
+    }
+
+    // Same as `Ssa::expression_type` without panicking on malformed values.
+    fn value_type(&mut self, expr: Expr) -> Option<Type> {
+        match expr {
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => None,
+                Val::Sentinel(_) | Val::Value(_) => Some(self.ssa.const_type(const_)),
+            },
+            Expr::Inst(inst) if !self.definitions.contains_key(&inst) => None,
+            Expr::Inst(inst) => self.inst_type(inst),
+            Expr::BlockArg(_) => Some(self.ssa.expression_type(self.types, expr)),
+        }
+    }
+
+    fn inst_type(&mut self, inst: Inst) -> Option<Type> {
+        if let Some(type_) = self.value_types.get(&inst) {
+            return *type_;
+        }
+
+        // Instructions using themselves have no type.
+        self.value_types.insert(inst, None);
+
+        let type_ = match &self.ssa.insts[inst] {
+            &InstData::Field(base, field) => {
+                let base = self.value_type(base)?;
+                match self.types.get(base) {
+                    Val::Value(TypeData::Product { fields }) => {
+                        fields.get(field as usize).map(|(_, field)| *field)
+                    }
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => None,
+                }
+            }
+            &InstData::LoadPointer(pointer) => {
+                let pointer = self.value_type(pointer)?;
+                match self.types.get(pointer) {
+                    Val::Value(&TypeData::Pointer { pointee }) => Some(pointee),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => None,
+                }
+            }
+            &InstData::Load { base, .. } | &InstData::Pop { vec: base, .. } => {
+                let base = self.value_type(base)?;
+                match self.types.get(base) {
+                    Val::Value(
+                        &TypeData::Array { element, .. }
+                        | &TypeData::Slice { element }
+                        | &TypeData::Vec { element },
+                    ) => Some(element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => None,
+                }
+            }
+            &InstData::Add(lhs, _)
+            | &InstData::Sub(lhs, _)
+            | &InstData::Mul(lhs, _)
+            | &InstData::Div(lhs, _) => self.value_type(lhs),
+            _ => Some(self.ssa.instruction_type(self.types, inst)),
+        };
+
+        self.value_types.insert(inst, type_);
+        type_
+    }
+}
+
+// Whether a value of type `found` can be given where `expected` is, a
+// constant boolean is a boolean and a value that is never produced is
+// anything.
+fn compatible(types: &Types, found: Type, expected: Type) -> bool {
+    match (types.get(found), types.get(expected)) {
+        (Val::Sentinel(TypeSentinel::Never), _)
+        | (
+            Val::Sentinel(TypeSentinel::False | TypeSentinel::True),
+            Val::Sentinel(TypeSentinel::Bool),
+        ) => true,
+        (
+            Val::Value(TypeData::Product {
+                fields: found_fields,
+            }),
+            Val::Value(TypeData::Product {
+                fields: expected_fields,
+            }),
+        ) => {
+            found_fields.len() == expected_fields.len()
+                && found_fields
+                    .iter()
+                    .zip(expected_fields)
+                    .all(|((_, found), (_, expected))| compatible(types, *found, *expected))
+        }
+        _ => types_equals(types, found, expected),
+    }
+}
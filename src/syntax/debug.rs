@@ -66,15 +66,40 @@ impl Debug for DebugSyn<'_> {
             }
             SynData::Ascription { syn, type_ } => ("ascription".white(), &[*syn, *type_]),
             SynData::Access { syn, key } => ("access".white(), &[*syn, *key]),
+            SynData::Index { syn, index, .. } => ("index".white(), &[*syn, *index]),
+            SynData::Slice {
+                syn, start, end, ..
+            } => {
+                return [Some(*syn), *start, *end]
+                    .iter()
+                    .fold(
+                        &mut f.debug_tuple(&"slice".white().to_string()),
+                        |tuple, field| match field {
+                            Some(syn) => tuple.field(&debug_syn(*syn)),
+                            None => tuple.field(&format_args!("_")),
+                        },
+                    )
+                    .finish();
+            }
             SynData::EmptyParen(_) => ("empty_paren".white(), &[]),
             SynData::Paren(expr) => ("paren".white(), &[*expr]),
             SynData::EmptyCurly(_) => ("empty_curly".white(), &[]),
             SynData::Curly(expr) => ("curly".white(), &[*expr]),
+            SynData::EmptyBracket(_) => ("empty_bracket".white(), &[]),
+            SynData::Bracket(expr) => ("bracket".white(), &[*expr]),
             SynData::Tuple(syns) => ("tuple".white(), syns.as_slice()),
             SynData::Application { function, argument } => {
                 ("application".bright_green(), &[*function, *argument])
             }
-            SynData::Loop(body) => ("loop".bright_red(), &[*body]),
+            SynData::Loop { body, .. } => ("loop".bright_red(), &[*body]),
+            SynData::Break { value, .. } => ("break".bright_red(), value.as_slice()),
+            SynData::Continue { .. } => ("continue".bright_red(), &[]),
+            SynData::For {
+                pattern,
+                iterable,
+                body,
+                ..
+            } => ("for".bright_red(), &[*pattern, *iterable, *body]),
             SynData::Match(content) => ("match".bright_red(), &[*content]),
             SynData::If { condition, then } => ("if".bright_red(), &[*condition, *then]),
             SynData::IfElse {
@@ -212,18 +212,38 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
         Some(match self.tokens.peek() {
             Some((_, TokenKind::Colon)) => {
                 self.tokens.next();
-                let type_ = self.parse_ascription().unwrap();
+                let type_ = self.parse_type_application().unwrap();
                 self.syntax.push(SynData::Ascription { syn, type_ })
             }
             _ => syn,
         })
     }
 
+    // Types taking arguments such as `vec u32` bind tighter than the
+    // ascription.
+    fn parse_type_application(&mut self) -> Option<Syn> {
+        let syn = self.parse_ascription()?;
+
+        Some(match self.parse_type_application() {
+            Some(argument) => self.syntax.push(SynData::Application {
+                function: syn,
+                argument,
+            }),
+            None => syn,
+        })
+    }
+
     fn parse_access(&mut self) -> Option<Syn> {
         let mut syn = self.parse_terminal()?;
 
         while let Some((_, TokenKind::Dot)) = self.tokens.peek() {
             self.tokens.next();
+
+            if let Some((_, TokenKind::LeftBracket)) = self.tokens.peek() {
+                syn = self.parse_index(syn);
+                continue;
+            }
+
             let key = self.parse_terminal().unwrap();
             syn = self.syntax.push(SynData::Access { syn, key });
         }
@@ -236,6 +256,7 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
         Some(match kind {
             TokenKind::LeftParen => self.parse_paren(),
             TokenKind::LeftCurly => self.parse_curly(),
+            TokenKind::LeftBracket => self.parse_bracket(),
 
             TokenKind::Number => {
                 self.tokens.next();
@@ -254,8 +275,21 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
             TokenKind::Loop => {
                 self.tokens.next();
                 let body = self.parse_application().unwrap();
-                self.syntax.push(SynData::Loop(body))
+                self.syntax.push(SynData::Loop { label: None, body })
+            }
+            TokenKind::Label => self.parse_labeled_loop(),
+            TokenKind::Break => {
+                self.tokens.next();
+                let label = self.parse_optional_label();
+                let value = self.parse_application();
+                self.syntax.push(SynData::Break { label, value })
             }
+            TokenKind::Continue => {
+                self.tokens.next();
+                let label = self.parse_optional_label();
+                self.syntax.push(SynData::Continue { label })
+            }
+            TokenKind::For => self.parse_for(None),
             TokenKind::Match => self.parse_match(),
             TokenKind::If => self.parse_if(),
             TokenKind::False => {
@@ -272,6 +306,7 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
             TokenKind::EqualGreater
             | TokenKind::HyphenGreater
             | TokenKind::DoubleEqual
+            | TokenKind::DoubleDot
             | TokenKind::Equal
             | TokenKind::Plus
             | TokenKind::Hyphen
@@ -283,6 +318,8 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
             | TokenKind::Dot
             | TokenKind::RightParen
             | TokenKind::RightCurly
+            | TokenKind::RightBracket
+            | TokenKind::In
             | TokenKind::Then
             | TokenKind::Else
             | TokenKind::StringEnd
@@ -327,6 +364,58 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
         }
     }
 
+    fn parse_bracket(&mut self) -> Syn {
+        let Some((token, TokenKind::LeftBracket)) = self.tokens.next() else {
+            panic!()
+        };
+
+        let expr = self.parse_chain();
+
+        let Some((_, TokenKind::RightBracket)) = self.tokens.next() else {
+            panic!();
+        };
+
+        match expr {
+            Some(expr) => self.syntax.push(SynData::Bracket(expr)),
+            None => self.syntax.push(SynData::EmptyBracket(token)),
+        }
+    }
+
+    fn parse_index(&mut self, syn: Syn) -> Syn {
+        let Some((bracket, TokenKind::LeftBracket)) = self.tokens.next() else {
+            panic!()
+        };
+
+        let start = self.parse_application();
+
+        let syn = if self
+            .tokens
+            .next_if(|(_, token)| *token == TokenKind::DoubleDot)
+            .is_some()
+        {
+            let end = self.parse_application();
+
+            self.syntax.push(SynData::Slice {
+                syn,
+                bracket,
+                start,
+                end,
+            })
+        } else {
+            self.syntax.push(SynData::Index {
+                syn,
+                bracket,
+                index: start.unwrap(),
+            })
+        };
+
+        let Some((_, TokenKind::RightBracket)) = self.tokens.next() else {
+            panic!();
+        };
+
+        syn
+    }
+
     fn parse_let(&mut self) -> Syn {
         let Some((_, TokenKind::Let)) = self.tokens.next() else {
             panic!()
@@ -343,6 +432,59 @@ impl<I: Iterator<Item = (Token, TokenKind)>> Parser<I> {
         self.syntax.push(SynData::Binding { pattern, value })
     }
 
+    fn parse_labeled_loop(&mut self) -> Syn {
+        let Some((label, TokenKind::Label)) = self.tokens.next() else {
+            panic!()
+        };
+
+        let Some((_, TokenKind::Colon)) = self.tokens.next() else {
+            panic!()
+        };
+
+        match self.tokens.peek() {
+            Some((_, TokenKind::Loop)) => {
+                self.tokens.next();
+
+                let body = self.parse_application().unwrap();
+
+                self.syntax.push(SynData::Loop {
+                    label: Some(label),
+                    body,
+                })
+            }
+            Some((_, TokenKind::For)) => self.parse_for(Some(label)),
+            _ => panic!(),
+        }
+    }
+
+    fn parse_for(&mut self, label: Option<Token>) -> Syn {
+        let Some((_, TokenKind::For)) = self.tokens.next() else {
+            panic!()
+        };
+
+        let pattern = self.parse_terminal().unwrap();
+
+        let Some((_, TokenKind::In)) = self.tokens.next() else {
+            panic!()
+        };
+
+        let iterable = self.parse_access().unwrap();
+        let body = self.parse_application().unwrap();
+
+        self.syntax.push(SynData::For {
+            label,
+            pattern,
+            iterable,
+            body,
+        })
+    }
+
+    fn parse_optional_label(&mut self) -> Option<Token> {
+        self.tokens
+            .next_if(|(_, token)| *token == TokenKind::Label)
+            .map(|(token, _)| token)
+    }
+
     fn parse_match(&mut self) -> Syn {
         let Some((_, TokenKind::Match)) = self.tokens.next() else {
             panic!()
@@ -47,10 +47,23 @@ pub enum SynData {
         syn: Syn,
         key: Syn,
     },
+    Index {
+        syn: Syn,
+        bracket: Token,
+        index: Syn,
+    },
+    Slice {
+        syn: Syn,
+        bracket: Token,
+        start: Option<Syn>,
+        end: Option<Syn>,
+    },
     EmptyParen(Token),
     Paren(Syn),
     EmptyCurly(Token),
     Curly(Syn),
+    EmptyBracket(Token),
+    Bracket(Syn),
     // TODO: Find a solution to avoid nested allocation from the [`Vec<Syn>`],
     // maybe reference a range of syns (with a start and a length) in the main
     // vector.
@@ -59,7 +72,23 @@ pub enum SynData {
         function: Syn,
         argument: Syn,
     },
-    Loop(Syn),
+    Loop {
+        label: Option<Token>,
+        body: Syn,
+    },
+    Break {
+        label: Option<Token>,
+        value: Option<Syn>,
+    },
+    Continue {
+        label: Option<Token>,
+    },
+    For {
+        label: Option<Token>,
+        pattern: Syn,
+        iterable: Syn,
+        body: Syn,
+    },
     Match(Syn),
     If {
         condition: Syn,
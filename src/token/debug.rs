@@ -1,8 +1,8 @@
 use colored::Colorize as _;
 
 use crate::token::{
-    TokenKind, Tokens, parse_identifer, parse_string_escape, parse_string_segment, parse_u64,
-    token_length,
+    TokenKind, Tokens, parse_identifer, parse_label, parse_string_escape, parse_string_segment,
+    parse_u64, token_length,
 };
 
 pub fn debug(source: &str, tokens: &Tokens) {
@@ -15,6 +15,7 @@ pub fn debug(source: &str, tokens: &Tokens) {
             TokenKind::EqualGreater => "=>".bright_yellow(),
             TokenKind::HyphenGreater => "->".bright_yellow(),
             TokenKind::DoubleEqual => "==".bright_yellow(),
+            TokenKind::DoubleDot => "..".bright_yellow(),
 
             TokenKind::Equal => "=".bright_yellow(),
             TokenKind::Plus => "+".bright_yellow(),
@@ -31,6 +32,8 @@ pub fn debug(source: &str, tokens: &Tokens) {
             TokenKind::RightParen => ")".bright_white(),
             TokenKind::LeftCurly => "{".bright_white(),
             TokenKind::RightCurly => "}".bright_white(),
+            TokenKind::LeftBracket => "[".bright_white(),
+            TokenKind::RightBracket => "]".bright_white(),
 
             TokenKind::Number => parse_u64(source, &tokens.offsets, token)
                 .to_string()
@@ -39,12 +42,19 @@ pub fn debug(source: &str, tokens: &Tokens) {
             TokenKind::Let => "let".bright_red(),
             TokenKind::Mut => "mut".bright_red(),
             TokenKind::Loop => "loop".bright_red(),
+            TokenKind::Break => "break".bright_red(),
+            TokenKind::Continue => "continue".bright_red(),
+            TokenKind::For => "for".bright_red(),
+            TokenKind::In => "in".bright_red(),
             TokenKind::Match => "match".bright_red(),
             TokenKind::If => "if".bright_red(),
             TokenKind::Then => "then".bright_red(),
             TokenKind::Else => "else".bright_red(),
             TokenKind::False => "false".bright_purple(),
             TokenKind::True => "true".bright_purple(),
+            TokenKind::Label => format!("'{}", parse_label(source, &tokens.offsets, token))
+                .bright_cyan()
+                .italic(),
 
             TokenKind::StringStart => "\"".bright_yellow().bold(),
             TokenKind::StringEnd => "\"".bright_yellow().bold(),
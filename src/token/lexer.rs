@@ -57,6 +57,7 @@ pub fn lex(source: &str) -> Tokens {
                 '=' if chars.next_if(|(_, ch)| *ch == '>').is_some() => TokenKind::EqualGreater,
                 '-' if chars.next_if(|(_, ch)| *ch == '>').is_some() => TokenKind::HyphenGreater,
                 '=' if chars.next_if(|(_, ch)| *ch == '=').is_some() => TokenKind::DoubleEqual,
+                '.' if chars.next_if(|(_, ch)| *ch == '.').is_some() => TokenKind::DoubleDot,
                 '=' => TokenKind::Equal,
                 '+' => TokenKind::Plus,
                 '-' => TokenKind::Hyphen,
@@ -70,6 +71,8 @@ pub fn lex(source: &str) -> Tokens {
 
                 '(' => TokenKind::LeftParen,
                 ')' => TokenKind::RightParen,
+                '[' => TokenKind::LeftBracket,
+                ']' => TokenKind::RightBracket,
                 '{' => {
                     if let Some(curly_count) = interpolations_curly_nesting.last_mut() {
                         *curly_count += 1;
@@ -108,6 +111,10 @@ pub fn lex(source: &str) -> Tokens {
                         "let" => TokenKind::Let,
                         "mut" => TokenKind::Mut,
                         "loop" => TokenKind::Loop,
+                        "break" => TokenKind::Break,
+                        "continue" => TokenKind::Continue,
+                        "for" => TokenKind::For,
+                        "in" => TokenKind::In,
                         "match" => TokenKind::Match,
                         "if" => TokenKind::If,
                         "then" => TokenKind::Then,
@@ -117,6 +124,17 @@ pub fn lex(source: &str) -> Tokens {
                         _ => TokenKind::Ident,
                     }
                 }
+                '\'' if chars
+                    .peek()
+                    .is_some_and(|(_, ch)| unicode_ident::is_xid_start(*ch)) =>
+                {
+                    while chars
+                        .next_if(|(_, ch)| unicode_ident::is_xid_continue(*ch))
+                        .is_some()
+                    {}
+
+                    TokenKind::Label
+                }
                 _ if char.is_whitespace() => {
                     while chars.next_if(|(_, ch)| ch.is_whitespace()).is_some() {}
                     continue;
@@ -1,11 +1,15 @@
 mod debug;
 mod lexer;
 mod parse;
+#[allow(clippy::module_inception)]
 mod token;
 
 pub use self::{
     debug::debug,
     lexer::lex,
-    parse::{parse_identifer, parse_string_escape, parse_string_segment, parse_u64, token_length},
+    parse::{
+        parse_identifer, parse_label, parse_string_escape, parse_string_segment, parse_u64,
+        token_length,
+    },
     token::{Token, TokenKind, TokenKinds, TokenOffsets, TokenSentinel, Tokens},
 };
@@ -20,6 +20,21 @@ pub fn parse_identifer<'a>(source: &'a str, tokens: &TokenOffsets, token: Token)
         .unwrap_or(source_from_token.len())]
 }
 
+pub fn parse_label<'a>(source: &'a str, tokens: &TokenOffsets, token: Token) -> &'a str {
+    let source_from_token = &source[tokens[token]..];
+
+    assert_eq!(source_from_token.chars().next(), Some('\''));
+
+    let label = &source_from_token[1..];
+
+    &label[..label
+        .char_indices()
+        .skip(1)
+        .find(|(_, ch)| !unicode_ident::is_xid_continue(*ch))
+        .map(|(i, _)| i)
+        .unwrap_or(label.len())]
+}
+
 pub fn parse_u64(source: &str, tokens: &TokenOffsets, token: Token) -> u64 {
     let source_from_token = &source[tokens[token]..];
 
@@ -66,7 +81,10 @@ pub fn parse_string_escape(source: &str, tokens: &TokenOffsets, token: Token) ->
 
 pub fn token_length(source: &str, tokens: &Tokens, token: Token) -> usize {
     match tokens.kinds[token] {
-        TokenKind::EqualGreater | TokenKind::HyphenGreater | TokenKind::DoubleEqual => 2,
+        TokenKind::EqualGreater
+        | TokenKind::HyphenGreater
+        | TokenKind::DoubleEqual
+        | TokenKind::DoubleDot => 2,
         TokenKind::Equal
         | TokenKind::Plus
         | TokenKind::Hyphen
@@ -79,7 +97,9 @@ pub fn token_length(source: &str, tokens: &Tokens, token: Token) -> usize {
         | TokenKind::LeftParen
         | TokenKind::RightParen
         | TokenKind::LeftCurly
-        | TokenKind::RightCurly => 1,
+        | TokenKind::RightCurly
+        | TokenKind::LeftBracket
+        | TokenKind::RightBracket => 1,
         TokenKind::Number => {
             let source_from_token = &source[tokens.offsets[token]..];
             source_from_token
@@ -93,12 +113,17 @@ pub fn token_length(source: &str, tokens: &Tokens, token: Token) -> usize {
         | TokenKind::Let
         | TokenKind::Mut
         | TokenKind::Loop
+        | TokenKind::Break
+        | TokenKind::Continue
+        | TokenKind::For
+        | TokenKind::In
         | TokenKind::Match
         | TokenKind::If
         | TokenKind::Then
         | TokenKind::Else
         | TokenKind::False
         | TokenKind::True => parse_identifer(source, &tokens.offsets, token).len(),
+        TokenKind::Label => parse_label(source, &tokens.offsets, token).len() + 1,
         TokenKind::StringStart | TokenKind::StringEnd => 1,
         TokenKind::StringSegment => parse_string_segment(source, &tokens.offsets, token).len(),
         TokenKind::StringEscape => 2,
@@ -5,6 +5,7 @@ pub enum TokenKind {
     EqualGreater,
     HyphenGreater,
     DoubleEqual,
+    DoubleDot,
 
     Equal,
     Plus,
@@ -21,18 +22,25 @@ pub enum TokenKind {
     RightParen,
     LeftCurly,
     RightCurly,
+    LeftBracket,
+    RightBracket,
 
     Number,
     Ident,
     Let,
     Mut,
     Loop,
+    Break,
+    Continue,
+    For,
+    In,
     Match,
     If,
     Then,
     Else,
     False,
     True,
+    Label,
 
     StringStart,
     StringEnd,
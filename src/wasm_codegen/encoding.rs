@@ -0,0 +1,382 @@
+// Binary encoding of wasm modules, only with `i32` values.
+
+pub const PAGE_SIZE: u32 = 0x10000;
+
+// Code of a function, instructions are named after their text format.
+#[derive(Default)]
+pub struct Code {
+    pub bytes: Vec<u8>,
+}
+
+// Blocks, loops and ifs are always of the empty block type.
+const EMPTY_BLOCK_TYPE: u8 = 0x40;
+
+impl Code {
+    fn opcode(&mut self, opcode: u8) -> &mut Self {
+        self.bytes.push(opcode);
+        self
+    }
+
+    fn memory_access(&mut self, opcode: u8, align: u32, offset: u32) -> &mut Self {
+        self.bytes.push(opcode);
+        uleb128(&mut self.bytes, align);
+        uleb128(&mut self.bytes, offset);
+        self
+    }
+
+    pub fn unreachable(&mut self) -> &mut Self {
+        self.opcode(0x00)
+    }
+
+    pub fn block(&mut self) -> &mut Self {
+        self.bytes.extend([0x02, EMPTY_BLOCK_TYPE]);
+        self
+    }
+
+    pub fn loop_(&mut self) -> &mut Self {
+        self.bytes.extend([0x03, EMPTY_BLOCK_TYPE]);
+        self
+    }
+
+    pub fn if_(&mut self) -> &mut Self {
+        self.bytes.extend([0x04, EMPTY_BLOCK_TYPE]);
+        self
+    }
+
+    pub fn else_(&mut self) -> &mut Self {
+        self.opcode(0x05)
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        self.opcode(0x0b)
+    }
+
+    pub fn br(&mut self, label: u32) -> &mut Self {
+        self.bytes.push(0x0c);
+        uleb128(&mut self.bytes, label);
+        self
+    }
+
+    pub fn br_if(&mut self, label: u32) -> &mut Self {
+        self.bytes.push(0x0d);
+        uleb128(&mut self.bytes, label);
+        self
+    }
+
+    pub fn return_(&mut self) -> &mut Self {
+        self.opcode(0x0f)
+    }
+
+    pub fn call(&mut self, function: u32) -> &mut Self {
+        self.bytes.push(0x10);
+        uleb128(&mut self.bytes, function);
+        self
+    }
+
+    pub fn return_call(&mut self, function: u32) -> &mut Self {
+        self.bytes.push(0x12);
+        uleb128(&mut self.bytes, function);
+        self
+    }
+
+    pub fn drop(&mut self) -> &mut Self {
+        self.opcode(0x1a)
+    }
+
+    pub fn local_get(&mut self, local: u32) -> &mut Self {
+        self.bytes.push(0x20);
+        uleb128(&mut self.bytes, local);
+        self
+    }
+
+    pub fn local_set(&mut self, local: u32) -> &mut Self {
+        self.bytes.push(0x21);
+        uleb128(&mut self.bytes, local);
+        self
+    }
+
+    pub fn local_tee(&mut self, local: u32) -> &mut Self {
+        self.bytes.push(0x22);
+        uleb128(&mut self.bytes, local);
+        self
+    }
+
+    pub fn global_get(&mut self, global: u32) -> &mut Self {
+        self.bytes.push(0x23);
+        uleb128(&mut self.bytes, global);
+        self
+    }
+
+    pub fn global_set(&mut self, global: u32) -> &mut Self {
+        self.bytes.push(0x24);
+        uleb128(&mut self.bytes, global);
+        self
+    }
+
+    pub fn i32_load(&mut self, offset: u32) -> &mut Self {
+        self.memory_access(0x28, 2, offset)
+    }
+
+    pub fn i32_store(&mut self, offset: u32) -> &mut Self {
+        self.memory_access(0x36, 2, offset)
+    }
+
+    pub fn i32_store8(&mut self, offset: u32) -> &mut Self {
+        self.memory_access(0x3a, 0, offset)
+    }
+
+    pub fn memory_size(&mut self) -> &mut Self {
+        self.bytes.extend([0x3f, 0x00]);
+        self
+    }
+
+    pub fn memory_grow(&mut self) -> &mut Self {
+        self.bytes.extend([0x40, 0x00]);
+        self
+    }
+
+    pub fn i32_const(&mut self, value: u32) -> &mut Self {
+        self.bytes.push(0x41);
+        sleb128(&mut self.bytes, value as i32);
+        self
+    }
+
+    pub fn i32_eqz(&mut self) -> &mut Self {
+        self.opcode(0x45)
+    }
+
+    pub fn i32_eq(&mut self) -> &mut Self {
+        self.opcode(0x46)
+    }
+
+    pub fn i32_gt_u(&mut self) -> &mut Self {
+        self.opcode(0x4b)
+    }
+
+    pub fn i32_le_u(&mut self) -> &mut Self {
+        self.opcode(0x4d)
+    }
+
+    pub fn i32_ge_u(&mut self) -> &mut Self {
+        self.opcode(0x4f)
+    }
+
+    pub fn i32_add(&mut self) -> &mut Self {
+        self.opcode(0x6a)
+    }
+
+    pub fn i32_sub(&mut self) -> &mut Self {
+        self.opcode(0x6b)
+    }
+
+    pub fn i32_mul(&mut self) -> &mut Self {
+        self.opcode(0x6c)
+    }
+
+    pub fn i32_div_u(&mut self) -> &mut Self {
+        self.opcode(0x6e)
+    }
+
+    pub fn i32_rem_u(&mut self) -> &mut Self {
+        self.opcode(0x70)
+    }
+
+    pub fn i32_and(&mut self) -> &mut Self {
+        self.opcode(0x71)
+    }
+
+    pub fn i32_or(&mut self) -> &mut Self {
+        self.opcode(0x72)
+    }
+
+    pub fn i32_shl(&mut self) -> &mut Self {
+        self.opcode(0x74)
+    }
+
+    // From the bulk memory operations.
+    pub fn memory_copy(&mut self) -> &mut Self {
+        self.bytes.extend([0xfc, 0x0a, 0x00, 0x00]);
+        self
+    }
+}
+
+// Signature of a function taking and returning `i32` values only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub params: u32,
+    pub results: u32,
+}
+
+pub struct Import {
+    pub module: &'static str,
+    pub name: &'static str,
+    pub signature: Signature,
+}
+
+pub struct Function {
+    pub signature: Signature,
+    // Locals in addition to the parameters.
+    pub locals: u32,
+    pub code: Code,
+}
+
+#[derive(Default)]
+pub struct Module {
+    // Imported functions are numbered before the defined functions.
+    pub imports: Vec<Import>,
+    pub functions: Vec<Function>,
+    pub memory_pages: u32,
+    // Initial values of the mutable globals.
+    pub globals: Vec<u32>,
+    // Exported functions, the memory is always exported as `memory`.
+    pub exports: Vec<(&'static str, u32)>,
+    // Bytes copied to memory at the given address on instantiation.
+    pub data: Vec<(u32, Vec<u8>)>,
+}
+
+impl Module {
+    pub fn write(&self) -> Vec<u8> {
+        let mut bytes = vec![0x00, b'a', b's', b'm', 0x01, 0x00, 0x00, 0x00];
+
+        let mut signatures = Vec::new();
+        let mut signature_index =
+            |signature: Signature| match signatures.iter().position(|other| *other == signature) {
+                Some(index) => index as u32,
+                None => {
+                    signatures.push(signature);
+                    signatures.len() as u32 - 1
+                }
+            };
+
+        let imports_signatures = self
+            .imports
+            .iter()
+            .map(|import| signature_index(import.signature))
+            .collect::<Vec<_>>();
+        let functions_signatures = self
+            .functions
+            .iter()
+            .map(|function| signature_index(function.signature))
+            .collect::<Vec<_>>();
+
+        section(&mut bytes, 1, signatures.len(), |section| {
+            for signature in &signatures {
+                section.push(0x60);
+                uleb128(section, signature.params);
+                section.extend((0..signature.params).map(|_| I32));
+                uleb128(section, signature.results);
+                section.extend((0..signature.results).map(|_| I32));
+            }
+        });
+
+        section(&mut bytes, 2, self.imports.len(), |section| {
+            for (import, signature) in self.imports.iter().zip(&imports_signatures) {
+                name(section, import.module);
+                name(section, import.name);
+                section.push(0x00);
+                uleb128(section, *signature);
+            }
+        });
+
+        section(&mut bytes, 3, self.functions.len(), |section| {
+            for signature in &functions_signatures {
+                uleb128(section, *signature);
+            }
+        });
+
+        section(&mut bytes, 5, 1, |section| {
+            section.push(0x00);
+            uleb128(section, self.memory_pages);
+        });
+
+        section(&mut bytes, 6, self.globals.len(), |section| {
+            for value in &self.globals {
+                section.extend([I32, 0x01, 0x41]);
+                sleb128(section, *value as i32);
+                section.push(0x0b);
+            }
+        });
+
+        section(&mut bytes, 7, self.exports.len() + 1, |section| {
+            for (export, function) in &self.exports {
+                name(section, export);
+                section.push(0x00);
+                uleb128(section, *function);
+            }
+            name(section, "memory");
+            section.extend([0x02, 0x00]);
+        });
+
+        section(&mut bytes, 10, self.functions.len(), |section| {
+            for function in &self.functions {
+                let mut body = Vec::new();
+                if function.locals == 0 {
+                    body.push(0x00);
+                } else {
+                    body.push(0x01);
+                    uleb128(&mut body, function.locals);
+                    body.push(I32);
+                }
+                body.extend(&function.code.bytes);
+                body.push(0x0b);
+
+                uleb128(section, body.len() as u32);
+                section.extend(body);
+            }
+        });
+
+        section(&mut bytes, 11, self.data.len(), |section| {
+            for (address, data) in &self.data {
+                section.extend([0x00, 0x41]);
+                sleb128(section, *address as i32);
+                section.push(0x0b);
+                uleb128(section, data.len() as u32);
+                section.extend(data);
+            }
+        });
+
+        bytes
+    }
+}
+
+const I32: u8 = 0x7f;
+
+// Append a section of `count` entries written by `entries`.
+fn section(bytes: &mut Vec<u8>, id: u8, count: usize, entries: impl FnOnce(&mut Vec<u8>)) {
+    let mut section = Vec::new();
+    uleb128(&mut section, count as u32);
+    entries(&mut section);
+
+    bytes.push(id);
+    uleb128(bytes, section.len() as u32);
+    bytes.extend(section);
+}
+
+fn name(bytes: &mut Vec<u8>, name: &str) {
+    uleb128(bytes, name.len() as u32);
+    bytes.extend(name.as_bytes());
+}
+
+fn uleb128(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sleb128(bytes: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,1075 @@
+mod encoding;
+mod runtime;
+
+use std::collections::{HashMap, HashSet};
+
+use self::encoding::{Code, Function, Module, Signature};
+
+use crate::{
+    key_vec::{KeyVec, Val},
+    semantic::{Type, TypeData, TypeSentinel, Types},
+    ssa::{
+        Block, BlockData, BlockSentinel, Cfg, ConstData, ConstSentinel, Expr, Inst, InstData,
+        InstSentinel, Location, Ssa,
+    },
+};
+
+// Generate a wasm module in the binary format, runnable by WASI runtimes from
+// its `_start` export.
+pub fn generate(types: &Types, ssa: &Ssa) -> Vec<u8> {
+    let mut generator = Generator {
+        types,
+        ssa,
+        functions_indices: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        args_values: KeyVec::from_vec((0..ssa.blocks.len()).map(|_| None).collect()),
+        insts_values: KeyVec::from_vec((0..ssa.insts.len()).map(|_| None).collect()),
+        module: Module {
+            imports: runtime::imports(),
+            functions: runtime::functions(&ssa.path),
+            memory_pages: runtime::MEMORY_PAGES,
+            globals: runtime::globals(),
+            exports: Vec::new(),
+            data: vec![runtime::data(&ssa.path)],
+        },
+        heap_offsets: Vec::new(),
+    };
+
+    generator.generate();
+
+    if !generator.heap_offsets.is_empty() {
+        let tables = generator
+            .heap_offsets
+            .iter()
+            .flatten()
+            .flat_map(|offset| offset.to_le_bytes())
+            .collect();
+        generator
+            .module
+            .data
+            .push((runtime::tables(&ssa.path), tables));
+    }
+
+    generator.module.write()
+}
+
+// Instantiate the module at the path given as argument and run it, exiting
+// with the code given to `proc_exit`.
+pub const WASI_RUNNER: &str = "
+const { WASI } = require('node:wasi');
+const wasi = new WASI({ version: 'preview1' });
+const wasm = new WebAssembly.Module(require('node:fs').readFileSync(process.argv[1]));
+process.exit(wasi.start(new WebAssembly.Instance(wasm, wasi.getImportObject())));
+";
+
+// Values of 4 bytes live in locals, products, arrays and slices live in the
+// frame of their function, in memory below the stack pointer global. Values
+// of every type are made of 4 bytes words: pointers and vecs are 4 bytes and
+// slices are a pointer followed by the length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Value {
+    None,
+    Local(u32),
+    // Offset from the frame pointer.
+    Memory(u32),
+}
+
+// Memory at an offset from the address held by a local.
+#[derive(Clone, Copy)]
+struct Place {
+    base: u32,
+    offset: u32,
+}
+
+// Enclosing constructs of the generated code, targets of branches.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    // Branching to the loop continues at its header.
+    LoopHeadedBy(Block),
+    // Branching to the block continues after its end, where the code of the
+    // block of the ssa follows.
+    BlockFollowedBy(Block),
+    IfThenElse,
+}
+
+struct Generator<'a> {
+    types: &'a Types,
+    ssa: &'a Ssa,
+    functions_indices: KeyVec<BlockSentinel, Option<u32>>,
+    args_values: KeyVec<BlockSentinel, Option<Value>>,
+    insts_values: KeyVec<InstSentinel, Option<Value>>,
+    module: Module,
+    // Offsets of the vecs held by the elements of vecs, the tables follow
+    // each other from `runtime::tables`.
+    heap_offsets: Vec<Vec<u32>>,
+}
+
+struct Frame {
+    code: Code,
+    return_type: Type,
+    // Local holding where to write the returned value when it lives in
+    // memory.
+    return_pointer: Option<u32>,
+    frame_pointer: u32,
+    // Value of the stack pointer global on entry, restored on return.
+    stack_pointer: u32,
+    // Local holding an address within the generated code of a single
+    // instruction.
+    address: u32,
+    locals: u32,
+    frame_size: u32,
+    // Structure of the control flow graph, for the stackifier.
+    cfg: Cfg,
+    forward_edges: HashMap<Block, usize>,
+    loop_headers: HashSet<Block>,
+    context: Vec<Context>,
+}
+
+impl Generator<'_> {
+    fn generate(&mut self) {
+        let mut index = (self.module.imports.len() + self.module.functions.len()) as u32;
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            match block_data {
+                BlockData::ExternFunction { name, .. } => {
+                    self.functions_indices[block] = Some(match name.as_str() {
+                        "builtin_print" => runtime::PRINT,
+                        "builtin_alloc" => runtime::ALLOC,
+                        "builtin_free" => runtime::FREE,
+                        _ => panic!("extern function {name} is not in the wasm runtime"),
+                    });
+                }
+                BlockData::Function { .. } => {
+                    self.functions_indices[block] = Some(index);
+                    index += 1;
+                }
+                BlockData::Block { .. } => {}
+            }
+        }
+
+        let mut main = None;
+
+        for (block, block_data) in self.ssa.blocks.entries() {
+            if let BlockData::Function { name, ret, .. } = block_data {
+                if name == "main" {
+                    main = Some((self.functions_indices[block].unwrap(), *ret));
+                }
+
+                let function = self.generate_function(block);
+                self.module.functions.push(function);
+            }
+        }
+
+        let (main, ret) = main.expect("no main function");
+
+        let mut code = Code::default();
+        code.call(main);
+        if self.is_local(ret) {
+            code.drop();
+        }
+
+        self.module.exports.push(("_start", index));
+        self.module.functions.push(Function {
+            signature: Signature {
+                params: 0,
+                results: 0,
+            },
+            locals: 0,
+            code,
+        });
+    }
+
+    fn generate_function(&mut self, function: Block) -> Function {
+        let ssa = self.ssa;
+
+        let BlockData::Function { arg, ret, .. } = &ssa.blocks[function] else {
+            panic!()
+        };
+
+        let signature = self.signature(*arg, *ret);
+
+        let mut params = 0..signature.params;
+
+        let return_pointer = self.is_memory(*ret).then(|| params.next().unwrap());
+        let arg_param = params.next();
+
+        let mut frame = Frame {
+            code: Code::default(),
+            return_type: *ret,
+            return_pointer,
+            frame_pointer: signature.params,
+            stack_pointer: signature.params + 1,
+            address: signature.params + 2,
+            locals: signature.params + 3,
+            frame_size: 0,
+            cfg: Cfg::new(ssa, function),
+            forward_edges: HashMap::new(),
+            loop_headers: HashSet::new(),
+            context: Vec::new(),
+        };
+
+        let blocks = frame.cfg.blocks().to_vec();
+        self.reserve_values(&blocks, &mut frame);
+        self.count_edges(&blocks, &mut frame);
+
+        // The argument is copied from the caller when it lives in memory, so
+        // that the caller does not need to keep it alive.
+        match (self.args_values[function].unwrap(), arg_param) {
+            (Value::Memory(offset), Some(param)) => {
+                frame.code.local_get(frame.frame_pointer);
+                if offset != 0 {
+                    frame.code.i32_const(offset).i32_add();
+                }
+                frame
+                    .code
+                    .local_get(param)
+                    .i32_const(self.type_size(*arg))
+                    .memory_copy();
+            }
+            (Value::Local(local), Some(param)) => {
+                frame.code.local_get(param).local_set(local);
+            }
+            _ => {}
+        }
+
+        self.generate_tree(function, &mut frame);
+
+        // Every path ends with a return.
+        frame.code.unreachable();
+
+        let frame_size = frame.frame_size.next_multiple_of(8);
+
+        let mut code = Code::default();
+        code.global_get(runtime::STACK_POINTER)
+            .local_tee(frame.stack_pointer)
+            .i32_const(frame_size)
+            .i32_sub()
+            .local_tee(frame.frame_pointer)
+            .global_set(runtime::STACK_POINTER);
+        code.bytes.extend(frame.code.bytes);
+
+        Function {
+            signature,
+            locals: frame.locals - signature.params,
+            code,
+        }
+    }
+
+    // Values of 4 bytes are passed and returned as is, others are passed by
+    // address. The caller gives the address where to write the returned value
+    // as the first parameter.
+    fn signature(&self, arg: Type, ret: Type) -> Signature {
+        Signature {
+            params: self.is_memory(ret) as u32 + (self.type_size(arg) != 0) as u32,
+            results: self.is_local(ret) as u32,
+        }
+    }
+
+    // Give a local or memory in the frame to every value of the function.
+    fn reserve_values(&mut self, blocks: &[Block], frame: &mut Frame) {
+        let ssa = self.ssa;
+
+        for &block in blocks {
+            let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                &ssa.blocks[block]
+            else {
+                panic!()
+            };
+
+            let arg_type = self.expr_type(Expr::BlockArg(block));
+            self.args_values[block] = Some(self.reserve_value(arg_type, frame));
+
+            for inst in insts {
+                let inst_type = self.expr_type(Expr::Inst(*inst));
+                self.insts_values[*inst] = Some(self.reserve_value(inst_type, frame));
+            }
+        }
+    }
+
+    fn reserve_value(&self, type_: Type, frame: &mut Frame) -> Value {
+        if self.is_memory(type_) {
+            Value::Memory(self.reserve_memory(self.type_size(type_), frame))
+        } else if self.is_local(type_) {
+            frame.locals += 1;
+            Value::Local(frame.locals - 1)
+        } else {
+            Value::None
+        }
+    }
+
+    fn reserve_memory(&self, size: u32, frame: &mut Frame) -> u32 {
+        let offset = frame.frame_size;
+        frame.frame_size += size;
+        offset
+    }
+
+    // Count the forward edges to each block and find the targets of back
+    // edges.
+    fn count_edges(&self, blocks: &[Block], frame: &mut Frame) {
+        for &block in blocks {
+            for &successor in frame.cfg.successors(block) {
+                if frame.cfg.order(successor) > frame.cfg.order(block) {
+                    *frame.forward_edges.entry(successor).or_default() += 1;
+                } else {
+                    frame.loop_headers.insert(successor);
+                }
+            }
+        }
+    }
+
+    // Structure the control flow following "Beyond Relooper" by Norman Ramsey:
+    // a block with several forward edges to it follows the code of its
+    // immediate dominator, after a wasm block that is the target of these
+    // edges. Loop headers are wrapped in a wasm loop that is the target of
+    // the back edges. Other blocks are generated where they are jumped to.
+    fn generate_tree(&mut self, block: Block, frame: &mut Frame) {
+        let mut merges = frame
+            .cfg
+            .dominated(block)
+            .iter()
+            .copied()
+            .filter(|dominated| frame.forward_edges.get(dominated).copied().unwrap_or(0) >= 2)
+            .collect::<Vec<_>>();
+        merges.sort_by_key(|merge| std::cmp::Reverse(frame.cfg.order(*merge)));
+
+        if frame.loop_headers.contains(&block) {
+            frame.code.loop_();
+            frame.context.push(Context::LoopHeadedBy(block));
+            self.generate_within(block, &merges, frame);
+            frame.context.pop();
+            frame.code.end();
+        } else {
+            self.generate_within(block, &merges, frame);
+        }
+    }
+
+    fn generate_within(&mut self, block: Block, merges: &[Block], frame: &mut Frame) {
+        match merges.split_first() {
+            Some((merge, merges)) => {
+                frame.code.block();
+                frame.context.push(Context::BlockFollowedBy(*merge));
+                self.generate_within(block, merges, frame);
+                frame.context.pop();
+                frame.code.end();
+
+                self.generate_tree(*merge, frame);
+            }
+            None => {
+                let ssa = self.ssa;
+
+                let (BlockData::Function { insts, .. } | BlockData::Block { insts, .. }) =
+                    &ssa.blocks[block]
+                else {
+                    panic!()
+                };
+
+                for inst in insts {
+                    self.generate_inst(block, *inst, frame);
+                }
+            }
+        }
+    }
+
+    fn generate_branch(&mut self, source: Block, target: Block, frame: &mut Frame) {
+        let context = if frame.cfg.order(target) <= frame.cfg.order(source) {
+            Context::LoopHeadedBy(target)
+        } else if frame.forward_edges[&target] >= 2 {
+            Context::BlockFollowedBy(target)
+        } else {
+            self.generate_tree(target, frame);
+            return;
+        };
+
+        let label = frame
+            .context
+            .iter()
+            .rev()
+            .position(|enclosing| *enclosing == context)
+            .unwrap();
+
+        frame.code.br(label as u32);
+    }
+
+    fn generate_inst(&mut self, block: Block, inst: Inst, frame: &mut Frame) {
+        let value = self.insts_values[inst].unwrap();
+
+        match &self.ssa.insts[inst] {
+            InstData::Field(expr, field) => {
+                let Value::Memory(offset) = self.expr_value(*expr) else {
+                    panic!()
+                };
+
+                let field_offset = self.field_offset(self.expr_type(*expr), *field as usize);
+                let field_type = self.expr_type(Expr::Inst(inst));
+
+                self.read(
+                    Place {
+                        base: frame.frame_pointer,
+                        offset: offset + field_offset,
+                    },
+                    value,
+                    field_type,
+                    frame,
+                );
+            }
+            InstData::Record(fields, type_) => {
+                let Value::Memory(offset) = value else {
+                    panic!()
+                };
+
+                for (i, field) in fields.iter().enumerate() {
+                    let place = Place {
+                        base: frame.frame_pointer,
+                        offset: offset + self.field_offset(*type_, i),
+                    };
+                    self.write(*field, place, frame);
+                }
+            }
+            InstData::Array(elements, _) => {
+                let Value::Memory(mut offset) = value else {
+                    panic!()
+                };
+
+                for element in elements {
+                    let place = Place {
+                        base: frame.frame_pointer,
+                        offset,
+                    };
+                    self.write(*element, place, frame);
+
+                    offset += self.type_size(self.expr_type(*element));
+                }
+            }
+            InstData::Length(base) if self.is_vec(*base) => {
+                // The length follows the pointer to the elements.
+                self.push(*base, frame);
+                frame.code.i32_load(4);
+                self.set(value, frame);
+            }
+            InstData::Length(base) => {
+                self.push_length(*base, frame);
+                self.set(value, frame);
+            }
+            InstData::Load {
+                base,
+                index,
+                location,
+            } => {
+                let element_type = self.expr_type(Expr::Inst(inst));
+
+                self.element_address(*base, *index, element_type, location, frame);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.read(place, value, element_type, frame);
+            }
+            InstData::Store {
+                base,
+                index,
+                value,
+                location,
+            } => {
+                let element_type = self.expr_type(*value);
+
+                self.element_address(*base, *index, element_type, location, frame);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Slice {
+                base,
+                start,
+                end,
+                type_,
+                location,
+            } => {
+                let element_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Slice { element }) => self.type_size(*element),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let Value::Memory(offset) = value else {
+                    panic!()
+                };
+
+                self.push(*start, frame);
+                self.push(*end, frame);
+                frame.code.i32_gt_u();
+                self.push(*end, frame);
+                self.push_length(*base, frame);
+                frame.code.i32_gt_u().i32_or().if_();
+                index_out_of_bounds(location, frame);
+                frame.code.end();
+
+                // The pointer is the base address offsetted by `start`
+                // elements, the length is `end - start`.
+                frame.code.local_get(frame.frame_pointer);
+                self.push_base_address(*base, frame);
+                self.push(*start, frame);
+                frame
+                    .code
+                    .i32_const(element_size)
+                    .i32_mul()
+                    .i32_add()
+                    .i32_store(offset);
+
+                frame.code.local_get(frame.frame_pointer);
+                self.push(*end, frame);
+                self.push(*start, frame);
+                frame.code.i32_sub().i32_store(offset + 4);
+            }
+            InstData::NewVec(elements, type_) => {
+                let element_type = match self.types.get(*type_) {
+                    Val::Value(TypeData::Vec { element }) => *element,
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+                let element_size = self.type_size(element_type);
+
+                // The runtime copies the elements from the frame.
+                let elements_offset =
+                    self.reserve_memory(element_size * elements.len() as u32, frame);
+
+                for (i, element) in elements.iter().enumerate() {
+                    let place = Place {
+                        base: frame.frame_pointer,
+                        offset: elements_offset + i as u32 * element_size,
+                    };
+                    self.write(*element, place, frame);
+                }
+
+                frame
+                    .code
+                    .i32_const(element_size)
+                    .i32_const(elements.len() as u32);
+                if elements.is_empty() {
+                    frame.code.i32_const(0);
+                } else {
+                    self.push_address(elements_offset, frame);
+                }
+                let (heap_offsets, heap_count) = self.heap_offsets_table(*type_);
+                frame
+                    .code
+                    .i32_const(heap_offsets)
+                    .i32_const(heap_count)
+                    .call(runtime::VEC_NEW);
+                self.set(value, frame);
+            }
+            InstData::Push { vec, value } => {
+                let element_type = self.expr_type(*value);
+
+                self.push(*vec, frame);
+                frame
+                    .code
+                    .i32_const(self.type_size(element_type))
+                    .call(runtime::VEC_PUSH)
+                    .local_set(frame.address);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Pop { vec, location } => {
+                let element_type = self.expr_type(Expr::Inst(inst));
+
+                self.push(*vec, frame);
+                frame
+                    .code
+                    .i32_const(self.type_size(element_type))
+                    .i32_const(location.line)
+                    .i32_const(location.column)
+                    .call(runtime::VEC_POP)
+                    .local_set(frame.address);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.read(place, value, element_type, frame);
+            }
+            InstData::Alloca(type_) => {
+                let pointee_size = match self.types.get(*type_) {
+                    Val::Value(TypeData::Pointer { pointee }) => self.type_size(*pointee),
+                    Val::None | Val::Sentinel(_) | Val::Value(_) => panic!(),
+                };
+
+                let offset = self.reserve_memory(pointee_size, frame);
+                self.push_address(offset, frame);
+                self.set(value, frame);
+            }
+            InstData::AddressOf(expr, _) => {
+                let offset = match self.expr_value(*expr) {
+                    Value::Memory(offset) => offset,
+                    // Values in locals are copied to memory.
+                    Value::None | Value::Local(_) => {
+                        let size = self.type_size(self.expr_type(*expr));
+                        let offset = self.reserve_memory(size, frame);
+
+                        let place = Place {
+                            base: frame.frame_pointer,
+                            offset,
+                        };
+                        self.write(*expr, place, frame);
+
+                        offset
+                    }
+                };
+
+                self.push_address(offset, frame);
+                self.set(value, frame);
+            }
+            InstData::LoadPointer(pointer) => {
+                let pointee_type = self.expr_type(Expr::Inst(inst));
+
+                self.push(*pointer, frame);
+                frame.code.local_set(frame.address);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.read(place, value, pointee_type, frame);
+            }
+            InstData::StorePointer { pointer, value } => {
+                self.push(*pointer, frame);
+                frame.code.local_set(frame.address);
+
+                let place = Place {
+                    base: frame.address,
+                    offset: 0,
+                };
+                self.write(*value, place, frame);
+            }
+            InstData::Retain(vec) => {
+                self.push(*vec, frame);
+                frame.code.call(runtime::VEC_RETAIN);
+            }
+            InstData::Release(vec) => {
+                self.push(*vec, frame);
+                frame.code.call(runtime::VEC_RELEASE);
+            }
+            InstData::Equal(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Code::i32_eq, frame)
+            }
+            InstData::Add(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Code::i32_add, frame)
+            }
+            InstData::Sub(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Code::i32_sub, frame)
+            }
+            InstData::Mul(lhs, rhs) => {
+                self.binary_operation(value, *lhs, *rhs, Code::i32_mul, frame)
+            }
+            InstData::Div(lhs, rhs) => {
+                // Dividing by zero would throw a `RuntimeError` out of the
+                // module instead of trapping as the other backends.
+                if self.ssa.divisor_may_be_zero(*rhs) {
+                    self.push(*rhs, frame);
+                    frame
+                        .code
+                        .i32_eqz()
+                        .if_()
+                        .call(runtime::DIVISION_BY_ZERO)
+                        .end();
+                }
+
+                self.binary_operation(value, *lhs, *rhs, Code::i32_div_u, frame)
+            }
+            InstData::Call { function, argument } => {
+                // The callee writes the returned value in our frame.
+                if let Value::Memory(offset) = value {
+                    self.push_address(offset, frame);
+                }
+
+                self.push(*argument, frame);
+                frame.code.call(self.functions_indices[*function].unwrap());
+
+                if let Value::Local(local) = value {
+                    frame.code.local_set(local);
+                } else if self.is_local(self.function_return_type(*function)) {
+                    frame.code.drop();
+                }
+            }
+            InstData::Jump {
+                block: target,
+                argument,
+            } => {
+                match self.args_values[*target].unwrap() {
+                    Value::None => {}
+                    Value::Local(local) => {
+                        self.push(*argument, frame);
+                        frame.code.local_set(local);
+                    }
+                    Value::Memory(offset) => {
+                        let place = Place {
+                            base: frame.frame_pointer,
+                            offset,
+                        };
+                        self.write(*argument, place, frame);
+                    }
+                }
+
+                self.generate_branch(block, *target, frame);
+            }
+            InstData::JumpCondition {
+                condition,
+                then,
+                else_,
+            } => {
+                self.push(*condition, frame);
+                frame.code.if_();
+                frame.context.push(Context::IfThenElse);
+                self.generate_branch(block, *then, frame);
+                frame.code.else_();
+                self.generate_branch(block, *else_, frame);
+                frame.context.pop();
+                frame.code.end();
+            }
+            InstData::Return(expr) => {
+                match frame.return_pointer {
+                    Some(return_pointer) => {
+                        let place = Place {
+                            base: return_pointer,
+                            offset: 0,
+                        };
+                        self.write(*expr, place, frame);
+                    }
+                    None if self.is_local(frame.return_type) => self.push(*expr, frame),
+                    None => {}
+                }
+
+                frame
+                    .code
+                    .local_get(frame.stack_pointer)
+                    .global_set(runtime::STACK_POINTER)
+                    .return_();
+            }
+            InstData::TailCall { function, argument } => {
+                // The callee writes the returned value where the caller
+                // expects it.
+                if let Some(return_pointer) = frame.return_pointer {
+                    frame.code.local_get(return_pointer);
+                }
+
+                // The frame is released before the call, an argument in
+                // memory is copied by the callee before its frame overwrites
+                // it.
+                self.push(*argument, frame);
+                frame
+                    .code
+                    .local_get(frame.stack_pointer)
+                    .global_set(runtime::STACK_POINTER)
+                    .return_call(self.functions_indices[*function].unwrap());
+            }
+        }
+    }
+
+    fn binary_operation(
+        &self,
+        value: Value,
+        lhs: Expr,
+        rhs: Expr,
+        operation: fn(&mut Code) -> &mut Code,
+        frame: &mut Frame,
+    ) {
+        self.push(lhs, frame);
+        self.push(rhs, frame);
+        operation(&mut frame.code);
+        self.set(value, frame);
+    }
+
+    // Push a value of 4 bytes, or the address of a value in memory.
+    fn push(&self, expr: Expr, frame: &mut Frame) {
+        match expr {
+            Expr::Const(const_) => match self.ssa.consts.get(const_) {
+                Val::None => panic!(),
+                Val::Sentinel(sentinel) => match sentinel {
+                    ConstSentinel::Unit => {}
+                    ConstSentinel::False => {
+                        frame.code.i32_const(0);
+                    }
+                    ConstSentinel::True => {
+                        frame.code.i32_const(1);
+                    }
+                },
+                Val::Value(const_data) => match const_data {
+                    ConstData::Uint32(value) => {
+                        frame.code.i32_const(*value);
+                    }
+                    ConstData::Product(_, _) => panic!(),
+                },
+            },
+            Expr::Inst(_) | Expr::BlockArg(_) => match self.expr_value(expr) {
+                Value::None => {}
+                Value::Local(local) => {
+                    frame.code.local_get(local);
+                }
+                Value::Memory(offset) => self.push_address(offset, frame),
+            },
+        }
+    }
+
+    fn push_address(&self, offset: u32, frame: &mut Frame) {
+        frame.code.local_get(frame.frame_pointer);
+        if offset != 0 {
+            frame.code.i32_const(offset).i32_add();
+        }
+    }
+
+    // Pop a value of 4 bytes in the local of `value`.
+    fn set(&self, value: Value, frame: &mut Frame) {
+        let Value::Local(local) = value else { panic!() };
+        frame.code.local_set(local);
+    }
+
+    // Read a value of type `type_` at `place` in `value`.
+    fn read(&self, place: Place, value: Value, type_: Type, frame: &mut Frame) {
+        match value {
+            Value::None => {}
+            Value::Local(local) => {
+                frame
+                    .code
+                    .local_get(place.base)
+                    .i32_load(place.offset)
+                    .local_set(local);
+            }
+            Value::Memory(offset) => {
+                self.push_address(offset, frame);
+                self.push_place(place, frame);
+                frame.code.i32_const(self.type_size(type_)).memory_copy();
+            }
+        }
+    }
+
+    // Write the value of `expr` at `place`.
+    fn write(&self, expr: Expr, place: Place, frame: &mut Frame) {
+        let expr_type = self.expr_type(expr);
+
+        if self.is_memory(expr_type) {
+            self.push_place(place, frame);
+            self.push(expr, frame);
+            frame
+                .code
+                .i32_const(self.type_size(expr_type))
+                .memory_copy();
+        } else if self.is_local(expr_type) {
+            frame.code.local_get(place.base);
+            self.push(expr, frame);
+            frame.code.i32_store(place.offset);
+        }
+    }
+
+    fn push_place(&self, place: Place, frame: &mut Frame) {
+        frame.code.local_get(place.base);
+        if place.offset != 0 {
+            frame.code.i32_const(place.offset).i32_add();
+        }
+    }
+
+    fn push_length(&self, base: Expr, frame: &mut Frame) {
+        match (self.types.get(self.expr_type(base)), self.expr_value(base)) {
+            (Val::Value(&TypeData::Array { length, .. }), _) => {
+                frame.code.i32_const(length);
+            }
+            (Val::Value(TypeData::Slice { .. }), Value::Memory(offset)) => {
+                frame
+                    .code
+                    .local_get(frame.frame_pointer)
+                    .i32_load(offset + 4);
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Push the address of the first element of an array or slice.
+    fn push_base_address(&self, base: Expr, frame: &mut Frame) {
+        match (self.types.get(self.expr_type(base)), self.expr_value(base)) {
+            (Val::Value(TypeData::Array { .. }), Value::Memory(offset)) => {
+                self.push_address(offset, frame)
+            }
+            (Val::Value(TypeData::Slice { .. }), Value::Memory(offset)) => {
+                frame.code.local_get(frame.frame_pointer).i32_load(offset);
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Put the address of the element `index` of an array, a slice or a vec in
+    // the address local, checking the bounds.
+    fn element_address(
+        &self,
+        base: Expr,
+        index: Expr,
+        element_type: Type,
+        location: &Location,
+        frame: &mut Frame,
+    ) {
+        let element_size = self.type_size(element_type);
+
+        if self.is_vec(base) {
+            self.push(base, frame);
+            self.push(index, frame);
+            frame
+                .code
+                .i32_const(element_size)
+                .i32_const(location.line)
+                .i32_const(location.column)
+                .call(runtime::VEC_GET)
+                .local_set(frame.address);
+            return;
+        }
+
+        self.push(index, frame);
+        self.push_length(base, frame);
+        frame.code.i32_ge_u().if_();
+        index_out_of_bounds(location, frame);
+        frame.code.end();
+
+        self.push_base_address(base, frame);
+        self.push(index, frame);
+        frame
+            .code
+            .i32_const(element_size)
+            .i32_mul()
+            .i32_add()
+            .local_set(frame.address);
+    }
+
+    // Address of the table of the offsets of the vecs held by an element of
+    // the vec type `type_` and its length, as expected by `builtin_vec_new`.
+    fn heap_offsets_table(&mut self, type_: Type) -> (u32, u32) {
+        let Val::Value(&TypeData::Vec { element }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        let mut offsets = Vec::new();
+        self.heap_offsets(element, 0, &mut offsets);
+
+        if offsets.is_empty() {
+            return (0, 0);
+        }
+
+        let count = offsets.len() as u32;
+        let table = match self.heap_offsets.iter().position(|table| *table == offsets) {
+            Some(table) => table,
+            None => {
+                self.heap_offsets.push(offsets);
+                self.heap_offsets.len() - 1
+            }
+        };
+        let address = runtime::tables(&self.ssa.path)
+            + 4 * self.heap_offsets[..table]
+                .iter()
+                .map(|offsets| offsets.len() as u32)
+                .sum::<u32>();
+
+        (address, count)
+    }
+
+    fn heap_offsets(&self, type_: Type, offset: u32, offsets: &mut Vec<u32>) {
+        match self.types.get(type_) {
+            Val::Value(TypeData::Vec { .. }) => offsets.push(offset),
+            Val::Value(TypeData::Product { fields }) => {
+                for (i, (_, field)) in fields.iter().enumerate() {
+                    self.heap_offsets(*field, offset + self.field_offset(type_, i), offsets);
+                }
+            }
+            Val::Value(&TypeData::Array { element, length }) => {
+                let element_size = self.type_size(element);
+                for i in 0..length {
+                    self.heap_offsets(element, offset + i * element_size, offsets);
+                }
+            }
+            Val::None | Val::Sentinel(_) | Val::Value(_) => {}
+        }
+    }
+
+    fn is_vec(&self, expr: Expr) -> bool {
+        matches!(
+            self.types.get(self.expr_type(expr)),
+            Val::Value(TypeData::Vec { .. })
+        )
+    }
+
+    fn is_memory(&self, type_: Type) -> bool {
+        matches!(
+            self.types.get(type_),
+            Val::Value(TypeData::Product { .. } | TypeData::Array { .. } | TypeData::Slice { .. })
+        )
+    }
+
+    fn is_local(&self, type_: Type) -> bool {
+        !self.is_memory(type_) && self.type_size(type_) == 4
+    }
+
+    fn function_return_type(&self, function: Block) -> Type {
+        let (BlockData::ExternFunction { ret, .. } | BlockData::Function { ret, .. }) =
+            &self.ssa.blocks[function]
+        else {
+            panic!()
+        };
+
+        *ret
+    }
+
+    #[track_caller]
+    fn expr_value(&self, expr: Expr) -> Value {
+        match expr {
+            Expr::Inst(inst) => self.insts_values[inst].unwrap(),
+            Expr::BlockArg(block) => self.args_values[block].unwrap(),
+            Expr::Const(_) => panic!(),
+        }
+    }
+
+    #[track_caller]
+    fn expr_type(&self, expr: Expr) -> Type {
+        self.ssa.expression_type(self.types, expr)
+    }
+
+    fn type_size(&self, type_: Type) -> u32 {
+        match self.types.get(type_) {
+            Val::None => panic!(),
+            Val::Sentinel(sentinel) => match sentinel {
+                TypeSentinel::Unknown => panic!(),
+                TypeSentinel::Bool | TypeSentinel::False | TypeSentinel::True => 4,
+                TypeSentinel::Unit | TypeSentinel::Never => 0,
+                TypeSentinel::Uint32 | TypeSentinel::Pointer => 4,
+            },
+            Val::Value(type_data) => match type_data {
+                TypeData::Function { .. } => panic!(),
+                TypeData::Product { fields } => {
+                    fields.iter().map(|(_, field)| self.type_size(*field)).sum()
+                }
+                TypeData::Array { element, length } => self.type_size(*element) * length,
+                TypeData::Slice { .. } => 8,
+                TypeData::Pointer { .. } | TypeData::Vec { .. } => 4,
+            },
+        }
+    }
+
+    // Every field is 4 bytes aligned as every type is made of 4 bytes words.
+    fn field_offset(&self, type_: Type, field: usize) -> u32 {
+        let Val::Value(TypeData::Product { fields }) = self.types.get(type_) else {
+            panic!()
+        };
+
+        fields[..field]
+            .iter()
+            .map(|(_, field)| self.type_size(*field))
+            .sum()
+    }
+}
+
+// Report the failed bounds check, the runtime never returns.
+fn index_out_of_bounds(location: &Location, frame: &mut Frame) {
+    frame
+        .code
+        .i32_const(location.line)
+        .i32_const(location.column)
+        .call(runtime::INDEX_OUT_OF_BOUNDS);
+}
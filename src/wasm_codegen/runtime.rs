@@ -0,0 +1,598 @@
+// Runtime of the wasm modules, the functions of `runtime.c` written directly
+// in wasm over the WASI `fd_write` and `proc_exit` functions.
+
+use super::encoding::{Code, Function, Import, PAGE_SIZE, Signature};
+
+// Imported functions.
+pub const FD_WRITE: u32 = 0;
+pub const PROC_EXIT: u32 = 1;
+
+// Defined functions, numbered after the imported functions.
+const WRITE: u32 = 2;
+const DECIMAL: u32 = 3;
+const TRAP: u32 = 4;
+pub const PRINT: u32 = 5;
+pub const ALLOC: u32 = 6;
+pub const FREE: u32 = 7;
+pub const INDEX_OUT_OF_BOUNDS: u32 = 8;
+pub const VEC_NEW: u32 = 9;
+pub const VEC_RETAIN: u32 = 10;
+pub const VEC_RELEASE: u32 = 11;
+pub const VEC_PUSH: u32 = 12;
+pub const VEC_POP: u32 = 13;
+pub const VEC_GET: u32 = 14;
+pub const DIVISION_BY_ZERO: u32 = 15;
+
+// Mutable globals.
+pub const STACK_POINTER: u32 = 0;
+const HEAP_END: u32 = 1;
+
+// Layout of the memory: the scratch memory of the runtime, its strings and the
+// path of the source, then the stack growing down from `STACK_TOP` and the heap growing up from it.
+const IOVEC: u32 = 0;
+const WRITTEN: u32 = 8;
+const FREE_LIST: u32 = 12;
+const DIGITS_END: u32 = 48;
+const DATA: u32 = 64;
+const STACK_TOP: u32 = 16 * PAGE_SIZE;
+
+pub const MEMORY_PAGES: u32 = STACK_TOP / PAGE_SIZE + 1;
+
+const STRINGS: [&str; 5] = [
+    " at ",
+    "out of memory\n",
+    "index out of bounds",
+    "pop from empty vec",
+    "division by zero\n",
+];
+
+// Address and length of a string of `STRINGS`.
+fn string(index: usize) -> (u32, u32) {
+    let offset = STRINGS[..index]
+        .iter()
+        .map(|string| string.len())
+        .sum::<usize>();
+    (DATA + offset as u32, STRINGS[index].len() as u32)
+}
+
+pub fn imports() -> Vec<Import> {
+    vec![
+        Import {
+            module: "wasi_snapshot_preview1",
+            name: "fd_write",
+            signature: Signature {
+                params: 4,
+                results: 1,
+            },
+        },
+        Import {
+            module: "wasi_snapshot_preview1",
+            name: "proc_exit",
+            signature: Signature {
+                params: 1,
+                results: 0,
+            },
+        },
+    ]
+}
+
+pub fn globals() -> Vec<u32> {
+    vec![STACK_TOP, STACK_TOP]
+}
+
+pub fn data(path: &str) -> (u32, Vec<u8>) {
+    (
+        DATA,
+        [STRINGS.concat().as_str(), path].concat().into_bytes(),
+    )
+}
+
+// Address and length of the path of the source, after the strings.
+fn path(path: &str) -> (u32, u32) {
+    (DATA + STRINGS.concat().len() as u32, path.len() as u32)
+}
+
+// Address of the data of the generated code, after the path of the source.
+pub fn tables(path: &str) -> u32 {
+    (DATA + STRINGS.concat().len() as u32 + path.len() as u32).next_multiple_of(4)
+}
+
+// Defined functions in the order of their numbers, the traps are reported at
+// `path`.
+pub fn functions(path: &str) -> Vec<Function> {
+    vec![
+        write(),
+        decimal(),
+        trap(path),
+        print(),
+        alloc(),
+        free(),
+        index_out_of_bounds(),
+        vec_new(),
+        vec_retain(),
+        vec_release(),
+        vec_push(),
+        vec_pop(),
+        vec_get(),
+        division_by_zero(),
+    ]
+}
+
+fn function(params: u32, results: u32, locals: u32, code: Code) -> Function {
+    Function {
+        signature: Signature { params, results },
+        locals,
+        code,
+    }
+}
+
+// Write the `len` bytes at `pointer` to the file descriptor `fd`.
+fn write() -> Function {
+    let (fd, pointer, len) = (0, 1, 2);
+
+    let mut code = Code::default();
+    code.i32_const(IOVEC).local_get(pointer).i32_store(0);
+    code.i32_const(IOVEC).local_get(len).i32_store(4);
+    code.local_get(fd)
+        .i32_const(IOVEC)
+        .i32_const(1)
+        .i32_const(WRITTEN)
+        .call(FD_WRITE)
+        .drop();
+
+    function(3, 0, 0, code)
+}
+
+// Write the decimal digits of `value` before `end`, returns the address of the
+// first digit.
+fn decimal() -> Function {
+    let (value, end) = (0, 1);
+
+    let mut code = Code::default();
+    code.loop_();
+    code.local_get(end).i32_const(1).i32_sub().local_tee(end);
+    code.local_get(value)
+        .i32_const(10)
+        .i32_rem_u()
+        .i32_const(b'0' as u32)
+        .i32_add()
+        .i32_store8(0);
+    code.local_get(value)
+        .i32_const(10)
+        .i32_div_u()
+        .local_tee(value)
+        .br_if(0);
+    code.end();
+    code.local_get(end);
+
+    function(2, 1, 0, code)
+}
+
+// Print the `len` bytes at `pointer` followed by the location at `line` and
+// `column` of the source at `path`, then exit as if aborted.
+fn trap(path: &str) -> Function {
+    let (pointer, len, line, column) = (0, 1, 2, 3);
+    let start = 4;
+    let (at, at_len) = string(0);
+    let (path, path_len) = self::path(path);
+
+    let mut code = Code::default();
+    code.i32_const(2)
+        .local_get(pointer)
+        .local_get(len)
+        .call(WRITE);
+    code.i32_const(2)
+        .i32_const(at)
+        .i32_const(at_len)
+        .call(WRITE);
+    code.i32_const(2)
+        .i32_const(path)
+        .i32_const(path_len)
+        .call(WRITE);
+    code.i32_const(DIGITS_END - 1)
+        .i32_const(b'\n' as u32)
+        .i32_store8(0);
+    code.local_get(column)
+        .i32_const(DIGITS_END - 1)
+        .call(DECIMAL)
+        .i32_const(1)
+        .i32_sub()
+        .local_tee(start)
+        .i32_const(b':' as u32)
+        .i32_store8(0);
+    code.local_get(line)
+        .local_get(start)
+        .call(DECIMAL)
+        .i32_const(1)
+        .i32_sub()
+        .local_tee(start)
+        .i32_const(b':' as u32)
+        .i32_store8(0);
+    code.i32_const(2)
+        .local_get(start)
+        .i32_const(DIGITS_END)
+        .local_get(start)
+        .i32_sub()
+        .call(WRITE);
+    code.i32_const(134).call(PROC_EXIT).unreachable();
+
+    function(4, 0, 1, code)
+}
+
+fn print() -> Function {
+    let value = 0;
+    let start = 1;
+
+    let mut code = Code::default();
+    code.i32_const(DIGITS_END - 1)
+        .i32_const(b'\n' as u32)
+        .i32_store8(0);
+    code.local_get(value)
+        .i32_const(DIGITS_END - 1)
+        .call(DECIMAL)
+        .local_set(start);
+    code.i32_const(1)
+        .local_get(start)
+        .i32_const(DIGITS_END)
+        .local_get(start)
+        .i32_sub()
+        .call(WRITE);
+
+    function(1, 0, 1, code)
+}
+
+// Allocations are preceded by their size. Freed allocations are put in a list
+// linked through their first bytes, and reused by the first allocation that
+// fits in them. Otherwise memory is taken from the end of the heap.
+fn alloc() -> Function {
+    let size = 0;
+    let (previous, allocation) = (1, 2);
+    let (out_of_memory, out_of_memory_len) = string(1);
+
+    let mut code = Code::default();
+
+    // Rounded up to 8 bytes, at least 8 bytes to hold the link of the list.
+    code.local_get(size)
+        .i32_const(7)
+        .i32_add()
+        .i32_const(-8i32 as u32)
+        .i32_and()
+        .local_tee(size)
+        .i32_eqz()
+        .if_()
+        .i32_const(8)
+        .local_set(size)
+        .end();
+
+    code.i32_const(FREE_LIST).local_set(previous);
+    code.block().loop_();
+    code.local_get(previous)
+        .i32_load(0)
+        .local_tee(allocation)
+        .i32_eqz()
+        .br_if(1);
+    code.local_get(allocation)
+        .i32_const(4)
+        .i32_sub()
+        .i32_load(0)
+        .local_get(size)
+        .i32_ge_u()
+        .if_();
+    code.local_get(previous)
+        .local_get(allocation)
+        .i32_load(0)
+        .i32_store(0);
+    code.local_get(allocation).return_();
+    code.end();
+    code.local_get(allocation).local_set(previous).br(0);
+    code.end().end();
+
+    code.global_get(HEAP_END)
+        .i32_const(4)
+        .i32_add()
+        .local_set(allocation);
+    code.local_get(allocation)
+        .local_get(size)
+        .i32_add()
+        .global_set(HEAP_END);
+
+    code.block().loop_();
+    code.global_get(HEAP_END)
+        .memory_size()
+        .i32_const(16)
+        .i32_shl()
+        .i32_le_u()
+        .br_if(1);
+    code.i32_const(1)
+        .memory_grow()
+        .i32_const(-1i32 as u32)
+        .i32_eq()
+        .if_();
+    code.i32_const(2)
+        .i32_const(out_of_memory)
+        .i32_const(out_of_memory_len)
+        .call(WRITE);
+    code.i32_const(134).call(PROC_EXIT);
+    code.end();
+    code.br(0);
+    code.end().end();
+
+    code.local_get(allocation)
+        .i32_const(4)
+        .i32_sub()
+        .local_get(size)
+        .i32_store(0);
+    code.local_get(allocation);
+
+    function(1, 1, 2, code)
+}
+
+fn free() -> Function {
+    let allocation = 0;
+
+    let mut code = Code::default();
+    code.local_get(allocation).i32_eqz().if_().return_().end();
+    code.local_get(allocation)
+        .i32_const(FREE_LIST)
+        .i32_load(0)
+        .i32_store(0);
+    code.i32_const(FREE_LIST).local_get(allocation).i32_store(0);
+
+    function(1, 0, 0, code)
+}
+
+fn index_out_of_bounds() -> Function {
+    let (line, column) = (0, 1);
+    let (message, message_len) = string(2);
+
+    let mut code = Code::default();
+    code.i32_const(message)
+        .i32_const(message_len)
+        .local_get(line)
+        .local_get(column)
+        .call(TRAP);
+
+    function(2, 0, 0, code)
+}
+
+// Vecs have the layout of `struct builtin_vec` with 4 bytes pointers: items at
+// 0, length at 4, capacity at 8, references at 12, element size at 16, heap
+// offsets at 20 and heap count at 24.
+const VEC_SIZE: u32 = 28;
+
+fn vec_new() -> Function {
+    let (element_size, length, elements, heap_offsets, heap_count) = (0, 1, 2, 3, 4);
+    let (vec, size) = (5, 6);
+
+    let mut code = Code::default();
+    code.i32_const(VEC_SIZE).call(ALLOC).local_set(vec);
+    code.local_get(vec)
+        .local_get(element_size)
+        .local_get(length)
+        .i32_mul()
+        .local_tee(size)
+        .call(ALLOC)
+        .i32_store(0);
+    code.local_get(vec).local_get(length).i32_store(4);
+    code.local_get(vec).local_get(length).i32_store(8);
+    code.local_get(vec).i32_const(1).i32_store(12);
+    code.local_get(vec).local_get(element_size).i32_store(16);
+    code.local_get(vec).local_get(heap_offsets).i32_store(20);
+    code.local_get(vec).local_get(heap_count).i32_store(24);
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(elements)
+        .local_get(size)
+        .memory_copy();
+    code.local_get(vec);
+
+    function(5, 1, 2, code)
+}
+
+fn vec_retain() -> Function {
+    let vec = 0;
+
+    let mut code = Code::default();
+    code.local_get(vec)
+        .local_get(vec)
+        .i32_load(12)
+        .i32_const(1)
+        .i32_add()
+        .i32_store(12);
+
+    function(1, 0, 0, code)
+}
+
+fn vec_release() -> Function {
+    let vec = 0;
+    let (index, field) = (1, 2);
+
+    let mut code = Code::default();
+    code.local_get(vec)
+        .local_get(vec)
+        .i32_load(12)
+        .i32_const(1)
+        .i32_sub()
+        .local_tee(index)
+        .i32_store(12);
+    code.local_get(index).if_().return_().end();
+
+    code.i32_const(0).local_set(index);
+    code.block().loop_();
+    code.local_get(index)
+        .local_get(vec)
+        .i32_load(4)
+        .i32_ge_u()
+        .br_if(1);
+
+    // Release the vec at every offset of the table.
+    code.i32_const(0).local_set(field);
+    code.block().loop_();
+    code.local_get(field)
+        .local_get(vec)
+        .i32_load(24)
+        .i32_ge_u()
+        .br_if(1);
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(vec)
+        .i32_load(16)
+        .local_get(index)
+        .i32_mul()
+        .i32_add()
+        .local_get(vec)
+        .i32_load(20)
+        .local_get(field)
+        .i32_const(2)
+        .i32_shl()
+        .i32_add()
+        .i32_load(0)
+        .i32_add()
+        .i32_load(0)
+        .call(VEC_RELEASE);
+    code.local_get(field)
+        .i32_const(1)
+        .i32_add()
+        .local_set(field);
+    code.br(0);
+    code.end().end();
+
+    code.local_get(index)
+        .i32_const(1)
+        .i32_add()
+        .local_set(index);
+    code.br(0);
+    code.end().end();
+
+    code.local_get(vec).i32_load(0).call(FREE);
+    code.local_get(vec).call(FREE);
+
+    function(1, 0, 2, code)
+}
+
+// Returns where the pushed element must be written.
+fn vec_push() -> Function {
+    let (vec, element_size) = (0, 1);
+    let (capacity, items) = (2, 3);
+
+    let mut code = Code::default();
+    code.local_get(vec)
+        .i32_load(4)
+        .local_get(vec)
+        .i32_load(8)
+        .i32_eq()
+        .if_();
+    code.local_get(vec)
+        .i32_load(8)
+        .i32_const(1)
+        .i32_shl()
+        .local_tee(capacity)
+        .i32_eqz()
+        .if_()
+        .i32_const(4)
+        .local_set(capacity)
+        .end();
+    code.local_get(element_size)
+        .local_get(capacity)
+        .i32_mul()
+        .call(ALLOC)
+        .local_tee(items);
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(element_size)
+        .local_get(vec)
+        .i32_load(4)
+        .i32_mul()
+        .memory_copy();
+    code.local_get(vec).i32_load(0).call(FREE);
+    code.local_get(vec).local_get(items).i32_store(0);
+    code.local_get(vec).local_get(capacity).i32_store(8);
+    code.end();
+
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(element_size)
+        .local_get(vec)
+        .i32_load(4)
+        .i32_mul()
+        .i32_add();
+    code.local_get(vec)
+        .local_get(vec)
+        .i32_load(4)
+        .i32_const(1)
+        .i32_add()
+        .i32_store(4);
+
+    function(2, 1, 2, code)
+}
+
+// Returns where the popped element is, it stays valid until the next push.
+fn vec_pop() -> Function {
+    let (vec, element_size, line, column) = (0, 1, 2, 3);
+    let length = 4;
+    let (message, message_len) = string(3);
+
+    let mut code = Code::default();
+    code.local_get(vec)
+        .i32_load(4)
+        .local_tee(length)
+        .i32_eqz()
+        .if_();
+    code.i32_const(message)
+        .i32_const(message_len)
+        .local_get(line)
+        .local_get(column)
+        .call(TRAP);
+    code.end();
+    code.local_get(vec)
+        .local_get(length)
+        .i32_const(1)
+        .i32_sub()
+        .local_tee(length)
+        .i32_store(4);
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(element_size)
+        .local_get(length)
+        .i32_mul()
+        .i32_add();
+
+    function(4, 1, 1, code)
+}
+
+fn vec_get() -> Function {
+    let (vec, index, element_size, line, column) = (0, 1, 2, 3, 4);
+
+    let mut code = Code::default();
+    code.local_get(index)
+        .local_get(vec)
+        .i32_load(4)
+        .i32_ge_u()
+        .if_()
+        .local_get(line)
+        .local_get(column)
+        .call(INDEX_OUT_OF_BOUNDS)
+        .end();
+    code.local_get(vec)
+        .i32_load(0)
+        .local_get(element_size)
+        .local_get(index)
+        .i32_mul()
+        .i32_add();
+
+    function(5, 1, 0, code)
+}
+
+// Division has no location, as in the interpreter.
+fn division_by_zero() -> Function {
+    let (message, message_len) = string(4);
+
+    let mut code = Code::default();
+    code.i32_const(2)
+        .i32_const(message)
+        .i32_const(message_len)
+        .call(WRITE);
+    code.i32_const(134).call(PROC_EXIT).unreachable();
+
+    function(0, 0, 0, code)
+}
@@ -2,43 +2,346 @@
 
 use std::{
     env::temp_dir,
+    fs::Permissions,
     io::Write,
-    process::{Command, Stdio},
+    os::unix::{fs::PermissionsExt, process::ExitStatusExt},
+    process::{Command, ExitStatus, Output, Stdio},
     random::random,
 };
 
-use keb::{c_codegen, semantic, ssa, syntax, token};
+use keb::{
+    aarch64_asm_codegen, amd64_asm_codegen, c_codegen, elf, jit, llvm_ir_codegen, runtime,
+    semantic, ssa, syntax, token, wasm_codegen,
+};
 
-fn test_program(source: &str, expected_output: &str) {
-    let tokens = token::lex(&source);
+#[derive(Clone, Copy, Debug)]
+enum Backend {
+    C,
+    Amd64Asm,
+    // Assembled in-crate into an object file.
+    Amd64Elf,
+    // Linked in-crate with the assembly runtime, without a C toolchain, so
+    // the flags of the C compiler are ignored.
+    Amd64Static,
+    // Cross compiled and run under `qemu-aarch64`, the flags of the C
+    // compiler are ignored too.
+    Aarch64Asm,
+    // Run by the WASI implementation of node, the flags of the C compiler
+    // are ignored too.
+    Wasm,
+    // Compiled by `llc` and linked with the C runtime.
+    LlvmIr,
+    // Compiled in memory and run by the test itself, the flags of the C
+    // compiler are ignored too.
+    Jit,
+    // Executes the ssa directly, the reference the other backends are
+    // compared to.
+    Interpreter,
+}
+
+// Enough for every test, programs that do not terminate fail instead of
+// hanging.
+const STEP_LIMIT: u64 = 30_000_000;
+
+// The aarch64 backend is tested only where a cross toolchain and qemu are
+// installed, the wasm backend only where node is and the LLVM IR backend only
+// where `llc` is.
+fn backends() -> Vec<Backend> {
+    let mut backends = vec![
+        Backend::Interpreter,
+        Backend::C,
+        Backend::Amd64Asm,
+        Backend::Amd64Elf,
+        Backend::Amd64Static,
+        Backend::Jit,
+    ];
+
+    let installed = |program: &str| {
+        Command::new(program)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+
+    if installed("aarch64-linux-gnu-gcc") && installed("qemu-aarch64") {
+        backends.push(Backend::Aarch64Asm);
+    }
+
+    if installed("node") {
+        backends.push(Backend::Wasm);
+    }
+
+    if installed("llc") {
+        backends.push(Backend::LlvmIr);
+    }
+
+    backends
+}
+
+fn run_program(source: &str, backend: Backend) -> Output {
+    run_program_with_flags(source, backend, &[], false)
+}
+
+// With `optimize`, the ssa goes through every pass of `ssa::opt` first.
+fn run_program_with_flags(
+    source: &str,
+    backend: Backend,
+    flags: &[&str],
+    optimize: bool,
+) -> Output {
+    let tokens = token::lex(source);
     let syntax = syntax::parse(&tokens.kinds);
-    let (mut semantic, mut types) = semantic::parse(&source, &tokens.offsets, &syntax);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
     semantic::infer_types(&mut semantic, &mut types);
-    let ssa = ssa::generate(&source, &tokens.offsets, &semantic, &mut types);
-    let c = c_codegen::generate(&types, &ssa);
+    let mut ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+    if optimize {
+        ssa::opt::optimize(&mut types, &mut ssa, |_, _, _| {});
+    }
 
-    let program_path = temp_dir().join(format!("keb-test-c-output-{:0>32x}.c", random::<u128>(..)));
+    run_ssa(&types, &ssa, backend, flags)
+}
 
-    let mut clang = Command::new("clang")
-        .args(["-xc", "-std=c23", "-", "-o"])
-        .arg(&program_path)
-        .stdin(Stdio::piped())
-        .spawn()
-        .unwrap();
+fn run_ssa(types: &semantic::Types, ssa: &ssa::Ssa, backend: Backend, flags: &[&str]) -> Output {
+    // Failures are reported as the runtimes do, which abort.
+    if let Backend::Jit | Backend::Interpreter = backend {
+        let mut stdout = Vec::new();
+        let result = match backend {
+            Backend::Jit => jit::run(types, ssa, &mut stdout).map_err(|error| error.to_string()),
+            _ => ssa::interpret(types, ssa, &mut stdout, STEP_LIMIT)
+                .map_err(|error| error.to_string()),
+        };
+        let (status, stderr) = match result {
+            Ok(()) => (ExitStatus::from_raw(0), Vec::new()),
+            Err(error) => (
+                ExitStatus::from_raw(134 << 8),
+                format!("{error}\n").into_bytes(),
+            ),
+        };
+
+        return Output {
+            status,
+            stdout,
+            stderr,
+        };
+    }
+
+    let name = format!("keb-test-output-{:0>32x}", random::<u128>(..));
+    let program_path = temp_dir().join(&name);
+
+    match backend {
+        Backend::C => {
+            let c = c_codegen::generate(types, ssa);
+
+            let mut clang = Command::new("clang")
+                .args(["-xc", "-std=c23", "-", "-o"])
+                .arg(&program_path)
+                .args(flags)
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            let stdin = clang.stdin.as_mut().unwrap();
+            stdin.write_all(c.as_bytes()).unwrap();
+            stdin.flush().unwrap();
+
+            assert!(clang.wait_with_output().unwrap().status.success());
+        }
+        Backend::Amd64Asm | Backend::Amd64Elf => {
+            let asm = amd64_asm_codegen::generate(types, ssa);
+            let asm_path = match backend {
+                Backend::Amd64Elf => {
+                    let object_path = temp_dir().join(format!("{name}.o"));
+                    let object = amd64_asm_codegen::assemble(&asm);
+                    std::fs::write(&object_path, object.write()).unwrap();
+                    object_path
+                }
+                _ => {
+                    let asm_path = temp_dir().join(format!("{name}.s"));
+                    std::fs::write(&asm_path, asm).unwrap();
+                    asm_path
+                }
+            };
+            let runtime_path = temp_dir().join(format!("{name}-runtime.c"));
+            std::fs::write(&runtime_path, runtime::SOURCE).unwrap();
+
+            let gcc = Command::new("gcc")
+                .arg(&asm_path)
+                .arg(&runtime_path)
+                .args(["-Xlinker", "-z", "-Xlinker", "noexecstack", "-o"])
+                .arg(&program_path)
+                .args(flags)
+                .output()
+                .unwrap();
+
+            std::fs::remove_file(&asm_path).unwrap();
+            std::fs::remove_file(&runtime_path).unwrap();
 
-    let stdin = clang.stdin.as_mut().unwrap();
-    stdin.write_all(c.as_bytes()).unwrap();
-    stdin.flush().unwrap();
+            assert!(
+                gcc.status.success(),
+                "{}",
+                String::from_utf8_lossy(&gcc.stderr)
+            );
+        }
+        Backend::Amd64Static => {
+            let asm = amd64_asm_codegen::generate(types, ssa);
+            let objects = [
+                amd64_asm_codegen::assemble(&asm),
+                amd64_asm_codegen::assemble(runtime::ASSEMBLY),
+            ];
+            std::fs::write(&program_path, elf::link(&objects, "_start")).unwrap();
+            std::fs::set_permissions(&program_path, Permissions::from_mode(0o755)).unwrap();
+        }
+        Backend::Aarch64Asm => {
+            let asm_path = temp_dir().join(format!("{name}.s"));
+            let runtime_path = temp_dir().join(format!("{name}-runtime.c"));
+            std::fs::write(&asm_path, aarch64_asm_codegen::generate(types, ssa)).unwrap();
+            std::fs::write(&runtime_path, runtime::SOURCE).unwrap();
 
-    assert!(clang.wait_with_output().unwrap().status.success());
+            let gcc = Command::new("aarch64-linux-gnu-gcc")
+                .arg("-static")
+                .arg(&asm_path)
+                .arg(&runtime_path)
+                .arg("-o")
+                .arg(&program_path)
+                .output()
+                .unwrap();
 
-    let program = Command::new(&program_path).output().unwrap();
-    assert!(program.status.success());
+            std::fs::remove_file(&asm_path).unwrap();
+            std::fs::remove_file(&runtime_path).unwrap();
 
-    let stdout = &String::from_utf8(program.stdout).unwrap();
-    assert_eq!(stdout, expected_output);
+            assert!(
+                gcc.status.success(),
+                "{}",
+                String::from_utf8_lossy(&gcc.stderr)
+            );
+        }
+        Backend::Wasm => {
+            std::fs::write(&program_path, wasm_codegen::generate(types, ssa)).unwrap();
+        }
+        Backend::Jit | Backend::Interpreter => unreachable!(),
+        Backend::LlvmIr => {
+            let ir_path = temp_dir().join(format!("{name}.ll"));
+            let object_path = temp_dir().join(format!("{name}.o"));
+            let runtime_path = temp_dir().join(format!("{name}-runtime.c"));
+            std::fs::write(&ir_path, llvm_ir_codegen::generate(types, ssa)).unwrap();
+            std::fs::write(&runtime_path, runtime::SOURCE).unwrap();
+
+            let llc = Command::new("llc")
+                .args(llvm_ir_codegen::llc_flags())
+                .args(["-filetype=obj", "-relocation-model=pic", "-o"])
+                .arg(&object_path)
+                .arg(&ir_path)
+                .output()
+                .unwrap();
+
+            std::fs::remove_file(&ir_path).unwrap();
+
+            assert!(
+                llc.status.success(),
+                "{}",
+                String::from_utf8_lossy(&llc.stderr)
+            );
+
+            let gcc = Command::new("gcc")
+                .arg(&object_path)
+                .arg(&runtime_path)
+                .args(["-Xlinker", "-z", "-Xlinker", "noexecstack", "-o"])
+                .arg(&program_path)
+                .args(flags)
+                .output()
+                .unwrap();
+
+            std::fs::remove_file(&object_path).unwrap();
+            std::fs::remove_file(&runtime_path).unwrap();
+
+            assert!(
+                gcc.status.success(),
+                "{}",
+                String::from_utf8_lossy(&gcc.stderr)
+            );
+        }
+    }
+
+    let mut command = match backend {
+        Backend::Aarch64Asm => {
+            let mut command = Command::new("qemu-aarch64");
+            command.arg(&program_path);
+            command
+        }
+        Backend::Wasm => {
+            let mut command = Command::new("node");
+            command
+                .args(["--no-warnings", "-e", wasm_codegen::WASI_RUNNER])
+                .arg(&program_path);
+            command
+        }
+        _ => Command::new(&program_path),
+    };
+
+    let program = command
+        .env("ASAN_OPTIONS", "detect_leaks=1")
+        .output()
+        .unwrap();
 
     std::fs::remove_file(&program_path).unwrap();
+
+    program
+}
+
+// Programs are tested with and without optimizations.
+fn test_program(source: &str, expected_output: &str) {
+    for optimize in [false, true] {
+        for backend in backends() {
+            let program = run_program_with_flags(source, backend, &[], optimize);
+            assert!(
+                program.status.success(),
+                "{backend:?}, optimize: {optimize}"
+            );
+
+            let stdout = &String::from_utf8(program.stdout).unwrap();
+            assert_eq!(stdout, expected_output, "{backend:?}, optimize: {optimize}");
+        }
+    }
+}
+
+// Without an expected output, every backend must print what the interpreter
+// prints.
+fn test_program_against_interpreter(source: &str) {
+    let reference = run_program(source, Backend::Interpreter);
+    assert!(reference.status.success());
+
+    for optimize in [false, true] {
+        for backend in backends() {
+            let program = run_program_with_flags(source, backend, &[], optimize);
+            assert!(
+                program.status.success(),
+                "{backend:?}, optimize: {optimize}"
+            );
+            assert_eq!(
+                program.stdout, reference.stdout,
+                "{backend:?}, optimize: {optimize}"
+            );
+        }
+    }
+}
+
+// Fails on leaks and use after free.
+fn test_program_sanitized(source: &str, expected_output: &str) {
+    for optimize in [false, true] {
+        for backend in backends() {
+            let flags = ["-fsanitize=address", "-g"];
+            let program = run_program_with_flags(source, backend, &flags, optimize);
+
+            let stderr = &String::from_utf8(program.stderr).unwrap();
+            assert!(
+                program.status.success(),
+                "{backend:?}, optimize: {optimize}: {stderr}"
+            );
+
+            let stdout = &String::from_utf8(program.stdout).unwrap();
+            assert_eq!(stdout, expected_output, "{backend:?}, optimize: {optimize}");
+        }
+    }
 }
 
 #[test]
@@ -52,6 +355,21 @@ fn addition_function_with_argument_destructuring() {
     test_program(source, "12\n");
 }
 
+#[test]
+fn destructured_fields_keep_their_order() {
+    let source = r#"
+        let pick = (a: u32, xs: [u32; 2], b: u32) => a + xs.[1] * b;
+
+        let main = () => (
+            print pick (1, [2, 3], 4);
+            let (c, ys, d) = (5, [6, 7], 8);
+            print c + ys.[0] * d;
+        );
+    "#;
+
+    test_program(source, "13\n53\n");
+}
+
 #[test]
 fn if_then_else_expression() {
     let source = r#"
@@ -65,6 +383,25 @@ fn if_then_else_expression() {
     test_program(source, "11\n");
 }
 
+#[test]
+fn conditions_on_u32_and_on_equality() {
+    let source = r#"
+        let nonzero = (n: u32) => if n then 1 else 0;
+        let either = (n: u32) => if n == 1 then true else n == 2;
+
+        let main = () => (
+            print nonzero 0;
+            print nonzero 7;
+            let a = either 2;
+            let b = either 3;
+            if a then print 2;
+            if b then print 3;
+        );
+    "#;
+
+    test_program(source, "0\n1\n2\n");
+}
+
 #[test]
 fn factorial_recursive_if_then_else() {
     let source = r#"
@@ -103,3 +440,860 @@ fn factorial_recursive_match() {
 
     test_program(source, "40320\n");
 }
+
+#[test]
+fn loop_break_with_value() {
+    let source = r#"
+        let main = () => print loop (break 4 + 1);
+    "#;
+
+    test_program(source, "5\n");
+}
+
+#[test]
+fn loop_continue() {
+    let source = r#"
+        let double = (x: u32) => loop (if x == 0 then continue else break x * 2);
+        let main = () => print double 4;
+    "#;
+
+    test_program(source, "8\n");
+}
+
+#[test]
+fn nested_loops_with_label() {
+    let source = r#"
+        let main = () => print 'outer: loop (
+            loop (break 'outer 7);
+            3
+        );
+    "#;
+
+    test_program(source, "7\n");
+}
+
+#[test]
+fn break_and_continue_in_branches_with_values() {
+    let source = r#"
+        let first = (x: u32) => loop (
+            let y = if x == 0 then break 1 else 5;
+            if y == x then break 2 else 5;
+            break y + 1;
+        );
+
+        let skip = (n: u32) => (
+            let mut x = n;
+            let mut total = 0;
+            loop (
+                if x == 0 then break total;
+                x = x - 1;
+                let v = if x == 3 then continue else x + 1;
+                total = total + v;
+            )
+        );
+
+        let main = () => (
+            print first 0;
+            print first 5;
+            print first 7;
+            print skip 6;
+        );
+    "#;
+
+    test_program(source, "1\n2\n6\n17\n");
+}
+
+#[test]
+fn nested_loops_with_invariants_and_induction_variables() {
+    let source = r#"
+        let grid = (w: u32, h: u32) => (
+            let mut y = 0;
+            let mut total = 0;
+            loop (
+                if y == h then break total;
+                let mut x = w;
+                loop (
+                    if x == 0 then break;
+                    total = total + y * w + x * 5 + (w + h) / 2;
+                    x = x - 1;
+                );
+                y = y + if y - y / 2 * 2 then 3 else 1;
+                if y * 2 == 8 then print y * 7;
+            )
+        );
+
+        let main = () => (
+            print grid (4, 9);
+            let mut sum = 0;
+            for x in [3, 1, 4, 1, 5] (sum = sum + x * 3);
+            print sum;
+        );
+    "#;
+
+    test_program(source, "28\n658\n42\n");
+}
+
+#[test]
+fn branches_on_constants() {
+    let source = r#"
+        let sign = match {
+            0 => 0,
+            n => 1,
+        };
+
+        let pick = (n: u32) => if n == 3 then n * 5 else n + 1;
+
+        let main = () => (
+            let k = if false then 3 else 4;
+            let zero = sign 0;
+            let one = sign k;
+            print zero + one;
+            print pick (k - 1);
+            print loop (if k == 4 then break k * 2 else continue);
+            let limit = pick k;
+            let mut i = 0;
+            loop (
+                if i == limit then break;
+                i = i + if true then 1 else 10;
+            );
+            print i;
+        );
+    "#;
+
+    test_program(source, "1\n15\n8\n5\n");
+}
+
+#[test]
+fn array_index_and_length() {
+    let source = r#"
+        let main = () => (
+            let xs = [3, 5, 7];
+            print xs.[1];
+            print xs.len;
+        );
+    "#;
+
+    test_program(source, "5\n3\n");
+}
+
+#[test]
+fn for_loop_over_array_and_slice() {
+    let source = r#"
+        let sum = (xs: [u32]) => for x in xs print x * 2;
+
+        let main = () => (
+            let xs = [1, 2, 3, 4];
+            sum xs.[1..3];
+            for x in xs.[..] (if x == 3 then break else print x);
+        );
+    "#;
+
+    test_program(source, "4\n6\n1\n2\n");
+}
+
+#[test]
+fn store_through_slice() {
+    let source = r#"
+        let main = () => (
+            let xs = [0; 3];
+            let view = xs.[..];
+            view.[1] = 4;
+            print view.[1];
+            print xs.[1];
+        );
+    "#;
+
+    test_program(source, "4\n4\n");
+}
+
+#[test]
+fn slices_of_the_argument_can_be_returned() {
+    let source = r#"
+        let tail = (xs: [u32]) => xs.[1..];
+
+        let main = () => (
+            let xs = [1, 2, 3];
+            let rest = tail xs.[..];
+            for x in (tail rest) print x;
+        );
+    "#;
+
+    test_program(source, "3\n");
+}
+
+#[test]
+#[should_panic(expected = "slice outlives the array it views")]
+fn slice_of_a_local_array_can_not_be_returned() {
+    let source = r#"
+        let f = (n: u32) => (let xs = [n, n + 1, n + 2]; xs.[..]);
+
+        let main = () => print (f 1).[0];
+    "#;
+
+    run_program(source, Backend::Interpreter);
+}
+
+#[test]
+#[should_panic(expected = "slice outlives the array it views")]
+fn slice_can_not_be_assigned_past_its_array() {
+    let source = r#"
+        let main = () => (
+            let outer = [0];
+            let mut view = outer.[..];
+            (let inner = [1]; view = inner.[..]);
+            print view.[0];
+        );
+    "#;
+
+    run_program(source, Backend::Interpreter);
+}
+
+#[test]
+fn index_out_of_bounds_traps() {
+    let source = r#"
+        let main = () => (
+            let xs = [1, 2];
+            print xs.[2];
+        );
+    "#;
+
+    for backend in backends() {
+        let program = run_program(source, backend);
+        assert!(!program.status.success(), "{backend:?}");
+
+        let stderr = &String::from_utf8(program.stderr).unwrap();
+        assert_eq!(
+            stderr, "index out of bounds at input.keb:4:22\n",
+            "{backend:?}"
+        );
+    }
+}
+
+#[test]
+fn vec_push_pop_index_and_len() {
+    let source = r#"
+        let main = () => (
+            let xs = vec [1, 2];
+            for x in [3, 4, 5, 6, 7] push (xs, x);
+            print xs.len;
+            print pop xs;
+            xs.[0] = 9;
+            print xs.[0];
+            for x in xs print x;
+        );
+    "#;
+
+    test_program_sanitized(source, "7\n7\n9\n9\n2\n3\n4\n5\n6\n");
+}
+
+#[test]
+fn vec_is_shared_between_functions() {
+    let source = r#"
+        let fill = (xs: vec u32) => for x in [1, 2, 3] push (xs, x * 10);
+
+        let main = () => (
+            let xs: vec u32 = vec [];
+            fill xs;
+            print xs.len;
+            print xs.[2];
+        );
+    "#;
+
+    test_program_sanitized(source, "3\n30\n");
+}
+
+#[test]
+fn pop_from_empty_vec_traps() {
+    let source = r#"
+        let main = () => (
+            let xs: vec u32 = vec [];
+            print pop xs;
+        );
+    "#;
+
+    for backend in backends() {
+        let program = run_program(source, backend);
+        assert!(!program.status.success(), "{backend:?}");
+
+        let stderr = &String::from_utf8(program.stderr).unwrap();
+        assert_eq!(
+            stderr, "pop from empty vec at input.keb:4:19\n",
+            "{backend:?}"
+        );
+    }
+}
+
+#[test]
+fn trap_keeps_what_was_printed_before() {
+    let source = r#"
+        let main = () => (
+            let xs = vec [1];
+            print 2;
+            print pop xs;
+            print pop xs;
+        );
+    "#;
+
+    for backend in backends() {
+        let program = run_program(source, backend);
+        assert!(!program.status.success(), "{backend:?}");
+
+        let stdout = &String::from_utf8(program.stdout).unwrap();
+        assert_eq!(stdout, "2\n1\n", "{backend:?}");
+
+        let stderr = &String::from_utf8(program.stderr).unwrap();
+        assert_eq!(
+            stderr, "pop from empty vec at input.keb:6:19\n",
+            "{backend:?}"
+        );
+    }
+}
+
+#[test]
+fn alloc_and_free() {
+    let source = r#"
+        let main = () => (
+            let memory = alloc 16;
+            free memory;
+            print 1;
+        );
+    "#;
+
+    test_program(source, "1\n");
+}
+
+#[test]
+fn nested_vecs_are_freed() {
+    let source = r#"
+        let main = () => (
+            let rows = vec [vec [1, 2], vec [3]];
+            push (rows, vec [4, 5, 6]);
+            push (rows.[0], 7);
+            rows.[1] = vec [8];
+            let row = pop rows;
+            print row.len;
+            for r in rows print r.len;
+        );
+    "#;
+
+    test_program_sanitized(source, "3\n3\n1\n");
+}
+
+#[test]
+fn vecs_deep_in_large_elements_are_freed() {
+    let source = r#"
+        let make = (n: u32) => ([n; 40], vec [n], [vec [n, n], vec [n, n, n]]);
+
+        let main = () => (
+            let rows = vec [make 1, make 2];
+            push (rows, make 3);
+            let (padding, xs, more) = rows.[2];
+            print padding.[39];
+            print xs.[0];
+            print more.[1].len;
+            pop rows;
+            print rows.len;
+        );
+    "#;
+
+    test_program_sanitized(source, "3\n3\n3\n2\n");
+}
+
+#[test]
+fn vec_outlives_its_binding() {
+    let source = r#"
+        let make = (n: u32) => (
+            let xs = vec [n, n];
+            let ys = xs;
+            ys
+        );
+
+        let main = () => (
+            let pairs = vec [(1, make 2), (3, make 4)];
+            let (a, xs) = pairs.[1];
+            print a;
+            print xs.[0];
+            let found = loop (
+                let ys = make 5;
+                if ys.[1] == 5 then break ys;
+            );
+            print found.len;
+            for x in [make 6, make 7] (if x.[0] == 6 then continue else print x.[0]);
+        );
+    "#;
+
+    test_program_sanitized(source, "3\n4\n2\n7\n");
+}
+
+#[test]
+fn mutable_binding_assigned_in_branches() {
+    let source = r#"
+        let main = () => (
+            let mut total = 0;
+            for x in [1, 2, 3, 4] (if x == 2 then (total = total + 10) else (total = total + x));
+            print total;
+            let mut xs = vec [1];
+            loop (
+                if xs.len == 3 then break;
+                xs = vec [xs.len, xs.len];
+                push (xs, 0);
+            );
+            print xs.[0];
+        );
+    "#;
+
+    test_program_sanitized(source, "18\n1\n");
+}
+
+#[test]
+fn many_values_live_across_calls() {
+    let source = r#"
+        let id = (x: u32) => x;
+
+        let main = () => (
+            let a = id 1;
+            let b = id 2;
+            let c = id 3;
+            let d = id 4;
+            let e = id 5;
+            let f = id 6;
+            let g = id 7;
+            let h = id 8;
+            let i = id 9;
+            let j = id 10;
+            let k = id 11;
+            let l = id 12;
+            let m = a * b + c / d;
+            print m + e * f - g + h * i / j + k * l;
+            print a + b + c + d + e + f + g + h + i + j + k + l;
+        );
+    "#;
+
+    test_program(source, "164\n78\n");
+}
+
+#[test]
+fn records_passed_and_returned_by_value() {
+    let source = r#"
+        let swap = (a: u32, b: u32) => (b, a);
+        let reverse = (a: u32, b: u32, c: u32, d: u32, e: u32) => (e, d, c, b, a);
+        let pair = (n: u32) => (n, vec [n, n + 1]);
+
+        let main = () => (
+            let (x, y) = swap (1, 2);
+            print x;
+            print y;
+            let (p, q, r, s, t) = reverse (3, 4, 5, 6, 7);
+            print p;
+            print r;
+            print t;
+            let (n, xs) = pair 8;
+            print n + xs.[1];
+        );
+    "#;
+
+    test_program_sanitized(source, "2\n1\n7\n5\n3\n17\n");
+}
+
+#[test]
+fn large_records_passed_by_reference() {
+    let source = r#"
+        let total = (xs: vec u32, ys: [u32; 16], n: u32) =>
+            if n == 0 then xs.[0] else ys.[n - 1] + (total (xs, ys, n - 1));
+        let first = (xs: vec u32, ys: [u32; 16], n: u32) => ys.[0] + n;
+
+        let main = () => (
+            let ys = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+            let xs = vec [100];
+            print total (xs, ys, 16);
+            let empty: vec u32 = vec [];
+            print first (empty, ys, 7);
+            print xs.len;
+        );
+    "#;
+
+    test_program_sanitized(source, "236\n8\n1\n");
+}
+
+#[test]
+fn deep_self_recursion_does_not_overflow() {
+    let source = r#"
+        let count = (n: u32, total: u32) =>
+            if n == 0 then total else count (n - 1, total + 1);
+
+        let main = () => print count (100000, 0);
+    "#;
+
+    test_program(source, "100000\n");
+}
+
+#[test]
+fn deep_mutual_recursion_does_not_overflow() {
+    let source = r#"
+        let even = (n: u32) => if n == 0 then true else odd n - 1;
+        let odd = (n: u32) => if n == 0 then false else even n - 1;
+
+        let main = () => (
+            print if even 100000 then 1 else 0;
+            print if odd 100001 then 1 else 0;
+        );
+    "#;
+
+    test_program(source, "1\n1\n");
+}
+
+#[test]
+fn mutual_tail_calls_pass_and_return_records() {
+    let source = r#"
+        let ping = (n: u32, a: u32, b: u32, c: u32, d: u32) =>
+            if n then pong (n - 1, b, c, d, a + 1, 2) else (a, b, c, d, n);
+        let pong = (n: u32, a: u32, b: u32, c: u32, d: u32, e: u32) =>
+            if n then ping (n - 1, a, b, d + e, c) else (a, b, c, d, e);
+
+        let main = () => (
+            let result = ping (1000000, 0, 0, 0, 0);
+            let (a, b, c, d, e) = result;
+            print a;
+            print b;
+            print d;
+        );
+    "#;
+
+    test_program(source, "499998\n500001\n0\n");
+}
+
+#[test]
+fn object_file_is_readable_by_binutils() {
+    let source = r#"
+        let fact = (x: u32) => if x then x * (fact x - 1) else 1;
+        let main = () => print fact 8;
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let asm = amd64_asm_codegen::generate(&types, &ssa);
+    let object_path = temp_dir().join(format!("keb-test-object-{:0>32x}.o", random::<u128>(..)));
+    std::fs::write(&object_path, amd64_asm_codegen::assemble(&asm).write()).unwrap();
+
+    let readelf = Command::new("readelf")
+        .args(["-h", "-S", "-s", "-r", "-W"])
+        .arg(&object_path)
+        .output()
+        .unwrap();
+    let objdump = Command::new("objdump")
+        .arg("-d")
+        .arg(&object_path)
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&object_path).unwrap();
+
+    assert!(readelf.status.success());
+    assert!(readelf.stderr.is_empty());
+    let readelf = String::from_utf8(readelf.stdout).unwrap();
+    assert!(readelf.contains("REL (Relocatable file)"));
+    assert!(readelf.contains("Advanced Micro Devices X86-64"));
+    assert!(readelf.contains(".note.GNU-stack"));
+    assert!(
+        readelf
+            .lines()
+            .any(|line| line.contains("GLOBAL") && line.ends_with(" main"))
+    );
+    assert!(
+        readelf
+            .lines()
+            .any(|line| line.contains("R_X86_64_PLT32") && line.contains("builtin_print - 4"))
+    );
+
+    assert!(objdump.status.success());
+    let objdump = String::from_utf8(objdump.stdout).unwrap();
+    assert!(objdump.contains("_fact>:"));
+    assert!(objdump.contains("<main>:"));
+    assert!(objdump.contains("mul    %r11d"));
+    assert!(!objdump.contains("(bad)"));
+}
+
+#[test]
+fn static_executable_has_no_interpreter() {
+    let source = r#"
+        let main = () => print 1;
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let asm = amd64_asm_codegen::generate(&types, &ssa);
+    let objects = [
+        amd64_asm_codegen::assemble(&asm),
+        amd64_asm_codegen::assemble(runtime::ASSEMBLY),
+    ];
+    let executable_path = temp_dir().join(format!("keb-test-static-{:0>32x}", random::<u128>(..)));
+    std::fs::write(&executable_path, elf::link(&objects, "_start")).unwrap();
+
+    let readelf = Command::new("readelf")
+        .args(["-h", "-l", "-W"])
+        .arg(&executable_path)
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&executable_path).unwrap();
+
+    assert!(readelf.status.success());
+    assert!(readelf.stderr.is_empty());
+    let readelf = String::from_utf8(readelf.stdout).unwrap();
+    assert!(readelf.contains("EXEC (Executable file)"));
+    assert!(readelf.contains("GNU_STACK"));
+    assert!(!readelf.contains("INTERP"));
+    assert!(!readelf.contains("DYNAMIC"));
+}
+
+#[test]
+fn aarch64_asm_passes_large_records_by_address() {
+    let source = r#"
+        let reverse = (a: u32, b: u32, c: u32, d: u32, e: u32) => (e, d, c, b, a);
+        let main = () => (
+            let (p, q, r, s, t) = reverse (3, 4, 5, 6, 7);
+            print p;
+        );
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let asm = aarch64_asm_codegen::generate(&types, &ssa);
+
+    // The caller gives the address of its copy of the argument in `x0` and
+    // where to write the result in `x8`, the callee saves the latter.
+    assert!(asm.contains("  add x0, sp, #"));
+    assert!(asm.contains("  add x8, sp, #"));
+    assert!(asm.contains("  str x8, [sp, #"));
+    assert!(asm.contains("  bl f0_builtin_print\n"));
+    assert!(asm.contains(".set main, f"));
+}
+
+#[test]
+fn wasm_module_only_imports_wasi() {
+    if !backends()
+        .iter()
+        .any(|backend| matches!(backend, Backend::Wasm))
+    {
+        return;
+    }
+
+    let source = r#"
+        let main = () => (
+            let mut i = 0;
+            loop (
+                if i == 3 then break;
+                print i;
+                i = i + 1;
+            );
+        );
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let module_path = temp_dir().join(format!("keb-test-wasm-{:0>32x}", random::<u128>(..)));
+    std::fs::write(&module_path, wasm_codegen::generate(&types, &ssa)).unwrap();
+
+    let node = Command::new("node")
+        .arg("-e")
+        .arg(
+            "const wasm = new WebAssembly.Module(require('node:fs').readFileSync(process.argv[1]));
+            for (const { module, name, kind } of WebAssembly.Module.imports(wasm)) console.log(`import ${module} ${name} ${kind}`);
+            for (const { name, kind } of WebAssembly.Module.exports(wasm)) console.log(`export ${name} ${kind}`);",
+        )
+        .arg(&module_path)
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&module_path).unwrap();
+
+    assert!(node.status.success());
+    assert_eq!(
+        String::from_utf8(node.stdout).unwrap(),
+        "import wasi_snapshot_preview1 fd_write function\n\
+         import wasi_snapshot_preview1 proc_exit function\n\
+         export _start function\n\
+         export memory memory\n"
+    );
+}
+
+#[test]
+fn llvm_ir_is_verified_by_opt() {
+    if !backends()
+        .iter()
+        .any(|backend| matches!(backend, Backend::LlvmIr))
+        || Command::new("opt").arg("--version").output().is_err()
+    {
+        return;
+    }
+
+    let source = r#"
+        let pick = (x: u32) => if x == 0 then (x, [1, 2]) else (x * 2, [3, 4]);
+        let main = () => (
+            let (a, xs) = pick 3;
+            print a + xs.[1];
+        );
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let ir = llvm_ir_codegen::generate(&types, &ssa);
+    assert!(ir.contains(" = phi "));
+
+    let ir_path = temp_dir().join(format!("keb-test-llvm-{:0>32x}.ll", random::<u128>(..)));
+    std::fs::write(&ir_path, ir).unwrap();
+
+    let opt = Command::new("opt")
+        .args(llvm_ir_codegen::llc_flags())
+        .args(["-O2", "-S", "-o", "-"])
+        .arg(&ir_path)
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(&ir_path).unwrap();
+
+    assert!(
+        opt.status.success(),
+        "{}",
+        String::from_utf8_lossy(&opt.stderr)
+    );
+    assert!(String::from_utf8(opt.stdout).unwrap().contains("define"));
+}
+
+#[test]
+fn jit_stops_at_the_first_trap() {
+    let source = r#"
+        let get = (xs: [u32], i: u32) => xs.[i];
+
+        let main = () => (
+            let xs = vec [1, 2];
+            print get ([3, 4].[..], 1);
+            print get ([3, 4].[..], 2);
+            print pop xs;
+        );
+    "#;
+
+    let program = run_program(source, Backend::Jit);
+    assert!(!program.status.success());
+    assert_eq!(String::from_utf8(program.stdout).unwrap(), "4\n");
+    assert_eq!(
+        String::from_utf8(program.stderr).unwrap(),
+        "index out of bounds at input.keb:2:45\n"
+    );
+}
+
+#[test]
+fn traps_are_reported_at_the_path_of_the_source() {
+    let source = "let main = () => (let xs = [1, 2]; print xs.[2];);";
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate(
+        "examples/bounds.keb",
+        source,
+        &tokens.offsets,
+        &semantic,
+        &mut types,
+    );
+
+    for backend in backends() {
+        let program = run_ssa(&types, &ssa, backend, &[]);
+        assert!(!program.status.success(), "{backend:?}");
+        assert_eq!(
+            String::from_utf8(program.stderr).unwrap(),
+            "index out of bounds at examples/bounds.keb:1:45\n",
+            "{backend:?}"
+        );
+    }
+}
+
+#[test]
+fn traps_on_division_by_zero() {
+    for source in [
+        "let main = () => (print 10 / 2; print 10 / 0; print 1;);",
+        "let divide = (a: u32, b: u32) => a / b;
+        let main = () => (print divide (10, 2); print divide (10, 0); print 1;);",
+    ] {
+        for backend in backends() {
+            let program = run_program(source, backend);
+            assert!(!program.status.success(), "{backend:?}");
+            assert_eq!(
+                String::from_utf8(program.stdout).unwrap(),
+                "5\n",
+                "{backend:?}"
+            );
+            assert_eq!(
+                String::from_utf8(program.stderr).unwrap(),
+                "division by zero\n",
+                "{backend:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn backends_agree_with_the_interpreter() {
+    let source = r#"
+        let collatz = (n: u32) => (
+            let mut steps = 0;
+            let mut m = n;
+            loop (
+                if m == 1 then break steps;
+                steps = steps + 1;
+                m = if m - m / 2 * 2 then (3 * m + 1) else (m / 2);
+            )
+        );
+
+        let swap = (a: u32, b: u32) => (b, a);
+
+        let main = () => (
+            let lengths = vec [collatz 3];
+            for n in [7, 9, 27] push (lengths, collatz n);
+            for length in lengths print length;
+
+            let (a, b) = swap (1, 2);
+            print a * 10 + b;
+        );
+    "#;
+
+    test_program_against_interpreter(source);
+}
+
+#[test]
+fn interpreter_stops_at_the_step_limit() {
+    let source = r#"
+        let main = () => loop print 1;
+    "#;
+
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+
+    let mut stdout = Vec::new();
+    let result = ssa::interpret(&types, &ssa, &mut stdout, 100);
+    assert!(matches!(result, Err(ssa::InterpretError::StepLimit)));
+    assert!(stdout.starts_with(b"1\n1\n"));
+}
@@ -0,0 +1,926 @@
+use std::collections::HashSet;
+
+use keb::{
+    semantic::{self, Types},
+    ssa::{self, Block, BlockData, Cfg, Expr, InstData, Ssa},
+    syntax, token,
+};
+
+const STEP_LIMIT: u64 = 1_000_000;
+
+fn compile(source: &str) -> (Types, Ssa) {
+    let tokens = token::lex(source);
+    let syntax = syntax::parse(&tokens.kinds);
+    let (mut semantic, mut types) = semantic::parse(source, &tokens.offsets, &syntax);
+    semantic::infer_types(&mut semantic, &mut types);
+    let ssa = ssa::generate("input.keb", source, &tokens.offsets, &semantic, &mut types);
+    (types, ssa)
+}
+
+fn interpret(types: &Types, ssa: &Ssa) -> String {
+    let mut stdout = Vec::new();
+    ssa::interpret(types, ssa, &mut stdout, STEP_LIMIT).unwrap();
+    String::from_utf8(stdout).unwrap()
+}
+
+// Printing the parsed text gives it back and the parsed ssa behaves the same.
+fn test_round_trip(source: &str) {
+    let (types, ssa) = compile(source);
+    let text = ssa::print(&types, &ssa);
+
+    let (parsed_types, parsed_ssa) = ssa::parse(&text).unwrap();
+    assert_eq!(ssa::print(&parsed_types, &parsed_ssa), text);
+    ssa::verify(&parsed_types, &parsed_ssa).unwrap();
+    assert_eq!(
+        interpret(&parsed_types, &parsed_ssa),
+        interpret(&types, &ssa)
+    );
+}
+
+#[test]
+fn text_round_trips_records_and_loops() {
+    let source = r#"
+        let collatz = (n: u32) => (
+            let mut steps = 0;
+            let mut m = n;
+            loop (
+                if m == 1 then break steps;
+                steps = steps + 1;
+                m = if m - m / 2 * 2 then (3 * m + 1) else (m / 2);
+            )
+        );
+
+        let swap = (a: u32, b: u32) => (b, a);
+
+        let main = () => (
+            print collatz 27;
+            let (a, b) = swap (1, 2);
+            print a * 10 + b;
+        );
+    "#;
+
+    test_round_trip(source);
+}
+
+#[test]
+fn text_round_trips_arrays_slices_and_vecs() {
+    let source = r#"
+        let sum = (xs: [u32]) => for x in xs print x * 2;
+        let pair = (n: u32) => (n, vec [n, n + 1]);
+
+        let main = () => (
+            let xs = [1, 2, 3, 4];
+            sum xs.[1..3];
+            let view = xs.[..];
+            view.[1] = 4;
+            print xs.[1] + xs.len;
+            let ys = vec [1, 2];
+            for x in [3, 4, 5] push (ys, x);
+            print pop ys;
+            let (n, zs) = pair 8;
+            print n + zs.[1];
+        );
+    "#;
+
+    test_round_trip(source);
+}
+
+#[test]
+fn hand_written_ssa_runs() {
+    let (types, ssa) = ssa::parse(include_str!("ssa/count_down.ssa")).unwrap();
+    assert_eq!(interpret(&types, &ssa), "3\n2\n1\n");
+}
+
+#[test]
+fn parse_errors_have_a_location() {
+    let text = "@0 fn main(()) -> ()\n  %0 = return %1;\n";
+
+    let error = ssa::parse(text).unwrap_err();
+    assert_eq!(error.to_string(), "unknown instruction `%1` at 2:15");
+}
+
+#[test]
+fn verifier_reports_every_error() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(()) -> ()
+          %pair = record $one, $one : (u32, u32);
+          %field = field %pair, 2;
+          %call = call @exit, ();
+          %early = return ();
+          %jump = jump @exit, $one;
+
+        @exit block(())
+          %print = call @print, %later;
+          %later = add $one, $one;
+
+        $one = 1_u32;
+    "#;
+
+    let (types, ssa) = ssa::parse(text).unwrap();
+    let errors = ssa::verify(&types, &ssa).unwrap_err();
+
+    let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            "@1, instruction 1: no field 2",
+            "@1, instruction 2: invalid target @2",
+            "@1, instruction 3: terminator before the end of the block",
+            "@1, instruction 4: argument does not have the type of the parameter",
+            "@2 does not end with a terminator",
+            "@2, instruction 0: use not dominated by its definition",
+        ]
+    );
+}
+
+fn run_pass(text: &str, passes: &[&str]) -> String {
+    let (mut types, mut ssa) = ssa::parse(text).unwrap();
+
+    for name in passes {
+        let pass = ssa::opt::PASSES
+            .iter()
+            .find(|pass| pass.name == *name)
+            .unwrap();
+        (pass.run)(&mut types, &mut ssa);
+        ssa::verify(&types, &ssa).unwrap();
+    }
+
+    ssa::print(&types, &ssa)
+}
+
+#[test]
+fn const_fold_computes_constants() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(()) -> ()
+          %sum = add $two, $three;
+          %product = mul %sum, $two;
+          %same = equal %product, $ten;
+          %zero = div %product, $zero;
+          %print = call @print, %product;
+          %return = return %same;
+
+        $zero = 0_u32;
+        $two = 2_u32;
+        $three = 3_u32;
+        $ten = 10_u32;
+    "#;
+
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(()) -> ()
+  %0 = div $3, $0;
+  %1 = call @0, $3;
+  %2 = return true;
+
+$0 = 0_u32;
+$1 = 2_u32;
+$2 = 3_u32;
+$3 = 10_u32;
+$4 = 5_u32;
+";
+
+    assert_eq!(run_pass(text, &["const_fold", "dead_code"]), expected);
+}
+
+#[test]
+fn forward_and_unreachable_remove_copies_and_blocks() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(()) -> ()
+          %pair = record $one, $two : (u32, u32);
+          %second = field %pair, 1;
+          %jump = jump @next, %second;
+
+        @dead block(())
+          %dead_jump = jump @next, $one;
+
+        @next block(u32)
+          %print = call @print, param(@next);
+          %return = return ();
+
+        $one = 1_u32;
+        $two = 2_u32;
+    "#;
+
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(()) -> ()
+  %0 = jump @2, $1;
+
+@2 block(u32)
+  %1 = call @0, $1;
+  %2 = return ();
+
+$0 = 1_u32;
+$1 = 2_u32;
+";
+
+    assert_eq!(
+        run_pass(text, &["forward", "dead_code", "unreachable"]),
+        expected
+    );
+}
+
+#[test]
+fn optimized_programs_print_the_same() {
+    let source = r#"
+        let collatz = (n: u32) => (
+            let mut steps = 0;
+            let mut m = n;
+            loop (
+                if m == 1 then break steps;
+                steps = steps + 1;
+                m = if m - m / 2 * 2 then (3 * m + 1) else (m / 2);
+            )
+        );
+
+        let main = () => (
+            let xs = vec [collatz 7];
+            for x in [1, 2, 3] push (xs, x * 2 + 1);
+            for x in xs print x;
+        );
+    "#;
+
+    let (mut types, mut ssa) = compile(source);
+    let expected = interpret(&types, &ssa);
+
+    let before = ssa.inst_count();
+    ssa::opt::optimize(&mut types, &mut ssa, |_, _, _| {});
+
+    assert_eq!(interpret(&types, &ssa), expected);
+    assert!(ssa.inst_count() < before);
+}
+
+#[test]
+fn inline_splices_small_functions_but_not_recursive_ones() {
+    let source = r#"
+        let add = (a: u32, b: u32) => a + b;
+        let fact = (x: u32) => if x then x * (fact x - 1) else 1;
+
+        let main = () => (
+            print add (1, 2);
+            print fact 5;
+        );
+    "#;
+
+    let (mut types, mut ssa) = compile(source);
+    let expected = interpret(&types, &ssa);
+    ssa::opt::optimize(&mut types, &mut ssa, |_, _, _| {});
+    assert_eq!(interpret(&types, &ssa), expected);
+
+    let text = ssa::print(&types, &ssa);
+    assert_eq!(count_calls(&text, "call", "add"), 0);
+    assert_eq!(count_calls(&text, "call", "builtin_add"), 0);
+    assert_eq!(count_calls(&text, "call", "fact"), 2);
+}
+
+// Number of `instruction`s, `call` or `tail_call`, of the function `name`.
+fn count_calls(text: &str, instruction: &str, name: &str) -> usize {
+    let function = text
+        .lines()
+        .find(|line| line.contains(&format!(" {name}(")))
+        .unwrap()
+        .split(' ')
+        .next()
+        .unwrap();
+
+    text.matches(&format!("= {instruction} {function},"))
+        .count()
+}
+
+#[test]
+fn recursive_tail_calls_become_jumps_and_tail_calls() {
+    let source = r#"
+        let count = (n: u32, total: u32) =>
+            if n == 0 then total else count (n - 1, total + 1);
+        let even = (n: u32) => if n == 0 then true else odd n - 1;
+        let odd = (n: u32) => if n == 0 then false else even n - 1;
+        let fact = (x: u32) => if x then x * (fact x - 1) else 1;
+
+        let main = () => (
+            print count (3, 0);
+            print if even 4 then 1 else 0;
+            print fact 4;
+        );
+    "#;
+
+    let (types, ssa) = compile(source);
+    assert_eq!(interpret(&types, &ssa), "3\n1\n24\n");
+
+    let text = ssa::print(&types, &ssa);
+    assert_eq!(count_calls(&text, "call", "count"), 1);
+    assert_eq!(count_calls(&text, "tail_call", "count"), 0);
+    assert_eq!(count_calls(&text, "call", "even"), 1);
+    assert_eq!(count_calls(&text, "tail_call", "even"), 1);
+    assert_eq!(count_calls(&text, "tail_call", "odd"), 1);
+    // The product is computed after the call returns.
+    assert_eq!(count_calls(&text, "call", "fact"), 2);
+
+    let (parsed_types, parsed_ssa) = ssa::parse(&text).unwrap();
+    assert_eq!(ssa::print(&parsed_types, &parsed_ssa), text);
+}
+
+#[test]
+fn cfg_finds_dominators_loops_and_live_values() {
+    // A loop halving its counter when it is even, decrementing it otherwise.
+    let text = r#"
+        @main fn main(u32) -> u32
+          %start = jump @head, param(@main);
+
+        @head block(u32)
+          %done = equal param(@head), $zero;
+          %branch = jump @exit if %done else @body;
+
+        @body block(())
+          %half = div param(@head), $two;
+          %double = mul %half, $two;
+          %even = equal %double, param(@head);
+          %parity = jump @even if %even else @odd;
+
+        @even block(())
+          %halve = jump @latch, %half;
+
+        @odd block(())
+          %less = sub param(@head), $one;
+          %decrement = jump @latch, %less;
+
+        @latch block(u32)
+          %again = jump @head, param(@latch);
+
+        @exit block(())
+          %return = return param(@head);
+
+        $zero = 0_u32;
+        $one = 1_u32;
+        $two = 2_u32;
+    "#;
+
+    let (_, ssa) = ssa::parse(text).unwrap();
+    let [main, head, body, even, odd, latch, exit] =
+        [0, 1, 2, 3, 4, 5, 6].map(Block::from_u32_index);
+    let cfg = Cfg::new(&ssa, main);
+
+    assert_eq!(cfg.blocks(), [main, head, body, odd, even, latch, exit]);
+    assert_eq!(cfg.predecessors(head), [main, latch]);
+    assert_eq!(cfg.successors(body), [even, odd]);
+
+    assert_eq!(cfg.immediate_dominator(main), main);
+    assert_eq!(cfg.immediate_dominator(latch), body);
+    assert_eq!(cfg.immediate_dominator(exit), head);
+    assert_eq!(cfg.dominated(body), [odd, even, latch]);
+    assert!(cfg.dominates(head, odd));
+    assert!(!cfg.dominates(even, latch));
+
+    let frontiers = cfg.dominance_frontiers();
+    assert_eq!(frontiers[&even], HashSet::from([latch]));
+    assert_eq!(frontiers[&body], HashSet::from([head]));
+    assert_eq!(frontiers[&head], HashSet::from([head]));
+    assert!(frontiers[&exit].is_empty());
+
+    let loops = cfg.loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].header, head);
+    assert_eq!(loops[0].latches, [latch]);
+    assert_eq!(loops[0].blocks, [head, body, odd, even, latch]);
+
+    let liveness = cfg.liveness(&ssa);
+    let counter = Expr::BlockArg(head);
+    let BlockData::Block { insts, .. } = &ssa.blocks[body] else {
+        panic!()
+    };
+    let half = Expr::Inst(insts[0]);
+    assert!(liveness.live_in[&head].is_empty());
+    assert_eq!(liveness.live_in[&even], HashSet::from([half]));
+    assert_eq!(liveness.live_in[&odd], HashSet::from([counter]));
+    assert_eq!(liveness.live_in[&exit], HashSet::from([counter]));
+    assert_eq!(liveness.live_out[&body], HashSet::from([half, counter]));
+}
+
+#[test]
+fn loops_compute_invariants_once_and_add_instead_of_multiplying() {
+    let source = r#"
+        let kernel = (n: u32, k: u32) => (
+            let mut i = 0;
+            let mut total = 0;
+            loop (
+                if i == n then break total;
+                total = total + i * 3 + k * k;
+                i = i + 1;
+            )
+        );
+
+        let main = () => print kernel (10, 4);
+    "#;
+
+    let (mut types, mut ssa) = compile(source);
+    assert_eq!(interpret(&types, &ssa), "295\n");
+    ssa::opt::optimize(&mut types, &mut ssa, |_, _, _| {});
+    assert_eq!(interpret(&types, &ssa), "295\n");
+
+    let (kernel, _) = ssa
+        .blocks
+        .entries()
+        .find(|(_, block_data)| {
+            matches!(block_data, BlockData::Function { name, .. } if name == "kernel")
+        })
+        .unwrap();
+    let cfg = Cfg::new(&ssa, kernel);
+    let loops = cfg.loops();
+    assert_eq!(loops.len(), 1);
+
+    let multiplications = |blocks: &[Block]| {
+        blocks
+            .iter()
+            .flat_map(|block| match &ssa.blocks[*block] {
+                BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts,
+                BlockData::ExternFunction { .. } => panic!(),
+            })
+            .filter(|inst| matches!(ssa.insts[**inst], InstData::Mul(..)))
+            .count()
+    };
+    assert_eq!(multiplications(&loops[0].blocks), 0);
+    // `k * k` and the first `i * 3`.
+    assert_eq!(multiplications(cfg.blocks()), 2);
+}
+
+#[test]
+fn loops_written_as_recursion_add_instead_of_multiplying() {
+    let source = r#"
+        let sum = (i: u32, n: u32, total: u32) =>
+            if i == n then total else sum (i + 1, n, total + i * 3);
+
+        let main = () => print sum (0, 10, 0);
+    "#;
+
+    let (mut types, mut ssa) = compile(source);
+    assert_eq!(interpret(&types, &ssa), "135\n");
+    ssa::opt::optimize(&mut types, &mut ssa, |_, _, _| {});
+    assert_eq!(interpret(&types, &ssa), "135\n");
+
+    let (sum, _) = ssa
+        .blocks
+        .entries()
+        .find(|(_, block_data)| {
+            matches!(block_data, BlockData::Function { name, .. } if name == "sum")
+        })
+        .unwrap();
+    let cfg = Cfg::new(&ssa, sum);
+    let loops = cfg.loops();
+    assert_eq!(loops.len(), 1);
+
+    let multiplications = loops[0]
+        .blocks
+        .iter()
+        .flat_map(|block| match &ssa.blocks[*block] {
+            BlockData::Function { insts, .. } | BlockData::Block { insts, .. } => insts,
+            BlockData::ExternFunction { .. } => panic!(),
+        })
+        .filter(|inst| matches!(ssa.insts[**inst], InstData::Mul(..)))
+        .count();
+    assert_eq!(multiplications, 0);
+}
+
+#[test]
+fn licm_makes_a_preheader_for_loops_entered_by_conditional_jumps() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(u32) -> ()
+          %zero = equal param(@main), $zero;
+          %enter = jump @exit if %zero else @head;
+
+        @head block(())
+          %square = mul param(@main), param(@main);
+          %print = call @print, %square;
+          %again = jump @head, ();
+
+        @exit block(())
+          %return = return ();
+
+        $zero = 0_u32;
+    "#;
+
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(u32) -> ()
+  %0 = equal param(@1), $0;
+  %1 = jump @3 if %0 else @4;
+
+@2 block(())
+  %2 = call @0, %5;
+  %3 = jump @2, ();
+
+@3 block(())
+  %4 = return ();
+
+@4 block(())
+  %5 = mul param(@1), param(@1);
+  %6 = jump @2, param(@4);
+
+$0 = 0_u32;
+";
+
+    assert_eq!(run_pass(text, &["licm"]), expected);
+}
+
+#[test]
+fn gvn_replaces_expressions_computed_by_a_dominating_block() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main((u32, u32)) -> ()
+          %a = field param(@main), 0;
+          %b = field param(@main), 1;
+          %sum = add %a, %b;
+          %zero = equal %sum, $zero;
+          %branch = jump @then if %zero else @else;
+
+        @then block(())
+          %a_again = field param(@main), 0;
+          %swapped = add %b, %a_again;
+          %then_product = mul %swapped, $two;
+          %then_print = call @print, %then_product;
+          %then_jump = jump @exit, ();
+
+        @else block(())
+          %else_product = mul %sum, $another_two;
+          %else_print = call @print, %else_product;
+          %else_jump = jump @exit, ();
+
+        @exit block(())
+          %exit_product = mul $two, %sum;
+          %return = return ();
+
+        $zero = 0_u32;
+        $two = 2_u32;
+        $another_two = 2_u32;
+    "#;
+
+    // The products of the branches do not dominate each other nor the exit.
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main((u32, u32)) -> ()
+  %0 = field param(@1), 0;
+  %1 = field param(@1), 1;
+  %2 = add %0, %1;
+  %3 = equal %2, $0;
+  %4 = jump @2 if %3 else @3;
+
+@2 block(())
+  %5 = mul %2, $1;
+  %6 = call @0, %5;
+  %7 = jump @4, ();
+
+@3 block(())
+  %8 = mul %2, $2;
+  %9 = call @0, %8;
+  %10 = jump @4, ();
+
+@4 block(())
+  %11 = mul $1, %2;
+  %12 = return ();
+
+$0 = 0_u32;
+$1 = 2_u32;
+$2 = 2_u32;
+";
+
+    assert_eq!(run_pass(text, &["gvn"]), expected);
+}
+
+#[test]
+fn gvn_replaces_records_of_the_same_fields() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(u32) -> ()
+          %first = record param(@main), param(@main) : (u32, u32);
+          %second = record param(@main), param(@main) : (u32, u32);
+          %x = field %first, 0;
+          %y = field %second, 1;
+          %sum = add %x, %y;
+          %print = call @print, %sum;
+          %return = return ();
+    "#;
+
+    // Each record was given its own type, they are equal.
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(u32) -> ()
+  %0 = record param(@1), param(@1) : (u32, u32);
+  %1 = field %0, 0;
+  %2 = field %0, 1;
+  %3 = add %1, %2;
+  %4 = call @0, %3;
+  %5 = return ();
+
+";
+
+    assert_eq!(run_pass(text, &["gvn"]), expected);
+}
+
+#[test]
+fn gvn_lowers_the_instruction_counts_of_programs() {
+    let programs = [
+        r#"
+            let fact = (x: u32) => if x == 0 then 1 else x * (fact x - 1);
+
+            let pick = (n: u32, k: u32) => (
+                let r = (n, k * 2);
+                if n - n / 2 * 2 then fact n - 1 else (fact n - 1) + r.1 + r.1 * (k * 2)
+            );
+
+            let main = () => (
+                print pick (5, 3);
+                print pick (4, 3);
+            );
+        "#,
+        r#"
+            let area = (p: (u32, u32)) => p.0 * p.1 + p.0 * p.1;
+
+            let main = () => (
+                let xs = vec [3, 4];
+                print area (xs.[0], xs.[1]);
+            );
+        "#,
+    ];
+
+    // Instructions left by every pass, with and without `gvn`.
+    let counts = |source: &str, skipped: &str| {
+        let (mut types, mut ssa) = compile(source);
+        let expected = interpret(&types, &ssa);
+
+        for pass in ssa::opt::PASSES.iter().filter(|pass| pass.name != skipped) {
+            (pass.run)(&mut types, &mut ssa);
+            ssa::verify(&types, &ssa).unwrap();
+        }
+
+        assert_eq!(interpret(&types, &ssa), expected);
+        ssa.inst_count()
+    };
+
+    for source in programs {
+        let (without, with) = (counts(source, "gvn"), counts(source, ""));
+        assert!(
+            with < without,
+            "{with} instructions with gvn, {without} without"
+        );
+    }
+}
+
+#[test]
+fn sccp_folds_branches_on_constants_flowing_through_blocks() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(u32) -> ()
+          %ready = equal $two, $another_two;
+          %branch = jump @then if %ready else @else;
+
+        @then block(())
+          %then_jump = jump @loop, $two;
+
+        @else block(())
+          %else_print = call @print, param(@main);
+          %else_jump = jump @loop, param(@main);
+
+        @loop block(u32)
+          %sum = add param(@loop), $one;
+          %print = call @print, %sum;
+          %done = equal param(@main), %sum;
+          %loop_branch = jump @exit if %done else @body;
+
+        @body block(())
+          %body_jump = jump @loop, param(@loop);
+
+        @exit block(())
+          %known = equal %sum, $three;
+          %exit_branch = jump @return if %known else @trap;
+
+        @trap block(())
+          %trap = div param(@main), $zero;
+          %trap_return = return ();
+
+        @return block(())
+          %return = return ();
+
+        $zero = 0_u32;
+        $one = 1_u32;
+        $two = 2_u32;
+        $another_two = 2_u32;
+        $three = 3_u32;
+    "#;
+
+    // The loop is entered with 2 and goes around with the same value, so the
+    // sum is always 3 and the division by zero is never reached.
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(u32) -> ()
+  %0 = equal $2, $3;
+  %1 = jump @2, $2;
+
+@2 block(u32)
+  %2 = add $2, $1;
+  %3 = call @0, $4;
+  %4 = equal param(@1), $4;
+  %5 = jump @4 if %4 else @3;
+
+@3 block(())
+  %6 = jump @2, $2;
+
+@4 block(())
+  %7 = equal $4, $4;
+  %8 = return ();
+
+$0 = 0_u32;
+$1 = 1_u32;
+$2 = 2_u32;
+$3 = 2_u32;
+$4 = 3_u32;
+";
+
+    assert_eq!(run_pass(text, &["sccp"]), expected);
+}
+
+#[test]
+fn sccp_folds_branches_on_singleton_types_and_u32_conditions() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @is_ready fn is_ready(u32) -> true
+          %ready_print = call @print, param(@is_ready);
+          %ready_return = return true;
+
+        @main fn main(u32) -> ()
+          %ready = call @is_ready, param(@main);
+          %branch = jump @then if %ready else @else;
+
+        @then block(())
+          %left = sub $two, $one;
+          %left_branch = jump @known if %left else @trap;
+
+        @else block(())
+          %else_trap = div param(@main), $zero;
+          %else_return = return ();
+
+        @trap block(())
+          %trap = div param(@main), $zero;
+          %trap_return = return ();
+
+        @known block(())
+          %print = call @print, %left;
+          %return = return ();
+
+        $zero = 0_u32;
+        $one = 1_u32;
+        $two = 2_u32;
+    "#;
+
+    // The call is kept for what it prints, its value is known from its type.
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn is_ready(u32) -> true
+  %0 = call @0, param(@1);
+  %1 = return true;
+
+@2 fn main(u32) -> ()
+  %2 = call @1, param(@2);
+  %3 = sub $2, $1;
+  %4 = call @0, $1;
+  %5 = return ();
+
+$0 = 0_u32;
+$1 = 1_u32;
+$2 = 2_u32;
+";
+
+    assert_eq!(run_pass(text, &["sccp"]), expected);
+}
+#[test]
+fn strength_reduction_adds_instead_of_multiplying_arguments_of_loops() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(u32) -> ()
+          %start = record $zero, $zero : (u32, u32);
+          %enter = jump @loop, %start;
+
+        @loop block((u32, u32))
+          %i = field param(@loop), 0;
+          %total = field param(@loop), 1;
+          %done = equal %i, param(@main);
+          %branch = jump @exit if %done else @body;
+
+        @body block(())
+          %scaled = mul %i, $three;
+          %next_total = add %total, %scaled;
+          %next_i = add %i, $one;
+          %next = record %next_i, %next_total : (u32, u32);
+          %back = jump @loop, %next;
+
+        @exit block(())
+          %print = call @print, %total;
+          %return = return ();
+
+        $zero = 0_u32;
+        $one = 1_u32;
+        $three = 3_u32;
+    "#;
+
+    // The product of the counter by 3 is kept in memory, its first value is
+    // computed before the loop and the jump back adds 3 times the step.
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(u32) -> ()
+  %0 = alloca &u32;
+  %1 = record $0, $0 : (u32, u32);
+  %2 = field %1, 0;
+  %3 = mul %2, $2;
+  %4 = store_pointer %0, %3;
+  %5 = mul $1, $2;
+  %6 = jump @2, %1;
+
+@2 block((u32, u32))
+  %7 = field param(@2), 0;
+  %8 = field param(@2), 1;
+  %9 = equal %7, param(@1);
+  %10 = jump @4 if %9 else @3;
+
+@3 block(())
+  %11 = load_pointer %0;
+  %12 = add %8, %11;
+  %13 = add %7, $1;
+  %14 = record %13, %12 : (u32, u32);
+  %15 = load_pointer %0;
+  %16 = add %15, %5;
+  %17 = store_pointer %0, %16;
+  %18 = jump @2, %14;
+
+@4 block(())
+  %19 = call @0, %8;
+  %20 = return ();
+
+$0 = 0_u32;
+$1 = 1_u32;
+$2 = 3_u32;
+";
+
+    assert_eq!(run_pass(text, &["strength_reduction"]), expected);
+}
+
+#[test]
+fn licm_moves_records_and_their_fields() {
+    let text = r#"
+        @print extern builtin_print(u32) -> ()
+
+        @main fn main(u32) -> ()
+          %enter = jump @loop, $zero;
+
+        @loop block(u32)
+          %done = equal param(@loop), param(@main);
+          %branch = jump @exit if %done else @body;
+
+        @body block(())
+          %pair = record param(@main), $one : (u32, u32);
+          %step = field %pair, 1;
+          %next = add param(@loop), %step;
+          %print = call @print, %next;
+          %back = jump @loop, %next;
+
+        @exit block(())
+          %return = return ();
+
+        $zero = 0_u32;
+        $one = 1_u32;
+    "#;
+
+    let expected = "\
+@0 extern builtin_print(u32) -> ()
+@1 fn main(u32) -> ()
+  %0 = record param(@1), $1 : (u32, u32);
+  %1 = field %0, 1;
+  %2 = jump @2, $0;
+
+@2 block(u32)
+  %3 = equal param(@2), param(@1);
+  %4 = jump @4 if %3 else @3;
+
+@3 block(())
+  %5 = add param(@2), %1;
+  %6 = call @0, %5;
+  %7 = jump @2, %5;
+
+@4 block(())
+  %8 = return ();
+
+$0 = 0_u32;
+$1 = 1_u32;
+";
+
+    assert_eq!(run_pass(text, &["licm"]), expected);
+}